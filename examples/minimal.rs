@@ -257,6 +257,20 @@ async fn handle_event(index: usize, node: &mut Routing, event: Event) -> bool {
             dst,
             HexFmt(&content)
         ),
+        Event::RequestReceived {
+            id,
+            content,
+            src,
+            dst,
+            ..
+        } => info!(
+            "Node #{} received request {:?} - src: {:?}, dst: {:?}, content: {}",
+            index,
+            id,
+            src,
+            dst,
+            HexFmt(&content)
+        ),
         Event::RelocationStarted { previous_name } => info!(
             "Node #{} relocation started - previous_name: {}",
             index, previous_name
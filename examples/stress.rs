@@ -17,21 +17,23 @@ use itertools::Itertools;
 use lru_time_cache::LruCache;
 use rand::{
     distributions::{Distribution, WeightedIndex},
-    Rng,
+    Rng, SeedableRng,
 };
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use sn_messaging::{
     location::{Aggregation, Itinerary},
     DstLocation, SrcLocation,
 };
 use sn_routing::{
-    Config, Error as RoutingError, Event as RoutingEvent, NodeElderChange, Routing, TransportConfig,
+    Config, Error as RoutingError, Event as RoutingEvent, NodeElderChange, Routing, SimNetwork,
+    TransportConfig,
 };
 use std::{
     collections::BTreeMap,
     fs::File,
     io::BufWriter,
-    net::{Ipv4Addr, SocketAddr},
+    net::SocketAddr,
     time::{Duration, Instant},
 };
 use structopt::StructOpt;
@@ -50,9 +52,20 @@ const MIN_PRINT_DELAY: Duration = Duration::from_millis(500);
 // Time after which we stop tracking a probe message, regardless of its state (delivered or not).
 const PROBE_WINDOW: Duration = Duration::from_secs(60);
 
-/// Stress test for sn-routing.
+/// Stress test for sn-routing, running all the nodes over the simulated network.
 #[derive(Debug, StructOpt)]
 struct Options {
+    /// Seed of the simulated network. Runs with the same seed and schedule create the same nodes
+    /// and churn.
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+    /// Maximum latency of the simulated network in milliseconds. The latency of every message is
+    /// picked between zero and this value.
+    #[structopt(long, default_value = "0")]
+    latency: u64,
+    /// Probability (between 0.0 and 1.0) that a message gets lost in the simulated network.
+    #[structopt(long, default_value = "0")]
+    loss_rate: f64,
     /// Enable logging. Takes path to a file to log to or "-" to log to stdout. If omitted, logging
     /// is disabled.
     #[structopt(short, long, name = "PATH")]
@@ -82,7 +95,9 @@ struct Options {
     schedule: Vec<String>,
 }
 
-#[tokio::main]
+// The simulated network seeds the randomness of the nodes on the current thread only, so keep all of
+// them on it.
+#[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let opts = Options::from_args();
 
@@ -117,14 +132,19 @@ async fn main() -> Result<()> {
 
     let schedule = ChurnSchedule::parse(&opts.schedule)?;
 
+    let sim_network = SimNetwork::new(opts.seed);
+    sim_network.set_latency(Duration::default(), Duration::from_millis(opts.latency));
+    sim_network.set_loss_rate(opts.loss_rate);
+
+    let mut rng = ChaCha8Rng::seed_from_u64(opts.seed);
+
     let (event_tx, mut event_rx) = mpsc::unbounded_channel();
-    let mut network = Network::new();
+    let mut churn_events = schedule.events(&mut rng);
+    let mut network = Network::new(sim_network, rng);
 
     // Create the genesis node
     network.create_node(event_tx.clone()).await;
 
-    let mut churn_events = schedule.events();
-
     let probe_interval = Duration::from_secs_f64(1.0 / opts.probe_frequency);
     let mut probes = time::interval(probe_interval);
 
@@ -188,6 +208,8 @@ struct ElderState {
 }
 
 struct Network {
+    sim_network: SimNetwork,
+    rng: ChaCha8Rng,
     nodes: BTreeMap<u64, Node>,
     next_id: u64,
     start_time: Instant,
@@ -198,8 +220,10 @@ struct Network {
 }
 
 impl Network {
-    fn new() -> Self {
+    fn new(sim_network: SimNetwork, rng: ChaCha8Rng) -> Self {
         Self {
+            sim_network,
+            rng,
             nodes: BTreeMap::new(),
             next_id: 0,
             start_time: Instant::now(),
@@ -222,9 +246,9 @@ impl Network {
             first: bootstrap_addrs.is_empty(),
             transport_config: TransportConfig {
                 hard_coded_contacts: bootstrap_addrs.into_iter().collect(),
-                local_ip: Some(Ipv4Addr::LOCALHOST.into()),
                 ..Default::default()
             },
+            simulated_network: Some(self.sim_network.clone()),
             ..Default::default()
         };
 
@@ -253,7 +277,7 @@ impl Network {
                 return;
             };
 
-        let index = dist.sample(&mut rand::thread_rng());
+        let index = dist.sample(&mut self.rng);
         let id = weighted_ids[index].0;
 
         if let Some(node) = self.nodes.remove(&id) {
@@ -415,9 +439,13 @@ impl Network {
         });
 
         for (node, prefix) in nodes {
-            let dst = *cache
-                .entry(prefix)
-                .or_insert_with(|| prefix.substituted_in(rand::random()));
+            let dst = if let Some(dst) = cache.get(prefix) {
+                *dst
+            } else {
+                let dst = prefix.substituted_in(self.rng.gen());
+                let _ = cache.insert(prefix, dst);
+                dst
+            };
 
             if self.try_send_probe(node, dst).await? {
                 self.probe_tracker.send(*prefix, dst);
@@ -681,8 +709,7 @@ impl ChurnSchedule {
     }
 
     // Returns a stream yielding the churn events at their scheduled times.
-    fn events(&self) -> impl Stream<Item = ChurnEvent> + Unpin {
-        let mut rng = rand::thread_rng();
+    fn events(&self, rng: &mut impl Rng) -> impl Stream<Item = ChurnEvent> + Unpin {
         let mut start = Duration::default();
         let mut queue = DelayQueue::new();
 
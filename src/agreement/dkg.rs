@@ -12,6 +12,7 @@ use crate::{
     messages::{Message, Variant},
     node::Node,
     peer::Peer,
+    rng::MainRng,
     routing::command::{self, Command},
     section::{EldersInfo, SectionKeyShare},
     supermajority,
@@ -22,13 +23,14 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sn_messaging::DstLocation;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeSet, HashMap, VecDeque},
     fmt::{self, Debug, Formatter},
     iter, mem,
     net::SocketAddr,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tiny_keccak::{Hasher, Sha3};
+use xor_name::XorName;
 
 // Interval to progress DKG timed phase
 const DKG_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
@@ -36,13 +38,14 @@ const DKG_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
 const BACKLOG_CAPACITY: usize = 100;
 
 /// Unique identified of a DKG session.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct DkgKey {
     hash: Digest256,
     generation: u64,
 }
 
 impl DkgKey {
+    /// Creates the identifier of the session generating the key for `elders_info`.
     pub fn new(elders_info: &EldersInfo, generation: u64) -> Self {
         // Calculate the hash without involving serialization to avoid having to return `Result`.
         let mut hasher = Sha3::v256();
@@ -59,6 +62,12 @@ impl DkgKey {
 
         Self { hash, generation }
     }
+
+    /// Generation of the session. Sessions of the same elders with a higher generation supersede
+    /// the older ones.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
 }
 
 impl Debug for DkgKey {
@@ -67,6 +76,43 @@ impl Debug for DkgKey {
     }
 }
 
+/// State of a DKG session this node takes part in, as returned by `Routing::dkg_sessions`.
+#[derive(Clone, Debug)]
+pub struct DkgSessionInfo {
+    /// Identifier of the session.
+    pub dkg_key: DkgKey,
+    /// The proposed elders generating the new section key.
+    pub elders_info: EldersInfo,
+    /// Participants we received DKG messages from so far, including us.
+    pub responded: BTreeSet<XorName>,
+    /// Participants known to have observed the session failing.
+    pub failure_observers: BTreeSet<XorName>,
+    /// Why we observed the session failing, if we did.
+    pub failure: Option<DkgFailureReason>,
+    /// Whether the session concluded, either with a new key or with a failure agreed on by the
+    /// participants.
+    pub complete: bool,
+    /// How long ago the session started.
+    pub running_for: Duration,
+    /// How long until the key generation is forced into its next phase. `None` if the session is
+    /// complete or hasn't exchanged any message yet.
+    pub next_phase_in: Option<Duration>,
+}
+
+/// Why a DKG participant considers the session failed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DkgFailureReason {
+    /// The key generation failed to move to its next phase.
+    Stalled(String),
+    /// The key generation finished without the contributions of these participants.
+    MissingParticipants(BTreeSet<XorName>),
+    /// The generated key share doesn't match the generated public key set. This happens when the
+    /// messages of a session restarted with the same generation mix with the original ones.
+    CorruptedOutcome,
+    /// The session was aborted using `Routing::abort_dkg`.
+    Aborted,
+}
+
 /// DKG voter carries out the work of participating and/or observing a DKG.
 ///
 /// # Usage
@@ -127,9 +173,10 @@ impl DkgVoter {
 
         // Special case: only one participant.
         if elders_info.elders.len() == 1 {
-            let secret_key_set = bls::SecretKeySet::random(0, &mut rand::thread_rng());
+            let secret_key_set = bls::SecretKeySet::random(0, &mut MainRng);
 
             return vec![DkgCommand::HandleOutcome {
+                dkg_key,
                 elders_info,
                 outcome: SectionKeyShare {
                     public_key_set: secret_key_set.public_keys(),
@@ -156,18 +203,20 @@ impl DkgVoter {
                     elders_info,
                     participant_index,
                     timer_token: 0,
+                    timer_deadline: None,
                     failures: Default::default(),
+                    failure_observers: BTreeSet::new(),
+                    failure: None,
+                    responded: iter::once(name).collect(),
                     complete: false,
+                    started: Instant::now(),
                 };
 
                 let mut commands = vec![];
                 commands.extend(session.broadcast(&dkg_key, keypair, message));
-                commands.extend(
-                    self.backlog
-                        .take(&dkg_key)
-                        .into_iter()
-                        .flat_map(|message| session.process_message(&dkg_key, keypair, message)),
-                );
+                for (sender, message) in self.backlog.take(&dkg_key) {
+                    commands.extend(session.receive(&dkg_key, keypair, sender, message));
+                }
 
                 let _ = self.sessions.insert(dkg_key, session);
 
@@ -200,17 +249,18 @@ impl DkgVoter {
         }
     }
 
-    // Handle a received DkgMessage.
+    // Handle a DkgMessage received from `sender`.
     pub fn process_message(
         &mut self,
         keypair: &Keypair,
         dkg_key: &DkgKey,
+        sender: XorName,
         message: DkgMessage,
     ) -> Vec<DkgCommand> {
         if let Some(session) = self.sessions.get_mut(dkg_key) {
-            session.process_message(dkg_key, keypair, message)
+            session.receive(dkg_key, keypair, sender, message)
         } else {
-            self.backlog.push(*dkg_key, message);
+            self.backlog.push(*dkg_key, sender, message);
             vec![]
         }
     }
@@ -224,6 +274,48 @@ impl DkgVoter {
             .get_mut(dkg_key)?
             .process_failure(dkg_key, proof)
     }
+
+    // Returns the current state of all the sessions, the most recent generations first.
+    pub fn sessions(&self) -> Vec<DkgSessionInfo> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|(dkg_key, session)| session.info(dkg_key))
+            .collect();
+        sessions.sort_by(|lhs, rhs| rhs.dkg_key.generation.cmp(&lhs.dkg_key.generation));
+        sessions
+    }
+
+    pub fn session(&self, dkg_key: &DkgKey) -> Option<DkgSessionInfo> {
+        self.sessions
+            .get(dkg_key)
+            .map(|session| session.info(dkg_key))
+    }
+
+    // Stops our part in the key generation and reports the session as failed to the other
+    // participants, so they can agree on the failure and have it restarted. Returns `None` if
+    // there is no such session.
+    pub fn abort(&mut self, keypair: &Keypair, dkg_key: &DkgKey) -> Option<Vec<DkgCommand>> {
+        let session = self.sessions.get_mut(dkg_key)?;
+        if session.complete {
+            return Some(vec![]);
+        }
+
+        trace!("DKG for {} aborted", session.elders_info);
+
+        let commands = session.report_failure(dkg_key, keypair, DkgFailureReason::Aborted);
+        session.complete = true;
+
+        Some(commands)
+    }
+
+    // Returns the time elapsed since the start of the completed session for the given elders.
+    pub fn session_duration(&self, elders_info: &EldersInfo) -> Option<Duration> {
+        self.sessions
+            .values()
+            .find(|session| session.complete && session.elders_info == *elders_info)
+            .map(|session| session.started.elapsed())
+    }
 }
 
 // Data for a DKG participant.
@@ -232,14 +324,38 @@ struct Session {
     participant_index: usize,
     key_gen: KeyGen,
     timer_token: u64,
+    // When the timer with `timer_token` fires.
+    timer_deadline: Option<Instant>,
     failures: DkgFailureProofSet,
+    // Participants that observed the session failing, kept after `failures` is taken.
+    failure_observers: BTreeSet<XorName>,
+    // Why we observed the session failing.
+    failure: Option<DkgFailureReason>,
+    // Participants we received messages from.
+    responded: BTreeSet<XorName>,
     // Flag to track whether this session has completed (either with success or failure). We don't
     // remove complete sessions because the other participants might still need us to respond to
     // their messages.
     complete: bool,
+    started: Instant,
 }
 
 impl Session {
+    // Handle a message received from another participant.
+    fn receive(
+        &mut self,
+        dkg_key: &DkgKey,
+        keypair: &Keypair,
+        sender: XorName,
+        message: DkgMessage,
+    ) -> Vec<DkgCommand> {
+        if self.elders_info.elders.contains_key(&sender) {
+            let _ = self.responded.insert(sender);
+        }
+
+        self.process_message(dkg_key, keypair, message)
+    }
+
     fn process_message(
         &mut self,
         dkg_key: &DkgKey,
@@ -249,7 +365,7 @@ impl Session {
         trace!("process DKG message {:?}", message);
         let responses = self
             .key_gen
-            .handle_message(&mut rand::thread_rng(), message)
+            .handle_message(&mut MainRng, message)
             .unwrap_or_default();
 
         // Only a valid DkgMessage, which results in some responses, shall reset the ticker.
@@ -307,7 +423,7 @@ impl Session {
 
         trace!("DKG for {} progressing", self.elders_info);
 
-        match self.key_gen.timed_phase_transition(&mut rand::thread_rng()) {
+        match self.key_gen.timed_phase_transition(&mut MainRng) {
             Ok(messages) => {
                 let mut commands: Vec<_> = messages
                     .into_iter()
@@ -319,7 +435,11 @@ impl Session {
             }
             Err(error) => {
                 trace!("DKG for {} failed: {}", self.elders_info, error);
-                self.report_failure(dkg_key, keypair)
+                self.report_failure(
+                    dkg_key,
+                    keypair,
+                    DkgFailureReason::Stalled(error.to_string()),
+                )
             }
         }
     }
@@ -348,7 +468,19 @@ impl Session {
                 participants.iter().format(", ")
             );
 
-            return self.report_failure(dkg_key, keypair);
+            let missing = self
+                .elders_info
+                .elders
+                .keys()
+                .filter(|name| !participants.contains(*name))
+                .copied()
+                .collect();
+
+            return self.report_failure(
+                dkg_key,
+                keypair,
+                DkgFailureReason::MissingParticipants(missing),
+            );
         }
 
         // Corrupted DKG outcome. This can happen when a DKG session is restarted using the same set
@@ -361,7 +493,7 @@ impl Session {
             != outcome.secret_key_share.public_key_share()
         {
             trace!("DKG for {} failed: corrupted outcome", self.elders_info);
-            return self.report_failure(dkg_key, keypair);
+            return self.report_failure(dkg_key, keypair, DkgFailureReason::CorruptedOutcome);
         }
 
         trace!(
@@ -379,19 +511,30 @@ impl Session {
         };
 
         vec![DkgCommand::HandleOutcome {
+            dkg_key: *dkg_key,
             elders_info: self.elders_info.clone(),
             outcome,
         }]
     }
 
-    fn report_failure(&mut self, dkg_key: &DkgKey, keypair: &Keypair) -> Vec<DkgCommand> {
+    fn report_failure(
+        &mut self,
+        dkg_key: &DkgKey,
+        keypair: &Keypair,
+        reason: DkgFailureReason,
+    ) -> Vec<DkgCommand> {
         let proof = DkgFailureProof::new(keypair, dkg_key);
 
         if !self.failures.insert(proof) {
             return vec![];
         }
 
-        self.check_failure_agreement()
+        let _ = self
+            .failure_observers
+            .insert(crypto::name(&proof.public_key));
+        self.failure = Some(reason);
+
+        self.check_failure_agreement(dkg_key)
             .into_iter()
             .chain(iter::once(DkgCommand::SendFailureObservation {
                 recipients: self.recipients(),
@@ -418,16 +561,20 @@ impl Session {
             return None;
         }
 
-        self.check_failure_agreement()
+        let _ = self
+            .failure_observers
+            .insert(crypto::name(&proof.public_key));
+        self.check_failure_agreement(dkg_key)
     }
 
-    fn check_failure_agreement(&mut self) -> Option<DkgCommand> {
+    fn check_failure_agreement(&mut self, dkg_key: &DkgKey) -> Option<DkgCommand> {
         if self.failures.has_agreement(&self.elders_info) {
             self.complete = true;
 
-            Some(DkgCommand::HandleFailureAgreement(mem::take(
-                &mut self.failures,
-            )))
+            Some(DkgCommand::HandleFailureAgreement {
+                dkg_key: *dkg_key,
+                proofs: mem::take(&mut self.failures),
+            })
         } else {
             None
         }
@@ -435,11 +582,32 @@ impl Session {
 
     fn reset_timer(&mut self) -> DkgCommand {
         self.timer_token = command::next_timer_token();
+        self.timer_deadline = Some(Instant::now() + DKG_PROGRESS_INTERVAL);
         DkgCommand::ScheduleTimeout {
             duration: DKG_PROGRESS_INTERVAL,
             token: self.timer_token,
         }
     }
+
+    fn info(&self, dkg_key: &DkgKey) -> DkgSessionInfo {
+        let next_phase_in = if self.complete {
+            None
+        } else {
+            self.timer_deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+        };
+
+        DkgSessionInfo {
+            dkg_key: *dkg_key,
+            elders_info: self.elders_info.clone(),
+            responded: self.responded.clone(),
+            failure_observers: self.failure_observers.clone(),
+            failure: self.failure.clone(),
+            complete: self.complete,
+            running_for: self.started.elapsed(),
+            next_phase_in,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -449,7 +617,7 @@ pub(crate) struct DkgFailureProof {
 }
 
 impl DkgFailureProof {
-    fn new(keypair: &Keypair, dkg_key: &DkgKey) -> Self {
+    pub fn new(keypair: &Keypair, dkg_key: &DkgKey) -> Self {
         Self {
             public_key: keypair.public,
             signature: crypto::sign(&failure_proof_hash(dkg_key), keypair),
@@ -502,6 +670,11 @@ impl DkgFailureProofSet {
 
         has_failure_agreement(elders_info.elders.len(), votes)
     }
+
+    // Names of the participants that signed the proofs in this set.
+    pub fn signers(&self) -> impl Iterator<Item = XorName> + '_ {
+        self.0.iter().map(|proof| crypto::name(&proof.public_key))
+    }
 }
 
 // Check whether we have enough proofs to reach agreement on the failure. We only need
@@ -523,31 +696,31 @@ fn failure_proof_hash(dkg_key: &DkgKey) -> Digest256 {
     hash
 }
 
-struct Backlog(VecDeque<(DkgKey, DkgMessage)>);
+struct Backlog(VecDeque<(DkgKey, XorName, DkgMessage)>);
 
 impl Backlog {
     fn new() -> Self {
         Self(VecDeque::with_capacity(BACKLOG_CAPACITY))
     }
 
-    fn push(&mut self, dkg_key: DkgKey, message: DkgMessage) {
+    fn push(&mut self, dkg_key: DkgKey, sender: XorName, message: DkgMessage) {
         if self.0.len() == self.0.capacity() {
             let _ = self.0.pop_front();
         }
 
-        self.0.push_back((dkg_key, message))
+        self.0.push_back((dkg_key, sender, message))
     }
 
-    fn take(&mut self, dkg_key: &DkgKey) -> Vec<DkgMessage> {
+    fn take(&mut self, dkg_key: &DkgKey) -> Vec<(XorName, DkgMessage)> {
         let mut output = Vec::new();
         let max = self.0.len();
 
         for _ in 0..max {
-            if let Some((message_dkg_key, message)) = self.0.pop_front() {
+            if let Some((message_dkg_key, sender, message)) = self.0.pop_front() {
                 if &message_dkg_key == dkg_key {
-                    output.push(message)
+                    output.push((sender, message))
                 } else {
-                    self.0.push_back((message_dkg_key, message))
+                    self.0.push_back((message_dkg_key, sender, message))
                 }
             }
         }
@@ -557,7 +730,7 @@ impl Backlog {
 
     fn prune(&mut self, dkg_key: &DkgKey) {
         self.0
-            .retain(|(old_dkg_key, _)| old_dkg_key.generation >= dkg_key.generation)
+            .retain(|(old_dkg_key, _, _)| old_dkg_key.generation >= dkg_key.generation)
     }
 }
 
@@ -573,6 +746,7 @@ pub(crate) enum DkgCommand {
         token: u64,
     },
     HandleOutcome {
+        dkg_key: DkgKey,
         elders_info: EldersInfo,
         outcome: SectionKeyShare,
     },
@@ -581,7 +755,10 @@ pub(crate) enum DkgCommand {
         dkg_key: DkgKey,
         proof: DkgFailureProof,
     },
-    HandleFailureAgreement(DkgFailureProofSet),
+    HandleFailureAgreement {
+        dkg_key: DkgKey,
+        proofs: DkgFailureProofSet,
+    },
 }
 
 impl DkgCommand {
@@ -598,16 +775,18 @@ impl DkgCommand {
                 Ok(Command::send_message_to_nodes(
                     &recipients,
                     recipients.len(),
-                    message.to_bytes(),
+                    &message,
                 ))
             }
             Self::ScheduleTimeout { duration, token } => {
-                Ok(Command::ScheduleTimeout { duration, token })
+                Ok(Command::ScheduleDkgTimeout { duration, token })
             }
             Self::HandleOutcome {
+                dkg_key,
                 elders_info,
                 outcome,
             } => Ok(Command::HandleDkgOutcome {
+                dkg_key,
                 elders_info,
                 outcome,
             }),
@@ -622,10 +801,12 @@ impl DkgCommand {
                 Ok(Command::send_message_to_nodes(
                     &recipients,
                     recipients.len(),
-                    message.to_bytes(),
+                    &message,
                 ))
             }
-            Self::HandleFailureAgreement(proofs) => Ok(Command::HandleDkgFailure(proofs)),
+            Self::HandleFailureAgreement { dkg_key, proofs } => {
+                Ok(Command::HandleDkgFailure { dkg_key, proofs })
+            }
         }
     }
 }
@@ -680,6 +861,56 @@ mod tests {
         assert_matches!(&commands[..], &[DkgCommand::HandleOutcome { .. }]);
     }
 
+    #[test]
+    fn list_and_abort_session() {
+        let nodes: Vec<_> = (0..2)
+            .map(|_| {
+                Node::new(
+                    crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+                    gen_addr(),
+                )
+            })
+            .collect();
+        let elders_info = EldersInfo::new(nodes.iter().map(Node::peer), Prefix::default());
+        let dkg_key = DkgKey::new(&elders_info, 0);
+
+        let mut voter = DkgVoter::default();
+        let _ = voter.start(&nodes[0].keypair, dkg_key, elders_info.clone());
+
+        let sessions = voter.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].dkg_key, dkg_key);
+        assert_eq!(sessions[0].elders_info, elders_info);
+        assert_eq!(sessions[0].responded, iter::once(nodes[0].name()).collect());
+        assert_eq!(sessions[0].failure, None);
+        assert!(!sessions[0].complete);
+
+        // With two participants, our own failure observation already makes an agreement.
+        let commands = voter
+            .abort(&nodes[0].keypair, &dkg_key)
+            .expect("session not found");
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            DkgCommand::SendFailureObservation { dkg_key: key, .. } if *key == dkg_key
+        )));
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            DkgCommand::HandleFailureAgreement { dkg_key: key, .. } if *key == dkg_key
+        )));
+
+        // The session is kept so we can still respond to the other participants.
+        let info = voter.session(&dkg_key).expect("session not found");
+        assert_eq!(info.failure, Some(DkgFailureReason::Aborted));
+        assert!(info.complete);
+
+        assert_matches!(voter.abort(&nodes[0].keypair, &dkg_key), Some(commands) => {
+            assert!(commands.is_empty())
+        });
+        assert!(voter
+            .abort(&nodes[0].keypair, &DkgKey::new(&elders_info, 1))
+            .is_none());
+    }
+
     proptest! {
         // Run a DKG session where every participant handles every message sent to them.
         // Expect the session to successfully complete without timed transitions.
@@ -728,12 +959,13 @@ mod tests {
             // NOTE: this panics if `messages` is empty, but that's OK because it would mean
             // failure anyway.
             let index = rng.gen_range(0, messages.len());
-            let (addr, message) = messages.swap_remove(index);
+            let (addr, sender, message) = messages.swap_remove(index);
 
             let actor = actors.get_mut(&addr).expect("unknown message recipient");
-            let commands = actor
-                .voter
-                .process_message(&actor.node.keypair, &dkg_key, message);
+            let commands =
+                actor
+                    .voter
+                    .process_message(&actor.node.keypair, &dkg_key, sender, message);
 
             for command in commands {
                 messages.extend(actor.handle(command, &dkg_key))
@@ -760,7 +992,7 @@ mod tests {
             &mut self,
             command: DkgCommand,
             expected_dkg_key: &DkgKey,
-        ) -> Vec<(SocketAddr, DkgMessage)> {
+        ) -> Vec<(SocketAddr, XorName, DkgMessage)> {
             match command {
                 DkgCommand::SendMessage {
                     recipients,
@@ -771,7 +1003,7 @@ mod tests {
                     assert_eq!(dkg_key, *expected_dkg_key);
                     recipients
                         .into_iter()
-                        .map(|addr| (addr, message.clone()))
+                        .map(|addr| (addr, self.node.name(), message.clone()))
                         .collect()
                 }
                 DkgCommand::HandleOutcome { outcome, .. } => {
@@ -12,11 +12,14 @@ mod proven;
 #[cfg(test)]
 pub mod test_utils;
 
-pub use self::{dkg::DkgKey, proven::Proven};
 pub(crate) use self::{
     dkg::{DkgCommands, DkgFailureProof, DkgFailureProofSet, DkgVoter},
     proposal::{Proposal, ProposalAggregationError, ProposalAggregator},
 };
+pub use self::{
+    dkg::{DkgFailureReason, DkgKey, DkgSessionInfo},
+    proven::Proven,
+};
 pub(crate) use bls_signature_aggregator::{Proof, ProofShare, SignatureAggregator};
 use serde::Serialize;
 
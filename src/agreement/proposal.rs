@@ -10,9 +10,14 @@ use super::{Proof, ProofShare, Proven, SignatureAggregator};
 use crate::{
     error::Result,
     messages::PlainMessage,
-    section::{EldersInfo, MemberInfo, SectionChain},
+    metrics::AggregationTimer,
+    network_params::ResourceProofParams,
+    protocol::ProtocolUpgrade,
+    section::{Checkpoint, EldersInfo, MemberInfo, SectionChain},
 };
-use serde::{Deserialize, Serialize, Serializer};
+use bytes::Bytes;
+use serde::{ser::SerializeTuple, Deserialize, Serialize, Serializer};
+use std::time::Duration;
 use thiserror::Error;
 use xor_name::{Prefix, XorName};
 
@@ -73,6 +78,22 @@ pub(crate) enum Proposal {
 
     // Proposal to change whether new nodes are allowed to join our section.
     JoinsAllowed(bool),
+
+    // Proposal to change the resource proof challenge issued to the nodes joining our section.
+    ResourceProof(ResourceProofParams),
+
+    // Proposal to prune our section chain at the checkpoint.
+    Checkpoint(Checkpoint),
+
+    // Proposal to prove the unchanged info of a member again with our current key, so our section
+    // chain can be pruned past the key it was proven with.
+    Reprove(MemberInfo),
+
+    // Proposal to sign an application payload (a serialized `SectionPayload`) with the section key.
+    SectionSigned(Bytes),
+
+    // Proposal to switch our section to a newer protocol version.
+    ProtocolUpgrade(ProtocolUpgrade),
 }
 
 impl Proposal {
@@ -113,23 +134,42 @@ impl<'a> Serialize for SignableView<'a> {
                 message.as_signable().serialize(serializer)
             }
             Proposal::JoinsAllowed(joins_allowed) => joins_allowed.serialize(serializer),
+            Proposal::ResourceProof(params) => params.serialize(serializer),
+            Proposal::Checkpoint(checkpoint) => checkpoint.serialize(serializer),
+            Proposal::Reprove(member_info) => member_info.serialize(serializer),
+            // Signed as is, so the signature verifies against the serialized payload.
+            Proposal::SectionSigned(payload) => {
+                let mut tuple = serializer.serialize_tuple(payload.len())?;
+                for byte in payload.iter() {
+                    tuple.serialize_element(byte)?;
+                }
+                tuple.end()
+            }
+            Proposal::ProtocolUpgrade(upgrade) => upgrade.serialize(serializer),
         }
     }
 }
 
 // Aggregator of `Proposal`s.
 #[derive(Default)]
-pub(crate) struct ProposalAggregator(SignatureAggregator);
+pub(crate) struct ProposalAggregator {
+    aggregator: SignatureAggregator,
+    timer: AggregationTimer,
+}
 
 impl ProposalAggregator {
+    // On success, returns also the time it took the proposal to aggregate since its first share
+    // was added.
     pub fn add(
         &mut self,
         proposal: Proposal,
         proof_share: ProofShare,
-    ) -> Result<(Proposal, Proof), ProposalAggregationError> {
+    ) -> Result<(Proposal, Proof, Duration), ProposalAggregationError> {
         let bytes = bincode::serialize(&SignableView(&proposal))?;
-        let proof = self.0.add(&bytes, proof_share)?;
-        Ok((proposal, proof))
+        self.timer.start(&bytes);
+        let proof = self.aggregator.add(&bytes, proof_share)?;
+        let latency = self.timer.stop(&bytes).unwrap_or_default();
+        Ok((proposal, proof, latency))
     }
 }
 
@@ -144,7 +184,7 @@ pub(crate) enum ProposalAggregationError {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{agreement, section};
+    use crate::{agreement, routing::SectionPayload, section};
     use anyhow::Result;
     use rand::Rng;
     use std::fmt::Debug;
@@ -175,6 +215,11 @@ mod tests {
         let proposal = Proposal::TheirKnowledge { prefix, key };
         verify_serialize_for_signing(&proposal, &(prefix, key))?;
 
+        // Proposal::SectionSigned
+        let payload = SectionPayload(rand::random::<u64>());
+        let proposal = Proposal::SectionSigned(bincode::serialize(&payload)?.into());
+        verify_serialize_for_signing(&proposal, &payload)?;
+
         Ok(())
     }
 
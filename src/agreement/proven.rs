@@ -6,30 +6,90 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{verify_proof, Proof};
-use crate::section::SectionChain;
-use serde::{Deserialize, Serialize};
-use std::{borrow::Borrow, fmt::Debug};
+use super::Proof;
+use crate::{section::SectionChain, wire};
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    ser::Error as _,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    borrow::Borrow,
+    fmt::{self, Debug, Formatter},
+};
 use xor_name::Prefix;
 
 /// A value together with the proof that it was agreed on by the majority of the section elders.
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Proven<T: Serialize> {
+    /// The agreed on value.
     pub value: T,
+    /// Proof of the section signature on the serialized `value`.
     pub proof: Proof,
+    // Fields appended to `value` by newer versions of the wire format. They are signed too.
+    unknown: wire::Unknown,
 }
 
 impl<T: Serialize> Proven<T> {
+    /// Creates `Proven` from the value and its proof.
     pub fn new(value: T, proof: Proof) -> Self {
-        Self { value, proof }
+        Self {
+            value,
+            proof,
+            unknown: wire::Unknown::default(),
+        }
     }
 
+    /// Returns whether the proof is valid and signed by a key from `section_chain`.
     pub fn verify(&self, section_chain: &SectionChain) -> bool {
         section_chain.has_key(&self.proof.public_key) && self.self_verify()
     }
 
+    /// Returns whether the proof is valid, without checking the signing key is trusted.
     pub fn self_verify(&self) -> bool {
-        verify_proof(&self.proof, &self.value)
+        self.signed_bytes()
+            .map(|bytes| self.proof.verify(&bytes))
+            .unwrap_or(false)
+    }
+
+    /// Returns the bytes the proof signs: the serialized `value`, followed by the fields appended
+    /// to it by newer versions if it was received from a node running one.
+    pub fn signed_bytes(&self) -> bincode::Result<Vec<u8>> {
+        wire::encode(&self.value, &self.unknown).map(|(_, body)| body)
+    }
+}
+
+impl<T: Serialize + Debug> Debug for Proven<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Proven")
+            .field("value", &self.value)
+            .field("proof", &self.proof)
+            .finish()
+    }
+}
+
+// `Proven` uses the versioned wire format (see the `wire` module). The body of its envelope is the
+// value, followed by the proof.
+impl<T: Serialize> Serialize for Proven<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (version, body) = wire::encode(&self.value, &self.unknown).map_err(S::Error::custom)?;
+        (version, body, &self.proof).serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Proven<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (version, body, proof) = <(u16, Vec<u8>, Proof)>::deserialize(deserializer)?;
+        let (value, unknown) = wire::decode(version, &body).map_err(D::Error::custom)?;
+        Ok(Self {
+            value,
+            proof,
+            unknown,
+        })
     }
 }
 
@@ -12,6 +12,7 @@ pub use ed25519_dalek::{
     Keypair, PublicKey, SecretKey, Signature, Verifier, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH,
 };
 
+use crate::rng::MainRng;
 use ed25519_dalek::ExpandedSecretKey;
 use std::ops::RangeInclusive;
 use xor_name::{XorName, XOR_NAME_LEN};
@@ -57,7 +58,7 @@ pub fn gen_name_with_age(age: u8) -> XorName {
 /// Construct a `Keypair` whose name is in the interval [start, end] (both endpoints inclusive).
 /// And the last byte equals to the targeted age.
 pub fn gen_keypair(range: &RangeInclusive<XorName>, age: u8) -> Keypair {
-    let mut rng = rand::thread_rng();
+    let mut rng = MainRng;
 
     loop {
         let keypair = Keypair::generate(&mut rng);
@@ -13,7 +13,6 @@ use crate::{
     network::Network,
     peer::Peer,
     section::Section,
-    ELDER_SIZE,
 };
 use itertools::Itertools;
 use sn_messaging::DstLocation;
@@ -111,17 +110,18 @@ fn candidates(
         .sorted_by(|lhs, rhs| lhs.prefix.cmp_distance(&rhs.prefix, target_name))
         .map(|info| (&info.prefix, info.elders.len(), info.elders.values()));
 
-    let mut dg_size = ELDER_SIZE;
+    let elder_size = section.network_params().elder_size;
+    let mut dg_size = elder_size;
     let mut nodes_to_send = Vec::new();
     for (idx, (prefix, len, connected)) in sections.enumerate() {
         nodes_to_send.extend(connected.cloned());
         // If we don't have enough contacts send to as many as possible
         // up to majority of Elders
         dg_size = cmp::min(len, dg_size);
-        if len < ELDER_SIZE {
+        if len < elder_size {
             warn!(
                 "Delivery group only {:?} when it should be {:?}",
-                len, ELDER_SIZE
+                len, elder_size
             )
         }
 
@@ -154,9 +154,9 @@ fn get_peer<'a>(name: &XorName, section: &'a Section, network: &'a Network) -> O
         .or_else(|| network.get_elder(name))
 }
 
-// Returns the set of peers that are responsible for collecting signatures to verify a message;
-// this may contain us or only other nodes.
-pub fn signature_targets<I>(dst: &DstLocation, our_elders: I) -> Vec<Peer>
+// Returns the set of (at most `elder_size`) peers that are responsible for collecting signatures
+// to verify a message; this may contain us or only other nodes.
+pub fn signature_targets<I>(dst: &DstLocation, our_elders: I, elder_size: usize) -> Vec<Peer>
 where
     I: IntoIterator<Item = Peer>,
 {
@@ -173,7 +173,7 @@ where
         .into_iter()
         .sorted_by(|lhs, rhs| dst_name.cmp_distance(lhs.name(), rhs.name()))
         .collect();
-    list.truncate(cmp::min(list.len(), ELDER_SIZE));
+    list.truncate(cmp::min(list.len(), elder_size));
     list
 }
 
@@ -183,10 +183,12 @@ mod tests {
     use crate::{
         agreement::test_utils::proven,
         crypto,
+        network_params::NetworkParams,
         section::{
             test_utils::{gen_addr, gen_elders_info},
             EldersInfo, MemberInfo, SectionChain, MIN_AGE,
         },
+        ELDER_SIZE,
     };
     use anyhow::{Context, Result};
     use rand::seq::IteratorRandom;
@@ -408,7 +410,12 @@ mod tests {
         let elders0: Vec<_> = elders_info0.peers().copied().collect();
         let elders_info0 = proven(&sk, elders_info0)?;
 
-        let mut section = Section::new(pk, chain, elders_info0)?;
+        let mut section = Section::new(
+            pk,
+            proven(&sk, NetworkParams::default())?,
+            chain,
+            elders_info0,
+        )?;
 
         for peer in elders0 {
             let member_info = MemberInfo::joined(peer);
@@ -436,7 +443,12 @@ mod tests {
 
         let (elders_info, _) = gen_elders_info(prefix0, ELDER_SIZE);
         let elders_info = proven(&sk, elders_info)?;
-        let section = Section::new(pk, chain, elders_info)?;
+        let section = Section::new(
+            pk,
+            proven(&sk, NetworkParams::default())?,
+            chain,
+            elders_info,
+        )?;
 
         let network = Network::new();
         let our_name = section.prefix().substituted_in(rand::random());
@@ -6,7 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::section::SectionChainError;
+use crate::{
+    messages::JoinRejectionReason, protocol::ProtocolInfo, routing::BootstrapPhase,
+    section::SectionChainError,
+};
 use thiserror::Error;
 
 /// The type returned by the sn_routing message handling methods.
@@ -42,4 +45,36 @@ pub enum Error {
     InvalidSectionChain(#[from] SectionChainError),
     #[error("Messaging protocol error: {0}")]
     Messaging(#[from] sn_messaging::Error),
+    #[error("State store error: {0}")]
+    StateStore(#[from] std::io::Error),
+    #[error("The persisted node state is invalid.")]
+    InvalidStoredState,
+    #[error("The network parameters are invalid or don't match the ones of the network.")]
+    InvalidNetworkParams,
+    #[error("The node config is invalid: {0}.")]
+    InvalidConfig(&'static str),
+    #[error("The delivery of the message was not confirmed.")]
+    DeliveryFailed,
+    #[error("The delivery of the same message is already waiting to be confirmed.")]
+    DeliveryPending,
+    #[error("No response to the request arrived in time.")]
+    RequestTimeout,
+    #[error("The request was cancelled.")]
+    RequestCancelled,
+    #[error("The maximum number of connected clients has been reached.")]
+    TooManyClients,
+    #[error("The client has reached the maximum number of its connections.")]
+    TooManyClientConnections,
+    #[error("The section rejected the request to join it: {0:?}.")]
+    JoinRejected(JoinRejectionReason),
+    #[error("Bootstrapping timed out in the {0:?} phase.")]
+    BootstrapTimeout(BootstrapPhase),
+    #[error("The section didn't sign the payload in time.")]
+    SectionSigningTimeout,
+    #[error("The peer speaks no protocol version we speak: {0:?}.")]
+    IncompatibleProtocol(ProtocolInfo),
+    #[error("Some of our elders don't support the feature.")]
+    FeatureNotSupported,
+    #[error("The node was relocated before the operation completed.")]
+    Relocated,
 }
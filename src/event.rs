@@ -6,7 +6,11 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::section::SectionChain;
+use crate::{
+    agreement::{DkgFailureReason, DkgKey},
+    messages::MessageHash,
+    section::{EldersInfo, SectionChain},
+};
 use bytes::Bytes;
 use ed25519_dalek::Keypair;
 use hex_fmt::HexFmt;
@@ -40,6 +44,7 @@ pub enum NodeElderChange {
 /// `Request` and `Response` events from section locations are only raised once the majority has
 /// been reached, i.e. enough members of the section have sent the same message.
 #[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
 pub enum Event {
     /// Received a message.
     MessageReceived {
@@ -52,6 +57,20 @@ pub enum Event {
         /// The proof chain for the message, if any.
         proof_chain: Option<SectionChain>,
     },
+    /// Received a request sent with `Routing::send_request`. Reply to it with
+    /// `Routing::send_response`, passing it the `id`, `src` and `dst` of this event.
+    RequestReceived {
+        /// Id correlating the request with its response.
+        id: MessageHash,
+        /// The content of the request.
+        content: Bytes,
+        /// The source location that sent the request and expects the response.
+        src: SrcLocation,
+        /// The destination location that receives the request.
+        dst: DstLocation,
+        /// The proof chain for the request, if any.
+        proof_chain: Option<SectionChain>,
+    },
     /// A new peer joined our section.
     MemberJoined {
         /// Name of the node
@@ -105,8 +124,53 @@ pub enum Event {
         /// (Note: socket_id will be a random hash, to map against the actual socketaddr)
         user: EndUser,
     },
-    /// Failed in sending a message to client, or connection to client is lost
+    /// Failed in sending a message to client, connection to client is lost, or the client was
+    /// disconnected for being idle or by `Routing::disconnect_client`.
     ClientLost(SocketAddr),
+    /// Our section switched to a newer version of the routing protocol, after enough of its elders
+    /// started supporting it.
+    ProtocolUpgraded {
+        /// The new protocol version of the section.
+        version: u16,
+    },
+    /// A fork was detected in our section chain: a key of our section signed more than one key,
+    /// meaning the section agreed on multiple successors of it (e.g. the outcomes of two DKG
+    /// sessions got both signed). Raised again if the fork gains more keys.
+    SectionChainForked {
+        /// The key that signed all of `keys`.
+        parent: bls::PublicKey,
+        /// The keys signed by `parent`, in the section chain order.
+        keys: Vec<bls::PublicKey>,
+    },
+    /// We started taking part in a DKG session as one of the proposed new elders.
+    DkgStarted {
+        /// Identifier of the session.
+        dkg_key: DkgKey,
+        /// The proposed elders.
+        elders_info: EldersInfo,
+    },
+    /// A DKG session we take part in generated a new section key.
+    DkgCompleted {
+        /// Identifier of the session.
+        dkg_key: DkgKey,
+        /// The proposed elders.
+        elders_info: EldersInfo,
+        /// The generated section key.
+        public_key: bls::PublicKey,
+    },
+    /// A DKG session we take part in failed, because enough of its participants observed it
+    /// failing or because it was aborted with `Routing::abort_dkg`.
+    DkgFailed {
+        /// Identifier of the session.
+        dkg_key: DkgKey,
+        /// The proposed elders.
+        elders_info: EldersInfo,
+        /// Why we observed the session failing. `None` if we didn't, but enough of the other
+        /// participants did.
+        reason: Option<DkgFailureReason>,
+        /// Participants that observed the session failing.
+        observers: BTreeSet<XorName>,
+    },
 }
 
 impl Debug for Event {
@@ -121,6 +185,20 @@ impl Debug for Event {
                 src,
                 dst
             ),
+            Self::RequestReceived {
+                id,
+                content,
+                src,
+                dst,
+                ..
+            } => write!(
+                formatter,
+                "RequestReceived {{ id: {:?}, content: \"{:<8}\", src: {:?}, dst: {:?} }}",
+                id,
+                HexFmt(content),
+                src,
+                dst
+            ),
             Self::MemberJoined {
                 name,
                 previous_name,
@@ -169,6 +247,45 @@ impl Debug for Event {
                 msg, user,
             ),
             Self::ClientLost(addr) => write!(formatter, "ClientLost({:?})", addr),
+            Self::ProtocolUpgraded { version } => formatter
+                .debug_struct("ProtocolUpgraded")
+                .field("version", version)
+                .finish(),
+            Self::SectionChainForked { parent, keys } => formatter
+                .debug_struct("SectionChainForked")
+                .field("parent", parent)
+                .field("keys", keys)
+                .finish(),
+            Self::DkgStarted {
+                dkg_key,
+                elders_info,
+            } => formatter
+                .debug_struct("DkgStarted")
+                .field("dkg_key", dkg_key)
+                .field("elders_info", elders_info)
+                .finish(),
+            Self::DkgCompleted {
+                dkg_key,
+                elders_info,
+                public_key,
+            } => formatter
+                .debug_struct("DkgCompleted")
+                .field("dkg_key", dkg_key)
+                .field("elders_info", elders_info)
+                .field("public_key", public_key)
+                .finish(),
+            Self::DkgFailed {
+                dkg_key,
+                elders_info,
+                reason,
+                observers,
+            } => formatter
+                .debug_struct("DkgFailed")
+                .field("dkg_key", dkg_key)
+                .field("elders_info", elders_info)
+                .field("reason", reason)
+                .field("observers", observers)
+                .finish(),
         }
     }
 }
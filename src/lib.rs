@@ -70,16 +70,30 @@ extern crate tracing;
 // ############################################################################
 // Public API
 // ############################################################################
+#[cfg(feature = "simulated-network")]
+pub use self::routing::SimNetwork;
 pub use self::{
+    agreement::{DkgFailureReason, DkgKey, DkgSessionInfo, Proven},
     error::{Error, Result},
     event::{Event, NodeElderChange, SendStream},
-    routing::{Config, EventStream, Routing},
-    section::{SectionChain, SectionChainError, MIN_AGE},
+    messages::{JoinRejectionReason, MessageHash},
+    metrics::{Histogram, Metrics},
+    network_params::NetworkParams,
+    protocol::{Features, ProtocolInfo, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+    routing::{
+        BootstrapConfig, BootstrapPhase, ClientConfig, ClientInfo, Config, Delivery, EventFilter,
+        EventStream, FileStateStore, GossipConfig, JoinQueueConfig, LagPolicy, OverflowPolicy,
+        QueueConfig, QueuedJoin, RateLimitConfig, Response, Routing, SectionPayload,
+        SectionSigningApproval, StateStore, Subscription,
+    },
+    section::{Checkpoint, SectionChain, SectionChainError, SectionChainFork, MIN_AGE},
 };
 pub use qp2p::Config as TransportConfig;
 
 pub use xor_name::{Prefix, XorName, XOR_NAME_LEN}; // TODO remove pub on API update
 
+pub mod verification;
+
 // ############################################################################
 // Private
 // ############################################################################
@@ -91,20 +105,25 @@ mod error;
 mod event;
 mod message_filter;
 mod messages;
+mod metrics;
 mod network;
+mod network_params;
 mod node;
 mod peer;
+mod protocol;
 mod relocation;
+mod rng;
 mod routing;
 mod section;
+mod wire;
 
-/// Recommended section size. sn_routing will keep adding nodes until the section reaches this size.
-/// More nodes might be added if requested by the upper layers.
+/// Default recommended section size. sn_routing will keep adding nodes until the section reaches
+/// this size. More nodes might be added if requested by the upper layers.
 /// This number also detemines when split happens - if both post-split sections would have at least
-/// this number of nodes.
+/// this number of nodes. Can be changed via `NetworkParams::recommended_section_size`.
 pub const RECOMMENDED_SECTION_SIZE: usize = 2 * ELDER_SIZE;
 
-/// Number of elders per section.
+/// Default number of elders per section. Can be changed via `NetworkParams::elder_size`.
 pub const ELDER_SIZE: usize = 7;
 
 /// Supermajority of a given group (i.e. > 2/3)
@@ -11,7 +11,7 @@ mod plain_message;
 mod src_authority;
 mod variant;
 
-pub use self::{hash::MessageHash, src_authority::SrcAuthority};
+pub use self::{hash::MessageHash, src_authority::SrcAuthority, variant::JoinRejectionReason};
 pub(crate) use self::{
     plain_message::PlainMessage,
     variant::{JoinRequest, ResourceProofResponse, Variant},
@@ -20,6 +20,7 @@ use crate::{
     crypto::{self, Verifier},
     error::{Error, Result},
     node::Node,
+    protocol::ProtocolInfo,
     section::{SectionChain, SectionChainError, SectionKeyShare},
 };
 use bls_signature_aggregator::{Proof, ProofShare};
@@ -31,6 +32,11 @@ use thiserror::Error;
 use xor_name::XorName;
 
 /// Message sent over the network.
+///
+/// It's serialized as its header, the `ProtocolInfo` of the node or section that created it,
+/// followed by the rest of its fields. The layout of the header never changes, so a node decodes it
+/// whatever the protocol version of the sender and only decodes the rest when they speak a common
+/// version. The header is signed along with the body.
 #[derive(Clone, Eq, Serialize, Deserialize)]
 pub(crate) struct Message {
     /// Source authority.
@@ -50,6 +56,10 @@ pub(crate) struct Message {
     /// to determine the length of the proof of messages sent to the source so the source would
     /// trust it (the proof needs to start at this key).
     dst_key: Option<bls::PublicKey>,
+    /// Protocol versions and features of the node or section that created the message.
+    /// Serialized in the header, before the other fields.
+    #[serde(skip)]
+    protocol: ProtocolInfo,
     /// Serialised message, this is a signed and fully serialised message ready to send.
     #[serde(skip)]
     serialized: Bytes,
@@ -58,15 +68,19 @@ pub(crate) struct Message {
 }
 
 impl Message {
-    /// Deserialize the message. Only called on message receipt.
-    pub(crate) fn from_bytes(msg_bytes: Bytes) -> Result<Self, CreateError> {
-        let mut msg: Message = bincode::deserialize(&msg_bytes)?;
-
-        let signed_bytes = bincode::serialize(&SignableView {
-            dst: &msg.dst,
-            dst_key: msg.dst_key.as_ref(),
-            variant: &msg.variant,
-        })?;
+    /// Deserialize the message. Only called on message receipt. Fails if the sender speaks no
+    /// protocol version in common with `ours`.
+    pub(crate) fn from_bytes(msg_bytes: Bytes, ours: &ProtocolInfo) -> Result<Self, CreateError> {
+        // `bincode::deserialize` doesn't reject trailing bytes, so this decodes just the header.
+        let protocol: ProtocolInfo = bincode::deserialize(&msg_bytes)?;
+        if !protocol.is_compatible(ours) {
+            return Err(CreateError::IncompatibleProtocol(protocol));
+        }
+
+        let (_, mut msg): (ProtocolInfo, Message) = bincode::deserialize(&msg_bytes)?;
+        msg.protocol = protocol;
+
+        let signed_bytes = bincode::serialize(&msg.signable_view())?;
 
         match &msg.src {
             SrcAuthority::Node {
@@ -125,6 +139,7 @@ impl Message {
         variant: Variant,
         proof_chain: Option<SectionChain>,
         dst_key: Option<bls::PublicKey>,
+        protocol: ProtocolInfo,
     ) -> Result<Message, CreateError> {
         let mut msg = Message {
             dst,
@@ -133,17 +148,19 @@ impl Message {
             proof_chain,
             variant,
             dst_key,
+            protocol,
             serialized: Default::default(),
             hash: Default::default(),
         };
 
-        msg.serialized = bincode::serialize(&msg)?.into();
+        msg.serialized = bincode::serialize(&(&msg.protocol, &msg))?.into();
         msg.hash = MessageHash::from_bytes(&msg.serialized);
 
         Ok(msg)
     }
 
-    /// Creates a message signed using a BLS KeyShare for destination accumulation
+    /// Creates a message signed using a BLS KeyShare for destination accumulation. `protocol`
+    /// must be the same for all the shares, see `ProtocolInfo::section`.
     pub(crate) fn for_dst_accumulation(
         key_share: &SectionKeyShare,
         src_name: XorName,
@@ -151,8 +168,10 @@ impl Message {
         variant: Variant,
         proof_chain: SectionChain,
         dst_key: Option<bls::PublicKey>,
+        protocol: ProtocolInfo,
     ) -> Result<Self, CreateError> {
         let serialized = bincode::serialize(&SignableView {
+            protocol: &protocol,
             dst: &dst,
             dst_key: dst_key.as_ref(),
             variant: &variant,
@@ -168,7 +187,7 @@ impl Message {
             proof_share,
         };
 
-        Self::new_signed(src, dst, variant, Some(proof_chain), dst_key)
+        Self::new_signed(src, dst, variant, Some(proof_chain), dst_key, protocol)
     }
 
     /// Converts the message src authority from `BlsShare` to `Section` on successful accumulation.
@@ -211,6 +230,7 @@ impl Message {
 
     pub(crate) fn signable_view(&self) -> SignableView {
         SignableView {
+            protocol: &self.protocol,
             dst: &self.dst,
             dst_key: self.dst_key.as_ref(),
             variant: &self.variant,
@@ -226,6 +246,7 @@ impl Message {
         dst_key: Option<bls::PublicKey>,
     ) -> Result<Self, CreateError> {
         let serialized = bincode::serialize(&SignableView {
+            protocol: &node.protocol,
             dst: &dst,
             dst_key: dst_key.as_ref(),
             variant: &variant,
@@ -236,7 +257,7 @@ impl Message {
             signature,
         };
 
-        Self::new_signed(src, dst, variant, proof_chain, dst_key)
+        Self::new_signed(src, dst, variant, proof_chain, dst_key, node.protocol)
     }
 
     /// Creates a signed message from a section.
@@ -255,6 +276,7 @@ impl Message {
             plain.variant,
             Some(proof_chain),
             Some(plain.dst_key),
+            plain.protocol,
         )
     }
 
@@ -263,11 +285,7 @@ impl Message {
     where
         I: IntoIterator<Item = &'a bls::PublicKey>,
     {
-        let bytes = bincode::serialize(&SignableView {
-            dst: &self.dst,
-            dst_key: self.dst_key.as_ref(),
-            variant: &self.variant,
-        })?;
+        let bytes = bincode::serialize(&self.signable_view())?;
 
         match &self.src {
             SrcAuthority::Node {
@@ -349,6 +367,11 @@ impl Message {
         &self.hash
     }
 
+    /// Protocol versions and features of the node that created the message.
+    pub(crate) fn protocol(&self) -> &ProtocolInfo {
+        &self.protocol
+    }
+
     /// Returns the attached proof chain, if any.
     pub(crate) fn proof_chain(&self) -> Result<&SectionChain> {
         self.proof_chain.as_ref().ok_or(Error::InvalidMessage)
@@ -390,6 +413,7 @@ impl Message {
             self.variant,
             self.proof_chain,
             self.dst_key,
+            self.protocol,
         )?)
     }
 }
@@ -403,6 +427,7 @@ impl PartialEq for Message {
             && self.variant == other.variant
             && self.proof_chain == other.proof_chain
             && self.dst_key == other.dst_key
+            && self.protocol == other.protocol
     }
 }
 
@@ -449,6 +474,8 @@ pub enum CreateError {
     FailedSignature,
     #[error("public key mismatch")]
     PublicKeyMismatch,
+    #[error("sender speaks no common protocol version: {:?}", .0)]
+    IncompatibleProtocol(ProtocolInfo),
 }
 
 impl From<CreateError> for Error {
@@ -457,6 +484,7 @@ impl From<CreateError> for Error {
             CreateError::Bincode(inner) => Self::Bincode(inner),
             CreateError::FailedSignature => Self::FailedSignature,
             CreateError::PublicKeyMismatch => Self::InvalidMessage,
+            CreateError::IncompatibleProtocol(protocol) => Self::IncompatibleProtocol(protocol),
         }
     }
 }
@@ -476,6 +504,7 @@ pub enum ExtendProofChainError {
 #[derive(Serialize)]
 pub(crate) struct SignableView<'a> {
     // TODO: why don't we include also `src`?
+    pub protocol: &'a ProtocolInfo,
     pub dst: &'a DstLocation,
     pub dst_key: Option<&'a bls::PublicKey>,
     pub variant: &'a Variant,
@@ -486,7 +515,9 @@ mod tests {
     use super::*;
     use crate::{
         agreement, crypto,
+        network_params::NetworkParams,
         peer::Peer,
+        protocol::Features,
         section::{self, test_utils::gen_addr, MemberInfo},
         MIN_AGE,
     };
@@ -514,14 +545,9 @@ mod tests {
         let (elders_info, _) = section::test_utils::gen_elders_info(Default::default(), 3);
         let elders_info = agreement::test_utils::proven(&sk1, elders_info)?;
 
-        let peer = Peer::new(rand::random(), gen_addr());
-        let member_info = MemberInfo::joined(peer);
-        let member_info = agreement::test_utils::proven(&sk1, member_info)?;
-
-        let variant = Variant::NodeApproval {
-            genesis_key: pk0,
+        let variant = Variant::OtherSection {
             elders_info,
-            member_info,
+            nonce: MessageHash::from_bytes(b"nonce"),
         };
         let message = Message::single_src(
             &node,
@@ -540,4 +566,153 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn node_approval_network_params_signed_with_genesis_key() -> Result<()> {
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+
+        let sk0 = bls::SecretKey::random();
+        let pk0 = sk0.public_key();
+
+        let sk1 = bls::SecretKey::random();
+        let pk1 = sk1.public_key();
+
+        let mut proof_chain = SectionChain::new(pk0);
+        let pk1_sig = sk0.sign(&bincode::serialize(&pk1)?);
+        let _ = proof_chain.insert(&pk0, pk1, pk1_sig);
+
+        let (elders_info, _) = section::test_utils::gen_elders_info(Default::default(), 3);
+        let elders_info = agreement::test_utils::proven(&sk1, elders_info)?;
+
+        let peer = Peer::new(rand::random(), gen_addr());
+        let member_info = agreement::test_utils::proven(&sk1, MemberInfo::joined(peer))?;
+
+        let approval = |network_params: agreement::Proven<NetworkParams>,
+                        proof_chain: &SectionChain| {
+            Message::single_src(
+                &node,
+                DstLocation::Direct,
+                Variant::NodeApproval {
+                    genesis_key: pk0,
+                    network_params,
+                    elders_info: elders_info.clone(),
+                    member_info: member_info.clone(),
+                },
+                Some(proof_chain.clone()),
+                Some(pk1),
+            )
+        };
+
+        let network_params = agreement::test_utils::proven(&sk0, NetworkParams::default())?;
+        let message = approval(network_params.clone(), &proof_chain)?;
+        assert_eq!(message.verify(iter::once(&pk0))?, VerifyStatus::Full);
+
+        // Network params signed by a later section key are rejected.
+        let message = approval(
+            agreement::test_utils::proven(&sk1, NetworkParams::default())?,
+            &proof_chain,
+        )?;
+        assert!(matches!(
+            message.verify(iter::once(&pk0)),
+            Err(Error::InvalidMessage)
+        ));
+
+        // So is a proof chain that doesn't start at the genesis key.
+        let message = approval(network_params, &proof_chain.truncate(1))?;
+        assert!(matches!(
+            message.verify(iter::once(&pk1)),
+            Err(Error::InvalidMessage)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn protocol_header() -> Result<()> {
+        let mut node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        node.protocol.max_version += 1;
+
+        let message = Message::single_src(
+            &node,
+            DstLocation::Direct,
+            Variant::UserMessage(Bytes::from_static(b"hello")),
+            None,
+            None,
+        )?;
+        let decoded = Message::from_bytes(message.to_bytes(), &ProtocolInfo::default())?;
+        assert_eq!(decoded, message);
+        assert_eq!(*decoded.protocol(), node.protocol);
+
+        // A sender speaking only newer versions is rejected by its header alone, whatever the
+        // encoding of the rest of the message.
+        let protocol = ProtocolInfo {
+            min_version: node.protocol.max_version,
+            ..node.protocol
+        };
+        let mut bytes = bincode::serialize(&protocol)?;
+        bytes.extend_from_slice(b"message of a future version");
+
+        assert!(matches!(
+            Message::from_bytes(Bytes::from(bytes), &ProtocolInfo::default()),
+            Err(CreateError::IncompatibleProtocol(header)) if header == protocol
+        ));
+
+        // The header is signed, so it can't be swapped for another one.
+        let forged = ProtocolInfo {
+            features: Features::empty(),
+            ..node.protocol
+        };
+        let bytes = bincode::serialize(&(&forged, &message))?;
+
+        assert!(matches!(
+            Message::from_bytes(Bytes::from(bytes), &ProtocolInfo::default()),
+            Err(CreateError::FailedSignature)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn section_protocol_version_mismatch() -> Result<()> {
+        let sk = bls::SecretKey::random();
+        let upgraded = ProtocolInfo::section(2);
+        let message = PlainMessage {
+            src: Prefix::default().name(),
+            dst: DstLocation::Direct,
+            dst_key: sk.public_key(),
+            variant: Variant::UserMessage(Bytes::from_static(b"hello")),
+            protocol: upgraded,
+        };
+        let signature = sk.sign(&bincode::serialize(&message.as_signable())?);
+        let message = Message::section_src(message, signature, SectionChain::new(sk.public_key()))?;
+
+        let old = ProtocolInfo {
+            min_version: 1,
+            max_version: 1,
+            features: Features::all(),
+        };
+        assert!(matches!(
+            Message::from_bytes(message.to_bytes(), &old),
+            Err(CreateError::IncompatibleProtocol(header)) if header == upgraded
+        ));
+
+        let new = ProtocolInfo {
+            max_version: 2,
+            ..old
+        };
+        let decoded = Message::from_bytes(message.to_bytes(), &new)?;
+        assert_eq!(*decoded.protocol(), upgraded);
+        assert_eq!(
+            decoded.verify(iter::once(&sk.public_key()))?,
+            VerifyStatus::Full
+        );
+
+        Ok(())
+    }
 }
@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{SignableView, Variant};
+use crate::protocol::ProtocolInfo;
 use serde::{Deserialize, Serialize};
 use sn_messaging::DstLocation;
 use xor_name::XorName;
@@ -22,11 +23,14 @@ pub(crate) struct PlainMessage {
     pub dst_key: bls::PublicKey,
     /// Message body.
     pub variant: Variant,
+    /// Protocol the source section runs, see `ProtocolInfo::section`.
+    pub protocol: ProtocolInfo,
 }
 
 impl PlainMessage {
     pub fn as_signable(&self) -> SignableView {
         SignableView {
+            protocol: &self.protocol,
             dst: &self.dst,
             dst_key: Some(&self.dst_key),
             variant: &self.variant,
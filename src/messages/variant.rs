@@ -12,6 +12,7 @@ use crate::{
     crypto::Signature,
     error::{Error, Result},
     network::Network,
+    network_params::NetworkParams,
     relocation::{RelocateDetails, RelocatePayload, RelocatePromise},
     section::{EldersInfo, MemberInfo, Section, SectionChain},
 };
@@ -23,11 +24,15 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
     fmt::{self, Debug, Formatter},
+    time::Duration,
 };
+use xor_name::Prefix;
 
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[allow(clippy::large_enum_variant)]
 /// Message variant
+///
+/// Variants are encoded by their index, so new ones must only be appended at the end.
 pub(crate) enum Variant {
     /// Inform other sections about our section or vice-versa.
     OtherSection {
@@ -44,6 +49,7 @@ pub(crate) enum Variant {
     /// section.
     NodeApproval {
         genesis_key: bls::PublicKey,
+        network_params: Proven<NetworkParams>,
         elders_info: Proven<EldersInfo>,
         member_info: Proven<MemberInfo>,
     },
@@ -100,7 +106,11 @@ pub(crate) enum Variant {
     },
     /// Sent to the current elders by the DKG participants when at least majority of them observe
     /// a DKG failure.
-    DkgFailureAgreement(DkgFailureProofSet),
+    DkgFailureAgreement {
+        /// The identifier of the failed DKG session.
+        dkg_key: DkgKey,
+        proofs: DkgFailureProofSet,
+    },
     /// Message containing a single `Proposal` to be aggregated in the proposal aggregator.
     Propose {
         content: Proposal,
@@ -113,6 +123,46 @@ pub(crate) enum Variant {
         nonce: [u8; 32],
         nonce_signature: Signature,
     },
+    /// Sent by an elder to a section member it hasn't heard from in a while to check whether the
+    /// member is still responsive.
+    LivenessCheck,
+    /// Response to `LivenessCheck`.
+    LivenessAck,
+    /// User-facing message whose recipient is asked to confirm the delivery with
+    /// `DeliveryReceipt` carrying the same `id`.
+    AckedUserMessage { id: MessageHash, content: Bytes },
+    /// Confirmation of the delivery of `AckedUserMessage`, sent by its destination back to its
+    /// source. Identified by the id of the message.
+    DeliveryReceipt(MessageHash),
+    /// User-facing request whose destination is expected to reply with `UserResponse` carrying
+    /// the same `id`.
+    UserRequest { id: MessageHash, content: Bytes },
+    /// Reply to `UserRequest`, sent by its destination back to its source.
+    UserResponse { id: MessageHash, content: Bytes },
+    /// Sent by the elders of a section that shrunk too much to the elders of its sibling section
+    /// to merge the two sections into one. The sibling replies with the same message so each of
+    /// the two sections knows the members of the other one.
+    MergeRequest {
+        // Information about the section requesting the merge.
+        section: Section,
+    },
+    /// Periodic anti-entropy gossip sent by our elders to the elders of other sections.
+    Gossip {
+        /// `EldersInfo` of the sender's section, with the proof chain.
+        elders_info: Proven<EldersInfo>,
+        /// Latest keys of other sections known to the sender, so the recipient can detect where
+        /// their views of the network differ.
+        known_keys: Vec<(Prefix, bls::PublicKey)>,
+        /// Sequence number of the sender's gossip round, so that gossips with unchanged content
+        /// are not filtered out as duplicates.
+        round: u64,
+    },
+    /// Response to a JoinRequest the section can't accept, telling the joining node why and when
+    /// to send it again.
+    JoinRejected {
+        reason: JoinRejectionReason,
+        retry_after: Option<Duration>,
+    },
 }
 
 impl Variant {
@@ -126,12 +176,22 @@ impl Variant {
     {
         let proof_chain = match self {
             Self::NodeApproval {
+                genesis_key,
+                network_params,
                 elders_info,
                 member_info,
-                ..
             } => {
                 let proof_chain = proof_chain.ok_or(Error::InvalidMessage)?;
 
+                // The network params are signed with the genesis key which the proof chain has to
+                // start from.
+                if proof_chain.root_key() != genesis_key
+                    || network_params.proof.public_key != *genesis_key
+                    || !network_params.self_verify()
+                {
+                    return Err(Error::InvalidMessage);
+                }
+
                 if !elders_info.verify(proof_chain) {
                     return Err(Error::InvalidMessage);
                 }
@@ -142,8 +202,8 @@ impl Variant {
 
                 proof_chain
             }
-            Self::Sync { section, .. } => section.chain(),
-            Self::OtherSection { elders_info, .. } => {
+            Self::Sync { section, .. } | Self::MergeRequest { section } => section.chain(),
+            Self::OtherSection { elders_info, .. } | Self::Gossip { elders_info, .. } => {
                 let proof_chain = proof_chain.ok_or(Error::InvalidMessage)?;
 
                 if !elders_info.verify(proof_chain) {
@@ -161,6 +221,49 @@ impl Variant {
             Ok(VerifyStatus::Unknown)
         }
     }
+
+    // Whether this message carries application data, as opposed to being part of the routing
+    // protocol itself.
+    pub(crate) fn is_user(&self) -> bool {
+        matches!(
+            self,
+            Self::UserMessage(_)
+                | Self::AckedUserMessage { .. }
+                | Self::UserRequest { .. }
+                | Self::UserResponse { .. }
+        )
+    }
+
+    // Name of the variant, used to label the message metrics.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::OtherSection { .. } => "OtherSection",
+            Self::Gossip { .. } => "Gossip",
+            Self::UserMessage(_) => "UserMessage",
+            Self::AckedUserMessage { .. } => "AckedUserMessage",
+            Self::DeliveryReceipt(_) => "DeliveryReceipt",
+            Self::UserRequest { .. } => "UserRequest",
+            Self::UserResponse { .. } => "UserResponse",
+            Self::NodeApproval { .. } => "NodeApproval",
+            Self::Sync { .. } => "Sync",
+            Self::MergeRequest { .. } => "MergeRequest",
+            Self::Relocate(_) => "Relocate",
+            Self::RelocatePromise(_) => "RelocatePromise",
+            Self::JoinRequest(_) => "JoinRequest",
+            Self::JoinRetry { .. } => "JoinRetry",
+            Self::JoinRejected { .. } => "JoinRejected",
+            Self::BouncedUntrustedMessage(_) => "BouncedUntrustedMessage",
+            Self::BouncedUnknownMessage { .. } => "BouncedUnknownMessage",
+            Self::DkgStart { .. } => "DkgStart",
+            Self::DkgMessage { .. } => "DkgMessage",
+            Self::DkgFailureObservation { .. } => "DkgFailureObservation",
+            Self::DkgFailureAgreement { .. } => "DkgFailureAgreement",
+            Self::Propose { .. } => "Propose",
+            Self::ResourceChallenge { .. } => "ResourceChallenge",
+            Self::LivenessCheck => "LivenessCheck",
+            Self::LivenessAck => "LivenessAck",
+        }
+    }
 }
 
 impl Debug for Variant {
@@ -171,14 +274,45 @@ impl Debug for Variant {
                 .field("elders_info", elders_info)
                 .field("nonce", nonce)
                 .finish(),
+            Self::Gossip {
+                elders_info,
+                known_keys,
+                round,
+            } => f
+                .debug_struct("Gossip")
+                .field("elders_info", elders_info)
+                .field("known_keys", &known_keys.len())
+                .field("round", round)
+                .finish(),
             Self::UserMessage(payload) => write!(f, "UserMessage({:10})", HexFmt(payload)),
+            Self::AckedUserMessage { id, content } => write!(
+                f,
+                "AckedUserMessage {{ id: {:?}, content: {:10} }}",
+                id,
+                HexFmt(content)
+            ),
+            Self::DeliveryReceipt(hash) => write!(f, "DeliveryReceipt({:?})", hash),
+            Self::UserRequest { id, content } => write!(
+                f,
+                "UserRequest {{ id: {:?}, content: {:10} }}",
+                id,
+                HexFmt(content)
+            ),
+            Self::UserResponse { id, content } => write!(
+                f,
+                "UserResponse {{ id: {:?}, content: {:10} }}",
+                id,
+                HexFmt(content)
+            ),
             Self::NodeApproval {
                 genesis_key,
+                network_params,
                 elders_info,
                 member_info,
             } => f
                 .debug_struct("NodeApproval")
                 .field("genesis_key", genesis_key)
+                .field("network_params", network_params)
                 .field("elders_info", elders_info)
                 .field("member_info", member_info)
                 .finish(),
@@ -191,6 +325,11 @@ impl Debug for Variant {
                     &format_args!("({:b})", network.prefixes().format(", ")),
                 )
                 .finish(),
+            Self::MergeRequest { section } => f
+                .debug_struct("MergeRequest")
+                .field("elders_info", section.elders_info())
+                .field("section_key", section.chain().last_key())
+                .finish(),
             Self::Relocate(payload) => write!(f, "Relocate({:?})", payload),
             Self::RelocatePromise(payload) => write!(f, "RelocatePromise({:?})", payload),
             Self::JoinRequest(payload) => write!(f, "JoinRequest({:?})", payload),
@@ -202,6 +341,14 @@ impl Debug for Variant {
                 .field("elders_info", elders_info)
                 .field("section_key", section_key)
                 .finish(),
+            Self::JoinRejected {
+                reason,
+                retry_after,
+            } => f
+                .debug_struct("JoinRejected")
+                .field("reason", reason)
+                .field("retry_after", retry_after)
+                .finish(),
             Self::BouncedUntrustedMessage(message) => f
                 .debug_tuple("BouncedUntrustedMessage")
                 .field(message)
@@ -229,9 +376,11 @@ impl Debug for Variant {
                 .field("dkg_key", dkg_key)
                 .field("proof", proof)
                 .finish(),
-            Self::DkgFailureAgreement(proofs) => {
-                f.debug_tuple("DkgFailureAgreement").field(proofs).finish()
-            }
+            Self::DkgFailureAgreement { dkg_key, proofs } => f
+                .debug_struct("DkgFailureAgreement")
+                .field("dkg_key", dkg_key)
+                .field("proofs", proofs)
+                .finish(),
             Self::Propose {
                 content,
                 proof_share,
@@ -249,6 +398,8 @@ impl Debug for Variant {
                 .field("data_size", data_size)
                 .field("difficulty", difficulty)
                 .finish(),
+            Self::LivenessCheck => write!(f, "LivenessCheck"),
+            Self::LivenessAck => write!(f, "LivenessAck"),
         }
     }
 }
@@ -273,6 +424,39 @@ pub(crate) struct JoinRequest {
     pub resource_proof_response: Option<ResourceProofResponse>,
 }
 
+/// Reason why a section rejected the request of a node to join it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum JoinRejectionReason {
+    /// The name of the node doesn't match the prefix of the section.
+    WrongPrefix,
+    /// A different node with the same name is already a member of the section.
+    AlreadyMember,
+    /// The age of the node doesn't match the age the section expects it to join with.
+    AgeMismatch {
+        /// The expected age.
+        expected: u8,
+    },
+    /// The section doesn't accept new nodes at the moment.
+    JoinsNotAllowed,
+    /// The response to the resource proof challenge is invalid.
+    InvalidResourceProof,
+    /// The relocation details of the node are invalid or not meant for the section.
+    InvalidRelocation,
+    /// The section can't yet trust the relocation details of the node.
+    UntrustedRelocation,
+    /// The queue of the nodes waiting to join is full.
+    QueueFull,
+    /// The node waited too long to be admitted or agreed on.
+    TimedOut,
+    /// The node doesn't speak the version of the routing protocol the section runs.
+    IncompatibleProtocol {
+        /// The protocol version of the section.
+        section_version: u16,
+    },
+    /// The node was a member of the section before and can't rejoin it under the same name.
+    FormerMember,
+}
+
 impl Debug for JoinRequest {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         formatter
@@ -0,0 +1,477 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::crypto::{self, Digest256};
+use lru_time_cache::LruCache;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+// Upper bounds (in milliseconds) of the histogram buckets.
+const BUCKET_BOUNDS_MS: [u64; 14] = [
+    1, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000,
+];
+
+// Signature shares that don't aggregate within this time are no longer tracked.
+const AGGREGATION_EXPIRY_DURATION: Duration = Duration::from_secs(10 * 60);
+const MAX_AGGREGATIONS: usize = 5_000;
+// Send failures are counted per recipient for at most this many recipients.
+const MAX_SEND_FAILURE_RECIPIENTS: usize = 1_000;
+
+/// Snapshot of the metrics of a routing node, obtained by `Routing::metrics`.
+///
+/// Counters and histograms accumulate since the node started, gauges reflect the state at the time
+/// the snapshot was taken.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Metrics {
+    /// Number of node messages sent, per message variant.
+    pub messages_sent: BTreeMap<&'static str, u64>,
+    /// Number of node messages received, per message variant.
+    pub messages_received: BTreeMap<&'static str, u64>,
+    /// Number of messages we bounced back to their sender because we didn't trust them.
+    pub bounced_untrusted: u64,
+    /// Number of messages we bounced to our elders because we didn't know how to handle them.
+    pub bounced_unknown: u64,
+    /// Time from receiving the first signature share of a message until its signature aggregated.
+    pub message_aggregation_latency: Histogram,
+    /// Time from receiving the first signature share of a proposal until its signature aggregated.
+    pub proposal_aggregation_latency: Histogram,
+    /// Duration of the successfully completed DKG sessions we took part in.
+    pub dkg_session_duration: Histogram,
+    /// Number of DKG sessions we observed to fail.
+    pub dkg_failures: u64,
+    /// Number of failed sends, per recipient. Only the first 1000 recipients are tracked, the
+    /// failed sends to the others are counted in `send_failures_other`.
+    pub send_failures: BTreeMap<SocketAddr, u64>,
+    /// Number of failed sends to the recipients not tracked in `send_failures`.
+    pub send_failures_other: u64,
+    /// Number of incoming and outgoing messages dropped by the message filter as duplicates.
+    pub filter_hits: u64,
+    /// Number of incoming and outgoing messages that passed the message filter.
+    pub filter_misses: u64,
+    /// Number of events dropped because the application didn't take them from the `EventStream`
+    /// fast enough.
+    pub events_dropped: u64,
+    /// Number of incoming messages dropped because too many were already being handled.
+    pub incoming_dropped: u64,
+    /// Number of incoming client messages dropped for exceeding the rate limits.
+    pub rate_limited: u64,
+    /// Number of times a peer got temporarily banned for sending malformed messages.
+    pub peers_banned: u64,
+    /// Current number of events waiting to be taken from the `EventStream`.
+    pub event_queue_depth: usize,
+    /// Current number of commands being handled (including the scheduled timeouts).
+    pub commands_in_flight: usize,
+    /// Current number of joined members of our section.
+    pub section_members: usize,
+    /// Current number of elders of our section.
+    pub section_elders: usize,
+    /// Current number of elders in the network we know of.
+    pub known_network_elders: u64,
+    /// Current estimate of the total number of elders in the network.
+    pub estimated_network_elders: u64,
+}
+
+impl Metrics {
+    /// Ratio of the messages dropped by the message filter to all the messages it checked, or
+    /// `None` if no message was checked yet.
+    pub fn filter_hit_rate(&self) -> Option<f64> {
+        let total = self.filter_hits + self.filter_misses;
+        if total > 0 {
+            Some(self.filter_hits as f64 / total as f64)
+        } else {
+            None
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    #[cfg(feature = "prometheus")]
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# TYPE sn_routing_messages_sent_total counter");
+        for (variant, count) in &self.messages_sent {
+            let _ = writeln!(
+                out,
+                "sn_routing_messages_sent_total{{variant=\"{}\"}} {}",
+                variant, count
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE sn_routing_messages_received_total counter");
+        for (variant, count) in &self.messages_received {
+            let _ = writeln!(
+                out,
+                "sn_routing_messages_received_total{{variant=\"{}\"}} {}",
+                variant, count
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE sn_routing_bounced_messages_total counter");
+        let _ = writeln!(
+            out,
+            "sn_routing_bounced_messages_total{{reason=\"untrusted\"}} {}",
+            self.bounced_untrusted
+        );
+        let _ = writeln!(
+            out,
+            "sn_routing_bounced_messages_total{{reason=\"unknown\"}} {}",
+            self.bounced_unknown
+        );
+
+        self.message_aggregation_latency
+            .write_prometheus(&mut out, "sn_routing_message_aggregation_seconds");
+        self.proposal_aggregation_latency
+            .write_prometheus(&mut out, "sn_routing_proposal_aggregation_seconds");
+        self.dkg_session_duration
+            .write_prometheus(&mut out, "sn_routing_dkg_session_seconds");
+
+        let _ = writeln!(out, "# TYPE sn_routing_dkg_failures_total counter");
+        let _ = writeln!(out, "sn_routing_dkg_failures_total {}", self.dkg_failures);
+
+        let _ = writeln!(out, "# TYPE sn_routing_send_failures_total counter");
+        for (recipient, count) in &self.send_failures {
+            let _ = writeln!(
+                out,
+                "sn_routing_send_failures_total{{recipient=\"{}\"}} {}",
+                recipient, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "sn_routing_send_failures_total{{recipient=\"other\"}} {}",
+            self.send_failures_other
+        );
+
+        let _ = writeln!(out, "# TYPE sn_routing_message_filter_total counter");
+        let _ = writeln!(
+            out,
+            "sn_routing_message_filter_total{{result=\"hit\"}} {}",
+            self.filter_hits
+        );
+        let _ = writeln!(
+            out,
+            "sn_routing_message_filter_total{{result=\"miss\"}} {}",
+            self.filter_misses
+        );
+
+        let _ = writeln!(out, "# TYPE sn_routing_dropped_total counter");
+        let _ = writeln!(
+            out,
+            "sn_routing_dropped_total{{queue=\"events\"}} {}",
+            self.events_dropped
+        );
+        let _ = writeln!(
+            out,
+            "sn_routing_dropped_total{{queue=\"incoming\"}} {}",
+            self.incoming_dropped
+        );
+
+        let _ = writeln!(out, "# TYPE sn_routing_rate_limited_total counter");
+        let _ = writeln!(out, "sn_routing_rate_limited_total {}", self.rate_limited);
+        let _ = writeln!(out, "# TYPE sn_routing_peers_banned_total counter");
+        let _ = writeln!(out, "sn_routing_peers_banned_total {}", self.peers_banned);
+
+        for (name, value) in &[
+            (
+                "sn_routing_event_queue_depth",
+                self.event_queue_depth as u64,
+            ),
+            (
+                "sn_routing_commands_in_flight",
+                self.commands_in_flight as u64,
+            ),
+            ("sn_routing_section_members", self.section_members as u64),
+            ("sn_routing_section_elders", self.section_elders as u64),
+            ("sn_routing_known_network_elders", self.known_network_elders),
+            (
+                "sn_routing_estimated_network_elders",
+                self.estimated_network_elders,
+            ),
+        ] {
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        out
+    }
+}
+
+/// Histogram of durations with fixed buckets ranging from 1 millisecond to 1 minute.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Histogram {
+    buckets: [u64; BUCKET_BOUNDS_MS.len()],
+    count: u64,
+    sum: Duration,
+}
+
+impl Histogram {
+    /// Number of observed values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sum of all the observed values.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Mean of the observed values, or `None` if there are none.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count > 0 {
+            Some(Duration::from_nanos(
+                (self.sum.as_nanos() / self.count as u128) as u64,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the upper bound of every bucket together with the number of observed values less
+    /// than or equal to it. Values above the last bound are only included in `count`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        BUCKET_BOUNDS_MS
+            .iter()
+            .map(|bound| Duration::from_millis(*bound))
+            .zip(self.buckets.iter().copied())
+    }
+
+    pub(crate) fn observe(&mut self, value: Duration) {
+        let bounds = BUCKET_BOUNDS_MS
+            .iter()
+            .map(|bound| Duration::from_millis(*bound));
+        for (bucket, bound) in self.buckets.iter_mut().zip(bounds) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+
+        self.count += 1;
+        self.sum += value;
+    }
+
+    #[cfg(feature = "prometheus")]
+    fn write_prometheus(&self, out: &mut String, name: &str) {
+        use std::fmt::Write;
+
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, count) in self.buckets() {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound.as_secs_f64(),
+                count
+            );
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum.as_secs_f64());
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+// Collects the metrics of a routing node. Cheap to clone - all the clones share the same data.
+#[derive(Clone, Default)]
+pub(crate) struct MetricsRecorder(Arc<Mutex<Metrics>>);
+
+impl MetricsRecorder {
+    // Returns the metrics recorded so far. The gauges are left for the caller to fill in.
+    pub fn snapshot(&self) -> Metrics {
+        self.lock().clone()
+    }
+
+    pub fn message_sent(&self, variant: &'static str) {
+        *self.lock().messages_sent.entry(variant).or_default() += 1;
+    }
+
+    pub fn message_received(&self, variant: &'static str) {
+        *self.lock().messages_received.entry(variant).or_default() += 1;
+    }
+
+    pub fn bounced_untrusted(&self) {
+        self.lock().bounced_untrusted += 1;
+    }
+
+    pub fn bounced_unknown(&self) {
+        self.lock().bounced_unknown += 1;
+    }
+
+    pub fn message_aggregated(&self, latency: Duration) {
+        self.lock().message_aggregation_latency.observe(latency);
+    }
+
+    pub fn proposal_aggregated(&self, latency: Duration) {
+        self.lock().proposal_aggregation_latency.observe(latency);
+    }
+
+    pub fn dkg_completed(&self, duration: Duration) {
+        self.lock().dkg_session_duration.observe(duration);
+    }
+
+    pub fn dkg_failed(&self) {
+        self.lock().dkg_failures += 1;
+    }
+
+    pub fn send_failed(&self, recipient: SocketAddr) {
+        let mut metrics = self.lock();
+        if let Some(count) = metrics.send_failures.get_mut(&recipient) {
+            *count += 1;
+        } else if metrics.send_failures.len() < MAX_SEND_FAILURE_RECIPIENTS {
+            let _ = metrics.send_failures.insert(recipient, 1);
+        } else {
+            metrics.send_failures_other += 1;
+        }
+    }
+
+    pub fn incoming_dropped(&self) {
+        self.lock().incoming_dropped += 1;
+    }
+
+    pub fn rate_limited(&self) {
+        self.lock().rate_limited += 1;
+    }
+
+    pub fn peer_banned(&self) {
+        self.lock().peers_banned += 1;
+    }
+
+    pub fn filtered(&self, is_new: bool) {
+        let mut metrics = self.lock();
+        if is_new {
+            metrics.filter_misses += 1;
+        } else {
+            metrics.filter_hits += 1;
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<Metrics> {
+        self.0.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+// Measures the time it takes for the signature shares over the same payload to aggregate.
+pub(crate) struct AggregationTimer(LruCache<Digest256, Instant>);
+
+impl AggregationTimer {
+    pub fn new() -> Self {
+        Self(LruCache::with_expiry_duration_and_capacity(
+            AGGREGATION_EXPIRY_DURATION,
+            MAX_AGGREGATIONS,
+        ))
+    }
+
+    // Record that a signature share over `payload` was received. Only the first one starts the
+    // timer.
+    pub fn start(&mut self, payload: &[u8]) {
+        let hash = crypto::sha3_256(payload);
+        if !self.0.contains_key(&hash) {
+            let _ = self.0.insert(hash, Instant::now());
+        }
+    }
+
+    // Record that the signature over `payload` aggregated and return the time elapsed since the
+    // first share was received.
+    pub fn stop(&mut self, payload: &[u8]) -> Option<Duration> {
+        self.0
+            .remove(&crypto::sha3_256(payload))
+            .map(|start| start.elapsed())
+    }
+}
+
+impl Default for AggregationTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.mean(), None);
+
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(7));
+        histogram.observe(Duration::from_secs(120));
+
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), Duration::from_millis(120_010));
+        assert_eq!(histogram.mean(), Some(Duration::from_nanos(40_003_333_333)));
+
+        let buckets: Vec<_> = histogram.buckets().collect();
+        assert_eq!(buckets[0], (Duration::from_millis(1), 0));
+        assert_eq!(buckets[1], (Duration::from_millis(5), 1));
+        assert_eq!(buckets[2], (Duration::from_millis(10), 2));
+        assert_eq!(buckets.last(), Some(&(Duration::from_secs(60), 2)));
+    }
+
+    #[test]
+    fn recorder_clones_share_data() {
+        let recorder = MetricsRecorder::default();
+        let clone = recorder.clone();
+
+        recorder.message_sent("Sync");
+        clone.message_sent("Sync");
+        clone.filtered(true);
+        clone.filtered(false);
+
+        let metrics = recorder.snapshot();
+        assert_eq!(metrics.messages_sent.get("Sync"), Some(&2));
+        assert_eq!(metrics.filter_hit_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn send_failures_capped() {
+        let recorder = MetricsRecorder::default();
+        let addrs: Vec<SocketAddr> = (0..=MAX_SEND_FAILURE_RECIPIENTS as u16)
+            .map(|port| ([127, 0, 0, 1], port).into())
+            .collect();
+
+        for addr in &addrs {
+            recorder.send_failed(*addr);
+        }
+        recorder.send_failed(addrs[0]);
+
+        let metrics = recorder.snapshot();
+        assert_eq!(metrics.send_failures.len(), MAX_SEND_FAILURE_RECIPIENTS);
+        assert_eq!(metrics.send_failures.get(&addrs[0]), Some(&2));
+        assert_eq!(metrics.send_failures_other, 1);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus_format() {
+        let recorder = MetricsRecorder::default();
+        recorder.message_received("JoinRequest");
+        recorder.dkg_completed(Duration::from_millis(20));
+
+        let text = recorder.snapshot().to_prometheus();
+        assert!(text.contains("sn_routing_messages_received_total{variant=\"JoinRequest\"} 1\n"));
+        assert!(text.contains("sn_routing_dkg_session_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(text.contains("sn_routing_dkg_session_seconds_count 1\n"));
+    }
+
+    #[test]
+    fn aggregation_timer() {
+        let mut timer = AggregationTimer::new();
+        assert_eq!(timer.stop(b"payload"), None);
+
+        timer.start(b"payload");
+        timer.start(b"payload");
+        assert!(timer.stop(b"payload").is_some());
+        assert_eq!(timer.stop(b"payload"), None);
+    }
+}
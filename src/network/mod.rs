@@ -53,7 +53,6 @@ impl Network {
     }
 
     /// Get `EldersInfo` of a known section with the given prefix.
-    #[allow(unused)]
     pub fn get(&self, prefix: &Prefix) -> Option<&EldersInfo> {
         self.sections
             .get(prefix)
@@ -138,6 +137,28 @@ impl Network {
         true
     }
 
+    /// Remove the info about the sections whose prefixes are extensions of `prefix`, because they
+    /// merged into the section with `prefix`.
+    pub fn remove_merged(&mut self, prefix: &Prefix) {
+        self.sections.remove_descendants(prefix);
+        self.keys.remove_descendants(prefix);
+        self.knowledge.remove_descendants(prefix);
+    }
+
+    /// Returns whether we know of any section whose prefix is an extension of `prefix`.
+    pub fn has_descendants(&self, prefix: &Prefix) -> bool {
+        self.sections.descendants(prefix).next().is_some()
+            || self.keys.descendants(prefix).next().is_some()
+    }
+
+    /// Returns the known keys of the sections whose prefixes are extensions of `prefix`.
+    pub fn descendant_keys<'a>(
+        &'a self,
+        prefix: &'a Prefix,
+    ) -> impl Iterator<Item = &'a bls::PublicKey> + 'a {
+        self.keys.descendants(prefix).map(|entry| &entry.value.1)
+    }
+
     /// Updates the entry in `keys` for `prefix` to the latest known key.
     pub fn update_their_key(&mut self, new_key: Proven<(Prefix, bls::PublicKey)>) -> bool {
         // TODO: verify against section chain
@@ -202,6 +223,11 @@ impl Network {
             .map(|entry| &entry.value.1)
     }
 
+    /// Returns the keys in our chain known by the other sections.
+    pub fn knowledge_keys(&self) -> impl Iterator<Item = &bls::PublicKey> {
+        self.knowledge.iter().map(|entry| &entry.value.1)
+    }
+
     /// Updates the key of our section that is known by some other section.
     /// The passed in proven tuple consist of the prefix of the section whose knowledge we are
     /// updaing and the key of our section we are updating it to.
@@ -417,6 +443,34 @@ mod tests {
         assert_eq!(map.closest(&n11).map(|i| &i.prefix), Some(&p10));
     }
 
+    #[test]
+    fn remove_merged() {
+        let sk = bls::SecretKey::random();
+        let chain = SectionChain::new(sk.public_key());
+
+        let p0: Prefix = "0".parse().unwrap();
+        let p10: Prefix = "10".parse().unwrap();
+        let p11: Prefix = "11".parse().unwrap();
+        let p1: Prefix = "1".parse().unwrap();
+
+        let mut map = Network::new();
+        for prefix in &[p0, p10, p11] {
+            let _ = map.update_section(gen_proven_elders_info(&sk, *prefix), None, &chain);
+            let key = agreement::test_utils::proven(&sk, (*prefix, gen_key())).unwrap();
+            let _ = map.update_their_key(key);
+        }
+        assert!(map.has_descendants(&p1));
+        assert_eq!(map.descendant_keys(&p1).count(), 2);
+
+        // (10) and (11) merged into (1).
+        map.remove_merged(&p1);
+        assert!(!map.has_descendants(&p1));
+        assert!(map.update_section(gen_proven_elders_info(&sk, p1), None, &chain));
+
+        let prefixes: Vec<_> = map.prefixes().copied().collect();
+        assert_eq!(prefixes, vec![p0, p1]);
+    }
+
     // Create a `Network` and apply a series of `update_keys` calls to it, then verify the stored
     // keys are as expected.
     //
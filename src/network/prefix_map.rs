@@ -114,6 +114,12 @@ where
             .map(|entry| &entry.0)
     }
 
+    /// Removes all entries whose prefixes are descendants (extensions) of `prefix`.
+    pub fn remove_descendants(&mut self, prefix: &Prefix) {
+        self.0
+            .retain(|entry| !entry.prefix().is_extension_of(prefix))
+    }
+
     // Remove `prefix` and any of its ancestors if they are covered by their descendants.
     // For example, if `(00)` and `(01)` are both in the map, we can remove `(0)` and `()`.
     fn prune(&mut self, mut prefix: Prefix) {
@@ -351,6 +357,21 @@ mod tests {
         assert_eq!(map.get(&prefix("00")), Some(&(prefix("00"), 1)));
     }
 
+    #[test]
+    fn insert_ancestor_after_removing_descendants() {
+        let mut map = PrefixMap::new();
+        let _ = map.insert((prefix("0"), 0));
+        let _ = map.insert((prefix("10"), 1));
+        let _ = map.insert((prefix("11"), 2));
+
+        map.remove_descendants(&prefix("1"));
+        assert_eq!(map.insert((prefix("1"), 3)), None);
+        assert_eq!(map.get(&prefix("0")), Some(&(prefix("0"), 0)));
+        assert_eq!(map.get(&prefix("1")), Some(&(prefix("1"), 3)));
+        assert_eq!(map.get(&prefix("10")), None);
+        assert_eq!(map.get(&prefix("11")), None);
+    }
+
     #[test]
     fn get_equal_or_ancestor() {
         let mut map = PrefixMap::new();
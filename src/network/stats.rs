@@ -13,6 +13,14 @@ pub struct NetworkStats {
 }
 
 impl NetworkStats {
+    pub fn known_elders(&self) -> u64 {
+        self.known_elders
+    }
+
+    pub fn total_elders(&self) -> u64 {
+        self.total_elders
+    }
+
     pub fn print(&self) {
         if self.total_elders_exact {
             info!("*** Exact total network elders: {} ***", self.known_elders)
@@ -0,0 +1,120 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::{Error, Result},
+    section::MIN_AGE,
+    ELDER_SIZE, RECOMMENDED_SECTION_SIZE,
+};
+use serde::{Deserialize, Serialize};
+
+const RESOURCE_PROOF_DATA_SIZE: usize = 64;
+const RESOURCE_PROOF_DIFFICULTY: u8 = 2;
+const KEY_CACHE_SIZE: u8 = 5;
+
+/// Parameters of the network.
+///
+/// They are chosen by the genesis node when the network is started, recorded alongside the genesis
+/// key and never change afterwards. Every node joining the network must be configured with the
+/// same parameters, otherwise it's not allowed to join.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct NetworkParams {
+    /// Number of elders per section.
+    pub elder_size: usize,
+    /// Recommended section size. Nodes keep being added until the section reaches this size. This
+    /// number also determines when split happens - if both post-split sections would have at
+    /// least this number of mature nodes.
+    pub recommended_section_size: usize,
+    /// The minimum age a node can have. New nodes start at `min_age + 1`, nodes with age of
+    /// `min_age` are not considered mature.
+    pub min_age: u8,
+    /// Size of the data used in the resource proof challenge of the joining nodes. Sections
+    /// increase it, together with the difficulty, while they see many join attempts.
+    pub resource_proof_data_size: usize,
+    /// Base difficulty of the resource proof challenge of the joining nodes. Sections increase it
+    /// while they see many join attempts.
+    pub resource_proof_difficulty: u8,
+    /// Number of the most recent section keys an elder keeps its key shares for.
+    pub key_cache_size: u8,
+}
+
+impl NetworkParams {
+    /// Checks the parameters are consistent. Returns `Error::InvalidNetworkParams` if not.
+    pub fn validate(&self) -> Result<()> {
+        if self.elder_size == 0
+            || self.recommended_section_size < self.elder_size
+            || self.resource_proof_data_size == 0
+            || self.key_cache_size == 0
+        {
+            Err(Error::InvalidNetworkParams)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn resource_proof_params(&self) -> ResourceProofParams {
+        ResourceProofParams {
+            data_size: self.resource_proof_data_size,
+            difficulty: self.resource_proof_difficulty,
+        }
+    }
+}
+
+/// Parameters of the resource proof challenge currently issued by a section to the joining nodes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ResourceProofParams {
+    pub data_size: usize,
+    pub difficulty: u8,
+}
+
+impl Default for NetworkParams {
+    fn default() -> Self {
+        Self {
+            elder_size: ELDER_SIZE,
+            recommended_section_size: RECOMMENDED_SECTION_SIZE,
+            min_age: MIN_AGE,
+            resource_proof_data_size: RESOURCE_PROOF_DATA_SIZE,
+            resource_proof_difficulty: RESOURCE_PROOF_DIFFICULTY,
+            key_cache_size: KEY_CACHE_SIZE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn validate() {
+        assert!(NetworkParams::default().validate().is_ok());
+
+        let small = NetworkParams {
+            elder_size: 3,
+            recommended_section_size: 3,
+            ..Default::default()
+        };
+        assert!(small.validate().is_ok());
+
+        let no_elders = NetworkParams {
+            elder_size: 0,
+            ..Default::default()
+        };
+        assert_matches!(no_elders.validate(), Err(Error::InvalidNetworkParams));
+
+        let too_small_sections = NetworkParams {
+            elder_size: 7,
+            recommended_section_size: 5,
+            ..Default::default()
+        };
+        assert_matches!(
+            too_small_sections.validate(),
+            Err(Error::InvalidNetworkParams)
+        );
+    }
+}
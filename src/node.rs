@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{crypto, peer::Peer};
+use crate::{crypto, peer::Peer, protocol::ProtocolInfo};
 use ed25519_dalek::Keypair;
 use std::{
     fmt::{self, Debug, Display, Formatter},
@@ -23,6 +23,8 @@ pub(crate) struct Node {
     // TODO: find a way to not require `Clone`.
     pub keypair: Arc<Keypair>,
     pub addr: SocketAddr,
+    // Protocol versions and features we advertise in the header of the messages we create.
+    pub protocol: ProtocolInfo,
 }
 
 impl Node {
@@ -30,6 +32,7 @@ impl Node {
         Self {
             keypair: Arc::new(keypair),
             addr,
+            protocol: ProtocolInfo::default(),
         }
     }
 
@@ -0,0 +1,197 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+
+/// Newest version of the routing protocol implemented by this build.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Oldest version of the routing protocol this build still speaks.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+// Version every section runs before it agrees on an upgrade.
+pub(crate) const INITIAL_PROTOCOL_VERSION: u16 = 1;
+
+/// Set of optional routing protocol features.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Features(u64);
+
+impl Features {
+    /// Signing of application payloads by the section (`Routing::sign_as_section`).
+    pub const SECTION_SIGNING: Self = Self(1);
+    /// Pruning of the section chain at checkpoints agreed on by the section.
+    pub const CHAIN_CHECKPOINTS: Self = Self(1 << 1);
+
+    const NAMES: &'static [(Self, &'static str)] = &[
+        (Self::SECTION_SIGNING, "SECTION_SIGNING"),
+        (Self::CHAIN_CHECKPOINTS, "CHAIN_CHECKPOINTS"),
+    ];
+
+    /// Returns the empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the set of all the features implemented by this build.
+    pub const fn all() -> Self {
+        Self(Self::SECTION_SIGNING.0 | Self::CHAIN_CHECKPOINTS.0)
+    }
+
+    /// Returns whether this set contains all the features of `other`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features in either this set or `other`.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns the features in both this set and `other`.
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl Debug for Features {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut list = f.debug_set();
+        let mut unknown = self.0;
+
+        for (feature, name) in Self::NAMES {
+            if self.contains(*feature) {
+                let _ = list.entry(&format_args!("{}", name));
+                unknown &= !feature.0;
+            }
+        }
+
+        // Features of newer versions, unknown to this build.
+        if unknown != 0 {
+            let _ = list.entry(&format_args!("{:#x}", unknown));
+        }
+
+        list.finish()
+    }
+}
+
+/// Routing protocol versions and features supported by a node. Nodes advertise it in the header of
+/// every message they send, so its layout must never change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProtocolInfo {
+    /// Oldest protocol version the node speaks.
+    pub min_version: u16,
+    /// Newest protocol version the node speaks.
+    pub max_version: u16,
+    /// Optional features the node supports.
+    pub features: Features,
+}
+
+impl ProtocolInfo {
+    /// Returns whether the node speaks the protocol `version`.
+    pub fn supports(&self, version: u16) -> bool {
+        self.min_version <= version && version <= self.max_version
+    }
+
+    /// Returns whether the two nodes speak at least one common protocol version.
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        self.min_version <= other.max_version && other.min_version <= self.max_version
+    }
+
+    // Protocol advertised by the messages a section signs: just the version the section runs, as
+    // every elder signing the message must advertise the same. Features are only advertised by
+    // individual nodes.
+    pub(crate) fn section(version: u16) -> Self {
+        Self {
+            min_version: version,
+            max_version: version,
+            features: Features::empty(),
+        }
+    }
+}
+
+impl Default for ProtocolInfo {
+    /// Versions and features implemented by this build.
+    fn default() -> Self {
+        Self {
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+            features: Features::all(),
+        }
+    }
+}
+
+// Agreement of the section elders to switch the section to a newer protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct ProtocolUpgrade {
+    pub version: u16,
+}
+
+// Returns the newest version above `current` that is supported by `ours` and by at least
+// `threshold` of `elders`, if any.
+pub(crate) fn upgrade_version<'a, I>(
+    current: u16,
+    ours: &ProtocolInfo,
+    elders: I,
+    threshold: usize,
+) -> Option<u16>
+where
+    I: IntoIterator<Item = &'a ProtocolInfo>,
+    I::IntoIter: Clone,
+{
+    let elders = elders.into_iter();
+
+    (current.saturating_add(1)..=ours.max_version)
+        .rev()
+        .filter(|version| ours.supports(*version))
+        .find(|version| {
+            elders
+                .clone()
+                .filter(|info| info.supports(*version))
+                .count()
+                >= threshold
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features() {
+        let features = Features::SECTION_SIGNING.union(Features(1 << 63));
+
+        assert!(Features::all().contains(Features::SECTION_SIGNING));
+        assert!(!features.contains(Features::all()));
+        assert_eq!(
+            features.intersection(Features::all()),
+            Features::SECTION_SIGNING
+        );
+        assert_eq!(
+            format!("{:?}", features),
+            "{SECTION_SIGNING, 0x8000000000000000}"
+        );
+    }
+
+    #[test]
+    fn upgrade_to_version_supported_by_enough_elders() {
+        let info = |min_version, max_version| ProtocolInfo {
+            min_version,
+            max_version,
+            features: Features::all(),
+        };
+        let ours = info(1, 3);
+        let elders = [info(1, 3), info(1, 2), info(2, 3), info(1, 1)];
+
+        assert_eq!(upgrade_version(1, &ours, &elders, 3), Some(2));
+        assert_eq!(upgrade_version(1, &ours, &elders, 2), Some(3));
+        assert_eq!(upgrade_version(1, &ours, &elders, 4), None);
+        assert_eq!(upgrade_version(3, &ours, &elders, 1), None);
+        assert_eq!(upgrade_version(1, &info(1, 1), &elders, 1), None);
+    }
+}
@@ -16,7 +16,6 @@ use crate::{
     peer::Peer,
     section::{MemberInfo, Section},
 };
-use bytes::Bytes;
 use serde::{de::Error as SerdeDeError, Deserialize, Deserializer, Serialize, Serializer};
 use sn_messaging::MessageType;
 use std::net::SocketAddr;
@@ -209,9 +208,9 @@ pub(crate) struct RelocatePromise {
 pub(crate) enum RelocateState {
     // Node is undergoing delayed relocation. This happens when the node is selected for relocation
     // while being an elder. It must keep fulfilling its duties as elder until its demoted, then it
-    // can send the bytes (which are serialized `RelocatePromise` message) back to the elders who
-    // will exchange it for an actual `Relocate` message.
-    Delayed(Bytes),
+    // can send the `RelocatePromise` message back to the elders who will exchange it for an actual
+    // `Relocate` message.
+    Delayed(Message),
     // Relocation in progress. The sender is used to pass messages to the bootstrap task.
     InProgress(mpsc::Sender<(MessageType, SocketAddr)>),
 }
@@ -290,6 +289,7 @@ mod tests {
     use super::*;
     use crate::{
         agreement::test_utils::proven,
+        network_params::NetworkParams,
         peer::test_utils::arbitrary_unique_peers,
         section::{EldersInfo, SectionChain},
         ELDER_SIZE, MIN_AGE,
@@ -349,7 +349,12 @@ mod tests {
         );
         let elders_info = proven(&sk, elders_info)?;
 
-        let mut section = Section::new(pk, SectionChain::new(pk), elders_info)?;
+        let mut section = Section::new(
+            pk,
+            proven(&sk, NetworkParams::default())?,
+            SectionChain::new(pk),
+            elders_info,
+        )?;
 
         for peer in &peers {
             let info = MemberInfo::joined(*peer);
@@ -0,0 +1,61 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Source of the randomness of the node.
+
+#[cfg(feature = "simulated-network")]
+use rand::SeedableRng;
+use rand::{CryptoRng, RngCore};
+use rand_chacha::ChaCha20Rng;
+use std::cell::RefCell;
+
+thread_local! {
+    // Replaces the thread-local RNG of `rand` on this thread once seeded.
+    static SEEDED: RefCell<Option<ChaCha20Rng>> = RefCell::new(None);
+}
+
+/// Random number generator for everything the node generates at random: its keypair, the keys of
+/// its section and the nonces of its messages. Draws from `rand::thread_rng` unless the current
+/// thread has been seeded.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct MainRng;
+
+impl RngCore for MainRng {
+    fn next_u32(&mut self) -> u32 {
+        with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+// Both `ThreadRng` and `ChaCha20Rng` are cryptographically secure.
+impl CryptoRng for MainRng {}
+
+/// Seeds the RNG of the current thread so the nodes running on it generate the same keys and
+/// nonces in every run with the same seed.
+#[cfg(feature = "simulated-network")]
+pub(crate) fn seed(seed: u64) {
+    SEEDED.with(|seeded| *seeded.borrow_mut() = Some(ChaCha20Rng::seed_from_u64(seed)))
+}
+
+fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    SEEDED.with(|seeded| match seeded.borrow_mut().as_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    })
+}
@@ -6,16 +6,20 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{comm::ConnectionEvent, Comm};
+use super::{comm::ConnectionEvent, BootstrapConfig, Comm};
 use crate::{
     agreement::Proven,
     crypto::{self, Signature},
     error::{Error, Result},
-    messages::{JoinRequest, Message, ResourceProofResponse, Variant, VerifyStatus},
+    messages::{
+        JoinRejectionReason, JoinRequest, Message, ResourceProofResponse, Variant, VerifyStatus,
+    },
+    network_params::NetworkParams,
     node::Node,
     peer::Peer,
     relocation::{RelocatePayload, SignedRelocateDetails},
     section::{EldersInfo, Section, SectionChain},
+    supermajority,
 };
 use bytes::Bytes;
 use futures::future;
@@ -29,33 +33,83 @@ use std::{
     collections::{BTreeMap, HashSet, VecDeque},
     mem,
     net::SocketAddr,
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc,
+    task,
+    time::{self, Instant},
 };
-use tokio::sync::mpsc;
 use tracing::Instrument;
 use xor_name::{Prefix, XorName};
 
 const BACKLOG_CAPACITY: usize = 100;
 
-/// Bootstrap into the network as new node.
+/// Phase of the bootstrapping.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BootstrapPhase {
+    /// Waiting for the response to the query for the section to join.
+    GetSection,
+    /// Waiting for the response to the request to join the section.
+    Join,
+    /// Solving the resource proof challenge given by the section.
+    ResourceProof,
+}
+
+/// Bootstrap into the network as new node. The first attempt contacts `bootstrap_addr` only, the
+/// retries all of `contacts`.
 ///
-/// NOTE: It's not guaranteed this function ever returns. This can happen due to messages being
-/// lost in transit or other reasons. It's the responsibility of the caller to handle this case,
-/// for example by using a timeout.
+/// Fails with `Error::BootstrapTimeout` if all the attempts time out.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn initial(
     node: Node,
     comm: &Comm,
     incoming_conns: &mut mpsc::Receiver<ConnectionEvent>,
     bootstrap_addr: SocketAddr,
+    contacts: Vec<SocketAddr>,
+    network_params: NetworkParams,
+    config: BootstrapConfig,
 ) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
     let (send_tx, send_rx) = mpsc::channel(1);
     let recv_rx = MessageReceiver::Raw(incoming_conns);
 
     let span = trace_span!("bootstrap", name = %node.name());
 
-    let state = State::new(node, send_tx, recv_rx);
+    let state = State::new(node, network_params, config, send_tx, recv_rx);
+
+    future::join(
+        state.run(vec![bootstrap_addr], contacts, None, None),
+        send_messages(send_rx, comm),
+    )
+    .instrument(span)
+    .await
+    .0
+}
+
+/// Rejoin our previous section using the identity restored from the persisted state. If the
+/// section rejects it as a former member too young to be relocated back, join anew with a fresh
+/// identity instead.
+///
+/// Fails with `Error::BootstrapTimeout` if all the attempts time out.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn rejoin(
+    node: Node,
+    comm: &Comm,
+    incoming_conns: &mut mpsc::Receiver<ConnectionEvent>,
+    bootstrap_addrs: Vec<SocketAddr>,
+    genesis_key: bls::PublicKey,
+    network_params: NetworkParams,
+    config: BootstrapConfig,
+) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
+    let (send_tx, send_rx) = mpsc::channel(1);
+    let recv_rx = MessageReceiver::Raw(incoming_conns);
+
+    let span = trace_span!("rejoin", name = %node.name());
+
+    let state = State::new(node, network_params, config, send_tx, recv_rx);
 
     future::join(
-        state.run(vec![bootstrap_addr], None, None),
+        state.run(bootstrap_addrs, vec![], Some(genesis_key), None),
         send_messages(send_rx, comm),
     )
     .instrument(span)
@@ -65,24 +119,30 @@ pub(crate) async fn initial(
 
 /// Re-bootstrap as a relocated node.
 ///
-/// NOTE: It's not guaranteed this function ever returns. This can happen due to messages being
-/// lost in transit or other reasons. It's the responsibility of the caller to handle this case,
-/// for example by using a timeout.
+/// Fails with `Error::BootstrapTimeout` if all the attempts time out.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn relocate(
     node: Node,
     comm: &Comm,
     recv_rx: mpsc::Receiver<(MessageType, SocketAddr)>,
     bootstrap_addrs: Vec<SocketAddr>,
     genesis_key: bls::PublicKey,
+    network_params: NetworkParams,
+    config: BootstrapConfig,
     relocate_details: SignedRelocateDetails,
 ) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
     let (send_tx, send_rx) = mpsc::channel(1);
     let recv_rx = MessageReceiver::Deserialized(recv_rx);
 
-    let state = State::new(node, send_tx, recv_rx);
+    let state = State::new(node, network_params, config, send_tx, recv_rx);
 
     future::join(
-        state.run(bootstrap_addrs, Some(genesis_key), Some(relocate_details)),
+        state.run(
+            bootstrap_addrs,
+            vec![],
+            Some(genesis_key),
+            Some(relocate_details),
+        ),
         send_messages(send_rx, comm),
     )
     .await
@@ -95,6 +155,12 @@ struct State<'a> {
     // Receiver for incoming messages.
     recv_rx: MessageReceiver<'a>,
     node: Node,
+    // Parameters of the network we expect to join.
+    network_params: NetworkParams,
+    config: BootstrapConfig,
+    // Number of the current attempt, counting both the retries after a timeout and the retries of
+    // the join requests rejected for now.
+    attempt: u32,
     // Backlog for unknown messages
     backlog: VecDeque<(Message, SocketAddr)>,
 }
@@ -102,6 +168,8 @@ struct State<'a> {
 impl<'a> State<'a> {
     fn new(
         node: Node,
+        network_params: NetworkParams,
+        config: BootstrapConfig,
         send_tx: mpsc::Sender<(MessageType, Vec<SocketAddr>)>,
         recv_rx: MessageReceiver<'a>,
     ) -> Self {
@@ -109,30 +177,113 @@ impl<'a> State<'a> {
             send_tx,
             recv_rx,
             node,
+            network_params,
+            config,
+            attempt: 1,
             backlog: VecDeque::with_capacity(BACKLOG_CAPACITY),
         }
     }
 
+    // Bootstrap, retrying with backoff whenever an attempt times out. The first attempt contacts
+    // `bootstrap_addrs`, the retries also `fallback_addrs`.
     async fn run(
         mut self,
         bootstrap_addrs: Vec<SocketAddr>,
+        fallback_addrs: Vec<SocketAddr>,
+        genesis_key: Option<bls::PublicKey>,
+        mut relocate_details: Option<SignedRelocateDetails>,
+    ) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
+        let mut contacts = bootstrap_addrs;
+        let mut relocate_payload = None;
+        let mut renewed = false;
+
+        loop {
+            let result = self
+                .attempt(
+                    contacts.clone(),
+                    genesis_key,
+                    &mut relocate_details,
+                    &mut relocate_payload,
+                )
+                .await;
+
+            match result {
+                Err(Error::BootstrapTimeout(phase)) if self.attempt < self.config.max_attempts => {
+                    let backoff = self.backoff(self.attempt);
+                    warn!(
+                        "Bootstrap attempt {} timed out in the {:?} phase, retrying in {:?}",
+                        self.attempt, phase, backoff
+                    );
+                    time::sleep(backoff).await;
+
+                    for addr in &fallback_addrs {
+                        if !contacts.contains(addr) {
+                            contacts.push(*addr);
+                        }
+                    }
+
+                    self.attempt += 1;
+                }
+                Err(Error::JoinRejected(JoinRejectionReason::FormerMember)) if !renewed => {
+                    // We left the section and are too young to be relocated back into it, so the
+                    // section won't take our current identity again. Join anew with a fresh one.
+                    self.renew_identity();
+                    renewed = true;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn attempt(
+        &mut self,
+        bootstrap_addrs: Vec<SocketAddr>,
         genesis_key: Option<bls::PublicKey>,
-        relocate_details: Option<SignedRelocateDetails>,
+        relocate_details: &mut Option<SignedRelocateDetails>,
+        relocate_payload: &mut Option<RelocatePayload>,
     ) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
         let (prefix, section_key, elders) = self
             .bootstrap(bootstrap_addrs, relocate_details.as_ref())
             .await?;
 
-        let relocate_payload = if let Some(details) = relocate_details {
-            Some(self.process_relocation(&prefix, details)?)
-        } else {
-            None
-        };
+        // The relocation changes our name, so it's processed only once. The retries then look for
+        // the section of our new name, which is the destination section.
+        if let Some(details) = relocate_details.take() {
+            *relocate_payload = Some(self.process_relocation(&prefix, details)?);
+        }
 
-        self.join(section_key, elders, genesis_key, relocate_payload)
+        self.join(section_key, elders, genesis_key, relocate_payload.clone())
             .await
     }
 
+    fn renew_identity(&mut self) {
+        let keypair = crypto::gen_keypair(
+            &Prefix::default().range_inclusive(),
+            self.network_params.min_age + 1,
+        );
+        let protocol = self.node.protocol;
+        self.node = Node::new(keypair, self.node.addr);
+        self.node.protocol = protocol;
+
+        info!(
+            "Rejected as a former member - joining anew as {}",
+            self.node.name()
+        );
+    }
+
+    // How long to wait before the retry following the given attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.config
+            .initial_backoff
+            .checked_mul(factor)
+            .map_or(self.config.max_backoff, |backoff| {
+                backoff.min(self.config.max_backoff)
+            })
+    }
+
     // Send a `GetSectionQuery` and waits for the response. If the response is `Redirect`,
     // repeat with the new set of contacts. If it is `Success`, proceeed to the `join` phase.
     async fn bootstrap(
@@ -148,7 +299,12 @@ impl<'a> State<'a> {
             self.send_get_section_request(mem::take(&mut bootstrap_addrs), relocate_details)
                 .await?;
 
-            let (response, sender) = self.receive_get_section_response(relocate_details).await?;
+            let (response, sender) = time::timeout(
+                self.config.get_section_timeout,
+                self.receive_get_section_response(relocate_details),
+            )
+            .await
+            .map_err(|_| Error::BootstrapTimeout(BootstrapPhase::GetSection))??;
 
             match response {
                 GetSectionResponse::Success(SectionInfo {
@@ -241,7 +397,7 @@ impl<'a> State<'a> {
                     }
                 }
                 MessageType::NodeMessage(NodeMessage(msg_bytes)) => {
-                    let message = Message::from_bytes(Bytes::from(msg_bytes))?;
+                    let message = Message::from_bytes(Bytes::from(msg_bytes), &self.node.protocol)?;
                     self.backlog_message(message, sender)
                 }
                 MessageType::SectionInfo(_) | MessageType::ClientMessage(_) | MessageType::Ping => {
@@ -275,7 +431,9 @@ impl<'a> State<'a> {
             RelocatePayload::new(relocate_details, &new_name, &self.node.keypair);
 
         info!("Changing name to {}", new_name);
+        let protocol = self.node.protocol;
         self.node = Node::new(new_keypair, self.node.addr);
+        self.node.protocol = protocol;
 
         Ok(relocate_payload)
     }
@@ -283,8 +441,11 @@ impl<'a> State<'a> {
     // Send `JoinRequest` and wait for the response. If the response is `Rejoin`, repeat with the
     // new info. If it is `Approval`, returns the initial `Section` value to use by this node,
     // completing the bootstrap. If it is `Challenge`, carries out a resource proof calculation.
+    // If it is `Rejected`, sends the request again to the rejecting elders after the time they
+    // asked for, or fails with `Error::JoinRejected` if they don't want it sent again or we are out
+    // of attempts.
     async fn join(
-        mut self,
+        &mut self,
         mut section_key: bls::PublicKey,
         elders: BTreeMap<XorName, SocketAddr>,
         genesis_key: Option<bls::PublicKey>,
@@ -295,24 +456,61 @@ impl<'a> State<'a> {
             relocate_payload: relocate_payload.clone(),
             resource_proof_response: None,
         };
-        let recipients = elders.into_iter().map(|(_, addr)| addr).collect();
-        self.send_join_requests(join_request, recipients).await?;
+        let mut elder_addrs: Vec<_> = elders.into_iter().map(|(_, addr)| addr).collect();
+        self.send_join_requests(join_request, elder_addrs.clone())
+            .await?;
+
+        // Time by which we expect a response to the requests we sent.
+        let mut response_deadline = Instant::now() + self.config.join_timeout;
+        // Elders that rejected our request for now, together with the time to retry it at.
+        let mut retry: Option<(Instant, Vec<SocketAddr>)> = None;
+        // Elders that rejected our request for good. A single elder can be lagging or faulty, so
+        // we give up only once a supermajority of them rejected us.
+        let mut rejected_by = HashSet::new();
 
         loop {
-            let (response, sender) = self
-                .receive_join_response(genesis_key.as_ref(), relocate_payload.as_ref())
-                .await?;
+            // While asked to retry later, we wait for that rather than for a response.
+            let deadline = retry
+                .as_ref()
+                .map_or(response_deadline, |(deadline, _)| *deadline);
+            let received = time::timeout_at(
+                deadline,
+                self.receive_join_response(genesis_key.as_ref(), relocate_payload.as_ref()),
+            )
+            .await
+            .ok();
+
+            let (response, sender) = if let Some(received) = received {
+                received?
+            } else if let Some((_, recipients)) = retry.take() {
+                self.attempt += 1;
+                info!(
+                    "Retrying the rejected join request (attempt {})",
+                    self.attempt
+                );
+                let join_request = JoinRequest {
+                    section_key,
+                    relocate_payload: relocate_payload.clone(),
+                    resource_proof_response: None,
+                };
+                self.send_join_requests(join_request, recipients).await?;
+                response_deadline = Instant::now() + self.config.join_timeout;
+                continue;
+            } else {
+                return Err(Error::BootstrapTimeout(BootstrapPhase::Join));
+            };
 
             match response {
                 JoinResponse::Approval {
                     elders_info,
                     genesis_key,
+                    network_params,
                     section_chain,
                 } => {
                     return Ok((
-                        self.node,
-                        Section::new(genesis_key, section_chain, elders_info)?,
-                        self.backlog.into_iter().collect(),
+                        self.node.clone(),
+                        Section::new(genesis_key, network_params, section_chain, elders_info)?,
+                        mem::take(&mut self.backlog).into_iter().collect(),
                     ));
                 }
                 JoinResponse::Retry {
@@ -334,8 +532,11 @@ impl<'a> State<'a> {
                             relocate_payload: relocate_payload.clone(),
                             resource_proof_response: None,
                         };
-                        let recipients = elders_info.peers().map(Peer::addr).copied().collect();
-                        self.send_join_requests(join_request, recipients).await?;
+                        elder_addrs = elders_info.peers().map(Peer::addr).copied().collect();
+                        rejected_by.clear();
+                        self.send_join_requests(join_request, elder_addrs.clone())
+                            .await?;
+                        response_deadline = Instant::now() + self.config.join_timeout;
                     } else {
                         warn!(
                             "Newer Join response not for our prefix {:?} from {:?}",
@@ -349,10 +550,19 @@ impl<'a> State<'a> {
                     nonce,
                     nonce_signature,
                 } => {
-                    let rp = ResourceProof::new(data_size, difficulty);
-                    let data = rp.create_proof_data(&nonce);
-                    let mut prover = rp.create_prover(data.clone());
-                    let solution = prover.solve();
+                    let solve = task::spawn_blocking(move || {
+                        let rp = ResourceProof::new(data_size, difficulty);
+                        let data = rp.create_proof_data(&nonce);
+                        let mut prover = rp.create_prover(data.clone());
+                        let solution = prover.solve();
+                        (data, solution)
+                    });
+                    // The solving can't be interrupted. On timeout it runs to completion in the
+                    // background and its result is discarded.
+                    let (data, solution) = time::timeout(self.config.resource_proof_timeout, solve)
+                        .await
+                        .map_err(|_| Error::BootstrapTimeout(BootstrapPhase::ResourceProof))?
+                        .map_err(|_| Error::InvalidState)?;
 
                     let join_request = JoinRequest {
                         section_key,
@@ -366,6 +576,52 @@ impl<'a> State<'a> {
                     };
                     let recipients = vec![sender];
                     self.send_join_requests(join_request, recipients).await?;
+                    response_deadline = Instant::now() + self.config.join_timeout;
+                }
+                JoinResponse::Rejected {
+                    reason,
+                    retry_after,
+                } => {
+                    if !elder_addrs.contains(&sender) {
+                        warn!(
+                            "Ignoring join rejection from {:?} which is not an elder: {:?}",
+                            sender, reason
+                        );
+                        continue;
+                    }
+
+                    let retry_after = match retry_after {
+                        Some(retry_after) if self.attempt < self.config.max_attempts => {
+                            retry_after.min(self.config.max_retry_after)
+                        }
+                        _ => {
+                            let _ = rejected_by.insert(sender);
+                            if rejected_by.len() >= supermajority(elder_addrs.len()) {
+                                error!("Join request rejected by {:?}: {:?}", sender, reason);
+                                return Err(Error::JoinRejected(reason));
+                            }
+
+                            warn!(
+                                "Join request rejected by {:?}: {:?}, waiting for the other elders",
+                                sender, reason
+                            );
+                            continue;
+                        }
+                    };
+
+                    info!(
+                        "Join request rejected by {:?}: {:?}, retrying in {:?}",
+                        sender, reason, retry_after
+                    );
+
+                    let retry_at = Instant::now()
+                        .checked_add(retry_after)
+                        .unwrap_or(response_deadline);
+                    let (deadline, recipients) = retry.get_or_insert((retry_at, vec![]));
+                    *deadline = (*deadline).max(retry_at);
+                    if !recipients.contains(&sender) {
+                        recipients.push(sender);
+                    }
                 }
             }
         }
@@ -398,7 +654,7 @@ impl<'a> State<'a> {
         while let Some((message, sender)) = self.recv_rx.next().await {
             let message = match message {
                 MessageType::NodeMessage(NodeMessage(msg_bytes)) => {
-                    Message::from_bytes(Bytes::from(msg_bytes))?
+                    Message::from_bytes(Bytes::from(msg_bytes), &self.node.protocol)?
                 }
                 MessageType::Ping | MessageType::ClientMessage(_) | MessageType::SectionInfo(_) => {
                     continue
@@ -422,6 +678,22 @@ impl<'a> State<'a> {
                         sender,
                     ));
                 }
+                Variant::JoinRejected {
+                    reason,
+                    retry_after,
+                } => {
+                    if !self.verify_message(&message, None) {
+                        continue;
+                    }
+
+                    return Ok((
+                        JoinResponse::Rejected {
+                            reason: *reason,
+                            retry_after: *retry_after,
+                        },
+                        sender,
+                    ));
+                }
                 Variant::ResourceChallenge {
                     data_size,
                     difficulty,
@@ -449,6 +721,7 @@ impl<'a> State<'a> {
                 }
                 Variant::NodeApproval {
                     genesis_key,
+                    network_params,
                     elders_info,
                     member_info,
                 } => {
@@ -474,6 +747,14 @@ impl<'a> State<'a> {
                         continue;
                     }
 
+                    if network_params.value != self.network_params {
+                        error!(
+                            "Network parameters mismatch - ours: {:?}, theirs: {:?}",
+                            self.network_params, network_params.value
+                        );
+                        return Err(Error::InvalidNetworkParams);
+                    }
+
                     let section_chain = message.proof_chain()?.clone();
 
                     info!(
@@ -485,6 +766,7 @@ impl<'a> State<'a> {
                         JoinResponse::Approval {
                             elders_info: elders_info.clone(),
                             genesis_key: *genesis_key,
+                            network_params: network_params.clone(),
                             section_chain,
                         },
                         sender,
@@ -529,6 +811,7 @@ enum JoinResponse {
     Approval {
         elders_info: Proven<EldersInfo>,
         genesis_key: bls::PublicKey,
+        network_params: Proven<NetworkParams>,
         section_chain: SectionChain,
     },
     Retry {
@@ -541,6 +824,10 @@ enum JoinResponse {
         nonce: [u8; 32],
         nonce_signature: Signature,
     },
+    Rejected {
+        reason: JoinRejectionReason,
+        retry_after: Option<Duration>,
+    },
 }
 
 // Receiver of incoming messages that can be backed either by a raw `qp2p::ConnectionEvent` receiver
@@ -593,8 +880,8 @@ async fn send_messages(mut rx: mpsc::Receiver<(MessageType, Vec<SocketAddr>)>, c
 mod tests {
     use super::*;
     use crate::{
-        agreement::test_utils::*, routing::tests::SecretKeySet, section::test_utils::*,
-        section::MemberInfo, ELDER_SIZE, MIN_AGE,
+        agreement::test_utils::*, protocol::ProtocolInfo, routing::tests::SecretKeySet,
+        section::test_utils::*, section::MemberInfo, ELDER_SIZE, MIN_AGE,
     };
     use anyhow::{anyhow, Error, Result};
     use assert_matches::assert_matches;
@@ -603,6 +890,7 @@ mod tests {
         pin_mut,
     };
     use sn_messaging::section_info::SectionInfo;
+    use std::iter;
     use tokio::task;
 
     #[tokio::test]
@@ -625,12 +913,18 @@ mod tests {
             gen_addr(),
         );
         let peer = node.peer();
-        let state = State::new(node, send_tx, recv_rx);
+        let state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
 
         // Create the bootstrap task, but don't run it yet.
         let bootstrap = async move {
             state
-                .run(vec![bootstrap_addr], None, None)
+                .run(vec![bootstrap_addr], vec![], None, None)
                 .await
                 .map_err(Error::from)
         };
@@ -668,7 +962,7 @@ mod tests {
                 .await
                 .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
             let message = assert_matches!(message, MessageType::NodeMessage(NodeMessage(bytes)) =>
-                Message::from_bytes(Bytes::from(bytes))?);
+                Message::from_bytes(Bytes::from(bytes), &ProtocolInfo::default())?);
 
             itertools::assert_equal(&recipients, elders_info.peers().map(Peer::addr));
             assert_matches!(message.variant(), Variant::JoinRequest(request) => {
@@ -685,6 +979,7 @@ mod tests {
                 DstLocation::Direct,
                 Variant::NodeApproval {
                     genesis_key: pk,
+                    network_params: proven(sk, NetworkParams::default())?,
                     elders_info,
                     member_info,
                 },
@@ -725,7 +1020,13 @@ mod tests {
             crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
             gen_addr(),
         );
-        let mut state = State::new(node, send_tx, recv_rx);
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
 
         let bootstrap_task = state.bootstrap(vec![bootstrap_node.addr], None);
         let test_task = async {
@@ -789,7 +1090,13 @@ mod tests {
             crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
             gen_addr(),
         );
-        let mut state = State::new(node, send_tx, recv_rx);
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
 
         let bootstrap_task = state.bootstrap(vec![bootstrap_node.addr], None);
         let test_task = async {
@@ -862,7 +1169,13 @@ mod tests {
             }
         };
 
-        let mut state = State::new(node, send_tx, recv_rx);
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
 
         let bootstrap_task = state.bootstrap(vec![bootstrap_node.addr], None);
 
@@ -942,7 +1255,13 @@ mod tests {
             }
         };
 
-        let state = State::new(node, send_tx, recv_rx);
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
 
         let section_key = bls::SecretKey::random().public_key();
         let elders = (0..ELDER_SIZE)
@@ -956,7 +1275,7 @@ mod tests {
                 .await
                 .ok_or_else(|| anyhow!("NodeMessage was not received"))?;
 
-            let message = assert_matches!(message, MessageType::NodeMessage(NodeMessage(bytes)) => Message::from_bytes(Bytes::from(bytes))?);
+            let message = assert_matches!(message, MessageType::NodeMessage(NodeMessage(bytes)) => Message::from_bytes(Bytes::from(bytes), &ProtocolInfo::default())?);
             assert_matches!(message.variant(), Variant::JoinRequest(_));
 
             // Send `Rejoin` with bad prefix
@@ -999,7 +1318,7 @@ mod tests {
                 .await
                 .ok_or_else(|| anyhow!("NodeMessage was not received"))?;
 
-            let message = assert_matches!(message, MessageType::NodeMessage(NodeMessage(bytes)) => Message::from_bytes(Bytes::from(bytes))?);
+            let message = assert_matches!(message, MessageType::NodeMessage(NodeMessage(bytes)) => Message::from_bytes(Bytes::from(bytes), &ProtocolInfo::default())?);
             assert_matches!(message.variant(), Variant::JoinRequest(_));
 
             Ok(())
@@ -1013,4 +1332,559 @@ mod tests {
             Either::Right((output, _)) => output,
         }
     }
+
+    #[tokio::test]
+    async fn join_rejects_approval_with_different_network_params() -> Result<()> {
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let (elders_info, mut nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let bootstrap_node = nodes.remove(0);
+
+        let sk = bls::SecretKey::random();
+        let pk = sk.public_key();
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let peer = node.peer();
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
+
+        let elders = elders_info
+            .peers()
+            .map(|peer| (*peer.name(), *peer.addr()))
+            .collect();
+        let join_task = state.join(pk, elders, None, None);
+
+        let test_task = async {
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
+
+            // Send NodeApproval from a network with different parameters.
+            let message = Message::single_src(
+                &bootstrap_node,
+                DstLocation::Direct,
+                Variant::NodeApproval {
+                    genesis_key: pk,
+                    network_params: proven(
+                        &sk,
+                        NetworkParams {
+                            elder_size: 3,
+                            recommended_section_size: 6,
+                            ..Default::default()
+                        },
+                    )?,
+                    elders_info: proven(&sk, elders_info.clone())?,
+                    member_info: proven(&sk, MemberInfo::joined(peer))?,
+                },
+                Some(SectionChain::new(pk)),
+                None,
+            )?;
+
+            recv_tx.try_send((
+                MessageType::NodeMessage(NodeMessage::new(message.to_bytes())),
+                bootstrap_node.addr,
+            ))?;
+
+            Ok::<_, Error>(())
+        };
+
+        let (join_result, test_result) = future::join(join_task, test_task).await;
+        test_result?;
+        assert_matches!(join_result, Err(crate::Error::InvalidNetworkParams));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_fails_when_rejected_by_elder() -> Result<()> {
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let (elders_info, mut nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let bootstrap_node = nodes.remove(0);
+
+        let pk = bls::SecretKey::random().public_key();
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
+
+        let elders = elders_info
+            .peers()
+            .map(|peer| (*peer.name(), *peer.addr()))
+            .collect();
+        let join_task = state.join(pk, elders, None, None);
+
+        let reason = JoinRejectionReason::AgeMismatch {
+            expected: MIN_AGE + 2,
+        };
+        let rejection = |src: &Node| -> Result<_> {
+            let message = Message::single_src(
+                src,
+                DstLocation::Direct,
+                Variant::JoinRejected {
+                    reason,
+                    retry_after: None,
+                },
+                None,
+                None,
+            )?;
+            Ok(MessageType::NodeMessage(NodeMessage::new(
+                message.to_bytes(),
+            )))
+        };
+
+        let test_task = async {
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
+
+            // Rejection from a node which is not one of the elders is ignored.
+            let other_node = Node::new(
+                crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+                gen_addr(),
+            );
+            recv_tx
+                .send((rejection(&other_node)?, other_node.addr))
+                .await?;
+
+            // The join fails only once a supermajority of the elders rejected it.
+            for elder in iter::once(&bootstrap_node)
+                .chain(&nodes)
+                .take(supermajority(ELDER_SIZE))
+            {
+                recv_tx.send((rejection(elder)?, elder.addr)).await?;
+            }
+
+            Ok::<_, Error>(())
+        };
+
+        let (join_result, test_result) = future::join(join_task, test_task).await;
+        test_result?;
+        assert_matches!(
+            join_result,
+            Err(crate::Error::JoinRejected(actual)) => assert_eq!(actual, reason)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_approved_despite_rejection_by_minority_of_elders() -> Result<()> {
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let (elders_info, nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let sk = bls::SecretKey::random();
+        let pk = sk.public_key();
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let peer = node.peer();
+        let mut state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
+
+        let elders = elders_info
+            .peers()
+            .map(|peer| (*peer.name(), *peer.addr()))
+            .collect();
+        let join_task = state.join(pk, elders, None, None);
+
+        let test_task = async {
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
+
+            let (rejecting, approving) = nodes.split_at(supermajority(ELDER_SIZE) - 1);
+            for elder in rejecting {
+                let message = Message::single_src(
+                    elder,
+                    DstLocation::Direct,
+                    Variant::JoinRejected {
+                        reason: JoinRejectionReason::FormerMember,
+                        retry_after: None,
+                    },
+                    None,
+                    None,
+                )?;
+                recv_tx
+                    .send((
+                        MessageType::NodeMessage(NodeMessage::new(message.to_bytes())),
+                        elder.addr,
+                    ))
+                    .await?;
+            }
+
+            let message = Message::single_src(
+                &approving[0],
+                DstLocation::Direct,
+                Variant::NodeApproval {
+                    genesis_key: pk,
+                    network_params: proven(&sk, NetworkParams::default())?,
+                    elders_info: proven(&sk, elders_info.clone())?,
+                    member_info: proven(&sk, MemberInfo::joined(peer))?,
+                },
+                Some(SectionChain::new(pk)),
+                None,
+            )?;
+            recv_tx
+                .send((
+                    MessageType::NodeMessage(NodeMessage::new(message.to_bytes())),
+                    approving[0].addr,
+                ))
+                .await?;
+
+            Ok::<_, Error>(())
+        };
+
+        let (join_result, test_result) = future::join(join_task, test_task).await;
+        test_result?;
+        let (_, section, _) = join_result?;
+        assert_eq!(*section.elders_info(), elders_info);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_retries_after_capped_wait_when_rejected_for_now() -> Result<()> {
+        time::pause();
+
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let (elders_info, mut nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let bootstrap_node = nodes.remove(0);
+
+        let pk = bls::SecretKey::random().public_key();
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let config = BootstrapConfig {
+            max_retry_after: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let mut state = State::new(node, NetworkParams::default(), config, send_tx, recv_rx);
+
+        let elders = elders_info
+            .peers()
+            .map(|peer| (*peer.name(), *peer.addr()))
+            .collect();
+        let join_task = state.join(pk, elders, None, None);
+
+        let rejection = |src: &Node| -> Result<_> {
+            let message = Message::single_src(
+                src,
+                DstLocation::Direct,
+                Variant::JoinRejected {
+                    reason: JoinRejectionReason::QueueFull,
+                    retry_after: Some(Duration::MAX),
+                },
+                None,
+                None,
+            )?;
+            Ok(MessageType::NodeMessage(NodeMessage::new(
+                message.to_bytes(),
+            )))
+        };
+
+        let test_task = async {
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
+
+            // Rejection from a node which is not one of the elders is ignored.
+            let other_node = Node::new(
+                crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+                gen_addr(),
+            );
+            recv_tx
+                .send((rejection(&other_node)?, other_node.addr))
+                .await?;
+
+            // The wait asked for by the elder is cut to `max_retry_after`.
+            let rejected_at = Instant::now();
+            recv_tx
+                .send((rejection(&bootstrap_node)?, bootstrap_node.addr))
+                .await?;
+
+            let (_, recipients) = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not resent"))?;
+            assert_eq!(recipients, [bootstrap_node.addr]);
+            assert!(rejected_at.elapsed() <= config.max_retry_after);
+
+            Ok::<_, Error>(())
+        };
+
+        let (join_result, test_result) = future::join(join_task, test_task).await;
+        test_result?;
+        assert_matches!(
+            join_result,
+            Err(crate::Error::BootstrapTimeout(BootstrapPhase::Join))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_gives_up_when_rejected_for_now_too_many_times() -> Result<()> {
+        time::pause();
+
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let (elders_info, mut nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let bootstrap_node = nodes.remove(0);
+
+        let pk = bls::SecretKey::random().public_key();
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let config = BootstrapConfig {
+            max_attempts: 2,
+            ..Default::default()
+        };
+        let mut state = State::new(node, NetworkParams::default(), config, send_tx, recv_rx);
+
+        let elders = elders_info
+            .peers()
+            .map(|peer| (*peer.name(), *peer.addr()))
+            .collect();
+        let join_task = state.join(pk, elders, None, None);
+
+        let rejection = |src: &Node| -> Result<_> {
+            let message = Message::single_src(
+                src,
+                DstLocation::Direct,
+                Variant::JoinRejected {
+                    reason: JoinRejectionReason::QueueFull,
+                    retry_after: Some(Duration::from_secs(1)),
+                },
+                None,
+                None,
+            )?;
+            Ok(MessageType::NodeMessage(NodeMessage::new(
+                message.to_bytes(),
+            )))
+        };
+
+        let test_task = async {
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
+            for elder in iter::once(&bootstrap_node).chain(&nodes) {
+                recv_tx.send((rejection(elder)?, elder.addr)).await?;
+            }
+
+            // The retry is the second and last attempt.
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not resent"))?;
+            for elder in iter::once(&bootstrap_node)
+                .chain(&nodes)
+                .take(supermajority(ELDER_SIZE))
+            {
+                recv_tx.send((rejection(elder)?, elder.addr)).await?;
+            }
+
+            Ok::<_, Error>(())
+        };
+
+        let (join_result, test_result) = future::join(join_task, test_task).await;
+        test_result?;
+        assert_matches!(
+            join_result,
+            Err(crate::Error::JoinRejected(JoinRejectionReason::QueueFull))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn former_member_joins_with_fresh_identity() -> Result<()> {
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let (elders_info, nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let bootstrap_addr = nodes[0].addr;
+        let pk_set = SecretKeySet::random().public_keys();
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let old_name = node.name();
+        let state = State::new(
+            node,
+            NetworkParams::default(),
+            BootstrapConfig::default(),
+            send_tx,
+            recv_rx,
+        );
+
+        let bootstrap_task = state.run(vec![bootstrap_addr], vec![], None, None);
+        let test_task = async move {
+            let (message, _) = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("GetSectionQuery was not received"))?;
+            assert_matches!(
+                message,
+                MessageType::SectionInfo(SectionInfoMsg::GetSectionQuery(name)) => {
+                    assert_eq!(name, old_name)
+                }
+            );
+
+            let message =
+                SectionInfoMsg::GetSectionResponse(GetSectionResponse::Success(SectionInfo {
+                    prefix: elders_info.prefix,
+                    pk_set,
+                    elders: elders_info
+                        .peers()
+                        .map(|peer| (*peer.name(), *peer.addr()))
+                        .collect(),
+                }));
+            recv_tx
+                .send((MessageType::SectionInfo(message), bootstrap_addr))
+                .await?;
+
+            let _ = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("JoinRequest was not received"))?;
+
+            for elder in &nodes {
+                let message = Message::single_src(
+                    elder,
+                    DstLocation::Direct,
+                    Variant::JoinRejected {
+                        reason: JoinRejectionReason::FormerMember,
+                        retry_after: None,
+                    },
+                    None,
+                    None,
+                )?;
+                recv_tx
+                    .send((
+                        MessageType::NodeMessage(NodeMessage::new(message.to_bytes())),
+                        elder.addr,
+                    ))
+                    .await?;
+            }
+
+            // Bootstrapping starts over under a fresh name.
+            let (message, recipients) = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("GetSectionQuery was not resent"))?;
+            assert_eq!(recipients, [bootstrap_addr]);
+            assert_matches!(
+                message,
+                MessageType::SectionInfo(SectionInfoMsg::GetSectionQuery(name)) => {
+                    assert_ne!(name, old_name)
+                }
+            );
+
+            Ok::<_, Error>(())
+        };
+
+        // The bootstrap fails once the test task drops its end of the channel.
+        let (_, test_result) = future::join(bootstrap_task, test_task).await;
+        test_result
+    }
+
+    #[tokio::test]
+    async fn retry_after_timeout_with_all_contacts() -> Result<()> {
+        time::pause();
+
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (_recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let config = BootstrapConfig {
+            max_attempts: 2,
+            ..Default::default()
+        };
+        let state = State::new(node, NetworkParams::default(), config, send_tx, recv_rx);
+
+        let bootstrap_addr = gen_addr();
+        let other_addr = gen_addr();
+        let bootstrap_task = state.run(
+            vec![bootstrap_addr],
+            vec![bootstrap_addr, other_addr],
+            None,
+            None,
+        );
+
+        let test_task = async {
+            // The first attempt contacts only the bootstrap node, the retry all the contacts.
+            let (_, recipients) = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("GetSectionQuery was not received"))?;
+            assert_eq!(recipients, [bootstrap_addr]);
+
+            let (_, recipients) = send_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("GetSectionQuery was not received"))?;
+            assert_eq!(recipients, [bootstrap_addr, other_addr]);
+
+            Ok::<_, Error>(())
+        };
+
+        let (bootstrap_result, test_result) = future::join(bootstrap_task, test_task).await;
+        test_result?;
+        assert_matches!(
+            bootstrap_result,
+            Err(crate::Error::BootstrapTimeout(BootstrapPhase::GetSection))
+        );
+
+        Ok(())
+    }
 }
@@ -6,6 +6,8 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+#[cfg(feature = "simulated-network")]
+use super::sim_network::{SimEndpoint, SimNetwork};
 use crate::error::{Error, Result};
 use bytes::Bytes;
 use futures::stream::{FuturesUnordered, StreamExt};
@@ -21,8 +23,7 @@ use tokio::{sync::mpsc, task};
 
 // Communication component of the node to interact with other nodes.
 pub(crate) struct Comm {
-    _quic_p2p: QuicP2p,
-    endpoint: Endpoint,
+    transport: Transport,
     // Sender for connection events. Kept here so we can clone it and pass it to the incoming
     // messages handler every time we establish new connection. It's kept in an `Option` so we can
     // take it out and drop it on `terminate` which together with all the incoming message handlers
@@ -55,8 +56,10 @@ impl Comm {
         ));
 
         Ok(Self {
-            _quic_p2p: quic_p2p,
-            endpoint,
+            transport: Transport::Quic {
+                _quic_p2p: quic_p2p,
+                endpoint,
+            },
             event_tx: RwLock::new(Some(event_tx)),
         })
     }
@@ -84,8 +87,65 @@ impl Comm {
 
         Ok((
             Self {
-                _quic_p2p: quic_p2p,
-                endpoint,
+                transport: Transport::Quic {
+                    _quic_p2p: quic_p2p,
+                    endpoint,
+                },
+                event_tx: RwLock::new(Some(event_tx)),
+            },
+            bootstrap_addr,
+        ))
+    }
+
+    // Creates `Comm` on top of the in-process simulated network. Only the `local_ip` and
+    // `local_port` of the transport config are used.
+    #[cfg(feature = "simulated-network")]
+    pub fn new_simulated(
+        network: &SimNetwork,
+        transport_config: &qp2p::Config,
+        event_tx: mpsc::Sender<ConnectionEvent>,
+    ) -> Self {
+        let endpoint = network.new_endpoint(
+            transport_config.local_ip,
+            transport_config.local_port,
+            event_tx.clone(),
+        );
+
+        Self {
+            transport: Transport::Simulated(endpoint),
+            event_tx: RwLock::new(Some(event_tx)),
+        }
+    }
+
+    // Creates `Comm` on top of the in-process simulated network and picks the first reachable
+    // node out of the hard-coded contacts to bootstrap off.
+    #[cfg(feature = "simulated-network")]
+    pub fn bootstrap_simulated(
+        network: &SimNetwork,
+        transport_config: &qp2p::Config,
+        event_tx: mpsc::Sender<ConnectionEvent>,
+    ) -> Result<(Self, SocketAddr)> {
+        let endpoint = network.new_endpoint(
+            transport_config.local_ip,
+            transport_config.local_port,
+            event_tx.clone(),
+        );
+
+        let bootstrap_addr = if let Some(addr) = transport_config
+            .hard_coded_contacts
+            .iter()
+            .find(|addr| endpoint.connect_to(addr).is_ok())
+        {
+            *addr
+        } else {
+            error!("Failed to bootstrap to the simulated network - no reachable contacts");
+            endpoint.close();
+            return Err(Error::FailedSend);
+        };
+
+        Ok((
+            Self {
+                transport: Transport::Simulated(endpoint),
                 event_tx: RwLock::new(Some(event_tx)),
             },
             bootstrap_addr,
@@ -94,7 +154,7 @@ impl Comm {
 
     // Close all existing connections and stop accepting new ones.
     pub fn terminate(&self) {
-        self.endpoint.close();
+        self.transport.close();
         let _ = self
             .event_tx
             .write()
@@ -102,8 +162,13 @@ impl Comm {
             .take();
     }
 
+    // Close the connection to the given peer, if any.
+    pub fn disconnect(&self, peer: &SocketAddr) {
+        self.transport.disconnect_from(peer)
+    }
+
     pub fn our_connection_info(&self) -> SocketAddr {
-        self.endpoint.socket_addr()
+        self.transport.socket_addr()
     }
 
     /// Sends a message on an existing connection. If no such connection exists, returns an error.
@@ -112,7 +177,7 @@ impl Comm {
         recipient: &SocketAddr,
         msg: Bytes,
     ) -> Result<(), SendError> {
-        self.endpoint
+        self.transport
             .send_message(msg, recipient)
             .await
             .map_err(|err| {
@@ -169,7 +234,7 @@ impl Comm {
         while let Some((result, addr)) = tasks.next().await {
             match result {
                 Ok(()) => successes += 1,
-                Err(qp2p::Error::Connection(qp2p::ConnectionError::LocallyClosed)) => {
+                Err(TransportError::LocallyClosed) => {
                     // The connection was closed by us which means we are terminating so let's cut
                     // this short.
                     return (Err(SendError), vec![]);
@@ -203,10 +268,10 @@ impl Comm {
     }
 
     // Low-level send
-    async fn send_to(&self, recipient: &SocketAddr, msg: Bytes) -> Result<(), qp2p::Error> {
+    async fn send_to(&self, recipient: &SocketAddr, msg: Bytes) -> Result<(), TransportError> {
         // This will attempt to use a cached connection
         if self
-            .endpoint
+            .transport
             .send_message(msg.clone(), recipient)
             .await
             .is_ok()
@@ -216,14 +281,92 @@ impl Comm {
 
         // If the sending of a message failed the connection would no longer
         // exist in the pool. So we connect again and then send the message.
-        self.endpoint.connect_to(recipient).await?;
-        self.endpoint.send_message(msg, recipient).await
+        self.transport.connect_to(recipient).await?;
+        self.transport.send_message(msg, recipient).await
+    }
+}
+
+// Transport the messages are sent over: either the real QUIC one or the in-process simulated one.
+enum Transport {
+    Quic {
+        _quic_p2p: QuicP2p,
+        endpoint: Endpoint,
+    },
+    #[cfg(feature = "simulated-network")]
+    Simulated(SimEndpoint),
+}
+
+impl Transport {
+    fn socket_addr(&self) -> SocketAddr {
+        match self {
+            Self::Quic { endpoint, .. } => endpoint.socket_addr(),
+            #[cfg(feature = "simulated-network")]
+            Self::Simulated(endpoint) => endpoint.socket_addr(),
+        }
+    }
+
+    async fn send_message(&self, msg: Bytes, recipient: &SocketAddr) -> Result<(), TransportError> {
+        match self {
+            Self::Quic { endpoint, .. } => Ok(endpoint.send_message(msg, recipient).await?),
+            #[cfg(feature = "simulated-network")]
+            Self::Simulated(endpoint) => endpoint.send_message(msg, recipient),
+        }
+    }
+
+    async fn connect_to(&self, recipient: &SocketAddr) -> Result<(), TransportError> {
+        match self {
+            Self::Quic { endpoint, .. } => Ok(endpoint.connect_to(recipient).await?),
+            #[cfg(feature = "simulated-network")]
+            Self::Simulated(endpoint) => endpoint.connect_to(recipient),
+        }
+    }
+
+    fn disconnect_from(&self, peer: &SocketAddr) {
+        match self {
+            Self::Quic { endpoint, .. } => {
+                if let Err(error) = endpoint.disconnect_from(peer) {
+                    trace!("Failed to disconnect from {}: {}", peer, error);
+                }
+            }
+            // Simulated endpoints are connectionless.
+            #[cfg(feature = "simulated-network")]
+            Self::Simulated(_) => (),
+        }
+    }
+
+    fn close(&self) {
+        match self {
+            Self::Quic { endpoint, .. } => endpoint.close(),
+            #[cfg(feature = "simulated-network")]
+            Self::Simulated(endpoint) => endpoint.close(),
+        }
+    }
+}
+
+// Error of the underlying transport.
+#[derive(Debug, Error)]
+pub(crate) enum TransportError {
+    #[error("Connection closed locally")]
+    LocallyClosed,
+    #[cfg(feature = "simulated-network")]
+    #[error("Peer {0} unreachable")]
+    Unreachable(SocketAddr),
+    #[error("{0}")]
+    Quic(qp2p::Error),
+}
+
+impl From<qp2p::Error> for TransportError {
+    fn from(error: qp2p::Error) -> Self {
+        match error {
+            qp2p::Error::Connection(qp2p::ConnectionError::LocallyClosed) => Self::LocallyClosed,
+            error => Self::Quic(error),
+        }
     }
 }
 
 impl Drop for Comm {
     fn drop(&mut self) {
-        self.endpoint.close()
+        self.transport.close()
     }
 }
 
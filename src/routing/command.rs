@@ -6,9 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::scheduler::Priority;
 use crate::{
-    agreement::{DkgFailureProofSet, Proposal},
-    messages::Message,
+    agreement::{DkgFailureProofSet, DkgKey, Proposal},
+    messages::{Message, Variant},
     relocation::SignedRelocateDetails,
     section::{EldersInfo, SectionKeyShare},
 };
@@ -44,25 +45,35 @@ pub(crate) enum Command {
     },
     /// Handle a timeout previously scheduled with `ScheduleTimeout`.
     HandleTimeout(u64),
+    /// Handle a DKG timeout previously scheduled with `ScheduleDkgTimeout`.
+    HandleDkgTimeout(u64),
     /// Handle lost connection to a peer.
     HandleConnectionLost(SocketAddr),
     /// Handle peer that's been detected as lost.
     HandlePeerLost(SocketAddr),
+    /// Close the connection to a client that's been removed from our registry.
+    DisconnectClient(SocketAddr),
     /// Handle agreement on a proposal.
     HandleAgreement { proposal: Proposal, proof: Proof },
     /// Handle the outcome of a DKG session where we are one of the participants (that is, one of
     /// the proposed new elders).
     HandleDkgOutcome {
+        dkg_key: DkgKey,
         elders_info: EldersInfo,
         outcome: SectionKeyShare,
     },
     /// Handle a DKG failure that was observed by a majority of the DKG participants.
-    HandleDkgFailure(DkgFailureProofSet),
-    /// Send a message to `delivery_group_size` peers out of the given `recipients`.
+    HandleDkgFailure {
+        dkg_key: DkgKey,
+        proofs: DkgFailureProofSet,
+    },
+    /// Send a message to `delivery_group_size` peers out of the given `recipients`. `variant` is
+    /// the name of the variant of a node message, for the metrics, if known.
     SendMessage {
         recipients: Vec<SocketAddr>,
         delivery_group_size: usize,
         message: MessageType,
+        variant: Option<&'static str>,
     },
     /// Send `UserMessage` with the given source and destination.
     SendUserMessage {
@@ -73,6 +84,9 @@ pub(crate) enum Command {
     /// Schedule a timeout after the given duration. When the timeout expires, a `HandleTimeout`
     /// command is raised. The token is used to identify the timeout.
     ScheduleTimeout { duration: Duration, token: u64 },
+    /// Like `ScheduleTimeout`, but raises a `HandleDkgTimeout` command instead, which is handled
+    /// with high priority.
+    ScheduleDkgTimeout { duration: Duration, token: u64 },
     /// Relocate
     Relocate {
         /// Contacts to re-bootstrap to
@@ -87,22 +101,74 @@ pub(crate) enum Command {
 }
 
 impl Command {
+    /// Priority with which this command gets access to the node state.
+    pub fn priority(&self) -> Priority {
+        match self {
+            Self::HandleMessage { message, .. } => match message.variant() {
+                Variant::Propose { .. }
+                | Variant::DkgStart { .. }
+                | Variant::DkgMessage { .. }
+                | Variant::DkgFailureObservation { .. }
+                | Variant::DkgFailureAgreement { .. }
+                | Variant::Sync { .. }
+                | Variant::MergeRequest { .. }
+                | Variant::Relocate(_)
+                | Variant::RelocatePromise(_)
+                | Variant::NodeApproval { .. } => Priority::High,
+                Variant::DeliveryReceipt(_) => Priority::Low,
+                variant if variant.is_user() => Priority::Low,
+                _ => Priority::Normal,
+            },
+            Self::HandleAgreement { .. }
+            | Self::HandleDkgOutcome { .. }
+            | Self::HandleDkgFailure { .. }
+            | Self::HandleDkgTimeout(_)
+            | Self::ScheduleDkgTimeout { .. }
+            | Self::Relocate { .. } => Priority::High,
+            Self::HandleSectionInfoMsg { .. } | Self::SendUserMessage { .. } => Priority::Low,
+            Self::HandleTimeout(_)
+            | Self::HandleConnectionLost(_)
+            | Self::HandlePeerLost(_)
+            | Self::DisconnectClient(_)
+            | Self::SendMessage { .. }
+            | Self::ScheduleTimeout { .. }
+            | Self::SetJoinsAllowed(_) => Priority::Normal,
+        }
+    }
+
     /// Convenience method to create `Command::SendMessage` with a single recipient.
-    pub fn send_message_to_node(recipient: &SocketAddr, message_bytes: Bytes) -> Self {
-        Self::send_message_to_nodes(slice::from_ref(recipient), 1, message_bytes)
+    pub fn send_message_to_node(recipient: &SocketAddr, message: &Message) -> Self {
+        Self::send_message_to_nodes(slice::from_ref(recipient), 1, message)
     }
 
     /// Convenience method to create `Command::SendMessage` with multiple recipients.
     pub fn send_message_to_nodes(
+        recipients: &[SocketAddr],
+        delivery_group_size: usize,
+        message: &Message,
+    ) -> Self {
+        Self::send_bytes_to_nodes(
+            recipients,
+            delivery_group_size,
+            message.to_bytes(),
+            Some(message.variant().name()),
+        )
+    }
+
+    /// Convenience method to create `Command::SendMessage` of an already serialized message with
+    /// the given variant name (if known).
+    pub fn send_bytes_to_nodes(
         recipients: &[SocketAddr],
         delivery_group_size: usize,
         message_bytes: Bytes,
+        variant: Option<&'static str>,
     ) -> Self {
         let node_msg = NodeMessage::new(message_bytes);
         Self::SendMessage {
             recipients: recipients.to_vec(),
             delivery_group_size,
             message: MessageType::NodeMessage(node_msg),
+            variant,
         }
     }
 }
@@ -121,35 +187,45 @@ impl Debug for Command {
                 .field("message", message)
                 .finish(),
             Self::HandleTimeout(token) => f.debug_tuple("HandleTimeout").field(token).finish(),
+            Self::HandleDkgTimeout(token) => {
+                f.debug_tuple("HandleDkgTimeout").field(token).finish()
+            }
             Self::HandleConnectionLost(addr) => {
                 f.debug_tuple("HandleConnectionLost").field(addr).finish()
             }
             Self::HandlePeerLost(addr) => f.debug_tuple("HandlePeerLost").field(addr).finish(),
+            Self::DisconnectClient(addr) => f.debug_tuple("DisconnectClient").field(addr).finish(),
             Self::HandleAgreement { proposal, proof } => f
                 .debug_struct("HandleAgreement")
                 .field("proposal", proposal)
                 .field("proof.public_key", &proof.public_key)
                 .finish(),
             Self::HandleDkgOutcome {
+                dkg_key,
                 elders_info,
                 outcome,
             } => f
                 .debug_struct("HandleDkgOutcome")
+                .field("dkg_key", dkg_key)
                 .field("elders_info", elders_info)
                 .field("outcome", &outcome.public_key_set.public_key())
                 .finish(),
-            Self::HandleDkgFailure(proofs) => {
-                f.debug_tuple("HandleDkgFailure").field(proofs).finish()
-            }
+            Self::HandleDkgFailure { dkg_key, proofs } => f
+                .debug_struct("HandleDkgFailure")
+                .field("dkg_key", dkg_key)
+                .field("proofs", proofs)
+                .finish(),
             Self::SendMessage {
                 recipients,
                 delivery_group_size,
                 message,
+                variant,
             } => f
                 .debug_struct("SendMessage")
                 .field("recipients", recipients)
                 .field("delivery_group_size", delivery_group_size)
                 .field("message", message)
+                .field("variant", variant)
                 .finish(),
             Self::SendUserMessage {
                 itinerary,
@@ -166,6 +242,11 @@ impl Debug for Command {
                 .field("duration", duration)
                 .field("token", token)
                 .finish(),
+            Self::ScheduleDkgTimeout { duration, token } => f
+                .debug_struct("ScheduleDkgTimeout")
+                .field("duration", duration)
+                .field("token", token)
+                .finish(),
             Self::Relocate {
                 bootstrap_addrs,
                 details,
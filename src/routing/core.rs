@@ -7,43 +7,57 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    enduser_registry::{EndUserRegistry, SocketId},
+    delivery::{Deliveries, Delivery},
+    enduser_registry::{ClientInfo, EndUserRegistry, SocketId},
+    event_stream::{EventSender, EventSubscribers},
+    gossip::Gossip,
+    join_difficulty::JoinDifficulty,
+    join_queue::{Enqueued, JoinQueue, QueuedJoin},
     lazy_messaging,
+    liveness::Liveness,
+    merge_barrier::MergeBarrier,
+    request::{self, Requests, Response},
+    section_signing::{self, SectionSigning, SectionSigningApproval},
     split_barrier::SplitBarrier,
-    Command,
+    state_store::{StateStore, StoredState},
+    BootstrapConfig, ClientConfig, Command, GossipConfig, JoinQueueConfig,
 };
 use crate::{
     agreement::{
-        DkgCommands, DkgFailureProof, DkgFailureProofSet, DkgKey, DkgVoter, Proof, ProofShare,
-        Proposal, ProposalAggregationError, ProposalAggregator, Proven,
+        DkgCommands, DkgFailureProof, DkgFailureProofSet, DkgKey, DkgSessionInfo, DkgVoter, Proof,
+        ProofShare, Proposal, ProposalAggregationError, ProposalAggregator, Proven,
     },
     crypto, delivery_group,
     error::{Error, Result},
     event::{Event, NodeElderChange},
     message_filter::MessageFilter,
     messages::{
-        JoinRequest, Message, MessageHash, MessageStatus, PlainMessage, ResourceProofResponse,
-        SrcAuthority, Variant, VerifyStatus,
+        JoinRejectionReason, JoinRequest, Message, MessageHash, MessageStatus, PlainMessage,
+        ResourceProofResponse, SrcAuthority, Variant, VerifyStatus,
     },
+    metrics::{AggregationTimer, Metrics, MetricsRecorder},
     network::Network,
+    network_params::NetworkParams,
     node::Node,
     peer::Peer,
+    protocol::{self, Features, ProtocolInfo, ProtocolUpgrade},
     relocation::{
         self, RelocateAction, RelocateDetails, RelocatePromise, RelocateState,
         SignedRelocateDetails,
     },
+    rng::MainRng,
     section::{
-        EldersInfo, MemberInfo, PeerState, Section, SectionChain, SectionKeyShare,
-        SectionKeysProvider, MIN_AGE,
+        is_merge_leader, Checkpoint, EldersInfo, MemberInfo, PeerState, Section, SectionChain,
+        SectionChainError, SectionChainFork, SectionKeyShare, SectionKeysProvider,
     },
-    ELDER_SIZE,
+    supermajority,
 };
 use bls_dkg::key_gen::message::Message as DkgMessage;
 use bls_signature_aggregator::{Error as AggregatorError, SignatureAggregator};
 use bytes::Bytes;
 use ed25519_dalek::Verifier;
 use itertools::Itertools;
-use resource_proof::ResourceProof;
+use rand::Rng;
 use sn_data_types::PublicKey as EndUserPK;
 use sn_messaging::{
     client::Message as ClientMessage,
@@ -51,20 +65,23 @@ use sn_messaging::{
     section_info::{
         Error as TargetSectionError, GetSectionResponse, Message as SectionInfoMsg, SectionInfo,
     },
-    DstLocation, EndUser, Itinerary, MessageType, SrcLocation,
+    Aggregation, DstLocation, EndUser, Itinerary, MessageType, SrcLocation,
 };
 use std::{
     cmp::{self, Ordering},
+    collections::BTreeMap,
     iter,
     net::SocketAddr,
     slice,
+    sync::Arc,
+    time::Duration,
 };
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use xor_name::{Prefix, XorName};
 
-pub(crate) const RESOURCE_PROOF_DATA_SIZE: usize = 64;
-pub(crate) const RESOURCE_PROOF_DIFFICULTY: u8 = 2;
-const KEY_CACHE_SIZE: u8 = 5;
+// Number of the most recent keys of our section chain kept when pruning it. The chain is pruned
+// once it grows to twice as many keys.
+pub(super) const CHAIN_KEYS_KEPT: usize = 64;
 
 // State + logic of a routing node.
 pub(crate) struct Core {
@@ -73,22 +90,41 @@ pub(crate) struct Core {
     network: Network,
     section_keys_provider: SectionKeysProvider,
     message_aggregator: SignatureAggregator,
+    message_aggregation_timer: AggregationTimer,
     proposal_aggregator: ProposalAggregator,
     split_barrier: SplitBarrier,
+    merge_barrier: MergeBarrier,
     // Voter for Dkg
     dkg_voter: DkgVoter,
     relocate_state: Option<RelocateState>,
     msg_filter: MessageFilter,
-    pub(super) event_tx: mpsc::UnboundedSender<Event>,
+    pub(super) event_tx: EventSender,
+    event_subscribers: EventSubscribers,
     joins_allowed: bool,
-    resource_proof: ResourceProof,
+    join_difficulty: JoinDifficulty,
+    join_queue: JoinQueue,
+    bootstrap_config: BootstrapConfig,
     end_users: EndUserRegistry,
+    state_store: Option<Arc<dyn StateStore>>,
+    liveness: Liveness,
+    gossip: Gossip,
+    metrics: MetricsRecorder,
+    deliveries: Deliveries,
+    requests: Requests,
+    section_signing: SectionSigning,
+    // Protocol versions and features advertised by the members of our section (and the nodes
+    // joining it).
+    peer_protocols: BTreeMap<XorName, ProtocolInfo>,
 }
 
 impl Core {
     // Creates `Core` for the first node in the network
-    pub fn first_node(node: Node, event_tx: mpsc::UnboundedSender<Event>) -> Result<Self> {
-        let (section, section_key_share) = Section::first_node(node.peer())?;
+    pub fn first_node(
+        node: Node,
+        network_params: NetworkParams,
+        event_tx: EventSender,
+    ) -> Result<Self> {
+        let (section, section_key_share) = Section::first_node(node.peer(), network_params)?;
         Ok(Self::new(node, section, Some(section_key_share), event_tx))
     }
 
@@ -97,9 +133,11 @@ impl Core {
         node: Node,
         section: Section,
         section_key_share: Option<SectionKeyShare>,
-        event_tx: mpsc::UnboundedSender<Event>,
+        event_tx: EventSender,
     ) -> Self {
-        let section_keys_provider = SectionKeysProvider::new(KEY_CACHE_SIZE, section_key_share);
+        let network_params = *section.network_params();
+        let section_keys_provider =
+            SectionKeysProvider::new(network_params.key_cache_size, section_key_share);
 
         Self {
             node,
@@ -108,19 +146,160 @@ impl Core {
             section_keys_provider,
             proposal_aggregator: Default::default(),
             split_barrier: SplitBarrier::new(),
+            merge_barrier: MergeBarrier::new(),
             message_aggregator: Default::default(),
+            message_aggregation_timer: AggregationTimer::new(),
             dkg_voter: Default::default(),
             relocate_state: None,
             msg_filter: MessageFilter::new(),
             event_tx,
+            event_subscribers: Default::default(),
             joins_allowed: true,
-            resource_proof: ResourceProof::new(RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY),
-            end_users: EndUserRegistry::new(),
+            join_difficulty: JoinDifficulty::new(&network_params),
+            join_queue: Default::default(),
+            bootstrap_config: Default::default(),
+            end_users: Default::default(),
+            state_store: None,
+            liveness: Default::default(),
+            gossip: Default::default(),
+            metrics: Default::default(),
+            deliveries: Deliveries::new(),
+            requests: Requests::new(),
+            section_signing: SectionSigning::new(),
+            peer_protocols: BTreeMap::new(),
         }
     }
 
-    pub fn get_enduser_by_addr(&self, sender: &SocketAddr) -> Option<&EndUser> {
-        self.end_users.get_enduser_by_addr(sender)
+    // Creates `Core` for a node restored from a previously persisted state.
+    pub fn restore(
+        node: Node,
+        section: Section,
+        network: Network,
+        section_key_share: Option<SectionKeyShare>,
+        event_tx: EventSender,
+    ) -> Self {
+        // The key share is only useful if it still belongs to the current key of our section.
+        let section_key_share = section_key_share
+            .filter(|share| share.public_key_set.public_key() == *section.chain().last_key());
+
+        let mut core = Self::new(node, section, section_key_share, event_tx);
+        core.network = network;
+        core
+    }
+
+    pub fn state_store(&self) -> Option<&Arc<dyn StateStore>> {
+        self.state_store.as_ref()
+    }
+
+    // Sets the store to persist our state into and persists the current state right away.
+    pub fn set_state_store(&mut self, state_store: Arc<dyn StateStore>) {
+        self.state_store = Some(state_store);
+        self.store_state();
+    }
+
+    pub fn unresponsive_threshold(&self) -> Duration {
+        self.liveness.threshold()
+    }
+
+    // Sets how long a section member can stay unresponsive before we propose it offline.
+    pub fn set_unresponsive_threshold(&mut self, threshold: Duration) {
+        self.liveness.set_threshold(threshold);
+    }
+
+    // Starts the periodic checks of the liveness of our section members.
+    pub fn start_liveness_checks(&mut self) -> Command {
+        self.liveness.schedule_check()
+    }
+
+    pub fn gossip_config(&self) -> GossipConfig {
+        self.gossip.config()
+    }
+
+    // Sets how we gossip with other sections.
+    pub fn set_gossip_config(&mut self, config: GossipConfig) {
+        self.gossip.set_config(config);
+    }
+
+    // Starts the periodic gossip with other sections.
+    pub fn start_gossip(&mut self) -> Command {
+        self.gossip.schedule()
+    }
+
+    // Starts the periodic adjustments of the resource proof difficulty for the joining nodes.
+    pub fn start_join_difficulty_adjustments(&mut self) -> Command {
+        self.join_difficulty.schedule_adjustment()
+    }
+
+    pub fn bootstrap_config(&self) -> BootstrapConfig {
+        self.bootstrap_config
+    }
+
+    // Sets the timeouts and retries of the bootstrapping when we get relocated.
+    pub fn set_bootstrap_config(&mut self, config: BootstrapConfig) {
+        self.bootstrap_config = config;
+    }
+
+    pub fn protocol_info(&self) -> ProtocolInfo {
+        self.node.protocol
+    }
+
+    // Sets the protocol versions and features we advertise to the other nodes.
+    pub fn set_protocol_info(&mut self, protocol: ProtocolInfo) {
+        self.node.protocol = protocol;
+    }
+
+    pub fn join_queue_config(&self) -> JoinQueueConfig {
+        self.join_queue.config()
+    }
+
+    // Sets how we admit new nodes into our section.
+    pub fn set_join_queue_config(&mut self, config: JoinQueueConfig) {
+        self.join_queue.set_config(config);
+    }
+
+    // Starts the periodic expiry of the stale entries in the join queue.
+    pub fn start_join_queue_expiry(&mut self) -> Command {
+        self.join_queue.schedule_expiry()
+    }
+
+    pub fn join_queue(&self) -> impl Iterator<Item = QueuedJoin> + '_ {
+        self.join_queue.entries()
+    }
+
+    pub fn dkg_sessions(&self) -> Vec<DkgSessionInfo> {
+        self.dkg_voter.sessions()
+    }
+
+    pub fn metrics_recorder(&self) -> &MetricsRecorder {
+        &self.metrics
+    }
+
+    // Sets the recorder to collect our metrics into, so they survive replacing this `Core`.
+    pub fn set_metrics_recorder(&mut self, metrics: MetricsRecorder) {
+        self.metrics = metrics;
+    }
+
+    pub fn event_subscribers(&self) -> &EventSubscribers {
+        &self.event_subscribers
+    }
+
+    pub fn set_event_subscribers(&mut self, event_subscribers: EventSubscribers) {
+        self.event_subscribers = event_subscribers;
+    }
+
+    // Returns the metrics recorded so far together with the current section and network sizes.
+    pub fn metrics(&self) -> Metrics {
+        let network_stats = self.network.network_stats(self.section.elders_info());
+
+        Metrics {
+            section_members: self.section.members().joined().count(),
+            section_elders: self.section.elders_info().elders.len(),
+            known_network_elders: network_stats.known_elders(),
+            estimated_network_elders: network_stats.total_elders(),
+            event_queue_depth: self.event_tx.depth(),
+            events_dropped: self.event_tx.dropped(),
+            ..self.metrics.snapshot()
+        }
     }
 
     pub fn get_socket_addr(&self, id: SocketId) -> Option<&SocketAddr> {
@@ -134,6 +313,43 @@ impl Core {
         self.end_users.get_all_socket_addr(end_user)
     }
 
+    pub fn client_config(&self) -> ClientConfig {
+        self.end_users.config()
+    }
+
+    // Sets the limits on our connected clients.
+    pub fn set_client_config(&mut self, config: ClientConfig) {
+        self.end_users.set_config(config);
+    }
+
+    // Starts the periodic expiry of idle clients.
+    pub fn start_client_expiry(&mut self) -> Command {
+        self.end_users.schedule_expiry()
+    }
+
+    pub fn client_info(&self, addr: &SocketAddr) -> Option<ClientInfo> {
+        self.end_users.client_info(addr)
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = ClientInfo> + '_ {
+        self.end_users.clients()
+    }
+
+    // Records that the client connected from `addr` sent us a message and returns it.
+    pub fn record_client_activity(&mut self, addr: &SocketAddr) -> Option<EndUser> {
+        self.end_users.record_activity(addr);
+        self.end_users.get_enduser_by_addr(addr).copied()
+    }
+
+    pub fn is_client(&self, addr: &SocketAddr) -> bool {
+        self.end_users.get_enduser_by_addr(addr).is_some()
+    }
+
+    // Removes the client connected from `addr`, returning whether it was registered.
+    pub fn remove_client(&mut self, addr: &SocketAddr) -> bool {
+        self.end_users.remove(addr).is_some()
+    }
+
     pub fn node(&self) -> &Node {
         &self.node
     }
@@ -213,10 +429,8 @@ impl Core {
     }
 
     pub fn send_event(&self, event: Event) {
-        // Note: cloning the sender to avoid mutable access. Should have negligible cost.
-        if self.event_tx.clone().send(event).is_err() {
-            error!("Event receiver has been closed");
-        }
+        self.event_subscribers.publish(&event);
+        self.event_tx.send(event);
     }
 
     pub async fn handle_message(
@@ -239,8 +453,16 @@ impl Core {
         }
 
         // Filter messages which were already handled
-        if self.msg_filter.contains_incoming(&msg) {
+        let already_handled = self.msg_filter.contains_incoming(&msg);
+        self.metrics.filtered(!already_handled);
+        if already_handled {
             trace!("not handling message - already handled: {:?}", msg);
+
+            // Our previous receipt might have been lost, so confirm the delivery again.
+            if let Variant::AckedUserMessage { id, .. } = msg.variant() {
+                commands.extend(self.send_delivery_receipt(&msg, *id)?);
+            }
+
             return Ok(commands);
         }
 
@@ -306,30 +528,33 @@ impl Core {
                     recipients: vec![sender],
                     delivery_group_size: 1,
                     message: MessageType::SectionInfo(response),
+                    variant: None,
                 }]
             }
             SectionInfoMsg::RegisterEndUserCmd {
                 end_user,
                 socketaddr_sig,
             } => {
-                if self
-                    .end_users
-                    .try_add(sender, end_user, socketaddr_sig)
-                    .is_ok()
-                {
-                    return vec![];
-                }
-
-                let response =
-                    SectionInfoMsg::RegisterEndUserError(TargetSectionError::InvalidBootstrap(
-                        format!("Failed to add enduser {} from {}", end_user, sender),
-                    ));
+                let error =
+                    if let Err(error) = self.end_users.try_add(sender, end_user, socketaddr_sig) {
+                        error
+                    } else {
+                        return vec![];
+                    };
+
+                let response = SectionInfoMsg::RegisterEndUserError(
+                    TargetSectionError::InvalidBootstrap(format!(
+                        "Failed to add enduser {} from {}: {}",
+                        end_user, sender, error
+                    )),
+                );
                 debug!("Sending {:?} to {}", response, sender);
 
                 vec![Command::SendMessage {
                     recipients: vec![sender],
                     delivery_group_size: 1,
                     message: MessageType::SectionInfo(response),
+                    variant: None,
                 }]
             }
             SectionInfoMsg::GetSectionResponse(_) => {
@@ -351,6 +576,57 @@ impl Core {
     }
 
     pub fn handle_timeout(&mut self, token: u64) -> Result<Vec<Command>> {
+        if self.liveness.is_timer(token) {
+            return self.check_liveness();
+        }
+
+        if self.gossip.is_timer(token) {
+            return self.gossip_round();
+        }
+
+        if self.end_users.is_timer(token) {
+            return Ok(self.expire_idle_clients());
+        }
+
+        if self.join_difficulty.is_timer(token) {
+            let mut commands = vec![self.join_difficulty.schedule_adjustment()];
+            commands.extend(self.adjust_join_difficulty()?);
+            return Ok(commands);
+        }
+
+        if self.join_queue.is_timer(token) {
+            return self.expire_join_queue();
+        }
+
+        if self.deliveries.is_timer(token) {
+            return self.deliveries.handle_timeout(
+                token,
+                &self.node.name(),
+                &self.section,
+                &self.network,
+            );
+        }
+
+        if self.requests.is_timer(token) {
+            self.requests.handle_timeout(token);
+            return Ok(vec![]);
+        }
+
+        if self.section_signing.is_timer(token) {
+            self.section_signing.handle_timeout(token);
+            return Ok(vec![]);
+        }
+
+        if self.merge_barrier.is_timer(token) {
+            if let Some(sibling) = self.merge_barrier.handle_timeout(token) {
+                warn!("Merge with ({:b}) timed out - aborting", sibling.prefix());
+            }
+        }
+
+        Ok(vec![])
+    }
+
+    pub fn handle_dkg_timeout(&mut self, token: u64) -> Result<Vec<Command>> {
         self.dkg_voter
             .handle_timeout(&self.node.keypair, token)
             .into_commands(&self.node)
@@ -363,7 +639,10 @@ impl Core {
         proof_share: ProofShare,
     ) -> Result<Vec<Command>> {
         match self.proposal_aggregator.add(proposal, proof_share) {
-            Ok((proposal, proof)) => Ok(vec![Command::HandleAgreement { proposal, proof }]),
+            Ok((proposal, proof, latency)) => {
+                self.metrics.proposal_aggregated(latency);
+                Ok(vec![Command::HandleAgreement { proposal, proof }])
+            }
             Err(ProposalAggregationError::Aggregation(
                 bls_signature_aggregator::Error::NotEnoughShares,
             )) => Ok(vec![]),
@@ -410,10 +689,34 @@ impl Core {
                 self.joins_allowed = joins_allowed;
                 Ok(vec![])
             }
+            Proposal::ResourceProof(params) => {
+                info!("Resource proof for joining nodes changed to {:?}", params);
+                self.join_difficulty.update(params);
+                Ok(vec![])
+            }
+            Proposal::Checkpoint(checkpoint) => {
+                self.handle_checkpoint_agreement(checkpoint, proof);
+                Ok(vec![])
+            }
+            Proposal::Reprove(member_info) => self.handle_reprove_agreement(member_info, proof),
+            Proposal::SectionSigned(payload) => {
+                self.section_signing
+                    .resolve(&payload, &proof, self.section.chain());
+                Ok(vec![])
+            }
+            Proposal::ProtocolUpgrade(upgrade) => {
+                self.handle_protocol_upgrade_agreement(upgrade, proof)
+            }
         }
     }
 
-    pub fn handle_connection_lost(&self, addr: SocketAddr) -> Option<Command> {
+    pub fn handle_connection_lost(&mut self, addr: SocketAddr) -> Option<Command> {
+        if self.remove_client(&addr) {
+            debug!("Lost connection to client {}", addr);
+            self.send_event(Event::ClientLost(addr));
+            return None;
+        }
+
         if !self.is_elder() {
             return None;
         }
@@ -431,6 +734,7 @@ impl Core {
             recipients: vec![addr],
             delivery_group_size: 1,
             message: MessageType::Ping,
+            variant: None,
         })
     }
 
@@ -448,19 +752,7 @@ impl Core {
         }
 
         if let Some(info) = self.section.members().get(&name) {
-            let info = info.clone().leave()?;
-
-            // Don't send the `Offline` proposal to the peer being lost as that send would fail,
-            // triggering a chain of further `Offline` proposals.
-            let elders: Vec<_> = self
-                .section
-                .elders_info()
-                .peers()
-                .filter(|peer| peer.name() != info.peer.name())
-                .copied()
-                .collect();
-
-            self.send_proposal(&elders, Proposal::Offline(info))
+            self.propose_offline(info.clone())
         } else {
             Ok(vec![])
         }
@@ -468,11 +760,30 @@ impl Core {
 
     pub fn handle_dkg_outcome(
         &mut self,
+        dkg_key: DkgKey,
         elders_info: EldersInfo,
         key_share: SectionKeyShare,
     ) -> Result<Vec<Command>> {
+        if let Some(duration) = self.dkg_voter.session_duration(&elders_info) {
+            self.metrics.dkg_completed(duration);
+        }
+
+        self.send_event(Event::DkgCompleted {
+            dkg_key,
+            elders_info: elders_info.clone(),
+            public_key: key_share.public_key_set.public_key(),
+        });
+
+        // The `SectionInfo` of the merged section needs the shares of the participants from both
+        // sections, so send it to the elders of our sibling too.
+        let mut recipients: Vec<_> = self.section.elders_info().peers().copied().collect();
+        if self.is_merged_prefix(&elders_info.prefix) {
+            if let Some(sibling) = self.merge_barrier.sibling() {
+                recipients.extend(sibling.elders_info().peers().copied());
+            }
+        }
+
         let proposal = Proposal::SectionInfo(elders_info);
-        let recipients: Vec<_> = self.section.elders_info().peers().copied().collect();
         let result = self.send_proposal_with(&recipients, proposal, &key_share);
 
         let public_key = key_share.public_key_set.public_key();
@@ -486,10 +797,248 @@ impl Core {
         result
     }
 
-    pub fn handle_dkg_failure(&mut self, proofs: DkgFailureProofSet) -> Result<Command> {
-        let variant = Variant::DkgFailureAgreement(proofs);
+    pub fn handle_dkg_failure(
+        &mut self,
+        dkg_key: DkgKey,
+        proofs: DkgFailureProofSet,
+    ) -> Result<Command> {
+        self.metrics.dkg_failed();
+
+        if let Some(info) = self.dkg_voter.session(&dkg_key) {
+            self.send_event(Event::DkgFailed {
+                dkg_key,
+                elders_info: info.elders_info,
+                reason: info.failure,
+                observers: info.failure_observers,
+            });
+        }
+
+        let variant = Variant::DkgFailureAgreement { dkg_key, proofs };
         let message = Message::single_src(&self.node, DstLocation::Direct, variant, None, None)?;
-        Ok(self.send_message_to_our_elders(message.to_bytes()))
+        Ok(self.send_message_to_our_elders(&message))
+    }
+
+    // Aborts our DKG session with `dkg_key` by reporting it as failed to the other participants.
+    // Once enough of them agree on the failure, the elders restart the session. Returns `None` if
+    // there is no such session.
+    pub fn abort_dkg(&mut self, dkg_key: &DkgKey) -> Result<Option<Vec<Command>>> {
+        self.dkg_voter
+            .abort(&self.node.keypair, dkg_key)
+            .map(|commands| commands.into_commands(&self.node))
+            .transpose()
+    }
+
+    // Periodically check the liveness of our section members: probe the ones we haven't heard from
+    // in a while and propose offline the ones that stayed unresponsive for too long.
+    fn check_liveness(&mut self) -> Result<Vec<Command>> {
+        let mut commands = vec![self.liveness.schedule_check()];
+
+        if !self.is_elder() || !self.section_keys_provider.has_key_share() {
+            return Ok(commands);
+        }
+
+        let our_name = self.node.name();
+        self.liveness.retain(
+            self.section
+                .members()
+                .joined()
+                .map(|info| info.peer.name())
+                .filter(|name| **name != our_name),
+        );
+
+        let unresponsive: Vec<_> = self
+            .liveness
+            .unresponsive()
+            .filter_map(|name| self.section.members().get(name))
+            .cloned()
+            .collect();
+        for info in unresponsive {
+            info!("Proposing unresponsive member {} offline", info.peer);
+            commands.extend(self.propose_offline(info)?);
+        }
+
+        let stale: Vec<_> = self
+            .liveness
+            .stale()
+            .filter_map(|name| self.section.members().get(name))
+            .map(|info| *info.peer.addr())
+            .collect();
+        for addr in stale {
+            commands.push(self.send_direct_message(&addr, Variant::LivenessCheck)?);
+        }
+
+        Ok(commands)
+    }
+
+    // Periodically push our section's elders and key to a bounded number of other sections, along
+    // with the keys we know of the sections near them. Their lazy messaging replies to us if our
+    // knowledge of them is outdated and they probe the sections they have a different view of.
+    fn gossip_round(&mut self) -> Result<Vec<Command>> {
+        let mut commands = vec![self.gossip.schedule()];
+
+        if !self.is_elder() || !self.section_keys_provider.has_key_share() {
+            return Ok(commands);
+        }
+
+        let known: Vec<_> = self.network.prefixes().copied().collect();
+        let targets = self.gossip.targets(self.section.prefix(), &known);
+        if targets.is_empty() {
+            return Ok(commands);
+        }
+
+        let round = self.gossip.next_round();
+        let max_keys = self.gossip.config().max_keys;
+
+        for prefix in targets {
+            let recipients: Vec<_> = if let Some(elders_info) = self.network.get(&prefix) {
+                elders_info.peers().map(Peer::addr).copied().collect()
+            } else {
+                continue;
+            };
+
+            // Include the keys of the sections closest to the recipient, as those are the ones
+            // most relevant for its routing.
+            let known_keys = self
+                .network
+                .keys()
+                .filter(|(known_prefix, _)| **known_prefix != prefix)
+                .sorted_by(|(lhs, _), (rhs, _)| lhs.cmp_distance(rhs, &prefix.name()))
+                .take(max_keys)
+                .map(|(prefix, key)| (*prefix, *key))
+                .collect();
+
+            let variant = Variant::Gossip {
+                elders_info: self.section.proven_elders_info().clone(),
+                known_keys,
+                round,
+            };
+
+            let dst_knowledge = self
+                .network
+                .knowledge_by_name(&prefix.name())
+                .unwrap_or_else(|| self.section.chain().root_key());
+            // Our chain keeps the keys known by the other sections when pruned, so this only fails
+            // if their knowledge predates a checkpoint of ours.
+            let proof_chain = match self
+                .section
+                .chain()
+                .minimize(vec![self.section.chain().last_key(), dst_knowledge])
+            {
+                Ok(proof_chain) => proof_chain,
+                Err(error) => {
+                    warn!(
+                        "Not gossiping with {:?} - no trusted proof: {}",
+                        prefix, error
+                    );
+                    continue;
+                }
+            };
+            let dst_key = self.network.key_by_prefix(&prefix).copied();
+
+            trace!("Gossiping with {:?}", prefix);
+            let message = Message::single_src(
+                &self.node,
+                DstLocation::Direct,
+                variant,
+                Some(proof_chain),
+                dst_key,
+            )?;
+            commands.push(Command::send_message_to_nodes(
+                &recipients,
+                recipients.len(),
+                &message,
+            ));
+        }
+
+        Ok(commands)
+    }
+
+    // Propose a new resource proof difficulty for the joining nodes if the current one no longer
+    // matches our section size and the recent join attempts.
+    fn adjust_join_difficulty(&mut self) -> Result<Vec<Command>> {
+        if !self.is_elder() || !self.section_keys_provider.has_key_share() {
+            return Ok(vec![]);
+        }
+
+        let section_size = self.section.members().joined().count();
+        let recommended_section_size = self.section.network_params().recommended_section_size;
+
+        if let Some(params) = self
+            .join_difficulty
+            .adjustment(section_size, recommended_section_size)
+        {
+            debug!("Proposing resource proof change to {:?}", params);
+            self.propose(Proposal::ResourceProof(params))
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    // Periodically drop the nodes that waited in the join queue for too long, telling them to try
+    // again, and admit the next ones in their place.
+    fn expire_join_queue(&mut self) -> Result<Vec<Command>> {
+        let mut commands = vec![self.join_queue.schedule_expiry()];
+        let retry_after = self.join_queue.retry_after();
+
+        for peer in self.join_queue.expire() {
+            debug!("Join of {} timed out", peer);
+            commands.push(self.send_join_rejection(
+                &peer,
+                JoinRejectionReason::TimedOut,
+                Some(retry_after),
+            )?);
+        }
+
+        commands.extend(self.admit_joins()?);
+        Ok(commands)
+    }
+
+    // Propose online as many of the queued new nodes as there is room for.
+    fn admit_joins(&mut self) -> Result<Vec<Command>> {
+        if !self.joins_allowed {
+            return Ok(vec![]);
+        }
+
+        let mut commands = vec![];
+        for peer in self.join_queue.admit() {
+            debug!("Admitting {} to join our section", peer);
+            commands.extend(self.propose(Proposal::Online {
+                member_info: MemberInfo::joined(peer),
+                previous_name: None,
+                their_knowledge: None,
+            })?);
+        }
+
+        Ok(commands)
+    }
+
+    // Periodically disconnect the clients that stayed idle for too long.
+    fn expire_idle_clients(&mut self) -> Vec<Command> {
+        let mut commands = vec![self.end_users.schedule_expiry()];
+
+        for addr in self.end_users.expire_idle() {
+            debug!("Disconnecting idle client {}", addr);
+            commands.push(Command::DisconnectClient(addr));
+        }
+
+        commands
+    }
+
+    // Propose the given member offline.
+    fn propose_offline(&self, info: MemberInfo) -> Result<Vec<Command>> {
+        let info = info.leave()?;
+
+        // Don't send the `Offline` proposal to the peer being proposed offline as that send would
+        // likely fail, triggering a chain of further `Offline` proposals.
+        let elders: Vec<_> = self
+            .section
+            .elders_info()
+            .peers()
+            .filter(|peer| peer.name() != info.peer.name())
+            .copied()
+            .collect();
+
+        self.send_proposal(&elders, Proposal::Offline(info))
     }
 
     // Send proposal to all our elders.
@@ -572,7 +1121,15 @@ impl Core {
                     return Ok(MessageStatus::Unknown);
                 }
             }
-            Variant::UserMessage(_) => {
+            Variant::Gossip { .. } => {
+                if !self.is_elder() {
+                    return Ok(MessageStatus::Useless);
+                }
+            }
+            Variant::UserMessage(_)
+            | Variant::AckedUserMessage(_)
+            | Variant::UserRequest { .. }
+            | Variant::UserResponse { .. } => {
                 if !self.should_handle_user_message(msg.dst()) {
                     return Ok(MessageStatus::Unknown);
                 }
@@ -592,7 +1149,9 @@ impl Core {
                     return Ok(MessageStatus::Useless);
                 }
             }
-            Variant::NodeApproval { .. } | Variant::JoinRetry { .. } => {
+            Variant::NodeApproval { .. }
+            | Variant::JoinRetry { .. }
+            | Variant::JoinRejected { .. } => {
                 // Skip validation of these. We will validate them inside the bootstrap task.
                 return Ok(MessageStatus::Useful);
             }
@@ -602,6 +1161,14 @@ impl Core {
                     return Ok(MessageStatus::Useless);
                 }
             }
+            Variant::MergeRequest { section } => {
+                // Ignore `MergeRequest` not from our sibling.
+                if section.prefix() != &self.section.prefix().sibling()
+                    || section.prefix() == self.section.prefix()
+                {
+                    return Ok(MessageStatus::Useless);
+                }
+            }
             Variant::Propose {
                 content,
                 proof_share,
@@ -624,7 +1191,10 @@ impl Core {
             | Variant::DkgMessage { .. }
             | Variant::DkgFailureObservation { .. }
             | Variant::DkgFailureAgreement { .. }
-            | Variant::ResourceChallenge { .. } => {}
+            | Variant::ResourceChallenge { .. }
+            | Variant::LivenessCheck
+            | Variant::LivenessAck
+            | Variant::DeliveryReceipt(_) => {}
         }
 
         if self.verify_message(msg)? {
@@ -643,12 +1213,19 @@ impl Core {
         };
 
         let signed_bytes = bincode::serialize(&msg.signable_view())?;
+        self.message_aggregation_timer.start(&signed_bytes);
+
         match self
             .message_aggregator
             .add(&signed_bytes, proof_share.clone())
         {
             Ok(proof) => {
                 trace!("Successfully accumulated signatures for message: {:?}", msg);
+
+                if let Some(latency) = self.message_aggregation_timer.stop(&signed_bytes) {
+                    self.metrics.message_aggregated(latency);
+                }
+
                 Ok(Some(msg.into_dst_accumulated(proof)?))
             }
             Err(AggregatorError::NotEnoughShares) => Ok(None),
@@ -672,12 +1249,26 @@ impl Core {
             return Ok(vec![]);
         };
 
+        // Any message from a section member proves it's still alive.
+        self.liveness.record_activity(&msg.src().name());
+
         match msg.variant() {
             Variant::OtherSection { elders_info, .. } => {
-                self.handle_other_section(elders_info.value.clone(), *msg.proof_chain_last_key()?)
+                self.handle_other_section(elders_info.value.clone(), msg.proof_chain()?)
             }
-            Variant::Sync { section, network } => {
-                self.handle_sync(section.clone(), network.clone())
+            Variant::Gossip {
+                elders_info,
+                known_keys,
+                ..
+            } => self.handle_gossip(elders_info.value.clone(), msg.proof_chain()?, known_keys),
+            Variant::Sync { section, network } => self.handle_sync(
+                msg.src().name(),
+                section.clone(),
+                network.clone(),
+                *msg.protocol(),
+            ),
+            Variant::MergeRequest { section } => {
+                self.handle_merge_request(&msg.src().name(), section.clone())
             }
             Variant::Relocate(_) => {
                 if msg.src().is_section() {
@@ -688,14 +1279,47 @@ impl Core {
                 }
             }
             Variant::RelocatePromise(promise) => {
-                self.handle_relocate_promise(*promise, msg.to_bytes())
+                self.handle_relocate_promise(*promise, msg.clone())
             }
             Variant::JoinRequest(join_request) => {
                 let sender = sender.ok_or(Error::InvalidSrcLocation)?;
-                self.handle_join_request(msg.src().peer(sender)?, *join_request.clone())
+                self.handle_join_request(
+                    msg.src().peer(sender)?,
+                    *join_request.clone(),
+                    *msg.protocol(),
+                )
             }
             Variant::UserMessage(content) => self.handle_user_message(&msg, content.clone()),
-            Variant::BouncedUntrustedMessage(message) => {
+            Variant::AckedUserMessage { id, content } => {
+                let mut commands = self.handle_user_message(&msg, content.clone())?;
+                commands.extend(self.send_delivery_receipt(&msg, *id)?);
+                Ok(commands)
+            }
+            Variant::DeliveryReceipt(hash) => {
+                if !self.deliveries.confirm(hash, msg.src()) {
+                    trace!("Ignoring unexpected {:?}", msg);
+                }
+
+                Ok(vec![])
+            }
+            Variant::UserRequest { id, content } => {
+                self.send_event(Event::RequestReceived {
+                    id: *id,
+                    content: content.clone(),
+                    src: msg.src().src_location(),
+                    dst: *msg.dst(),
+                    proof_chain: msg.proof_chain().ok().cloned(),
+                });
+                Ok(vec![])
+            }
+            Variant::UserResponse { id, content } => {
+                if !self.requests.resolve(id, msg.src(), content.clone()) {
+                    trace!("Ignoring unexpected {:?}", msg);
+                }
+
+                Ok(vec![])
+            }
+            Variant::BouncedUntrustedMessage(message) => {
                 let sender = sender.ok_or(Error::InvalidSrcLocation)?;
                 Ok(vec![self.handle_bounced_untrusted_message(
                     msg.src().peer(sender)?,
@@ -721,14 +1345,22 @@ impl Core {
             Variant::DkgFailureObservation { dkg_key, proof } => {
                 self.handle_dkg_failure_observation(*dkg_key, *proof)
             }
-            Variant::DkgFailureAgreement(proofs) => {
-                self.handle_dkg_failure_agreement(&msg.src().name(), proofs)
+            Variant::DkgFailureAgreement { dkg_key, proofs } => {
+                self.handle_dkg_failure_agreement(&msg.src().name(), dkg_key, proofs)
             }
             Variant::Propose {
                 content,
                 proof_share,
             } => {
                 let mut commands = vec![];
+                if let Proposal::SectionSigned(payload) = content {
+                    commands.extend(self.cosign_section_payload(
+                        &msg.src().name(),
+                        payload,
+                        proof_share,
+                    )?);
+                }
+
                 let result = self.handle_proposal(content.clone(), proof_share.clone());
 
                 if let Some(addr) = sender {
@@ -740,6 +1372,7 @@ impl Core {
             }
             Variant::NodeApproval { .. }
             | Variant::JoinRetry { .. }
+            | Variant::JoinRejected { .. }
             | Variant::ResourceChallenge { .. } => {
                 if let Some(RelocateState::InProgress(message_tx)) = &mut self.relocate_state {
                     if let Some(sender) = sender {
@@ -755,6 +1388,14 @@ impl Core {
 
                 Ok(vec![])
             }
+            Variant::LivenessCheck => {
+                let sender = sender.ok_or(Error::InvalidSrcLocation)?;
+                Ok(vec![
+                    self.send_direct_message(&sender, Variant::LivenessAck)?
+                ])
+            }
+            // The activity of the sender was already recorded above.
+            Variant::LivenessAck => Ok(vec![]),
         }
     }
 
@@ -780,7 +1421,8 @@ impl Core {
         match proposal {
             Proposal::SectionInfo(elders_info)
                 if elders_info.prefix == *self.section.prefix()
-                    || elders_info.prefix.is_extension_of(self.section.prefix()) =>
+                    || elders_info.prefix.is_extension_of(self.section.prefix())
+                    || self.is_merged_prefix(&elders_info.prefix) =>
             {
                 // This `SectionInfo` is proposed by the DKG participants and is signed by the new
                 // key created by the DKG so we don't know it yet. We only require the sender of the
@@ -792,11 +1434,14 @@ impl Core {
                 }
             }
             _ => {
-                // Any other proposal needs to be signed by a known key.
-                if self
-                    .section
-                    .chain()
-                    .has_key(&proof_share.public_key_set.public_key())
+                // Any other proposal needs to be signed by a known key. During a merge, that
+                // includes the keys of the sibling section.
+                let public_key = proof_share.public_key_set.public_key();
+                if self.section.chain().has_key(&public_key)
+                    || self
+                        .merge_barrier
+                        .sibling()
+                        .map_or(false, |sibling| sibling.chain().has_key(&public_key))
                 {
                     None
                 } else {
@@ -863,12 +1508,13 @@ impl Core {
             None,
             Some(bounce_dst_key),
         )?;
-        let bounce_msg = bounce_msg.to_bytes();
+
+        self.metrics.bounced_untrusted();
 
         if let Some(sender) = sender {
-            Ok(Command::send_message_to_node(&sender, bounce_msg))
+            Ok(Command::send_message_to_node(&sender, &bounce_msg))
         } else {
-            Ok(self.send_message_to_our_elders(bounce_msg))
+            Ok(self.send_message_to_our_elders(&bounce_msg))
         }
     }
 
@@ -890,7 +1536,8 @@ impl Core {
             None,
             None,
         )?;
-        let bounce_msg = bounce_msg.to_bytes();
+
+        self.metrics.bounced_unknown();
 
         // If the message came from one of our elders then bounce it only to them to avoid message
         // explosion.
@@ -902,9 +1549,9 @@ impl Core {
         });
 
         if let Some(sender) = our_elder_sender {
-            Ok(Command::send_message_to_node(&sender, bounce_msg))
+            Ok(Command::send_message_to_node(&sender, &bounce_msg))
         } else {
-            Ok(self.send_message_to_our_elders(bounce_msg))
+            Ok(self.send_message_to_our_elders(&bounce_msg))
         }
     }
 
@@ -928,12 +1575,19 @@ impl Core {
                 // Instead we use the section chain that's part of the included `Section` struct.
                 // Problem is we can't extend that chain as it would invalidate the signature. We
                 // must construct a new message instead.
-                let section = section
-                    .extend_chain(&dst_key, self.section.chain())
-                    .map_err(|err| {
-                        error!("extending section chain failed: {:?}", err);
-                        Error::InvalidMessage // TODO: more specific error
-                    })?;
+                let section = match section.extend_chain(&dst_key, self.section.chain()) {
+                    // The peer knows none of our keys. This happens when its section merged with
+                    // ours and the merged section continued our chain. Extend the chain all the way
+                    // to its root instead, as everyone trusts it.
+                    Err(SectionChainError::KeyNotFound) => {
+                        section.extend_chain(self.section.chain().root_key(), self.section.chain())
+                    }
+                    result => result,
+                }
+                .map_err(|err| {
+                    error!("extending section chain failed: {:?}", err);
+                    Error::InvalidMessage // TODO: more specific error
+                })?;
 
                 Message::single_src(
                     &self.node,
@@ -955,10 +1609,7 @@ impl Core {
         };
 
         trace!("resending with extended proof");
-        Ok(Command::send_message_to_node(
-            sender.addr(),
-            resend_msg.to_bytes(),
-        ))
+        Ok(Command::send_message_to_node(sender.addr(), &resend_msg))
     }
 
     fn handle_bounced_unknown_message(
@@ -998,16 +1649,34 @@ impl Core {
                     network: self.network.clone(),
                 },
             )?,
-            Command::send_message_to_node(sender.addr(), bounced_msg_bytes),
+            Command::send_bytes_to_nodes(
+                slice::from_ref(sender.addr()),
+                1,
+                bounced_msg_bytes,
+                None,
+            ),
         ])
     }
 
     fn handle_other_section(
         &self,
         elders_info: EldersInfo,
-        src_key: bls::PublicKey,
+        proof_chain: &SectionChain,
     ) -> Result<Vec<Command>> {
         let mut commands = vec![];
+        let src_key = *proof_chain.last_key();
+
+        // If we know sections with longer prefixes, this section is either their stale ancestor or
+        // they merged into it. Only in the latter case does its key descend from their keys.
+        if self.network.has_descendants(&elders_info.prefix)
+            && !self
+                .network
+                .descendant_keys(&elders_info.prefix)
+                .any(|key| proof_chain.has_key(key))
+        {
+            trace!("Ignore stale section info {:?}", elders_info);
+            return Ok(commands);
+        }
 
         if !self.network.has_key(&src_key) {
             commands.extend(self.propose(Proposal::TheirKey {
@@ -1028,6 +1697,46 @@ impl Core {
         Ok(commands)
     }
 
+    fn handle_gossip(
+        &mut self,
+        elders_info: EldersInfo,
+        proof_chain: &SectionChain,
+        known_keys: &[(Prefix, bls::PublicKey)],
+    ) -> Result<Vec<Command>> {
+        let src_prefix = elders_info.prefix;
+        let commands = self.handle_other_section(elders_info, proof_chain)?;
+
+        // Where the sender's view of a section differs from ours, one of them is stale. Gossip
+        // with that section next round - if ours is the stale one, its lazy messaging updates us.
+        for (prefix, key) in known_keys {
+            if prefix.is_compatible(self.section.prefix()) || prefix.is_compatible(&src_prefix) {
+                continue;
+            }
+
+            if self.network.has_key(key) {
+                continue;
+            }
+
+            let suspects: Vec<_> = self
+                .network
+                .prefixes()
+                .filter(|known| known.is_compatible(prefix))
+                .copied()
+                .collect();
+            for suspect in suspects {
+                trace!(
+                    "Our view of {:?} differs from {:?}'s: {:?}",
+                    suspect,
+                    src_prefix,
+                    key
+                );
+                self.gossip.suspect(suspect);
+            }
+        }
+
+        Ok(commands)
+    }
+
     fn handle_user_message(&mut self, msg: &Message, content: Bytes) -> Result<Vec<Command>> {
         if let DstLocation::EndUser(end_user) = msg.dst() {
             let recipients = match end_user {
@@ -1049,6 +1758,7 @@ impl Core {
                 recipients,
                 delivery_group_size: 1,
                 message: MessageType::ClientMessage(ClientMessage::from(content)?),
+                variant: None,
             }]);
         }
 
@@ -1061,16 +1771,73 @@ impl Core {
         Ok(vec![])
     }
 
-    fn handle_sync(&mut self, section: Section, network: Network) -> Result<Vec<Command>> {
+    // Confirm the delivery of `msg` sent with `AckedUserMessage` to its source. If the message
+    // was sent to our section, the receipt is signed by the section, otherwise by us.
+    fn send_delivery_receipt(&self, msg: &Message, id: MessageHash) -> Result<Vec<Command>> {
+        let src = msg.src().src_location();
+        let dst = match src {
+            SrcLocation::Node(name) => DstLocation::Node(name),
+            SrcLocation::Section(name) => DstLocation::Section(name),
+            SrcLocation::EndUser(_) => return Ok(vec![]),
+        };
+        let variant = Variant::DeliveryReceipt(id);
+
+        let (targets, dg_size) = delivery_group::delivery_targets(
+            &dst,
+            &self.node.name(),
+            &self.section,
+            &self.network,
+        )?;
+        let mut recipients: Vec<_> = targets.into_iter().take(dg_size).collect();
+        if dst.contains(&self.node.name(), self.section.prefix()) {
+            recipients.push(self.node.peer());
+        }
+
+        if let DstLocation::Section(name) = msg.dst() {
+            self.send_message_for_dst_accumulation(*name, dst, variant, None, &recipients)
+        } else {
+            let receipt = Message::single_src(&self.node, dst, variant, None, None)?;
+            Ok(self.send_or_handle(receipt, &recipients))
+        }
+    }
+
+    fn handle_sync(
+        &mut self,
+        sender: XorName,
+        section: Section,
+        network: Network,
+        protocol: ProtocolInfo,
+    ) -> Result<Vec<Command>> {
         if !section.prefix().matches(&self.node.name()) {
             trace!("ignore Sync - not our section");
             return Ok(vec![]);
         }
 
         let snapshot = self.state_snapshot();
-        self.section.merge(section)?;
+        for fork in self.section.merge(section)? {
+            self.report_fork(fork);
+        }
         self.network.merge(network, self.section.chain());
-        self.update_state(snapshot)
+
+        let mut commands = vec![];
+        if let Some(info) = self.section.members().get(&sender) {
+            if self.section.members().is_joined(&sender) {
+                let _ = self.peer_protocols.insert(sender, protocol);
+
+                if self.is_elder() && !protocol.supports(self.section.protocol_version()) {
+                    info!(
+                        "Proposing {} offline - it doesn't speak protocol version {}",
+                        info.peer,
+                        self.section.protocol_version()
+                    );
+                    commands.extend(self.propose_offline(info.clone())?);
+                }
+            }
+        }
+
+        commands.extend(self.update_state(snapshot)?);
+        commands.extend(self.propose_protocol_upgrade()?);
+        Ok(commands)
     }
 
     fn handle_relocate(&mut self, details: SignedRelocateDetails) -> Result<Option<Command>> {
@@ -1119,7 +1886,7 @@ impl Core {
     fn handle_relocate_promise(
         &mut self,
         promise: RelocatePromise,
-        msg_bytes: Bytes,
+        msg: Message,
     ) -> Result<Vec<Command>> {
         let mut commands = vec![];
 
@@ -1132,7 +1899,7 @@ impl Core {
                         "Received RelocatePromise to section at {}",
                         promise.destination
                     );
-                    self.relocate_state = Some(RelocateState::Delayed(msg_bytes.clone()));
+                    self.relocate_state = Some(RelocateState::Delayed(msg.clone()));
                     self.send_event(Event::RelocationStarted {
                         previous_name: self.node.name(),
                     });
@@ -1147,7 +1914,7 @@ impl Core {
 
             // We are no longer elder. Send the promise back already.
             if !self.is_elder() {
-                commands.push(self.send_message_to_our_elders(msg_bytes));
+                commands.push(self.send_message_to_our_elders(&msg));
             }
 
             return Ok(commands);
@@ -1183,16 +1950,48 @@ impl Core {
         &mut self,
         peer: Peer,
         join_request: JoinRequest,
+        protocol: ProtocolInfo,
     ) -> Result<Vec<Command>> {
         debug!("Received {:?} from {}", join_request, peer);
 
         if !self.section.prefix().matches(peer.name()) {
+            // Most likely the node contacted us before learning about our split. Point it to the
+            // section it belongs to if we know it, otherwise let it try again later.
+            if let (Some(section_key), Some(elders_info)) =
+                self.network.section_by_name(peer.name())
+            {
+                let variant = Variant::JoinRetry {
+                    elders_info: elders_info.clone(),
+                    section_key: *section_key,
+                };
+                trace!("Sending {:?} to {}", variant, peer);
+                return Ok(vec![self.send_direct_message(peer.addr(), variant)?]);
+            }
+
             debug!(
-                "Ignoring JoinRequest from {} - name doesn't match our prefix {:?}.",
+                "Rejecting JoinRequest from {} - name doesn't match our prefix {:?}.",
                 peer,
                 self.section.prefix()
             );
-            return Ok(vec![]);
+            let retry_after = self.join_queue.retry_after();
+            return Ok(vec![self.send_join_rejection(
+                &peer,
+                JoinRejectionReason::WrongPrefix,
+                Some(retry_after),
+            )?]);
+        }
+
+        let section_version = self.section.protocol_version();
+        if !protocol.supports(section_version) {
+            debug!(
+                "Rejecting JoinRequest from {} - protocol {:?} doesn't support our version {}.",
+                peer, protocol, section_version
+            );
+            return Ok(vec![self.send_join_rejection(
+                &peer,
+                JoinRejectionReason::IncompatibleProtocol { section_version },
+                None,
+            )?]);
         }
 
         if join_request.section_key != *self.section.chain().last_key() {
@@ -1205,43 +2004,99 @@ impl Core {
         }
 
         if self.section.members().is_joined(peer.name()) {
+            // A member restarted from its persisted state might be rejoining using its previous
+            // identity. Approve it again so it can complete the bootstrap.
+            if let Some(member_info) = self.section.members().get_proven(peer.name()) {
+                if member_info.value.peer.addr() == peer.addr() {
+                    debug!(
+                        "Re-approving JoinRequest from {} - already member of our section.",
+                        peer
+                    );
+                    return Ok(vec![self.send_node_approval(member_info.clone(), None)?]);
+                }
+            }
+
             debug!(
-                "Ignoring JoinRequest from {} - already member of our section.",
+                "Rejecting JoinRequest from {} - already member of our section.",
                 peer
             );
-            return Ok(vec![]);
+            return Ok(vec![self.send_join_rejection(
+                &peer,
+                JoinRejectionReason::AlreadyMember,
+                None,
+            )?]);
         }
 
+        // Former members of our section (e.g. restarted from their persisted state) that left it
+        // can rejoin with their previous age, only to be relocated with half of it once approved
+        // (see `handle_online_agreement`). Those too young to be relocated must join anew.
+        let former_age = if let Some(info) = self.section.members().get(peer.name()) {
+            let min_age = self.section.network_params().min_age;
+            if info.state != PeerState::Left || info.peer.age() / 2 <= min_age {
+                debug!(
+                    "Rejecting JoinRequest from {} - former member can't rejoin ({:?}).",
+                    peer, info.state
+                );
+                return Ok(vec![self.send_join_rejection(
+                    &peer,
+                    JoinRejectionReason::FormerMember,
+                    None,
+                )?]);
+            }
+
+            Some(info.peer.age())
+        } else {
+            None
+        };
+
         // This joining node is being relocated to us.
         let (age, previous_name, their_knowledge) =
             if let Some(payload) = join_request.relocate_payload {
                 if !payload.verify_identity(peer.name()) {
                     debug!(
-                        "Ignoring relocation JoinRequest from {} - invalid signature.",
+                        "Rejecting relocation JoinRequest from {} - invalid signature.",
                         peer
                     );
-                    return Ok(vec![]);
+                    return Ok(vec![self.send_join_rejection(
+                        &peer,
+                        JoinRejectionReason::InvalidRelocation,
+                        None,
+                    )?]);
                 }
 
                 let details = payload.relocate_details()?;
 
                 if !self.section.prefix().matches(&details.destination) {
                     debug!(
-                        "Ignoring relocation JoinRequest from {} - destination {} doesn't match \
+                        "Rejecting relocation JoinRequest from {} - destination {} doesn't match \
                          our prefix {:?}.",
                         peer,
                         details.destination,
                         self.section.prefix()
                     );
-                    return Ok(vec![]);
+                    return Ok(vec![self.send_join_rejection(
+                        &peer,
+                        JoinRejectionReason::InvalidRelocation,
+                        None,
+                    )?]);
                 }
 
                 if !self
                     .verify_message(payload.details.signed_msg())
                     .unwrap_or(false)
                 {
-                    debug!("Ignoring relocation JoinRequest from {} - untrusted.", peer);
-                    return Ok(vec![]);
+                    // We might not know the key the relocation is signed with yet, so let them
+                    // try again once we catch up.
+                    debug!(
+                        "Rejecting relocation JoinRequest from {} - untrusted.",
+                        peer
+                    );
+                    let retry_after = self.join_queue.retry_after();
+                    return Ok(vec![self.send_join_rejection(
+                        &peer,
+                        JoinRejectionReason::UntrustedRelocation,
+                        Some(retry_after),
+                    )?]);
                 }
 
                 (
@@ -1251,39 +2106,84 @@ impl Core {
                 )
             } else if !self.joins_allowed {
                 debug!(
-                    "Ignoring JoinRequest from {} - new node not acceptable.",
+                    "Rejecting JoinRequest from {} - new node not acceptable.",
                     peer,
                 );
-                return Ok(vec![]);
+                let retry_after = self.join_queue.retry_after();
+                return Ok(vec![self.send_join_rejection(
+                    &peer,
+                    JoinRejectionReason::JoinsNotAllowed,
+                    Some(retry_after),
+                )?]);
+            } else if let Some(age) = former_age {
+                (age, None, None)
             } else {
                 // Start as Adult as long as passed resource proofing.
-                (MIN_AGE + 1, None, None)
+                (self.section.network_params().min_age + 1, None, None)
             };
 
         // Requires the node name matches the age.
         if age != peer.age() {
             debug!(
-                "Ignoring JoinRequest from {} - required age {:?} not presented.",
+                "Rejecting JoinRequest from {} - required age {:?} not presented.",
                 peer, age,
             );
-            return Ok(vec![]);
+            return Ok(vec![self.send_join_rejection(
+                &peer,
+                JoinRejectionReason::AgeMismatch { expected: age },
+                None,
+            )?]);
         }
 
-        // Require resource proof only if joining as a new node.
+        // Require resource proof only if joining as a new node or rejoining as a former member.
         if previous_name.is_none() {
             if let Some(response) = join_request.resource_proof_response {
                 if !self.validate_resource_proof_response(peer.name(), response) {
                     debug!(
-                        "Ignoring JoinRequest from {} - invalid resource proof response",
+                        "Rejecting JoinRequest from {} - invalid resource proof response",
                         peer
                     );
-                    return Ok(vec![]);
+                    self.join_difficulty.record_rejection();
+                    return Ok(vec![self.send_join_rejection(
+                        &peer,
+                        JoinRejectionReason::InvalidResourceProof,
+                        None,
+                    )?]);
                 }
             } else {
-                return Ok(vec![self.send_resource_proof_challenge(&peer)?]);
+                self.join_difficulty.record_attempt();
+                let mut commands = vec![self.send_resource_proof_challenge(&peer)?];
+                commands.extend(self.adjust_join_difficulty()?);
+                return Ok(commands);
             }
+
+            // New nodes wait in the join queue to be admitted.
+            return match self.join_queue.push(peer) {
+                Enqueued::Waiting => {
+                    let _ = self.peer_protocols.insert(*peer.name(), protocol);
+                    self.admit_joins()
+                }
+                Enqueued::Admitted => {
+                    debug!(
+                        "Ignoring JoinRequest from {} - already admitted to join.",
+                        peer
+                    );
+                    Ok(vec![])
+                }
+                Enqueued::Full => {
+                    debug!("Rejecting JoinRequest from {} - join queue full.", peer);
+                    let retry_after = self.join_queue.retry_after();
+                    Ok(vec![self.send_join_rejection(
+                        &peer,
+                        JoinRejectionReason::QueueFull,
+                        Some(retry_after),
+                    )?])
+                }
+            };
         }
 
+        let _ = self.peer_protocols.insert(*peer.name(), protocol);
+
         self.propose(Proposal::Online {
             member_info: MemberInfo::joined(peer),
             previous_name,
@@ -1291,6 +2191,21 @@ impl Core {
         })
     }
 
+    // Tell the joining node why we rejected its request and, if it's worth trying again, when.
+    fn send_join_rejection(
+        &self,
+        peer: &Peer,
+        reason: JoinRejectionReason,
+        retry_after: Option<Duration>,
+    ) -> Result<Command> {
+        let variant = Variant::JoinRejected {
+            reason,
+            retry_after,
+        };
+        trace!("Sending {:?} to {}", variant, peer);
+        self.send_direct_message(peer.addr(), variant)
+    }
+
     fn validate_resource_proof_response(
         &self,
         peer_name: &XorName,
@@ -1312,16 +2227,17 @@ impl Core {
             return false;
         }
 
-        self.resource_proof
-            .validate_all(&response.nonce, &response.data, response.solution)
+        self.join_difficulty
+            .validate(&response.nonce, &response.data, response.solution)
     }
 
     fn send_resource_proof_challenge(&self, peer: &Peer) -> Result<Command> {
-        let nonce: [u8; 32] = rand::random();
+        let nonce: [u8; 32] = MainRng.gen();
         let serialized = bincode::serialize(&(peer.name(), &nonce))?;
+        let params = self.join_difficulty.current();
         let response = Variant::ResourceChallenge {
-            data_size: RESOURCE_PROOF_DATA_SIZE,
-            difficulty: RESOURCE_PROOF_DIFFICULTY,
+            data_size: params.data_size,
+            difficulty: params.difficulty,
             nonce,
             nonce_signature: crypto::sign(&serialized, &self.node.keypair),
         };
@@ -1335,9 +2251,19 @@ impl Core {
         new_elders_info: EldersInfo,
     ) -> Result<Vec<Command>> {
         trace!("Received DkgStart for {}", new_elders_info);
-        self.dkg_voter
-            .start(&self.node.keypair, dkg_key, new_elders_info)
-            .into_commands(&self.node)
+        let commands = self
+            .dkg_voter
+            .start(&self.node.keypair, dkg_key, new_elders_info.clone());
+
+        // No commands means the session was already running or failed to start.
+        if !commands.is_empty() {
+            self.send_event(Event::DkgStarted {
+                dkg_key,
+                elders_info: new_elders_info,
+            });
+        }
+
+        commands.into_commands(&self.node)
     }
 
     fn handle_dkg_message(
@@ -1348,8 +2274,10 @@ impl Core {
     ) -> Result<Vec<Command>> {
         trace!("handle DKG message {:?} from {}", message, sender);
 
+        self.liveness.record_dkg_message(dkg_key, sender);
+
         self.dkg_voter
-            .process_message(&self.node.keypair, &dkg_key, message)
+            .process_message(&self.node.keypair, &dkg_key, sender, message)
             .into_commands(&self.node)
     }
 
@@ -1364,8 +2292,9 @@ impl Core {
     }
 
     fn handle_dkg_failure_agreement(
-        &self,
+        &mut self,
         sender: &XorName,
+        dkg_key: &DkgKey,
         proofs: &DkgFailureProofSet,
     ) -> Result<Vec<Command>> {
         let sender = &self
@@ -1375,12 +2304,20 @@ impl Core {
             .ok_or(Error::InvalidSrcLocation)?
             .peer;
 
-        let generation = self.section.chain().main_branch_len() as u64;
-        let elders_info = self
-            .section
-            .promote_and_demote_elders(&self.node.name())
-            .into_iter()
-            .find(|elders_info| proofs.verify(elders_info, generation));
+        // The failed session might have been a restart, with a generation above the current one.
+        let generation = dkg_key.generation();
+        let elders_info = if generation < self.dkg_generation() {
+            None
+        } else {
+            self.section
+                .promote_and_demote_elders(&self.node.name())
+                .into_iter()
+                .chain(self.merged_elders_info())
+                .find(|elders_info| {
+                    DkgKey::new(elders_info, generation) == *dkg_key
+                        && proofs.verify(elders_info, generation)
+                })
+        };
         let elders_info = if let Some(elders_info) = elders_info {
             elders_info
         } else {
@@ -1393,12 +2330,32 @@ impl Core {
             elders_info
         );
 
-        self.send_dkg_start_to(elders_info, slice::from_ref(sender))
+        // Participants we received no message of the session from likely didn't take part in it.
+        // We only receive the messages of the sessions we take part in ourselves.
+        if elders_info.elders.contains_key(&self.node.name()) {
+            let our_name = self.node.name();
+            self.liveness.record_dkg_failure(
+                *dkg_key,
+                elders_info.elders.keys().filter(|name| **name != our_name),
+            );
+        }
+
+        // Restart with a fresh generation, so the participants don't take the restarted session
+        // for the failed one they still keep. All the elders derive the same generation, so their
+        // `DkgStart` messages accumulate.
+        let generation = cmp::max(self.dkg_generation(), generation + 1);
+        self.send_dkg_start_with_generation(elders_info, generation, slice::from_ref(sender))
     }
 
     // Generate a new section info based on the current set of members and if it differs from the
-    // current elders, trigger a DKG.
+    // current elders, trigger a DKG. If our section shrunk too much, merge with the sibling instead.
     fn promote_and_demote_elders(&mut self) -> Result<Vec<Command>> {
+        if self.merge_barrier.is_merging() || self.section.should_merge() {
+            if let Some(commands) = self.request_merge()? {
+                return Ok(commands);
+            }
+        }
+
         let mut commands = vec![];
 
         for info in self.section.promote_and_demote_elders(&self.node.name()) {
@@ -1408,6 +2365,131 @@ impl Core {
         Ok(commands)
     }
 
+    // Send the current state of our section to the elders of our sibling section to merge with it.
+    // If we already know the state of the sibling too, also start the DKG for the merged section.
+    // Returns `None` if we don't know the sibling and so can't merge with it.
+    fn request_merge(&mut self) -> Result<Option<Vec<Command>>> {
+        let sibling_elders: Vec<_> = if let Some(sibling) = self.merge_barrier.sibling() {
+            sibling
+                .elders_info()
+                .peers()
+                .map(Peer::addr)
+                .copied()
+                .collect()
+        } else if let Some(elders_info) = self.network.get(&self.section.prefix().sibling()) {
+            elders_info.peers().map(Peer::addr).copied().collect()
+        } else {
+            trace!("Can't merge - sibling section unknown");
+            return Ok(None);
+        };
+
+        let mut commands = vec![];
+
+        trace!("Request merge with ({:b})", self.section.prefix().sibling());
+
+        self.merge_barrier.record_ours(&self.section);
+
+        let message = Message::single_src(
+            &self.node,
+            DstLocation::Direct,
+            Variant::MergeRequest {
+                section: self.section.clone(),
+            },
+            None,
+            None,
+        )?;
+        commands.push(Command::send_message_to_nodes(
+            &sibling_elders,
+            sibling_elders.len(),
+            &message,
+        ));
+
+        if let Some(elders_info) = self.merged_elders_info() {
+            // Both sections start the same DKG session, each with its own members only because the
+            // members of the sibling might not be able to trust us.
+            let recipients: Vec<_> = elders_info
+                .peers()
+                .filter(|peer| self.section.members().is_joined(peer.name()))
+                .copied()
+                .collect();
+            commands.extend(self.send_dkg_start_to(elders_info, &recipients)?);
+        }
+
+        Ok(Some(commands))
+    }
+
+    fn handle_merge_request(&mut self, sender: &XorName, sibling: Section) -> Result<Vec<Command>> {
+        if !self.section.verify_sibling(&sibling) {
+            warn!("Ignore invalid MergeRequest from ({:b})", sibling.prefix());
+            return Ok(vec![]);
+        }
+
+        if !sibling.is_elder(sender) {
+            warn!(
+                "Ignore MergeRequest from {} who is not an elder of ({:b})",
+                sender,
+                sibling.prefix()
+            );
+            return Ok(vec![]);
+        }
+
+        // Only merge if one of the two sections shrunk too much.
+        if !self.merge_barrier.is_merging()
+            && !self.section.should_merge()
+            && !sibling.should_merge()
+        {
+            warn!(
+                "Ignore MergeRequest from ({:b}) - neither section needs to merge",
+                sibling.prefix()
+            );
+            return Ok(vec![]);
+        }
+
+        let (updated, timeout_command) = self.merge_barrier.update(sibling);
+        if !updated {
+            return Ok(vec![]);
+        }
+
+        debug!("Merging with ({:b})", self.section.prefix().sibling());
+
+        let mut commands: Vec<_> = timeout_command.into_iter().collect();
+
+        if !self.is_elder() || !self.section_keys_provider.has_key_share() {
+            return Ok(commands);
+        }
+
+        // Reply with our own state and (re)start the DKG for the merged section.
+        commands.extend(self.promote_and_demote_elders()?);
+        Ok(commands)
+    }
+
+    // Elders of the section resulting from the merge with our sibling, if we are merging. Computed
+    // from the state of our section we sent to the sibling, so the sibling computes the same.
+    fn merged_elders_info(&self) -> Option<EldersInfo> {
+        let ours = self.merge_barrier.ours()?;
+        let sibling = self.merge_barrier.sibling()?;
+        Some(ours.merged_elders_info(sibling))
+    }
+
+    // Whether `prefix` is the prefix of the section resulting from the merge with our sibling.
+    fn is_merged_prefix(&self, prefix: &Prefix) -> bool {
+        self.merge_barrier.is_merging()
+            && !self.section.prefix().is_empty()
+            && *prefix == self.section.prefix().popped()
+    }
+
+    // Generation of the DKG sessions we start. When merging, it must be the same in both sections.
+    fn dkg_generation(&self) -> u64 {
+        let ours = self.section.chain().main_branch_len();
+        let theirs = self
+            .merge_barrier
+            .sibling()
+            .map(|sibling| sibling.chain().main_branch_len())
+            .unwrap_or(0);
+
+        cmp::max(ours, theirs) as u64
+    }
+
     fn relocate_peers(
         &self,
         churn_name: &XorName,
@@ -1416,7 +2498,7 @@ impl Core {
         let mut commands = vec![];
 
         // Do not carry out relocation when there is not enough elder nodes.
-        if self.section.elders_info().elders.len() < ELDER_SIZE {
+        if self.section.elders_info().elders.len() < self.section.network_params().elder_size {
             return Ok(commands);
         }
 
@@ -1482,6 +2564,11 @@ impl Core {
     ) -> Result<Vec<Command>> {
         let mut commands = vec![];
 
+        // Free the slot of the node in the join queue.
+        if self.join_queue.complete(new_info.peer.name()) {
+            commands.extend(self.admit_joins()?);
+        }
+
         if let Some(old_info) = self.section.members().get_proven(new_info.peer.name()) {
             // This node is rejoin with same name.
 
@@ -1495,9 +2582,10 @@ impl Core {
                 return Ok(commands);
             }
 
-            let new_age = cmp::max(MIN_AGE, old_info.value.peer.age() / 2);
+            let min_age = self.section.network_params().min_age;
+            let new_age = cmp::max(min_age, old_info.value.peer.age() / 2);
 
-            if new_age > MIN_AGE {
+            if new_age > min_age {
                 // TODO: consider handling the relocation inside the bootstrap phase, to avoid
                 // having to send this `NodeApproval`.
                 commands.push(self.send_node_approval(old_info.clone(), their_knowledge)?);
@@ -1507,10 +2595,7 @@ impl Core {
             }
         }
 
-        let new_info = Proven {
-            value: new_info,
-            proof,
-        };
+        let new_info = Proven::new(new_info, proof);
 
         if !self.section.update_member(new_info.clone()) {
             info!("ignore Online: {:?}", new_info.value.peer);
@@ -1519,6 +2604,8 @@ impl Core {
 
         info!("handle Online: {:?}", new_info.value.peer);
 
+        self.store_state();
+
         self.send_event(Event::MemberJoined {
             name: *new_info.value.peer.name(),
             previous_name,
@@ -1546,16 +2633,15 @@ impl Core {
         let age = peer.age();
         let signature = proof.signature.clone();
 
-        if !self.section.update_member(Proven {
-            value: member_info,
-            proof,
-        }) {
+        if !self.section.update_member(Proven::new(member_info, proof)) {
             info!("ignore Offline: {:?}", peer);
             return Ok(commands);
         }
 
         info!("handle Offline: {:?}", peer);
 
+        self.store_state();
+
         commands.extend(self.relocate_peers(peer.name(), &signature)?);
         commands.extend(self.promote_and_demote_elders()?);
 
@@ -1610,7 +2696,7 @@ impl Core {
                 commands.push(Command::send_message_to_nodes(
                     &sync_recipients,
                     sync_recipients.len(),
-                    sync_message.to_bytes(),
+                    &sync_message,
                 ));
             }
 
@@ -1623,9 +2709,17 @@ impl Core {
             commands.extend(
                 self.send_proposal(&our_elders_recipients, Proposal::OurElders(elders_info))?,
             );
+        } else if self.is_merged_prefix(&elders_info.value.prefix) {
+            // Section resulting from the merge with our sibling
+
+            commands.extend(self.handle_merged_section_info_agreement(elders_info)?);
         } else {
             // Other section
 
+            if self.network.has_descendants(&elders_info.value.prefix) {
+                self.network.remove_merged(&elders_info.value.prefix);
+            }
+
             let _ = self
                 .network
                 .update_section(elders_info, None, self.section.chain());
@@ -1634,6 +2728,81 @@ impl Core {
         Ok(commands)
     }
 
+    // Both our section and our sibling agree on the elders of the merged section. The key of the
+    // merged section is signed by the leading section only, so the merged section continues its
+    // chain. That is why only the elders of the leading section propose `OurElders`.
+    fn handle_merged_section_info_agreement(
+        &self,
+        elders_info: Proven<EldersInfo>,
+    ) -> Result<Vec<Command>> {
+        if self.merged_elders_info().as_ref() != Some(&elders_info.value) {
+            // SectionInfo out of date, ignore.
+            return Ok(vec![]);
+        }
+
+        if !is_merge_leader(self.section.prefix()) {
+            return Ok(vec![]);
+        }
+
+        let mut commands = vec![];
+
+        let (ours, theirs): (Vec<_>, Vec<_>) = elders_info
+            .value
+            .peers()
+            .copied()
+            .partition(|peer| self.section.members().is_joined(peer.name()));
+
+        // Send our section to the to-be-elders from the sibling so they can trust the proposal.
+        let merge_recipients: Vec<_> = theirs.iter().map(Peer::addr).copied().collect();
+        if !merge_recipients.is_empty() {
+            let message = Message::single_src(
+                &self.node,
+                DstLocation::Direct,
+                Variant::MergeRequest {
+                    section: self.section.clone(),
+                },
+                None,
+                None,
+            )?;
+            commands.push(Command::send_message_to_nodes(
+                &merge_recipients,
+                merge_recipients.len(),
+                &message,
+            ));
+        }
+
+        // Send a `Sync` message to our to-be-promoted members so they have the full section and
+        // network data.
+        let sync_recipients: Vec<_> = ours
+            .iter()
+            .filter(|peer| !self.section.is_elder(peer.name()))
+            .map(Peer::addr)
+            .copied()
+            .collect();
+        if !sync_recipients.is_empty() {
+            let message = Message::single_src(
+                &self.node,
+                DstLocation::Direct,
+                Variant::Sync {
+                    section: self.section.clone(),
+                    network: self.network.clone(),
+                },
+                None,
+                None,
+            )?;
+            commands.push(Command::send_message_to_nodes(
+                &sync_recipients,
+                sync_recipients.len(),
+                &message,
+            ));
+        }
+
+        let recipients: Vec<_> = ours.into_iter().chain(theirs).collect();
+        commands.extend(self.send_proposal(&recipients, Proposal::OurElders(elders_info))?);
+
+        Ok(commands)
+    }
+
     fn handle_our_elders_agreement(
         &mut self,
         elders_info: Proven<EldersInfo>,
@@ -1647,10 +2816,15 @@ impl Core {
         }
 
         let snapshot = self.state_snapshot();
+        let mut readmitted = vec![];
 
         for (elders_info, key_proof) in updates {
-            if elders_info.value.prefix.matches(&self.node.name()) {
-                let _ = self.section.update_elders(elders_info, key_proof);
+            if self.is_merged_prefix(&elders_info.value.prefix) {
+                readmitted.extend(self.complete_merge(elders_info, key_proof));
+            } else if elders_info.value.prefix.matches(&self.node.name()) {
+                if let Ok(Some(fork)) = self.section.update_elders(elders_info, key_proof) {
+                    self.report_fork(fork);
+                }
             } else {
                 let _ =
                     self.network
@@ -1658,10 +2832,79 @@ impl Core {
             }
         }
 
-        self.update_state(snapshot)
-    }
-
+        let mut commands = self.update_state(snapshot)?;
+
+        // The members of the section whose chain didn't continue are not provable using the
+        // merged section chain. Admit them again, under the merged section key.
+        if self.is_elder() && self.section_keys_provider.has_key_share() {
+            for member_info in readmitted {
+                commands.extend(self.propose(Proposal::Online {
+                    member_info,
+                    previous_name: None,
+                    their_knowledge: None,
+                })?);
+            }
+        }
+
+        Ok(commands)
+    }
+
+    // Replace our section with the section resulting from the merge with our sibling. Returns the
+    // members that need to be admitted again into the merged section.
+    fn complete_merge(
+        &mut self,
+        elders_info: Proven<EldersInfo>,
+        key_proof: Proof,
+    ) -> Vec<MemberInfo> {
+        let sibling = if let Some(sibling) = self.merge_barrier.sibling() {
+            sibling.clone()
+        } else {
+            return vec![];
+        };
+
+        // The merged section continues the chain of the section that signed its key.
+        let (mut merged, other) = if self.section.chain().has_key(&key_proof.public_key) {
+            (self.section.clone(), sibling)
+        } else if sibling.chain().has_key(&key_proof.public_key) {
+            (sibling, self.section.clone())
+        } else {
+            error!(
+                "Merged section key signed with unknown key {:?}",
+                key_proof.public_key
+            );
+            return vec![];
+        };
+
+        let fork = match merged.update_elders(elders_info, key_proof) {
+            Ok(fork) => fork,
+            Err(_) => return vec![],
+        };
+
+        let readmitted = other
+            .members()
+            .joined()
+            .filter(|info| merged.members().get(info.peer.name()).is_none())
+            .cloned()
+            .collect();
+
+        self.section = merged;
+        self.network.remove_merged(self.section.prefix());
+        let _ = self.merge_barrier.complete();
+
+        if let Some(fork) = fork {
+            self.report_fork(fork);
+        }
+
+        readmitted
+    }
+
     fn handle_their_key_agreement(&mut self, prefix: Prefix, key: bls::PublicKey, proof: Proof) {
+        // Only the keys of merged sections are agreed on while we still know their descendants
+        // (see `handle_other_section`).
+        if self.network.has_descendants(&prefix) {
+            self.network.remove_merged(&prefix);
+        }
+
         let key = Proven::new((prefix, key), proof);
         let _ = self.network.update_their_key(key);
     }
@@ -1676,13 +2919,209 @@ impl Core {
         self.network.update_knowledge(knowledge)
     }
 
+    fn handle_checkpoint_agreement(&mut self, checkpoint: Checkpoint, proof: Proof) {
+        match self.section.prune_chain(Proven::new(checkpoint, proof)) {
+            Ok(()) => info!(
+                "Section chain pruned at {:?} (height {})",
+                checkpoint.key, checkpoint.height
+            ),
+            Err(error) => error!(
+                "Failed to prune section chain at {:?}: {}",
+                checkpoint.key, error
+            ),
+        }
+    }
+
+    fn handle_reprove_agreement(
+        &mut self,
+        member_info: MemberInfo,
+        proof: Proof,
+    ) -> Result<Vec<Command>> {
+        if !self.section.update_member(Proven::new(member_info, proof)) {
+            return Ok(vec![]);
+        }
+
+        self.store_state();
+        self.propose_ready_chain_checkpoint()
+    }
+
+    // Propose pruning our section chain if it grew too long. The members and the protocol upgrade
+    // proven with keys the pruning would discard are proven again with our current key first, the
+    // checkpoint itself is proposed once none of them is left (see
+    // `propose_ready_chain_checkpoint`).
+    fn propose_chain_checkpoint(&self) -> Result<Vec<Command>> {
+        let checkpoint = if let Some(checkpoint) = self.chain_checkpoint() {
+            checkpoint
+        } else {
+            return Ok(vec![]);
+        };
+
+        let mut commands = vec![];
+        for info in self.section.stale_members(&checkpoint) {
+            commands.extend(self.propose(Proposal::Reprove(info.clone()))?);
+        }
+        if let Some(upgrade) = self.section.stale_protocol_upgrade(&checkpoint) {
+            commands.extend(self.propose(Proposal::ProtocolUpgrade(upgrade))?);
+        }
+
+        if commands.is_empty() {
+            self.propose(Proposal::Checkpoint(checkpoint))
+        } else {
+            Ok(commands)
+        }
+    }
+
+    // Propose the checkpoint to prune our section chain at once nothing is proven with the keys it
+    // discards anymore.
+    fn propose_ready_chain_checkpoint(&self) -> Result<Vec<Command>> {
+        if !self.is_elder() || !self.section_keys_provider.has_key_share() {
+            return Ok(vec![]);
+        }
+
+        match self.chain_checkpoint() {
+            Some(checkpoint)
+                if self.section.stale_members(&checkpoint).next().is_none()
+                    && self.section.stale_protocol_upgrade(&checkpoint).is_none() =>
+            {
+                self.propose(Proposal::Checkpoint(checkpoint))
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    // Returns the checkpoint to prune our section chain at, if it grew too long. It keeps the keys
+    // the other sections know us by, so they keep trusting the proofs we send them.
+    fn chain_checkpoint(&self) -> Option<Checkpoint> {
+        if self.section.chain().len() < 2 * CHAIN_KEYS_KEPT {
+            return None;
+        }
+
+        if !self.elders_support(Features::CHAIN_CHECKPOINTS) {
+            trace!("Not pruning section chain - not all elders support it");
+            return None;
+        }
+
+        // A section we don't know the knowledge of might trust only the keys we'd prune.
+        if self
+            .network
+            .prefixes()
+            .any(|prefix| self.network.knowledge_by_name(&prefix.name()).is_none())
+        {
+            trace!("Not pruning section chain - knowledge of some sections unknown");
+            return None;
+        }
+
+        self.section
+            .chain_checkpoint(CHAIN_KEYS_KEPT, self.network.knowledge_keys())
+    }
+
+    fn handle_protocol_upgrade_agreement(
+        &mut self,
+        upgrade: ProtocolUpgrade,
+        proof: Proof,
+    ) -> Result<Vec<Command>> {
+        if !self.section.upgrade_protocol(Proven::new(upgrade, proof)) {
+            // Possibly the current version proven again before pruning our chain.
+            return self.propose_ready_chain_checkpoint();
+        }
+
+        info!("Section upgraded to protocol version {}", upgrade.version);
+        self.send_event(Event::ProtocolUpgraded {
+            version: upgrade.version,
+        });
+        self.store_state();
+
+        // Let the rest of the section know.
+        let mut commands = self.send_sync(self.section.clone(), self.network.clone())?;
+        commands.extend(self.propose_incompatible_members_offline()?);
+        Ok(commands)
+    }
+
+    // Propose offline the members that advertised they don't speak the protocol version of our
+    // section, as they can't talk to the rest of the section anymore.
+    fn propose_incompatible_members_offline(&self) -> Result<Vec<Command>> {
+        if !self.is_elder() {
+            return Ok(vec![]);
+        }
+
+        let version = self.section.protocol_version();
+        let mut commands = vec![];
+
+        for info in self.section.members().joined() {
+            let incompatible = self
+                .peer_protocols
+                .get(info.peer.name())
+                .map_or(false, |protocol| !protocol.supports(version));
+            if incompatible {
+                info!(
+                    "Proposing {} offline - it doesn't speak protocol version {}",
+                    info.peer, version
+                );
+                commands.extend(self.propose_offline(info.clone())?);
+            }
+        }
+
+        Ok(commands)
+    }
+
+    // Returns whether all our elders (including us) advertised support for the given features.
+    fn elders_support(&self, features: Features) -> bool {
+        let our_name = self.node.name();
+        self.section.elders_info().elders.keys().all(|name| {
+            let protocol = if *name == our_name {
+                Some(&self.node.protocol)
+            } else {
+                self.peer_protocols.get(name)
+            };
+
+            protocol.map_or(false, |protocol| protocol.features.contains(features))
+        })
+    }
+
+    // Propose switching our section to the newest protocol version supported by us and by a
+    // supermajority of our elders, if it's newer than the current one.
+    fn propose_protocol_upgrade(&self) -> Result<Vec<Command>> {
+        if !self.is_elder() || !self.section_keys_provider.has_key_share() {
+            return Ok(vec![]);
+        }
+
+        let our_name = self.node.name();
+        let elders: Vec<_> = self
+            .section
+            .elders_info()
+            .elders
+            .keys()
+            .filter_map(|name| {
+                if *name == our_name {
+                    Some(&self.node.protocol)
+                } else {
+                    self.peer_protocols.get(name)
+                }
+            })
+            .collect();
+        let threshold = supermajority(self.section.elders_info().elders.len());
+
+        if let Some(version) = protocol::upgrade_version(
+            self.section.protocol_version(),
+            &self.node.protocol,
+            elders.iter().copied(),
+            threshold,
+        ) {
+            info!("Proposing upgrade to protocol version {}", version);
+            self.propose(Proposal::ProtocolUpgrade(ProtocolUpgrade { version }))
+        } else {
+            Ok(vec![])
+        }
+    }
+
     fn handle_accumulate_at_src_agreement(
-        &self,
+        &mut self,
         message: PlainMessage,
         proof_chain: SectionChain,
         proof: Proof,
     ) -> Result<Command> {
         let message = Message::section_src(message, proof.signature, proof_chain)?;
+        self.track_delivery(&message)?;
 
         Ok(Command::HandleMessage {
             message,
@@ -1698,6 +3137,18 @@ impl Core {
         }
     }
 
+    fn report_fork(&self, fork: SectionChainFork) {
+        warn!(
+            "Section chain forked at {:?}: {:?}",
+            fork.parent,
+            fork.keys.iter().format(", ")
+        );
+        self.send_event(Event::SectionChainForked {
+            parent: fork.parent,
+            keys: fork.keys,
+        });
+    }
+
     fn update_state(&mut self, old: StateSnapshot) -> Result<Vec<Command>> {
         let mut commands = vec![];
         let new = self.state_snapshot();
@@ -1705,13 +3156,24 @@ impl Core {
         self.section_keys_provider
             .finalise_dkg(self.section.chain().last_key());
 
-        if new.prefix != old.prefix {
+        if new.prefix.is_extension_of(&old.prefix) {
             info!("Split");
+        } else if old.prefix.is_extension_of(&new.prefix) {
+            info!("Merge");
         }
 
         if new.last_key != old.last_key {
             self.msg_filter.reset();
 
+            // Forget the protocols of the nodes that are neither members nor waiting to join.
+            let queued: Vec<_> = self.join_queue.entries().map(|entry| entry.name).collect();
+            let members = self.section.members();
+            self.peer_protocols
+                .retain(|name, _| members.is_joined(name) || queued.contains(name));
+
+            self.liveness
+                .record_dkg_success(self.section.elders_info().elders.keys());
+
             if new.is_elder {
                 info!(
                     "Section updated: prefix: ({:b}), key: {:?}, elders: {}",
@@ -1725,6 +3187,11 @@ impl Core {
                     // Whenever there is an elders change, casting a round of joins_allowed
                     // proposals to sync.
                     commands.extend(self.propose(Proposal::JoinsAllowed(self.joins_allowed))?);
+                    commands.extend(
+                        self.propose(Proposal::ResourceProof(self.join_difficulty.current()))?,
+                    );
+                    commands.extend(self.propose_chain_checkpoint()?);
+                    commands.extend(self.propose_protocol_upgrade()?);
                 }
 
                 self.print_network_stats();
@@ -1734,7 +3201,7 @@ impl Core {
                 commands.extend(self.send_sync(self.section.clone(), self.network.clone())?);
             }
 
-            let sibling_key = if new.prefix != old.prefix {
+            let sibling_key = if new.prefix.is_extension_of(&old.prefix) {
                 self.section_key(&new.prefix.sibling()).copied()
             } else {
                 None
@@ -1747,7 +3214,8 @@ impl Core {
                 info!("Demoted");
                 self.section = self.section.trimmed(1);
                 self.network = Network::new();
-                self.section_keys_provider = SectionKeysProvider::new(KEY_CACHE_SIZE, None);
+                self.section_keys_provider =
+                    SectionKeysProvider::new(self.section.network_params().key_cache_size, None);
                 NodeElderChange::Demoted
             } else {
                 NodeElderChange::None
@@ -1766,28 +3234,33 @@ impl Core {
             commands.extend(self.return_relocate_promise());
         }
 
+        self.store_state();
+
         Ok(commands)
     }
 
-    /* FIXME: bring back unresponsiveness detection
-    // Detect non-responsive peers and vote them out.
-    fn vote_for_remove_unresponsive_peers(&mut self, core: &mut Core) -> Result<()> {
-        let unresponsive_nodes: Vec<_> = self
-            .consensus_engine
-            .detect_unresponsive(self.shared_state.our_info())
-            .into_iter()
-            .filter_map(|id| self.shared_state.our_members.get(id.name()))
-            .map(|info| info.clone().leave())
-            .collect();
+    // Persist our current state into the state store, if we have one.
+    fn store_state(&self) {
+        let state_store = if let Some(state_store) = &self.state_store {
+            state_store
+        } else {
+            return;
+        };
 
-        for info in unresponsive_nodes {
-            info!("Voting for unresponsive node {}", info.peer);
-            self.cast_unordered_vote(core, Vote::Offline(info))?;
-        }
+        let state = StoredState::new(
+            &self.node,
+            &self.section,
+            &self.network,
+            self.section_keys_provider.key_share().ok(),
+        );
 
-        Ok(())
+        if let Err(error) = state
+            .to_bytes()
+            .and_then(|bytes| Ok(state_store.store(&bytes)?))
+        {
+            error!("Failed to persist the node state: {}", error);
+        }
     }
-    */
 
     ////////////////////////////////////////////////////////////////////////////
     // Message sending
@@ -1807,16 +3280,19 @@ impl Core {
 
         let addr = *member_info.value.peer.addr();
 
-        // Attach proof chain that includes the key the approved node knows (if any), the key its
-        // `MemberInfo` is signed with and the last key of our section chain.
+        // Attach proof chain that includes the genesis key the network params are signed with, the
+        // key the approved node knows (if any), the key its `MemberInfo` is signed with and the last
+        // key of our section chain.
         let proof_chain = self.section.chain().minimize(
             iter::once(self.section.chain().last_key())
+                .chain(iter::once(self.section.genesis_key()))
                 .chain(their_knowledge.as_ref())
                 .chain(iter::once(&member_info.proof.public_key)),
         )?;
 
         let variant = Variant::NodeApproval {
             genesis_key: *self.section.genesis_key(),
+            network_params: self.section.proven_network_params().clone(),
             elders_info: self.section.proven_elders_info().clone(),
             member_info,
         };
@@ -1829,7 +3305,7 @@ impl Core {
             None,
         )?;
 
-        Ok(Command::send_message_to_node(&addr, message.to_bytes()))
+        Ok(Command::send_message_to_node(&addr, &message))
     }
 
     fn send_sync(&mut self, section: Section, network: Network) -> Result<Vec<Command>> {
@@ -1843,7 +3319,7 @@ impl Core {
             Ok(Command::send_message_to_nodes(
                 &recipients,
                 recipients.len(),
-                message.to_bytes(),
+                &message,
             ))
         };
 
@@ -1909,8 +3385,8 @@ impl Core {
 
     fn return_relocate_promise(&self) -> Option<Command> {
         // TODO: keep sending this periodically until we get relocated.
-        if let Some(RelocateState::Delayed(bytes)) = &self.relocate_state {
-            Some(self.send_message_to_our_elders(bytes.clone()))
+        if let Some(RelocateState::Delayed(msg)) = &self.relocate_state {
+            Some(self.send_message_to_our_elders(msg))
         } else {
             None
         }
@@ -1926,9 +3402,17 @@ impl Core {
         &self,
         elders_info: EldersInfo,
         recipients: &[Peer],
+    ) -> Result<Vec<Command>> {
+        self.send_dkg_start_with_generation(elders_info, self.dkg_generation(), recipients)
+    }
+
+    fn send_dkg_start_with_generation(
+        &self,
+        elders_info: EldersInfo,
+        generation: u64,
+        recipients: &[Peer],
     ) -> Result<Vec<Command>> {
         let src_prefix = elders_info.prefix;
-        let generation = self.section.chain().main_branch_len() as u64;
         let dkg_key = DkgKey::new(&elders_info, generation);
 
         trace!(
@@ -1952,6 +3436,40 @@ impl Core {
         )
     }
 
+    // If `msg` was sent with acknowledged delivery, remember it so it can be resent to alternate
+    // targets if the delivery is not confirmed in time.
+    fn track_delivery(&mut self, msg: &Message) -> Result<()> {
+        let hash = if let Variant::AckedUserMessage { id, .. } = msg.variant() {
+            *id
+        } else {
+            return Ok(());
+        };
+
+        if !self.deliveries.contains(&hash) {
+            return Ok(());
+        }
+
+        // These are the targets `relay_message` is going to send the message to first.
+        let targets: Vec<_> = delivery_group::delivery_targets(
+            msg.dst(),
+            &self.node.name(),
+            &self.section,
+            &self.network,
+        )
+        .map(|(targets, dg_size)| {
+            targets
+                .into_iter()
+                .take(dg_size)
+                .map(|peer| *peer.name())
+                .collect()
+        })
+        .unwrap_or_default();
+
+        self.deliveries.set_message(&hash, msg.clone(), targets);
+
+        Ok(())
+    }
+
     // Send message over the network.
     pub fn relay_message(&mut self, msg: &Message) -> Result<Option<Command>> {
         let (targets, dg_size) = delivery_group::delivery_targets(
@@ -1963,7 +3481,11 @@ impl Core {
 
         let targets: Vec<_> = targets
             .into_iter()
-            .filter(|peer| self.msg_filter.filter_outgoing(msg, peer.name()).is_new())
+            .filter(|peer| {
+                let is_new = self.msg_filter.filter_outgoing(msg, peer.name()).is_new();
+                self.metrics.filtered(is_new);
+                is_new
+            })
             .collect();
 
         if targets.is_empty() {
@@ -1978,7 +3500,7 @@ impl Core {
         );
 
         let targets: Vec<_> = targets.into_iter().map(|node| *node.addr()).collect();
-        let command = Command::send_message_to_nodes(&targets, dg_size, msg.to_bytes());
+        let command = Command::send_message_to_nodes(&targets, dg_size, &msg);
 
         Ok(Some(command))
     }
@@ -2028,6 +3550,267 @@ impl Core {
         itinerary: Itinerary,
         content: Bytes,
         additional_proof_chain_key: Option<&bls::PublicKey>,
+    ) -> Result<Vec<Command>> {
+        self.send_routed_message(
+            itinerary,
+            Variant::UserMessage(content),
+            additional_proof_chain_key,
+        )
+    }
+
+    // Sends a user message whose destination is asked to confirm its delivery. Returns the future
+    // resolving once the receipt arrives. Until then, the message is periodically resent to
+    // alternate delivery targets.
+    pub fn send_user_message_with_ack(
+        &mut self,
+        itinerary: Itinerary,
+        content: Bytes,
+        additional_proof_chain_key: Option<&bls::PublicKey>,
+    ) -> Result<(Delivery, Vec<Command>)> {
+        if !matches!(
+            itinerary.dst,
+            DstLocation::Node(_) | DstLocation::Section(_)
+        ) {
+            error!(
+                "Not sending user message {:?} -> {:?}: acknowledged delivery not supported",
+                itinerary.src, itinerary.dst
+            );
+            return Err(Error::InvalidDstLocation);
+        }
+
+        // The source location the destination is going to see the message coming from.
+        let src = if itinerary.aggregate_at_dst() {
+            SrcLocation::Section(itinerary.src.name())
+        } else if itinerary.aggregate_at_src() {
+            SrcLocation::Section(self.section.prefix().name())
+        } else {
+            SrcLocation::Node(self.node.name())
+        };
+        let hash = request::user_message_id(&src, &itinerary.dst, &content)?;
+        let (delivery, resend_command) = self.deliveries.insert(hash, itinerary.dst)?;
+
+        match self.send_routed_message(
+            itinerary,
+            Variant::AckedUserMessage { id: hash, content },
+            additional_proof_chain_key,
+        ) {
+            Ok(commands) => Ok((
+                delivery,
+                commands
+                    .into_iter()
+                    .chain(iter::once(resend_command))
+                    .collect(),
+            )),
+            Err(error) => {
+                self.deliveries.remove(&hash);
+                Err(error)
+            }
+        }
+    }
+
+    // Sends a user request and returns the future resolving to the content of the matching
+    // response, or to `Error::RequestTimeout` if it doesn't arrive within `timeout`.
+    pub fn send_user_request(
+        &mut self,
+        itinerary: Itinerary,
+        content: Bytes,
+        timeout: Duration,
+        additional_proof_chain_key: Option<&bls::PublicKey>,
+    ) -> Result<(Response, Vec<Command>)> {
+        if !matches!(
+            itinerary.dst,
+            DstLocation::Node(_) | DstLocation::Section(_)
+        ) {
+            error!(
+                "Not sending user request {:?} -> {:?}: destination can't respond",
+                itinerary.src, itinerary.dst
+            );
+            return Err(Error::InvalidDstLocation);
+        }
+
+        // The source location the response is going to be sent back to.
+        let src = if itinerary.aggregate_at_dst() {
+            SrcLocation::Section(itinerary.src.name())
+        } else if itinerary.aggregate_at_src() {
+            SrcLocation::Section(self.section.prefix().name())
+        } else {
+            SrcLocation::Node(self.node.name())
+        };
+        let id = request::user_message_id(&src, &itinerary.dst, &content)?;
+        let (response, timeout_command) = self.requests.insert(id, itinerary.dst, timeout);
+
+        match self.send_routed_message(
+            itinerary,
+            Variant::UserRequest { id, content },
+            additional_proof_chain_key,
+        ) {
+            Ok(commands) => Ok((
+                response,
+                commands
+                    .into_iter()
+                    .chain(iter::once(timeout_command))
+                    .collect(),
+            )),
+            Err(error) => {
+                let _ = self.requests.remove(&id);
+                Err(error)
+            }
+        }
+    }
+
+    // Sends the response to the request `id` which was sent from `src` to `dst`. The response
+    // travels back from `dst` to `src` - if `dst` is a section, it's signed by the section.
+    pub fn send_user_response(
+        &mut self,
+        id: MessageHash,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+    ) -> Result<Vec<Command>> {
+        let itinerary = Itinerary {
+            src: match dst {
+                DstLocation::Node(name) if name == self.node.name() => SrcLocation::Node(name),
+                DstLocation::Section(name)
+                    if self.is_elder() && self.section.prefix().matches(&name) =>
+                {
+                    SrcLocation::Section(name)
+                }
+                _ => {
+                    error!(
+                        "Not sending user response {:?} -> {:?}: we are not the request destination",
+                        dst, src
+                    );
+                    return Err(Error::InvalidSrcLocation);
+                }
+            },
+            dst: match src {
+                SrcLocation::Node(name) => DstLocation::Node(name),
+                SrcLocation::Section(name) => DstLocation::Section(name),
+                SrcLocation::EndUser(_) => return Err(Error::InvalidDstLocation),
+            },
+            aggregation: if matches!(dst, DstLocation::Section(_)) {
+                Aggregation::AtDestination
+            } else {
+                Aggregation::None
+            },
+        };
+
+        self.route_message(itinerary, Variant::UserResponse { id, content }, None)
+    }
+
+    // Cancels the pending request `id`. Returns whether there was such request.
+    pub fn cancel_user_request(&mut self, id: &MessageHash) -> bool {
+        self.requests.remove(id)
+    }
+
+    // Fails all the requests, deliveries and section signatures the application is waiting for
+    // with `Error::Relocated`. None of them can complete once we are relocated: the responses and
+    // receipts are addressed to our old name and the signatures are aggregated by our old section.
+    pub fn fail_pending_on_relocation(&mut self) {
+        self.requests.fail_all(|| Error::Relocated);
+        self.deliveries.fail_all(|| Error::Relocated);
+        self.section_signing.fail_all(|| Error::Relocated);
+    }
+
+    pub fn section_signing_approval(&self) -> Option<SectionSigningApproval> {
+        self.section_signing.approval().cloned()
+    }
+
+    // Sets which payloads proposed by the other elders for signing by our section we co-sign.
+    pub fn set_section_signing_approval(&mut self, approval: Option<SectionSigningApproval>) {
+        self.section_signing.set_approval(approval)
+    }
+
+    // Propose `payload` (a serialized `SectionPayload`) for signing by our section. Returns the
+    // receiver the proof of the signature and our section chain are sent to once the other elders
+    // co-sign it.
+    pub fn sign_as_section(
+        &mut self,
+        payload: Bytes,
+        timeout: Duration,
+    ) -> Result<(
+        oneshot::Receiver<Result<(Proof, SectionChain)>>,
+        Vec<Command>,
+    )> {
+        if !self.is_elder() {
+            return Err(Error::InvalidState);
+        }
+
+        let _ = self.section_keys_provider.key_share()?;
+
+        if !self.elders_support(Features::SECTION_SIGNING) {
+            return Err(Error::FeatureNotSupported);
+        }
+
+        let mut commands = vec![];
+        if self.section_signing.mark_signed(&payload) {
+            commands.extend(self.propose(Proposal::SectionSigned(payload.clone()))?);
+        }
+
+        let (rx, timeout_command) = self.section_signing.insert(payload, timeout);
+        commands.push(timeout_command);
+
+        Ok((rx, commands))
+    }
+
+    // Add our signature share to the payload another elder proposed for signing by our section.
+    // Only co-sign when the proposer is one of our elders with a valid share of our current key
+    // and the application approves the payload.
+    fn cosign_section_payload(
+        &mut self,
+        sender: &XorName,
+        payload: &Bytes,
+        proof_share: &ProofShare,
+    ) -> Result<Vec<Command>> {
+        if self.section_keys_provider.key_share().is_err() {
+            return Ok(vec![]);
+        }
+
+        if !self
+            .node
+            .protocol
+            .features
+            .contains(Features::SECTION_SIGNING)
+        {
+            trace!("Not co-signing payload - section signing not enabled");
+            return Ok(vec![]);
+        }
+
+        if !self.section.elders_info().elders.contains_key(sender) {
+            warn!("Not co-signing payload proposed by non-elder {}", sender);
+            return Ok(vec![]);
+        }
+
+        let proposal = Proposal::SectionSigned(payload.clone());
+        if proof_share.public_key_set.public_key() != *self.section.chain().last_key()
+            || !proof_share.verify(&bincode::serialize(&proposal.as_signable())?)
+        {
+            warn!("Not co-signing payload proposed with invalid proof share");
+            return Ok(vec![]);
+        }
+
+        if !section_signing::is_section_payload(payload) {
+            warn!("Not co-signing payload which isn't a SectionPayload");
+            return Ok(vec![]);
+        }
+
+        if !self.section_signing.approves(payload) {
+            trace!("Not co-signing payload not approved by the application");
+            return Ok(vec![]);
+        }
+
+        if !self.section_signing.mark_signed(payload) {
+            return Ok(vec![]);
+        }
+
+        self.propose(proposal)
+    }
+
+    fn send_routed_message(
+        &mut self,
+        itinerary: Itinerary,
+        variant: Variant,
+        additional_proof_chain_key: Option<&bls::PublicKey>,
     ) -> Result<Vec<Command>> {
         let are_we_src = itinerary.src.equals(&self.node.name())
             || itinerary.src.equals(&self.section().prefix().name());
@@ -2049,8 +3832,17 @@ impl Core {
             return Err(Error::InvalidDstLocation);
         }
 
-        let variant = Variant::UserMessage(content);
+        self.route_message(itinerary, variant, additional_proof_chain_key)
+    }
 
+    // Creates the message travelling along `itinerary` and sends it on. Assumes the itinerary was
+    // already validated.
+    fn route_message(
+        &mut self,
+        itinerary: Itinerary,
+        variant: Variant,
+        additional_proof_chain_key: Option<&bls::PublicKey>,
+    ) -> Result<Vec<Command>> {
         // If the msg is to be aggregated at dst, we don't vote among our peers, we simply send the
         // msg as our vote to the dst.
         let msg = if itinerary.aggregate_at_dst() {
@@ -2063,6 +3855,7 @@ impl Core {
                 variant,
                 proof_chain,
                 None,
+                ProtocolInfo::section(self.section.protocol_version()),
             )?
         } else if itinerary.aggregate_at_src() {
             let proof_chain =
@@ -2072,11 +3865,14 @@ impl Core {
             let recipients = delivery_group::signature_targets(
                 &itinerary.dst,
                 self.section.elders_info().peers().copied(),
+                self.section.network_params().elder_size,
             );
             return self.send_proposal(&recipients, proposal);
         } else {
             Message::single_src(&self.node, itinerary.dst, variant, None, None)?
         };
+        self.track_delivery(&msg)?;
+
         let mut commands = vec![];
 
         // TODO: consider removing this, we are getting duplciate msgs by it
@@ -2128,6 +3924,7 @@ impl Core {
             variant,
             proof_chain,
             Some(dst_key),
+            ProtocolInfo::section(self.section.protocol_version()),
         )?;
 
         trace!(
@@ -2160,7 +3957,7 @@ impl Core {
             commands.push(Command::send_message_to_nodes(
                 &others,
                 others.len(),
-                message.to_bytes(),
+                &message,
             ));
         }
 
@@ -2194,6 +3991,7 @@ impl Core {
             dst,
             dst_key,
             variant,
+            protocol: ProtocolInfo::section(self.section.protocol_version()),
         };
 
         let proposal = Proposal::AccumulateAtSrc {
@@ -2225,7 +4023,11 @@ impl Core {
         // to sign the message.
         let additional_key = additional_key
             .or_else(|| self.network.knowledge_by_name(&dst.name()?))
-            .filter(|key| self.section.chain().cmp_by_position(key, &last_key) == Ordering::Less);
+            .filter(|key| {
+                // The key might have been pruned from our chain already.
+                self.section.chain().has_key(key)
+                    && self.section.chain().cmp_by_position(key, &last_key) == Ordering::Less
+            });
 
         Ok(self
             .section
@@ -2235,12 +4037,12 @@ impl Core {
 
     fn send_direct_message(&self, recipient: &SocketAddr, variant: Variant) -> Result<Command> {
         let message = Message::single_src(&self.node, DstLocation::Direct, variant, None, None)?;
-        Ok(Command::send_message_to_node(recipient, message.to_bytes()))
+        Ok(Command::send_message_to_node(recipient, &message))
     }
 
     // TODO: consider changing this so it sends only to a subset of the elders
     // (say 1/3 of the ones closest to our name or so)
-    fn send_message_to_our_elders(&self, msg: Bytes) -> Command {
+    fn send_message_to_our_elders(&self, msg: &Message) -> Command {
         let targets: Vec<_> = self
             .section
             .elders_info()
@@ -0,0 +1,383 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::{self, Command};
+use crate::{
+    delivery_group,
+    error::{Error, Result},
+    messages::{Message, MessageHash, SrcAuthority},
+    network::Network,
+    section::Section,
+};
+use sn_messaging::DstLocation;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use xor_name::XorName;
+
+// How long to wait for the delivery receipt before resending the message.
+const RESEND_INTERVAL: Duration = Duration::from_secs(10);
+
+// How many times to resend an unconfirmed message before giving up.
+const MAX_RESENDS: usize = 3;
+
+/// Future returned by `Routing::send_message_with_ack` which resolves once the destination
+/// confirms it received the message. Resolves to `Error::DeliveryFailed` if the delivery is not
+/// confirmed even after all the resend attempts and to `Error::Relocated` if the node gets
+/// relocated before that. Use `tokio::time::timeout` to give up earlier.
+pub struct Delivery {
+    hash: MessageHash,
+    rx: oneshot::Receiver<Result<()>>,
+}
+
+impl Delivery {
+    /// Hash identifying the message whose delivery this is.
+    pub fn hash(&self) -> &MessageHash {
+        &self.hash
+    }
+}
+
+impl Future for Delivery {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(Error::DeliveryFailed)))
+    }
+}
+
+// Messages sent with acknowledged delivery which are still waiting for their delivery receipt.
+pub(crate) struct Deliveries {
+    pending: HashMap<MessageHash, PendingDelivery>,
+}
+
+impl Deliveries {
+    pub fn new() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+
+    // Start waiting for the receipt of the message to `dst` identified by `hash`. Returns the
+    // future to await the receipt on and the command scheduling the first resend, or
+    // `Error::DeliveryPending` if the receipt of the same message is already being waited for.
+    pub fn insert(&mut self, hash: MessageHash, dst: DstLocation) -> Result<(Delivery, Command)> {
+        if self.pending.contains_key(&hash) {
+            return Err(Error::DeliveryPending);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let mut pending = PendingDelivery {
+            dst,
+            message: None,
+            tried: HashSet::new(),
+            resends: 0,
+            timer_token: 0,
+            tx,
+        };
+        let command = pending.schedule_resend();
+        let _ = self.pending.insert(hash, pending);
+
+        Ok((Delivery { hash, rx }, command))
+    }
+
+    pub fn contains(&self, hash: &MessageHash) -> bool {
+        self.pending.contains_key(hash)
+    }
+
+    pub fn remove(&mut self, hash: &MessageHash) {
+        let _ = self.pending.remove(hash);
+    }
+
+    // Remember the message sent (or about to be sent) for the pending delivery `hash` together
+    // with the names of the nodes it was sent to, so it can be resent to alternate targets.
+    pub fn set_message(
+        &mut self,
+        hash: &MessageHash,
+        message: Message,
+        targets: impl IntoIterator<Item = XorName>,
+    ) {
+        if let Some(pending) = self.pending.get_mut(hash) {
+            pending.message = Some(message);
+            pending.tried.extend(targets);
+        }
+    }
+
+    // Handle a receipt for the delivery `hash`. The receipt is only accepted if it comes from the
+    // destination the message was sent to. Returns whether the receipt was accepted.
+    pub fn confirm(&mut self, hash: &MessageHash, src: &SrcAuthority) -> bool {
+        let is_from_dst = match self.pending.get(hash).map(|pending| &pending.dst) {
+            Some(DstLocation::Node(name)) => !src.is_section() && src.name() == *name,
+            Some(DstLocation::Section(name)) => src.is_section() && src.name() == *name,
+            Some(DstLocation::EndUser(_)) | Some(DstLocation::Direct) | None => false,
+        };
+
+        if !is_from_dst {
+            return false;
+        }
+
+        if let Some(pending) = self.pending.remove(hash) {
+            let _ = pending.tx.send(Ok(()));
+        }
+
+        true
+    }
+
+    // Stop waiting for all the receipts, resolving the deliveries to `error`.
+    pub fn fail_all(&mut self, error: impl Fn() -> Error) {
+        for (_, pending) in self.pending.drain() {
+            let _ = pending.tx.send(Err(error()));
+        }
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.pending
+            .values()
+            .any(|pending| pending.timer_token == token)
+    }
+
+    // Resend the message whose resend timer fired, unless we already gave up on it or no one is
+    // waiting for its receipt anymore.
+    pub fn handle_timeout(
+        &mut self,
+        token: u64,
+        our_name: &XorName,
+        section: &Section,
+        network: &Network,
+    ) -> Result<Vec<Command>> {
+        let hash = if let Some(hash) = self
+            .pending
+            .iter()
+            .find(|(_, pending)| pending.timer_token == token)
+            .map(|(hash, _)| *hash)
+        {
+            hash
+        } else {
+            return Ok(vec![]);
+        };
+
+        let give_up = self
+            .pending
+            .get(&hash)
+            .map(|pending| pending.tx.is_closed() || pending.resends >= MAX_RESENDS)
+            .unwrap_or(true);
+        if give_up {
+            if let Some(pending) = self.pending.remove(&hash) {
+                trace!("Delivery of {:?} not confirmed - giving up", hash);
+                let _ = pending.tx.send(Err(Error::DeliveryFailed));
+            }
+
+            return Ok(vec![]);
+        }
+
+        let pending = if let Some(pending) = self.pending.get_mut(&hash) {
+            pending
+        } else {
+            return Ok(vec![]);
+        };
+
+        pending.resends += 1;
+        trace!(
+            "Delivery of {:?} not confirmed - resending (attempt {})",
+            hash,
+            pending.resends
+        );
+
+        let mut commands = vec![pending.schedule_resend()];
+        commands.extend(pending.resend(our_name, section, network)?);
+        Ok(commands)
+    }
+}
+
+impl Default for Deliveries {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct PendingDelivery {
+    dst: DstLocation,
+    // The message to resend. `None` if not known yet (e.g. still being aggregated at source).
+    message: Option<Message>,
+    // Nodes the message was already sent to.
+    tried: HashSet<XorName>,
+    resends: usize,
+    timer_token: u64,
+    tx: oneshot::Sender<Result<()>>,
+}
+
+impl PendingDelivery {
+    fn schedule_resend(&mut self) -> Command {
+        self.timer_token = command::next_timer_token();
+        Command::ScheduleTimeout {
+            duration: RESEND_INTERVAL,
+            token: self.timer_token,
+        }
+    }
+
+    // Send the message to the delivery targets we haven't tried yet. Once every target was
+    // tried, start over from the best ones.
+    fn resend(
+        &mut self,
+        our_name: &XorName,
+        section: &Section,
+        network: &Network,
+    ) -> Result<Option<Command>> {
+        let message = if let Some(message) = &self.message {
+            message
+        } else {
+            return Ok(None);
+        };
+
+        let (targets, dg_size) =
+            delivery_group::delivery_targets(message.dst(), our_name, section, network)?;
+
+        let untried: Vec<_> = targets
+            .iter()
+            .filter(|peer| !self.tried.contains(peer.name()))
+            .copied()
+            .collect();
+        let targets = if untried.is_empty() {
+            self.tried.clear();
+            targets
+        } else {
+            untried
+        };
+
+        let dg_size = dg_size.min(targets.len());
+        if dg_size == 0 {
+            return Ok(None);
+        }
+
+        self.tried
+            .extend(targets.iter().take(dg_size).map(|peer| *peer.name()));
+
+        let targets: Vec<_> = targets.iter().map(|peer| *peer.addr()).collect();
+        Ok(Some(Command::send_message_to_nodes(
+            &targets, dg_size, &message,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto, messages::Variant, network_params::NetworkParams, node::Node,
+        routing::request::user_message_id, section::test_utils::gen_addr, MIN_AGE,
+    };
+    use anyhow::Result;
+    use assert_matches::assert_matches;
+    use futures::FutureExt;
+    use sn_messaging::SrcLocation;
+    use xor_name::Prefix;
+
+    #[test]
+    fn confirm_only_from_destination() -> Result<()> {
+        let dst_node = gen_node();
+        let other_node = gen_node();
+
+        let src = SrcLocation::Node(rand::random());
+        let dst = DstLocation::Node(dst_node.name());
+        let hash = user_message_id(&src, &dst, b"hello")?;
+
+        let mut deliveries = Deliveries::new();
+        let (mut delivery, _) = deliveries.insert(hash, dst)?;
+        assert_eq!(delivery.hash(), &hash);
+
+        // Receipt from someone else than the destination is ignored.
+        let receipt = gen_receipt(&other_node, &hash)?;
+        assert!(!deliveries.confirm(&hash, receipt.src()));
+        assert!((&mut delivery).now_or_never().is_none());
+
+        let receipt = gen_receipt(&dst_node, &hash)?;
+        assert!(deliveries.confirm(&hash, receipt.src()));
+        assert_matches!(delivery.now_or_never(), Some(Ok(())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_duplicate() -> Result<()> {
+        let src = SrcLocation::Section(rand::random());
+        let dst = DstLocation::Section(rand::random());
+        let hash = user_message_id(&src, &dst, b"hello")?;
+
+        let mut deliveries = Deliveries::new();
+        let (mut delivery, _) = deliveries.insert(hash, dst)?;
+        assert_matches!(deliveries.insert(hash, dst), Err(Error::DeliveryPending));
+
+        // The first delivery is still pending.
+        assert!(deliveries.contains(&hash));
+        assert!((&mut delivery).now_or_never().is_none());
+
+        // The same content sent by a single node gets a different id each time.
+        let src = SrcLocation::Node(rand::random());
+        let first = user_message_id(&src, &dst, b"hello")?;
+        let second = user_message_id(&src, &dst, b"hello")?;
+        assert_ne!(first, second);
+        let _ = deliveries.insert(first, dst)?;
+        let _ = deliveries.insert(second, dst)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn give_up_after_max_resends() -> Result<()> {
+        let node = gen_node();
+        let (section, _) = Section::first_node(node.peer(), NetworkParams::default())?;
+        let network = Network::new();
+
+        let src = SrcLocation::Node(node.name());
+        let dst = DstLocation::Section(rand::random());
+        let hash = user_message_id(&src, &dst, b"hello")?;
+
+        let mut deliveries = Deliveries::new();
+        let (delivery, command) = deliveries.insert(hash, dst)?;
+
+        let mut token = assert_matches!(command, Command::ScheduleTimeout { token, .. } => token);
+        for _ in 0..MAX_RESENDS {
+            assert!(deliveries.is_timer(token));
+            let commands = deliveries.handle_timeout(token, &node.name(), &section, &network)?;
+            token = assert_matches!(
+                commands.first(),
+                Some(Command::ScheduleTimeout { token, .. }) => *token
+            );
+        }
+
+        let commands = deliveries.handle_timeout(token, &node.name(), &section, &network)?;
+        assert!(commands.is_empty());
+        assert!(!deliveries.is_timer(token));
+        assert_matches!(delivery.now_or_never(), Some(Err(Error::DeliveryFailed)));
+
+        Ok(())
+    }
+
+    fn gen_node() -> Node {
+        Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        )
+    }
+
+    fn gen_receipt(node: &Node, hash: &MessageHash) -> Result<Message> {
+        Ok(Message::single_src(
+            node,
+            DstLocation::Direct,
+            Variant::DeliveryReceipt(*hash),
+            None,
+            None,
+        )?)
+    }
+}
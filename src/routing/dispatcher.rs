@@ -6,20 +6,45 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{bootstrap, Comm, Command, Core};
-use crate::{error::Result, event::Event, relocation::SignedRelocateDetails};
+use super::{
+    bootstrap,
+    event_stream::{EventSender, EventSubscribers},
+    scheduler::{Priority, ScheduledGuard, Scheduler},
+    Comm, Command, Core, OverflowPolicy, QueueConfig,
+};
+use crate::{
+    error::Result, event::Event, metrics::MetricsRecorder, relocation::SignedRelocateDetails,
+};
 use sn_messaging::{section_info::Error as TargetSectionError, MessageType};
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    iter,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::{
-    sync::{mpsc, watch, Mutex},
+    sync::{mpsc, watch, Mutex, OwnedSemaphorePermit, Semaphore},
     time,
 };
 use tracing::Instrument;
 
 // `Command` Dispatcher.
 pub(crate) struct Dispatcher {
-    pub(super) core: Mutex<Core>,
+    core: Mutex<Core>,
     pub(super) comm: Comm,
+    pub(super) metrics: MetricsRecorder,
+    pub(super) event_subscribers: EventSubscribers,
+    events: EventSender,
+    commands_in_flight: AtomicUsize,
+    scheduler: Scheduler,
+    // Bounds the number of commands being handled at the same time. Once the bound is reached,
+    // incoming messages are dropped or not read and follow-up commands wait until some of the
+    // commands are handled.
+    permits: Arc<Semaphore>,
+    overflow_policy: OverflowPolicy,
 
     cancel_timer_tx: watch::Sender<bool>,
     cancel_timer_rx: watch::Receiver<bool>,
@@ -28,40 +53,117 @@ pub(crate) struct Dispatcher {
 impl Dispatcher {
     pub fn new(state: Core, comm: Comm) -> Self {
         let (cancel_timer_tx, cancel_timer_rx) = watch::channel(false);
-
-        // Take out the initial value.
+        let metrics = state.metrics_recorder().clone();
+        let event_subscribers = state.event_subscribers().clone();
+        let events = state.event_tx.clone();
+        let queue_config = QueueConfig::default();
 
         Self {
             core: Mutex::new(state),
             comm,
+            metrics,
+            event_subscribers,
+            events,
+            commands_in_flight: AtomicUsize::new(0),
+            scheduler: Scheduler::new(),
+            permits: Arc::new(Semaphore::new(queue_config.incoming_capacity)),
+            overflow_policy: queue_config.overflow_policy,
             cancel_timer_tx,
             cancel_timer_rx,
         }
     }
 
-    /// Send provided Event to the user which shall receive it through the EventStream
-    pub async fn send_event(&self, event: Event) {
-        self.core.lock().await.send_event(event)
+    /// Sets the capacity of the command queue and what happens when it fills up.
+    pub fn set_queue_config(&mut self, config: &QueueConfig) {
+        self.permits = Arc::new(Semaphore::new(config.incoming_capacity.max(1)));
+        self.overflow_policy = config.overflow_policy;
+    }
+
+    /// Send provided Event to the user which shall receive it through the EventStream, accessing
+    /// the node state with the given priority.
+    pub async fn send_event(&self, event: Event, priority: Priority) {
+        self.lock_core(priority).await.send_event(event);
+        self.events.flush().await
     }
 
     /// Handles the given command and transitively any new commands that are produced during its
     /// handling.
     pub async fn handle_commands(self: Arc<Self>, command: Command) -> Result<()> {
-        let commands = self.handle_command(command).await?;
-        for command in commands {
-            self.clone().spawn_handle_commands(command)
+        self.handle_queued_commands(command, None).await
+    }
+
+    /// Returns the permit to handle a command produced by an incoming message with the given
+    /// priority, or `None` if the message is to be dropped because too many commands are being
+    /// handled already.
+    pub async fn acquire_incoming_permit(
+        &self,
+        priority: Priority,
+    ) -> Option<OwnedSemaphorePermit> {
+        let drop_if_full = match self.overflow_policy {
+            OverflowPolicy::DropNewest => true,
+            OverflowPolicy::ShedLowPriority => priority == Priority::Low,
+            OverflowPolicy::Block => false,
+        };
+
+        if drop_if_full {
+            let permit = self.permits.clone().try_acquire_owned().ok();
+            if permit.is_none() {
+                trace!("Too many incoming messages - dropping");
+                self.metrics.incoming_dropped();
+            }
+            permit
+        } else {
+            self.permits.clone().acquire_owned().await.ok()
+        }
+    }
+
+    // Handles `command` holding `permit` (if any), then its follow-up commands, each once there is
+    // room for it.
+    async fn handle_queued_commands(
+        self: Arc<Self>,
+        command: Command,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> Result<()> {
+        let _ = self.commands_in_flight.fetch_add(1, Ordering::Relaxed);
+        let result = self.handle_command(command).await;
+        let _ = self.commands_in_flight.fetch_sub(1, Ordering::Relaxed);
+
+        // Release the permit before waiting for the permits of the follow-up commands, otherwise
+        // commands waiting for each other could take up all of them.
+        drop(permit);
+
+        // Don't produce more work until the application takes the events raised so far, if the
+        // event queue overflowed.
+        self.events.flush().await;
+
+        for command in result? {
+            // Timers only sleep until they fire, so they don't take up a permit.
+            let permit = if let Command::ScheduleTimeout { .. }
+            | Command::ScheduleDkgTimeout { .. } = command
+            {
+                None
+            } else {
+                self.permits.clone().acquire_owned().await.ok()
+            };
+
+            self.clone().spawn_handle_commands(command, permit)
         }
 
         Ok(())
     }
 
+    /// Number of commands currently being handled.
+    pub fn commands_in_flight(&self) -> usize {
+        self.commands_in_flight.load(Ordering::Relaxed)
+    }
+
     /// Handles a single command.
     pub async fn handle_command(&self, command: Command) -> Result<Vec<Command>> {
         // Create a tracing span containing info about the current node. This is very useful when
         // analyzing logs produced by running multiple nodes within the same process, for example
         // from integration tests.
         let span = {
-            let state = self.core.lock().await;
+            let state = self.lock_core(command.priority()).await;
             trace_span!(
                 "handle_command",
                 name = %state.node().name(),
@@ -91,61 +193,78 @@ impl Dispatcher {
     }
 
     async fn try_handle_command(&self, command: Command) -> Result<Vec<Command>> {
+        let priority = command.priority();
+
         match command {
             Command::HandleMessage { sender, message } => {
-                self.core.lock().await.handle_message(sender, message).await
+                self.lock_core(priority)
+                    .await
+                    .handle_message(sender, message)
+                    .await
             }
             Command::HandleSectionInfoMsg { sender, message } => Ok(self
-                .core
-                .lock()
+                .lock_core(priority)
                 .await
                 .handle_section_info_msg(sender, message)
                 .await),
-            Command::HandleTimeout(token) => self.core.lock().await.handle_timeout(token),
-            Command::HandleAgreement { proposal, proof } => {
-                self.core.lock().await.handle_agreement(proposal, proof)
+            Command::HandleTimeout(token) => self.lock_core(priority).await.handle_timeout(token),
+            Command::HandleDkgTimeout(token) => {
+                self.lock_core(priority).await.handle_dkg_timeout(token)
             }
+            Command::HandleAgreement { proposal, proof } => self
+                .lock_core(priority)
+                .await
+                .handle_agreement(proposal, proof),
             Command::HandleConnectionLost(addr) => Ok(self
-                .core
-                .lock()
+                .lock_core(priority)
                 .await
                 .handle_connection_lost(addr)
                 .into_iter()
                 .collect()),
-            Command::HandlePeerLost(addr) => self.core.lock().await.handle_peer_lost(&addr),
+            Command::HandlePeerLost(addr) => self.lock_core(priority).await.handle_peer_lost(&addr),
+            Command::DisconnectClient(addr) => {
+                self.comm.disconnect(&addr);
+                self.send_event(Event::ClientLost(addr)).await;
+                Ok(vec![])
+            }
             Command::HandleDkgOutcome {
+                dkg_key,
                 elders_info,
                 outcome,
             } => self
-                .core
-                .lock()
+                .lock_core(priority)
                 .await
-                .handle_dkg_outcome(elders_info, outcome),
-            Command::HandleDkgFailure(proofs) => self
-                .core
-                .lock()
+                .handle_dkg_outcome(dkg_key, elders_info, outcome),
+            Command::HandleDkgFailure { dkg_key, proofs } => self
+                .lock_core(priority)
                 .await
-                .handle_dkg_failure(proofs)
+                .handle_dkg_failure(dkg_key, proofs)
                 .map(|command| vec![command]),
             Command::SendMessage {
                 recipients,
                 delivery_group_size,
                 message,
+                variant,
             } => {
-                self.send_message(&recipients, delivery_group_size, message)
+                self.send_message(&recipients, delivery_group_size, message, variant)
                     .await
             }
             Command::SendUserMessage {
                 itinerary,
                 content,
                 additional_proof_chain_key,
-            } => self.core.lock().await.send_user_message(
+            } => self.lock_core(priority).await.send_user_message(
                 itinerary,
                 content,
                 additional_proof_chain_key.as_ref(),
             ),
             Command::ScheduleTimeout { duration, token } => Ok(self
-                .handle_schedule_timeout(duration, token)
+                .handle_schedule_timeout(duration, Command::HandleTimeout(token))
+                .await
+                .into_iter()
+                .collect()),
+            Command::ScheduleDkgTimeout { duration, token } => Ok(self
+                .handle_schedule_timeout(duration, Command::HandleDkgTimeout(token))
                 .await
                 .into_iter()
                 .collect()),
@@ -157,23 +276,36 @@ impl Dispatcher {
                 self.handle_relocate(bootstrap_addrs, details, message_rx)
                     .await
             }
-            Command::SetJoinsAllowed(joins_allowed) => {
-                self.core.lock().await.set_joins_allowed(joins_allowed)
-            }
+            Command::SetJoinsAllowed(joins_allowed) => self
+                .lock_core(priority)
+                .await
+                .set_joins_allowed(joins_allowed),
         }
     }
 
-    // Note: this indirecton is needed. Trying to call `spawn(self.handle_commands(...))` directly
-    // inside `handle_commands` causes compile error about type check cycle.
-    fn spawn_handle_commands(self: Arc<Self>, command: Command) {
-        let _ = tokio::spawn(self.handle_commands(command));
+    /// Locks `Core` once it's our turn according to `priority`. Higher priority work gets the lock
+    /// first. All access to `Core` must go through here, otherwise it would jump the queue.
+    pub async fn lock_core(&self, priority: Priority) -> ScheduledGuard<'_, Core> {
+        self.scheduler.lock(&self.core, priority).await
+    }
+
+    /// Handles the given command and its follow-up commands in a separate task, holding `permit`
+    /// (if any) until the command itself is handled.
+    // Note: this indirecton is needed. Trying to call `spawn(self.handle_queued_commands(...))`
+    // directly inside `handle_queued_commands` causes compile error about type check cycle.
+    pub fn spawn_handle_commands(
+        self: Arc<Self>,
+        command: Command,
+        permit: Option<OwnedSemaphorePermit>,
+    ) {
+        let _ = tokio::spawn(self.handle_queued_commands(command, permit));
     }
 
     pub async fn check_key_status(
         &self,
         bls_pk: &bls::PublicKey,
     ) -> Result<(), TargetSectionError> {
-        self.core.lock().await.check_key_status(bls_pk)
+        self.lock_core(Priority::Low).await.check_key_status(bls_pk)
     }
 
     async fn send_message(
@@ -181,9 +313,14 @@ impl Dispatcher {
         recipients: &[SocketAddr],
         delivery_group_size: usize,
         message: MessageType,
+        variant: Option<&'static str>,
     ) -> Result<Vec<Command>> {
         let msg_bytes = message.serialize()?;
 
+        if let Some(variant) = variant {
+            self.metrics.message_sent(variant);
+        }
+
         let cmds = match message {
             MessageType::Ping | MessageType::NodeMessage(_) => self
                 .comm
@@ -191,7 +328,10 @@ impl Dispatcher {
                 .await
                 .1
                 .into_iter()
-                .map(Command::HandlePeerLost)
+                .map(|addr| {
+                    self.metrics.send_failed(addr);
+                    Command::HandlePeerLost(addr)
+                })
                 .collect(),
             MessageType::ClientMessage(_) => {
                 for recipient in recipients {
@@ -201,6 +341,11 @@ impl Dispatcher {
                         .await
                         .is_err()
                     {
+                        self.metrics.send_failed(*recipient);
+                        let _ = self
+                            .lock_core(Priority::Normal)
+                            .await
+                            .remove_client(recipient);
                         self.send_event(Event::ClientLost(*recipient)).await;
                     }
                 }
@@ -220,7 +365,12 @@ impl Dispatcher {
         Ok(cmds)
     }
 
-    async fn handle_schedule_timeout(&self, duration: Duration, token: u64) -> Option<Command> {
+    // Raise `command` once `duration` elapses, unless the timers get cancelled first.
+    async fn handle_schedule_timeout(
+        &self,
+        duration: Duration,
+        command: Command,
+    ) -> Option<Command> {
         let mut cancel_rx = self.cancel_timer_rx.clone();
 
         if *cancel_rx.borrow() {
@@ -229,7 +379,7 @@ impl Dispatcher {
         }
 
         tokio::select! {
-            _ = time::sleep(duration) => Some(Command::HandleTimeout(token)),
+            _ = time::sleep(duration) => Some(command),
             _ = cancel_rx.changed() => None,
         }
     }
@@ -240,9 +390,14 @@ impl Dispatcher {
         details: SignedRelocateDetails,
         message_rx: mpsc::Receiver<(MessageType, SocketAddr)>,
     ) -> Result<Vec<Command>> {
-        let (genesis_key, node) = {
-            let state = self.core.lock().await;
-            (*state.section().genesis_key(), state.node().clone())
+        let (genesis_key, network_params, bootstrap_config, node) = {
+            let state = self.lock_core(Priority::High).await;
+            (
+                *state.section().genesis_key(),
+                *state.section().network_params(),
+                state.bootstrap_config(),
+                state.node().clone(),
+            )
         };
         let previous_name = node.name();
 
@@ -252,15 +407,37 @@ impl Dispatcher {
             message_rx,
             bootstrap_addrs,
             genesis_key,
+            network_params,
+            bootstrap_config,
             details,
         )
         .await?;
 
-        let mut state = self.core.lock().await;
+        let mut state = self.lock_core(Priority::High).await;
         let event_tx = state.event_tx.clone();
+        let state_store = state.state_store().cloned();
+        let unresponsive_threshold = state.unresponsive_threshold();
+        let gossip_config = state.gossip_config();
+        let client_config = state.client_config();
+        let join_queue_config = state.join_queue_config();
+        let section_signing_approval = state.section_signing_approval();
         let new_keypair = node.keypair.clone();
+        state.fail_pending_on_relocation();
         *state = Core::new(node, section, None, event_tx);
 
+        if let Some(state_store) = state_store {
+            state.set_state_store(state_store);
+        }
+
+        state.set_bootstrap_config(bootstrap_config);
+        state.set_unresponsive_threshold(unresponsive_threshold);
+        state.set_gossip_config(gossip_config);
+        state.set_client_config(client_config);
+        state.set_join_queue_config(join_queue_config);
+        state.set_section_signing_approval(section_signing_approval);
+        state.set_metrics_recorder(self.metrics.clone());
+        state.set_event_subscribers(self.event_subscribers.clone());
+
         state.send_event(Event::Relocated {
             previous_name,
             new_keypair,
@@ -272,6 +449,11 @@ impl Dispatcher {
                 message,
                 sender: Some(sender),
             })
+            .chain(iter::once(state.start_liveness_checks()))
+            .chain(iter::once(state.start_gossip()))
+            .chain(iter::once(state.start_client_expiry()))
+            .chain(iter::once(state.start_join_difficulty_adjustments()))
+            .chain(iter::once(state.start_join_queue_expiry()))
             .collect();
         Ok(commands)
     }
@@ -6,31 +6,71 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::{
+    command::{self, Command},
+    ClientConfig,
+};
 use crate::error::{Error, Result};
 use sn_data_types::{PublicKey as EndUserPK, Signature as EndUserSig};
 use sn_messaging::EndUser;
-use std::{
-    collections::{btree_map::Entry, BTreeMap},
-    net::SocketAddr,
-};
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+use tokio::time::Instant;
 use xor_name::XorName;
 
+// How many times per idle timeout the idle clients are checked for.
+const CHECKS_PER_IDLE_TIMEOUT: u32 = 4;
+
 pub type SocketId = XorName;
+
+// Whether the clients can be checked for idling often enough to enforce `idle_timeout`.
+pub(crate) fn is_valid_idle_timeout(idle_timeout: Duration) -> bool {
+    check_interval(idle_timeout) > Duration::ZERO
+}
+
+fn check_interval(idle_timeout: Duration) -> Duration {
+    idle_timeout / CHECKS_PER_IDLE_TIMEOUT
+}
+
+/// Information about a client connected to this node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ClientInfo {
+    /// Identity of the client: its public key and the id of its socket.
+    pub end_user: EndUser,
+    /// Address the client is connected from.
+    pub addr: SocketAddr,
+    /// How long ago the client registered.
+    pub connected_for: Duration,
+    /// How long ago the client last sent us a message.
+    pub idle_for: Duration,
+}
+
 pub(crate) struct EndUserRegistry {
-    clients: BTreeMap<SocketAddr, EndUser>,
+    config: ClientConfig,
+    clients: BTreeMap<SocketAddr, Client>,
     socket_id_mapping: BTreeMap<SocketId, SocketAddr>,
+    timer_token: Option<u64>,
 }
 
 impl EndUserRegistry {
-    pub fn new() -> Self {
+    pub fn new(config: ClientConfig) -> Self {
         Self {
+            config,
             clients: BTreeMap::default(),
             socket_id_mapping: BTreeMap::default(),
+            timer_token: None,
         }
     }
 
+    pub fn config(&self) -> ClientConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: ClientConfig) {
+        self.config = config;
+    }
+
     pub fn get_enduser_by_addr(&self, socketaddr: &SocketAddr) -> Option<&EndUser> {
-        self.clients.get(socketaddr)
+        self.clients.get(socketaddr).map(|client| &client.end_user)
     }
 
     pub fn get_socket_addr(&self, socket_id: SocketId) -> Option<&SocketAddr> {
@@ -43,10 +83,22 @@ impl EndUserRegistry {
     ) -> impl Iterator<Item = &'a SocketAddr> {
         self.clients
             .iter()
-            .filter(move |(_, end_user)| end_user.id() == end_user_pk)
+            .filter(move |(_, client)| client.end_user.id() == end_user_pk)
             .map(|(socket_addr, _)| socket_addr)
     }
 
+    pub fn client_info(&self, socketaddr: &SocketAddr) -> Option<ClientInfo> {
+        self.clients
+            .get(socketaddr)
+            .map(|client| client.info(*socketaddr))
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = ClientInfo> + '_ {
+        self.clients
+            .iter()
+            .map(|(socket_addr, client)| client.info(*socket_addr))
+    }
+
     pub fn try_add(
         &mut self,
         sender: SocketAddr,
@@ -61,13 +113,239 @@ impl EndUserRegistry {
             public_key: end_user_pk,
             socket_id,
         };
-        match self.socket_id_mapping.entry(socket_id) {
-            Entry::Vacant(entry) => {
-                let _ = self.clients.insert(sender, end_user);
-                let _ = entry.insert(sender);
-            }
-            Entry::Occupied(_) => (),
+
+        // The signature covers the sender address, so a known socket id means a repeated
+        // registration of the same client.
+        if self.socket_id_mapping.get(&socket_id) == Some(&sender) {
+            self.record_activity(&sender);
+            return Ok(());
+        }
+
+        // A registration from an address already in use replaces the previous client there.
+        let replaced = self.clients.contains_key(&sender);
+
+        if !replaced && self.clients.len() >= self.config.max_clients {
+            return Err(Error::TooManyClients);
+        }
+
+        let connections = self
+            .get_all_socket_addr(&end_user_pk)
+            .filter(|addr| **addr != sender)
+            .count();
+        if connections >= self.config.max_connections_per_client {
+            return Err(Error::TooManyClientConnections);
+        }
+
+        let _ = self.remove(&sender);
+        let _ = self.clients.insert(sender, Client::new(end_user));
+        let _ = self.socket_id_mapping.insert(socket_id, sender);
+
+        Ok(())
+    }
+
+    // Remove the client connected from the given address.
+    pub fn remove(&mut self, socketaddr: &SocketAddr) -> Option<EndUser> {
+        let client = self.clients.remove(socketaddr)?;
+        if let EndUser::Client { socket_id, .. } = client.end_user {
+            let _ = self.socket_id_mapping.remove(&socket_id);
+        }
+
+        Some(client.end_user)
+    }
+
+    // Record that we received a message from the client connected from the given address.
+    pub fn record_activity(&mut self, socketaddr: &SocketAddr) {
+        if let Some(client) = self.clients.get_mut(socketaddr) {
+            client.last_active = Instant::now();
+        }
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Schedule the next check for idle clients.
+    pub fn schedule_expiry(&mut self) -> Command {
+        let token = command::next_timer_token();
+        self.timer_token = Some(token);
+
+        Command::ScheduleTimeout {
+            duration: check_interval(self.config.idle_timeout),
+            token,
+        }
+    }
+
+    // Remove the clients that have been idle for longer than the idle timeout and return their
+    // addresses.
+    pub fn expire_idle(&mut self) -> Vec<SocketAddr> {
+        let idle_timeout = self.config.idle_timeout;
+        let expired: Vec<_> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.last_active.elapsed() >= idle_timeout)
+            .map(|(socket_addr, _)| *socket_addr)
+            .collect();
+
+        for socket_addr in &expired {
+            let _ = self.remove(socket_addr);
+        }
+
+        expired
+    }
+}
+
+impl Default for EndUserRegistry {
+    fn default() -> Self {
+        Self::new(ClientConfig::default())
+    }
+}
+
+struct Client {
+    end_user: EndUser,
+    registered: Instant,
+    last_active: Instant,
+}
+
+impl Client {
+    fn new(end_user: EndUser) -> Self {
+        let now = Instant::now();
+        Self {
+            end_user,
+            registered: now,
+            last_active: now,
         }
+    }
+
+    fn info(&self, addr: SocketAddr) -> ClientInfo {
+        ClientInfo {
+            end_user: self.end_user,
+            addr,
+            connected_for: self.registered.elapsed(),
+            idle_for: self.last_active.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use assert_matches::assert_matches;
+    use sn_data_types::Keypair;
+    use std::net::Ipv4Addr;
+    use tokio::time;
+
+    fn gen_addr(port: u16) -> SocketAddr {
+        (Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    fn register(
+        registry: &mut EndUserRegistry,
+        keypair: &Keypair,
+        addr: SocketAddr,
+    ) -> Result<(), Error> {
+        let sig = keypair.sign(&bincode::serialize(&addr)?);
+        registry.try_add(addr, keypair.public_key(), sig)
+    }
+
+    #[test]
+    fn add_and_remove() -> Result<()> {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let mut registry = EndUserRegistry::default();
+
+        register(&mut registry, &keypair, gen_addr(1))?;
+        register(&mut registry, &keypair, gen_addr(2))?;
+        // Repeated registration is not an error.
+        register(&mut registry, &keypair, gen_addr(2))?;
+
+        assert_eq!(registry.clients().count(), 2);
+        assert_eq!(
+            registry.get_all_socket_addr(&keypair.public_key()).count(),
+            2
+        );
+
+        let socket_id = assert_matches!(
+            registry.remove(&gen_addr(1)),
+            Some(EndUser::Client { socket_id, .. }) => socket_id
+        );
+        assert!(registry.get_socket_addr(socket_id).is_none());
+        assert!(registry.client_info(&gen_addr(1)).is_none());
+        assert!(registry.client_info(&gen_addr(2)).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_signature() -> Result<()> {
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let mut registry = EndUserRegistry::default();
+
+        let sig = keypair.sign(&bincode::serialize(&gen_addr(1))?);
+        assert_matches!(
+            registry.try_add(gen_addr(2), keypair.public_key(), sig),
+            Err(Error::FailedSignature)
+        );
+        assert_eq!(registry.clients().count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn connection_limits() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let keypair0 = Keypair::new_ed25519(&mut rng);
+        let keypair1 = Keypair::new_ed25519(&mut rng);
+        let mut registry = EndUserRegistry::new(ClientConfig {
+            max_clients: 3,
+            max_connections_per_client: 2,
+            ..Default::default()
+        });
+
+        register(&mut registry, &keypair0, gen_addr(1))?;
+        register(&mut registry, &keypair0, gen_addr(2))?;
+        assert_matches!(
+            register(&mut registry, &keypair0, gen_addr(3)),
+            Err(Error::TooManyClientConnections)
+        );
+
+        register(&mut registry, &keypair1, gen_addr(3))?;
+        assert_matches!(
+            register(&mut registry, &keypair1, gen_addr(4)),
+            Err(Error::TooManyClients)
+        );
+
+        // Removing a client makes room for another one.
+        let _ = registry.remove(&gen_addr(1));
+        register(&mut registry, &keypair1, gen_addr(4))?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expire_idle_clients() -> Result<()> {
+        time::pause();
+
+        let idle_timeout = Duration::from_secs(60);
+        let keypair = Keypair::new_ed25519(&mut rand::thread_rng());
+        let mut registry = EndUserRegistry::new(ClientConfig {
+            idle_timeout,
+            ..Default::default()
+        });
+
+        register(&mut registry, &keypair, gen_addr(1))?;
+        register(&mut registry, &keypair, gen_addr(2))?;
+
+        time::advance(idle_timeout / 2).await;
+        registry.record_activity(&gen_addr(2));
+        assert!(registry.expire_idle().is_empty());
+
+        time::advance(idle_timeout / 2).await;
+        assert_eq!(registry.expire_idle(), [gen_addr(1)]);
+        assert_eq!(
+            registry.clients().map(|info| info.addr).collect::<Vec<_>>(),
+            [gen_addr(2)]
+        );
+
         Ok(())
     }
 }
@@ -6,21 +6,450 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::{OverflowPolicy, QueueConfig};
 use crate::event::Event;
-use tokio::sync::mpsc;
+use sn_messaging::DstLocation;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug, Formatter},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    Mutex as AsyncMutex,
+};
+
+// Creates the bounded channel carrying the events from `Core` to the `EventStream`.
+pub(crate) fn event_channel(config: &QueueConfig) -> (EventSender, EventStream) {
+    // `mpsc::channel` panics on zero capacity.
+    let (events_tx, events_rx) = mpsc::channel(config.event_capacity.max(1));
+    let depth = Arc::new(AtomicUsize::new(0));
+
+    let sender = EventSender {
+        events_tx,
+        overflow_policy: config.overflow_policy,
+        overflow: Default::default(),
+        flush_lock: Default::default(),
+        depth: depth.clone(),
+        dropped: Default::default(),
+    };
+    let stream = EventStream { events_rx, depth };
+
+    (sender, stream)
+}
 
 /// Stream of routing node events
 pub struct EventStream {
-    events_rx: mpsc::UnboundedReceiver<Event>,
+    events_rx: mpsc::Receiver<Event>,
+    depth: Arc<AtomicUsize>,
 }
 
 impl EventStream {
-    pub(crate) fn new(events_rx: mpsc::UnboundedReceiver<Event>) -> Self {
-        Self { events_rx }
+    /// Returns next event
+    pub async fn next(&mut self) -> Option<Event> {
+        let event = self.events_rx.recv().await;
+        if event.is_some() {
+            let _ = self.depth.fetch_sub(1, Ordering::Relaxed);
+        }
+        event
     }
+}
 
-    /// Returns next event
+// Sending half of the event channel. When the channel is full, the event is either dropped or
+// kept aside until the application makes room for it, according to the `OverflowPolicy`.
+#[derive(Clone)]
+pub(crate) struct EventSender {
+    events_tx: mpsc::Sender<Event>,
+    overflow_policy: OverflowPolicy,
+    // Events that didn't fit into the channel, waiting to be passed to it by `flush`.
+    overflow: Arc<Mutex<VecDeque<Event>>>,
+    flush_lock: Arc<AsyncMutex<()>>,
+    // Number of events in the channel not received by the application yet.
+    depth: Arc<AtomicUsize>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventSender {
+    pub fn send(&self, event: Event) {
+        let mut overflow = self.lock_overflow();
+
+        // Don't let the event overtake the ones already waiting for room in the channel.
+        let event = if overflow.is_empty() {
+            let _ = self.depth.fetch_add(1, Ordering::Relaxed);
+            match self.events_tx.try_send(event) {
+                Ok(()) => return,
+                Err(TrySendError::Full(event)) => {
+                    let _ = self.depth.fetch_sub(1, Ordering::Relaxed);
+                    event
+                }
+                Err(TrySendError::Closed(_)) => {
+                    let _ = self.depth.fetch_sub(1, Ordering::Relaxed);
+                    error!("Event receiver has been closed");
+                    return;
+                }
+            }
+        } else {
+            event
+        };
+
+        let drop = match self.overflow_policy {
+            OverflowPolicy::DropNewest => true,
+            OverflowPolicy::ShedLowPriority => is_low_priority(&event),
+            OverflowPolicy::Block => false,
+        };
+
+        if drop {
+            trace!("Event queue full - dropping {:?}", event);
+            let _ = self.dropped.fetch_add(1, Ordering::Relaxed);
+        } else {
+            overflow.push_back(event);
+        }
+    }
+
+    // Wait until all the events kept aside are passed to the channel. This is what applies the
+    // backpressure: the caller doesn't continue until the application catches up.
+    pub async fn flush(&self) {
+        if self.lock_overflow().is_empty() {
+            return;
+        }
+
+        let _guard = self.flush_lock.lock().await;
+
+        while !self.lock_overflow().is_empty() {
+            let permit = if let Ok(permit) = self.events_tx.reserve().await {
+                permit
+            } else {
+                error!("Event receiver has been closed");
+                self.lock_overflow().clear();
+                return;
+            };
+
+            if let Some(event) = self.lock_overflow().pop_front() {
+                let _ = self.depth.fetch_add(1, Ordering::Relaxed);
+                permit.send(event);
+            }
+        }
+    }
+
+    // Number of events sent but not received by the application yet.
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed) + self.lock_overflow().len()
+    }
+
+    // Number of events dropped because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn lock_overflow(&self) -> MutexGuard<VecDeque<Event>> {
+        self.overflow
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+// Events which are shed first when the application doesn't keep up: the ones carrying user
+// messages, as opposed to the ones about the state of the node and its section.
+fn is_low_priority(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::MessageReceived { .. }
+            | Event::RequestReceived { .. }
+            | Event::ClientMessageReceived { .. }
+    )
+}
+
+/// Selects the events a `Subscription` receives.
+#[derive(Clone)]
+pub enum EventFilter {
+    /// Every event.
+    All,
+    /// Only `Event::EldersChanged`.
+    EldersChanged,
+    /// Only messages and requests (`Event::MessageReceived` and `Event::RequestReceived`) sent to
+    /// the given destination.
+    MessagesTo(DstLocation),
+    /// Only events about clients (`Event::ClientMessageReceived` and `Event::ClientLost`).
+    ClientMessages,
+    /// Only events for which the function returns `true`.
+    Custom(Arc<dyn Fn(&Event) -> bool + Send + Sync>),
+}
+
+impl EventFilter {
+    /// Returns whether `event` passes this filter.
+    pub fn matches(&self, event: &Event) -> bool {
+        match self {
+            Self::All => true,
+            Self::EldersChanged => matches!(event, Event::EldersChanged { .. }),
+            Self::MessagesTo(location) => match event {
+                Event::MessageReceived { dst, .. } | Event::RequestReceived { dst, .. } => {
+                    dst == location
+                }
+                _ => false,
+            },
+            Self::ClientMessages => matches!(
+                event,
+                Event::ClientMessageReceived { .. } | Event::ClientLost(_)
+            ),
+            Self::Custom(filter) => filter(event),
+        }
+    }
+}
+
+impl Debug for EventFilter {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "All"),
+            Self::EldersChanged => write!(f, "EldersChanged"),
+            Self::MessagesTo(location) => write!(f, "MessagesTo({:?})", location),
+            Self::ClientMessages => write!(f, "ClientMessages"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// What happens to the events for a subscriber whose buffer is full.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LagPolicy {
+    /// Drop the events that don't fit in the buffer. The subscriber keeps receiving new events as
+    /// soon as it makes room for them. The dropped events are counted in
+    /// `Subscription::missed`.
+    DropNewest,
+    /// End the subscription. The subscriber still receives the events already in the buffer,
+    /// after which `Subscription::next` returns `None`.
+    Unsubscribe,
+}
+
+/// Stream of the events passing a filter, created with `Routing::subscribe`. Independent of the
+/// `EventStream` and of the other subscriptions. Dropping it ends the subscription.
+pub struct Subscription {
+    events_rx: mpsc::Receiver<Event>,
+    missed: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// Returns next event, or `None` if the subscription ended.
     pub async fn next(&mut self) -> Option<Event> {
         self.events_rx.recv().await
     }
+
+    /// Number of events which passed the filter but were dropped because the buffer was full.
+    pub fn missed(&self) -> u64 {
+        self.missed.load(Ordering::Relaxed)
+    }
+}
+
+// Registry of the event subscriptions. Cloning it shares the subscriptions, so they survive the
+// node relocating.
+#[derive(Clone, Default)]
+pub(crate) struct EventSubscribers(Arc<Mutex<Vec<Subscriber>>>);
+
+impl EventSubscribers {
+    pub fn subscribe(
+        &self,
+        filter: EventFilter,
+        capacity: usize,
+        lag_policy: LagPolicy,
+    ) -> Subscription {
+        // `mpsc::channel` panics on zero capacity.
+        let (events_tx, events_rx) = mpsc::channel(capacity.max(1));
+        let missed = Arc::new(AtomicU64::new(0));
+
+        self.lock().push(Subscriber {
+            filter,
+            lag_policy,
+            events_tx,
+            missed: missed.clone(),
+        });
+
+        Subscription { events_rx, missed }
+    }
+
+    // Pass `event` to every subscriber whose filter it passes. Removes the subscribers which were
+    // dropped or which lagged behind with `LagPolicy::Unsubscribe`.
+    pub fn publish(&self, event: &Event) {
+        self.lock().retain(|subscriber| {
+            if !subscriber.filter.matches(event) {
+                return !subscriber.events_tx.is_closed();
+            }
+
+            match subscriber.events_tx.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    let _ = subscriber.missed.fetch_add(1, Ordering::Relaxed);
+                    match subscriber.lag_policy {
+                        LagPolicy::DropNewest => true,
+                        LagPolicy::Unsubscribe => {
+                            debug!(
+                                "Event subscriber ({:?}) lagging behind - unsubscribing",
+                                subscriber.filter
+                            );
+                            false
+                        }
+                    }
+                }
+                Err(TrySendError::Closed(_)) => false,
+            }
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<Vec<Subscriber>> {
+        self.0.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+struct Subscriber {
+    filter: EventFilter,
+    lag_policy: LagPolicy,
+    events_tx: mpsc::Sender<Event>,
+    missed: Arc<AtomicU64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::NodeElderChange;
+    use futures::FutureExt;
+    use xor_name::Prefix;
+
+    #[tokio::test]
+    async fn overflow_policy() {
+        let config = |overflow_policy| QueueConfig {
+            event_capacity: 1,
+            overflow_policy,
+            ..Default::default()
+        };
+
+        // Drop everything that doesn't fit.
+        let (sender, mut stream) = event_channel(&config(OverflowPolicy::DropNewest));
+        sender.send(gen_elders_changed());
+        sender.send(gen_elders_changed());
+        assert_eq!(sender.depth(), 1);
+        assert_eq!(sender.dropped(), 1);
+        assert!(stream.next().await.is_some());
+        assert_eq!(sender.depth(), 0);
+
+        // Drop only the user messages, keep the rest until there is room for it.
+        let (sender, mut stream) = event_channel(&config(OverflowPolicy::ShedLowPriority));
+        sender.send(gen_message(DstLocation::Node(rand::random())));
+        sender.send(gen_message(DstLocation::Node(rand::random())));
+        sender.send(gen_elders_changed());
+        assert_eq!(sender.depth(), 2);
+        assert_eq!(sender.dropped(), 1);
+
+        // Flushing waits for the application to make room.
+        assert!(sender.flush().now_or_never().is_none());
+        assert!(matches!(
+            stream.next().await,
+            Some(Event::MessageReceived { .. })
+        ));
+        sender.flush().await;
+        assert!(matches!(
+            stream.next().await,
+            Some(Event::EldersChanged { .. })
+        ));
+
+        // Keep everything, preserving the order.
+        let (sender, mut stream) = event_channel(&config(OverflowPolicy::Block));
+        sender.send(gen_elders_changed());
+        sender.send(gen_message(DstLocation::Node(rand::random())));
+        sender.send(gen_elders_changed());
+        assert_eq!(sender.depth(), 3);
+        assert_eq!(sender.dropped(), 0);
+
+        let flush = tokio::spawn({
+            let sender = sender.clone();
+            async move { sender.flush().await }
+        });
+        assert!(matches!(
+            stream.next().await,
+            Some(Event::EldersChanged { .. })
+        ));
+        assert!(matches!(
+            stream.next().await,
+            Some(Event::MessageReceived { .. })
+        ));
+        assert!(matches!(
+            stream.next().await,
+            Some(Event::EldersChanged { .. })
+        ));
+        flush.await.expect("flush task panicked");
+        assert_eq!(sender.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn filter() {
+        let subscribers = EventSubscribers::default();
+        let dst = DstLocation::Section(rand::random());
+
+        let mut elders =
+            subscribers.subscribe(EventFilter::EldersChanged, 10, LagPolicy::DropNewest);
+        let mut messages =
+            subscribers.subscribe(EventFilter::MessagesTo(dst), 10, LagPolicy::DropNewest);
+
+        subscribers.publish(&gen_elders_changed());
+        subscribers.publish(&gen_message(DstLocation::Node(rand::random())));
+        subscribers.publish(&gen_message(dst));
+
+        assert!(matches!(
+            elders.next().await,
+            Some(Event::EldersChanged { .. })
+        ));
+        assert!(elders.next().now_or_never().is_none());
+
+        assert!(matches!(
+            messages.next().await,
+            Some(Event::MessageReceived { dst: received, .. }) if received == dst
+        ));
+        assert!(messages.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn lag_policy() {
+        let subscribers = EventSubscribers::default();
+
+        let mut dropping = subscribers.subscribe(EventFilter::All, 1, LagPolicy::DropNewest);
+        let mut unsubscribing = subscribers.subscribe(EventFilter::All, 1, LagPolicy::Unsubscribe);
+
+        subscribers.publish(&gen_elders_changed());
+        subscribers.publish(&gen_elders_changed());
+        assert_eq!(dropping.missed(), 1);
+        assert_eq!(unsubscribing.missed(), 1);
+
+        // The unsubscribed subscriber still gets the buffered event but nothing after that.
+        assert!(dropping.next().await.is_some());
+        assert!(unsubscribing.next().await.is_some());
+        assert!(unsubscribing.next().await.is_none());
+
+        subscribers.publish(&gen_elders_changed());
+        assert!(dropping.next().await.is_some());
+        assert_eq!(dropping.missed(), 1);
+
+        // Dropped subscriptions are removed.
+        drop(dropping);
+        subscribers.publish(&gen_elders_changed());
+        assert!(subscribers.lock().is_empty());
+    }
+
+    fn gen_elders_changed() -> Event {
+        Event::EldersChanged {
+            prefix: Prefix::default(),
+            key: bls::SecretKey::random().public_key(),
+            sibling_key: None,
+            elders: Default::default(),
+            self_status_change: NodeElderChange::None,
+        }
+    }
+
+    fn gen_message(dst: DstLocation) -> Event {
+        Event::MessageReceived {
+            content: Default::default(),
+            src: sn_messaging::SrcLocation::Node(rand::random()),
+            dst,
+            proof_chain: None,
+        }
+    }
 }
@@ -0,0 +1,263 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    command::{self, Command},
+    GossipConfig,
+};
+use itertools::Itertools;
+use std::{collections::BTreeSet, convert::TryFrom};
+use xor_name::Prefix;
+
+// Drives the periodic anti-entropy gossip through which our elders push our section's elders and
+// key to other sections and learn whether our view of the network is out of date.
+pub(crate) struct Gossip {
+    config: GossipConfig,
+    timer_token: Option<u64>,
+    round: u64,
+    // Round-robin positions in the lists of neighbouring and distant sections.
+    neighbour_cursor: usize,
+    distant_cursor: usize,
+    // Sections some other section has a different view of than us. They get gossiped with first,
+    // so their reply corrects whichever of the views is stale.
+    suspects: BTreeSet<Prefix>,
+}
+
+impl Gossip {
+    pub fn new(config: GossipConfig) -> Self {
+        Self {
+            config,
+            timer_token: None,
+            round: 0,
+            neighbour_cursor: 0,
+            distant_cursor: 0,
+            suspects: BTreeSet::new(),
+        }
+    }
+
+    pub fn config(&self) -> GossipConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: GossipConfig) {
+        self.config = config;
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Schedule the next gossip round.
+    pub fn schedule(&mut self) -> Command {
+        let token = command::next_timer_token();
+        self.timer_token = Some(token);
+
+        Command::ScheduleTimeout {
+            duration: self.config.interval,
+            token,
+        }
+    }
+
+    // Start a new gossip round and return its sequence number.
+    pub fn next_round(&mut self) -> u64 {
+        self.round = self.round.wrapping_add(1);
+        self.round
+    }
+
+    // Record that some other section's view of the section with `prefix` differs from ours.
+    pub fn suspect(&mut self, prefix: Prefix) {
+        if self.suspects.len() < self.config.max_suspects {
+            let _ = self.suspects.insert(prefix);
+        }
+    }
+
+    // Select the sections to gossip with this round, out of the `known` other sections: first the
+    // suspected ones, then neighbours of `our_prefix` and one distant section, each in
+    // round-robin, so that over time every known section is gossiped with.
+    pub fn targets(&mut self, our_prefix: &Prefix, known: &[Prefix]) -> Vec<Prefix> {
+        let mut targets: Vec<_> = known
+            .iter()
+            .filter(|prefix| self.suspects.contains(*prefix))
+            .take(self.config.fanout)
+            .copied()
+            .collect();
+        for prefix in &targets {
+            let _ = self.suspects.remove(prefix);
+        }
+
+        // Forget suspects which are no longer among the known sections.
+        self.suspects.retain(|prefix| known.contains(prefix));
+
+        let (neighbours, distant): (Vec<_>, Vec<_>) = known
+            .iter()
+            .filter(|prefix| !targets.contains(prefix))
+            .copied()
+            .sorted()
+            .partition(|prefix| is_neighbour(our_prefix, prefix));
+
+        let slots = self.config.fanout.saturating_sub(targets.len());
+        let distant_slots = if slots > 1 && !distant.is_empty() {
+            1
+        } else {
+            0
+        };
+        let neighbour_slots = (slots - distant_slots).min(neighbours.len());
+        let distant_slots = slots - neighbour_slots;
+
+        targets.extend(round_robin(
+            &neighbours,
+            &mut self.neighbour_cursor,
+            neighbour_slots,
+        ));
+        targets.extend(round_robin(
+            &distant,
+            &mut self.distant_cursor,
+            distant_slots,
+        ));
+        targets
+    }
+}
+
+impl Default for Gossip {
+    fn default() -> Self {
+        Self::new(GossipConfig::default())
+    }
+}
+
+// Two prefixes are neighbours if they differ in exactly one of their common bits.
+pub(crate) fn is_neighbour(lhs: &Prefix, rhs: &Prefix) -> bool {
+    let common = lhs.bit_count().min(rhs.bit_count());
+    (0..common)
+        .filter_map(|index| u8::try_from(index).ok())
+        .filter(|index| lhs.name().bit(*index) != rhs.name().bit(*index))
+        .count()
+        == 1
+}
+
+// Take up to `count` items from `items` starting at `cursor` (wrapping around) and advance it.
+fn round_robin(items: &[Prefix], cursor: &mut usize, count: usize) -> Vec<Prefix> {
+    if items.is_empty() {
+        return vec![];
+    }
+
+    let start = *cursor % items.len();
+    let count = count.min(items.len());
+    *cursor = start + count;
+
+    items
+        .iter()
+        .cycle()
+        .skip(start)
+        .take(count)
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(s: &str) -> Prefix {
+        s.parse().unwrap()
+    }
+
+    fn config(fanout: usize) -> GossipConfig {
+        GossipConfig {
+            fanout,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn neighbours() {
+        assert!(is_neighbour(&prefix("00"), &prefix("01")));
+        assert!(is_neighbour(&prefix("00"), &prefix("10")));
+        assert!(is_neighbour(&prefix("00"), &prefix("1")));
+        assert!(is_neighbour(&prefix("000"), &prefix("01")));
+        assert!(!is_neighbour(&prefix("00"), &prefix("11")));
+        assert!(!is_neighbour(&prefix("00"), &prefix("00")));
+        assert!(!is_neighbour(&prefix("00"), &prefix("0")));
+    }
+
+    #[test]
+    fn targets_cover_all_sections() {
+        let our_prefix = prefix("000");
+        let known = [
+            prefix("001"),
+            prefix("01"),
+            prefix("10"),
+            prefix("110"),
+            prefix("111"),
+        ];
+
+        let mut gossip = Gossip::new(config(2));
+        let mut seen = BTreeSet::new();
+
+        for _ in 0..3 {
+            let targets = gossip.targets(&our_prefix, &known);
+            assert_eq!(targets.len(), 2);
+            // Each round includes one neighbour and one distant section.
+            assert_eq!(
+                targets
+                    .iter()
+                    .filter(|prefix| is_neighbour(&our_prefix, prefix))
+                    .count(),
+                1
+            );
+            seen.extend(targets);
+        }
+
+        assert_eq!(seen.len(), known.len());
+    }
+
+    #[test]
+    fn suspects_come_first() {
+        let our_prefix = prefix("00");
+        let known = [prefix("01"), prefix("10"), prefix("11")];
+
+        let mut gossip = Gossip::new(config(1));
+        gossip.suspect(prefix("11"));
+        // Unknown sections are not gossiped with.
+        gossip.suspect(prefix("0101"));
+
+        assert_eq!(gossip.targets(&our_prefix, &known), [prefix("11")]);
+        assert_eq!(gossip.targets(&our_prefix, &known), [prefix("01")]);
+    }
+
+    #[test]
+    fn suspects_are_bounded() {
+        let our_prefix = prefix("00");
+        let known = [prefix("01"), prefix("10"), prefix("11")];
+
+        let mut gossip = Gossip::new(GossipConfig {
+            fanout: 3,
+            max_suspects: 1,
+            ..Default::default()
+        });
+        gossip.suspect(prefix("11"));
+        gossip.suspect(prefix("10"));
+
+        // Only the first suspect is remembered and goes before the neighbours.
+        assert_eq!(
+            gossip.targets(&our_prefix, &known),
+            [prefix("11"), prefix("01"), prefix("10")]
+        );
+    }
+
+    #[test]
+    fn fanout_bounds_targets() {
+        let our_prefix = prefix("00");
+        let known = [prefix("01"), prefix("10")];
+
+        let mut gossip = Gossip::new(config(5));
+        assert_eq!(gossip.targets(&our_prefix, &known).len(), 2);
+
+        let mut gossip = Gossip::new(config(0));
+        assert!(gossip.targets(&our_prefix, &known).is_empty());
+    }
+}
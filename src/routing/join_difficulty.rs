@@ -0,0 +1,267 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::{self, Command};
+use crate::network_params::{NetworkParams, ResourceProofParams};
+use resource_proof::ResourceProof;
+use std::{collections::VecDeque, time::Duration};
+use tokio::time::Instant;
+
+// How often the elders reconsider the difficulty.
+const ADJUST_INTERVAL: Duration = Duration::from_secs(30);
+// Period over which the join attempts and the rejections are counted.
+const WINDOW: Duration = Duration::from_secs(60);
+// Number of join attempts per window still considered normal. Every doubling above it adds one
+// level of difficulty.
+const NORMAL_JOIN_ATTEMPTS: usize = 8;
+// Number of rejected resource proofs per window above which one level of difficulty is added.
+const NORMAL_REJECTIONS: usize = 4;
+// Maximum number of levels added on top of the base difficulty.
+const MAX_EXTRA_DIFFICULTY: u8 = 6;
+// Maximum number of times the data size is doubled.
+const MAX_DATA_SIZE_DOUBLINGS: u8 = 4;
+
+// Tracks the join attempts to our section and tunes the resource proof challenge issued to the
+// joining nodes: harder during join floods and for sections that don't need to grow, easy
+// otherwise. Changes take effect only once agreed on by the elders, so all of them issue the same
+// challenges.
+pub(crate) struct JoinDifficulty {
+    base: ResourceProofParams,
+    current: ResourceProofParams,
+    // Responses to the challenges issued before the last change are still accepted.
+    previous: ResourceProofParams,
+    // The params we proposed and are waiting for the agreement on.
+    proposed: Option<ResourceProofParams>,
+    attempts: VecDeque<Instant>,
+    rejections: VecDeque<Instant>,
+    timer_token: Option<u64>,
+}
+
+impl JoinDifficulty {
+    pub fn new(network_params: &NetworkParams) -> Self {
+        let base = network_params.resource_proof_params();
+        Self {
+            base,
+            current: base,
+            previous: base,
+            proposed: None,
+            attempts: VecDeque::new(),
+            rejections: VecDeque::new(),
+            timer_token: None,
+        }
+    }
+
+    pub fn current(&self) -> ResourceProofParams {
+        self.current
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Schedule the next reconsideration of the difficulty.
+    pub fn schedule_adjustment(&mut self) -> Command {
+        let token = command::next_timer_token();
+        self.timer_token = Some(token);
+
+        Command::ScheduleTimeout {
+            duration: ADJUST_INTERVAL,
+            token,
+        }
+    }
+
+    // Record that a new node asked to join and was sent a challenge.
+    pub fn record_attempt(&mut self) {
+        self.attempts.push_back(Instant::now());
+    }
+
+    // Record that a joining node sent an invalid resource proof.
+    pub fn record_rejection(&mut self) {
+        self.rejections.push_back(Instant::now());
+    }
+
+    // Returns the params to propose if they differ from the current or already proposed ones.
+    pub fn adjustment(
+        &mut self,
+        section_size: usize,
+        recommended_section_size: usize,
+    ) -> Option<ResourceProofParams> {
+        let target = self.target(section_size, recommended_section_size);
+        if target == self.current || Some(target) == self.proposed {
+            None
+        } else {
+            self.proposed = Some(target);
+            Some(target)
+        }
+    }
+
+    // Apply the agreed params.
+    pub fn update(&mut self, params: ResourceProofParams) {
+        if params != self.current {
+            self.previous = self.current;
+            self.current = params;
+        }
+
+        if self.proposed == Some(params) {
+            self.proposed = None;
+        }
+    }
+
+    pub fn validate(&self, nonce: &[u8], data: &VecDeque<u8>, solution: u64) -> bool {
+        validate(&self.current, nonce, data, solution)
+            || (self.previous != self.current && validate(&self.previous, nonce, data, solution))
+    }
+
+    fn target(
+        &mut self,
+        section_size: usize,
+        recommended_section_size: usize,
+    ) -> ResourceProofParams {
+        let now = Instant::now();
+        prune(&mut self.attempts, now);
+        prune(&mut self.rejections, now);
+
+        let mut extra: u8 = 0;
+
+        let mut attempts = self.attempts.len();
+        while attempts > NORMAL_JOIN_ATTEMPTS {
+            extra = extra.saturating_add(1);
+            attempts /= 2;
+        }
+
+        if self.rejections.len() > NORMAL_REJECTIONS {
+            extra = extra.saturating_add(1);
+        }
+
+        // A section that already reached its recommended size has no need to grow fast.
+        if section_size >= recommended_section_size {
+            extra = extra.saturating_add(1);
+        }
+
+        let extra = extra.min(MAX_EXTRA_DIFFICULTY);
+
+        ResourceProofParams {
+            data_size: self
+                .base
+                .data_size
+                .saturating_mul(1 << extra.min(MAX_DATA_SIZE_DOUBLINGS)),
+            difficulty: self.base.difficulty.saturating_add(extra),
+        }
+    }
+}
+
+fn validate(
+    params: &ResourceProofParams,
+    nonce: &[u8],
+    data: &VecDeque<u8>,
+    solution: u64,
+) -> bool {
+    ResourceProof::new(params.data_size, params.difficulty).validate_all(nonce, data, solution)
+}
+
+// Forget the events that fell out of the window.
+fn prune(events: &mut VecDeque<Instant>, now: Instant) {
+    while events
+        .front()
+        .map_or(false, |time| now.saturating_duration_since(*time) >= WINDOW)
+    {
+        let _ = events.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time;
+
+    const RECOMMENDED_SECTION_SIZE: usize = 10;
+
+    #[tokio::test]
+    async fn join_flood_raises_difficulty() {
+        time::pause();
+
+        let network_params = NetworkParams::default();
+        let base = network_params.resource_proof_params();
+        let mut difficulty = JoinDifficulty::new(&network_params);
+
+        for _ in 0..NORMAL_JOIN_ATTEMPTS {
+            difficulty.record_attempt();
+        }
+        assert_eq!(difficulty.adjustment(1, RECOMMENDED_SECTION_SIZE), None);
+
+        for _ in 0..NORMAL_JOIN_ATTEMPTS * 3 {
+            difficulty.record_attempt();
+        }
+        let params = difficulty
+            .adjustment(1, RECOMMENDED_SECTION_SIZE)
+            .expect("difficulty not raised");
+        assert_eq!(params.difficulty, base.difficulty + 2);
+        assert_eq!(params.data_size, base.data_size * 4);
+
+        // Not proposed again while waiting for the agreement.
+        assert_eq!(difficulty.adjustment(1, RECOMMENDED_SECTION_SIZE), None);
+
+        difficulty.update(params);
+        assert_eq!(difficulty.current(), params);
+
+        // Once the flood is over, the difficulty goes back to the base.
+        time::advance(WINDOW).await;
+        assert_eq!(
+            difficulty.adjustment(1, RECOMMENDED_SECTION_SIZE),
+            Some(base)
+        );
+    }
+
+    #[test]
+    fn rejections_and_section_size_raise_difficulty() {
+        let network_params = NetworkParams::default();
+        let base = network_params.resource_proof_params();
+        let mut difficulty = JoinDifficulty::new(&network_params);
+
+        let params = difficulty
+            .adjustment(RECOMMENDED_SECTION_SIZE, RECOMMENDED_SECTION_SIZE)
+            .expect("difficulty not raised");
+        assert_eq!(params.difficulty, base.difficulty + 1);
+        difficulty.update(params);
+
+        for _ in 0..=NORMAL_REJECTIONS {
+            difficulty.record_rejection();
+        }
+        let params = difficulty
+            .adjustment(RECOMMENDED_SECTION_SIZE, RECOMMENDED_SECTION_SIZE)
+            .expect("difficulty not raised");
+        assert_eq!(params.difficulty, base.difficulty + 2);
+    }
+
+    #[test]
+    fn accept_previous_difficulty() {
+        let network_params = NetworkParams::default();
+        let base = network_params.resource_proof_params();
+        let mut difficulty = JoinDifficulty::new(&network_params);
+
+        let nonce = [7; 32];
+        let rp = ResourceProof::new(base.data_size, base.difficulty);
+        let data = rp.create_proof_data(&nonce);
+        let mut prover = rp.create_prover(data.clone());
+        let solution = prover.solve();
+        assert!(difficulty.validate(&nonce, &data, solution));
+
+        let harder = ResourceProofParams {
+            data_size: base.data_size * 2,
+            difficulty: base.difficulty + 1,
+        };
+        difficulty.update(harder);
+        assert!(difficulty.validate(&nonce, &data, solution));
+
+        difficulty.update(ResourceProofParams {
+            data_size: base.data_size * 4,
+            difficulty: base.difficulty + 2,
+        });
+        assert!(!difficulty.validate(&nonce, &data, solution));
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    command::{self, Command},
+    JoinQueueConfig,
+};
+use crate::peer::Peer;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::time::Instant;
+use xor_name::XorName;
+
+// How many times per timeout the stale entries are checked for.
+const CHECKS_PER_TIMEOUT: u32 = 4;
+
+/// Information about a node waiting to join our section.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QueuedJoin {
+    /// Name of the joining node.
+    pub name: XorName,
+    /// Address of the joining node.
+    pub addr: SocketAddr,
+    /// Age the node is joining with.
+    pub age: u8,
+    /// How long ago the node entered the queue.
+    pub queued_for: Duration,
+    /// Whether the node was already admitted, that is, its `Online` proposal was sent and the
+    /// agreement on it is pending.
+    pub admitted: bool,
+}
+
+// Outcome of adding a node to the queue.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Enqueued {
+    // The node is waiting in the queue.
+    Waiting,
+    // The node was already admitted.
+    Admitted,
+    // The queue is full.
+    Full,
+}
+
+// Queue of the new nodes that passed the resource proof and wait to be proposed online. The
+// nodes are admitted first come first served, each holding at most one place, and only a limited
+// number of them at a time. Entries that stay in the queue or remain admitted without agreement
+// for longer than the timeout are dropped so they don't block the others.
+pub(crate) struct JoinQueue {
+    config: JoinQueueConfig,
+    waiting: VecDeque<Entry>,
+    admitted: BTreeMap<XorName, Entry>,
+    timer_token: Option<u64>,
+}
+
+impl JoinQueue {
+    pub fn new(config: JoinQueueConfig) -> Self {
+        Self {
+            config,
+            waiting: VecDeque::new(),
+            admitted: BTreeMap::new(),
+            timer_token: None,
+        }
+    }
+
+    pub fn config(&self) -> JoinQueueConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: JoinQueueConfig) {
+        self.config = config;
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Schedule the next check for stale entries.
+    pub fn schedule_expiry(&mut self) -> Command {
+        let token = command::next_timer_token();
+        self.timer_token = Some(token);
+
+        Command::ScheduleTimeout {
+            duration: self.config.timeout / CHECKS_PER_TIMEOUT,
+            token,
+        }
+    }
+
+    pub fn push(&mut self, peer: Peer) -> Enqueued {
+        if self.admitted.contains_key(peer.name()) {
+            return Enqueued::Admitted;
+        }
+
+        // A repeated request keeps its place in the queue but updates the address.
+        if let Some(entry) = self
+            .waiting
+            .iter_mut()
+            .find(|entry| entry.peer.name() == peer.name())
+        {
+            entry.peer = peer;
+            return Enqueued::Waiting;
+        }
+
+        if self.waiting.len() >= self.config.capacity {
+            return Enqueued::Full;
+        }
+
+        self.waiting.push_back(Entry::new(peer));
+        Enqueued::Waiting
+    }
+
+    // How long the rejected nodes are told to wait before trying again. By then every node
+    // currently in the queue is either admitted or dropped.
+    pub fn retry_after(&self) -> Duration {
+        self.config.timeout
+    }
+
+    // Admit as many waiting nodes as there are free slots and return them.
+    pub fn admit(&mut self) -> Vec<Peer> {
+        let mut admitted = vec![];
+
+        while self.admitted.len() < self.config.max_in_flight {
+            let mut entry = if let Some(entry) = self.waiting.pop_front() {
+                entry
+            } else {
+                break;
+            };
+
+            entry.admitted = Some(Instant::now());
+            admitted.push(entry.peer);
+            let _ = self.admitted.insert(*entry.peer.name(), entry);
+        }
+
+        admitted
+    }
+
+    // Remove the node from the queue once it joined, freeing its slot.
+    pub fn complete(&mut self, name: &XorName) -> bool {
+        if self.admitted.remove(name).is_some() {
+            return true;
+        }
+
+        let len = self.waiting.len();
+        self.waiting.retain(|entry| entry.peer.name() != name);
+        self.waiting.len() != len
+    }
+
+    // Remove the nodes that waited in the queue, or for the agreement after being admitted, for
+    // longer than the timeout and return them.
+    pub fn expire(&mut self) -> Vec<Peer> {
+        let timeout = self.config.timeout;
+        let mut expired = vec![];
+
+        while self
+            .waiting
+            .front()
+            .map_or(false, |entry| entry.queued.elapsed() >= timeout)
+        {
+            if let Some(entry) = self.waiting.pop_front() {
+                expired.push(entry.peer);
+            }
+        }
+
+        let stale: Vec<_> = self
+            .admitted
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .admitted
+                    .map_or(false, |admitted| admitted.elapsed() >= timeout)
+            })
+            .map(|(name, _)| *name)
+            .collect();
+        for name in stale {
+            if let Some(entry) = self.admitted.remove(&name) {
+                expired.push(entry.peer);
+            }
+        }
+
+        expired
+    }
+
+    // Returns the admitted nodes followed by the waiting ones, in the order they will be admitted.
+    pub fn entries(&self) -> impl Iterator<Item = QueuedJoin> + '_ {
+        self.admitted
+            .values()
+            .chain(self.waiting.iter())
+            .map(Entry::info)
+    }
+}
+
+impl Default for JoinQueue {
+    fn default() -> Self {
+        Self::new(JoinQueueConfig::default())
+    }
+}
+
+struct Entry {
+    peer: Peer,
+    queued: Instant,
+    admitted: Option<Instant>,
+}
+
+impl Entry {
+    fn new(peer: Peer) -> Self {
+        Self {
+            peer,
+            queued: Instant::now(),
+            admitted: None,
+        }
+    }
+
+    fn info(&self) -> QueuedJoin {
+        QueuedJoin {
+            name: *self.peer.name(),
+            addr: *self.peer.addr(),
+            age: self.peer.age(),
+            queued_for: self.queued.elapsed(),
+            admitted: self.admitted.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto, section::test_utils::gen_addr, MIN_AGE};
+    use tokio::time;
+
+    fn gen_peer() -> Peer {
+        Peer::new(crypto::gen_name_with_age(MIN_AGE + 1), gen_addr())
+    }
+
+    #[test]
+    fn admit_in_order_up_to_max_in_flight() {
+        let mut queue = JoinQueue::new(JoinQueueConfig {
+            max_in_flight: 2,
+            ..Default::default()
+        });
+        let peers: Vec<_> = (0..3).map(|_| gen_peer()).collect();
+
+        for peer in &peers {
+            assert_eq!(queue.push(*peer), Enqueued::Waiting);
+        }
+        // A repeated request doesn't take another place.
+        assert_eq!(queue.push(peers[0]), Enqueued::Waiting);
+
+        assert_eq!(queue.admit(), &peers[..2]);
+        assert!(queue.admit().is_empty());
+        assert_eq!(queue.push(peers[0]), Enqueued::Admitted);
+
+        assert!(queue.complete(peers[1].name()));
+        assert_eq!(queue.admit(), &peers[2..]);
+
+        let admitted: Vec<_> = queue.entries().map(|entry| entry.admitted).collect();
+        assert_eq!(admitted, [true, true]);
+    }
+
+    #[test]
+    fn reject_when_full() {
+        let mut queue = JoinQueue::new(JoinQueueConfig {
+            capacity: 1,
+            ..Default::default()
+        });
+
+        assert_eq!(queue.push(gen_peer()), Enqueued::Waiting);
+        assert_eq!(queue.push(gen_peer()), Enqueued::Full);
+
+        // Completing a waiting node makes room for another one.
+        let peer = gen_peer();
+        let _ = queue.complete(queue.waiting[0].peer.name());
+        assert_eq!(queue.push(peer), Enqueued::Waiting);
+    }
+
+    #[tokio::test]
+    async fn expire_stale_entries() {
+        time::pause();
+
+        let timeout = Duration::from_secs(30);
+        let mut queue = JoinQueue::new(JoinQueueConfig {
+            max_in_flight: 1,
+            timeout,
+            ..Default::default()
+        });
+        let peer0 = gen_peer();
+        let peer1 = gen_peer();
+
+        let _ = queue.push(peer0);
+        assert_eq!(queue.admit(), [peer0]);
+
+        time::advance(timeout / 2).await;
+        let _ = queue.push(peer1);
+        assert!(queue.expire().is_empty());
+
+        // The admitted node never got agreed on, which frees its slot.
+        time::advance(timeout / 2).await;
+        assert_eq!(queue.expire(), [peer0]);
+        assert_eq!(queue.admit(), [peer1]);
+    }
+}
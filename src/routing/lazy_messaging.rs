@@ -135,6 +135,7 @@ mod tests {
     use crate::{
         agreement::test_utils::proven,
         crypto,
+        network_params::NetworkParams,
         section::{
             test_utils::{gen_addr, gen_elders_info},
             SectionChain,
@@ -171,7 +172,7 @@ mod tests {
         let their_old_pk = env.their_sk.public_key();
         let their_new_pk = bls::SecretKey::random().public_key();
         let mut proof_chain = SectionChain::new(their_old_pk);
-        proof_chain.insert(
+        let _ = proof_chain.insert(
             &their_old_pk,
             their_new_pk,
             env.their_sk.sign(&bincode::serialize(&their_new_pk)?),
@@ -211,7 +212,7 @@ mod tests {
         let our_new_sk = bls::SecretKey::random();
         let our_new_pk = our_new_sk.public_key();
         let mut proof_chain = SectionChain::new(our_old_pk);
-        proof_chain.insert(
+        let _ = proof_chain.insert(
             &our_old_pk,
             our_new_pk,
             env.our_sk.sign(&bincode::serialize(&our_new_pk)?),
@@ -312,15 +313,21 @@ mod tests {
             let prefix0 = Prefix::default().pushed(false);
             let prefix1 = Prefix::default().pushed(true);
 
-            let (chain, our_sk) =
-                create_chain(chain_len).context("failed to create section chain")?;
+            let genesis_sk = bls::SecretKey::random();
+            let (chain, our_sk) = create_chain(genesis_sk.clone(), chain_len)
+                .context("failed to create section chain")?;
 
             let (elders_info0, mut nodes) = gen_elders_info(prefix0, ELDER_SIZE);
             let node = nodes.remove(0);
 
             let elders_info0 = proven(&our_sk, elders_info0)?;
-            let section = Section::new(*chain.root_key(), chain, elders_info0)
-                .context("failed to create section")?;
+            let section = Section::new(
+                *chain.root_key(),
+                proven(&genesis_sk, NetworkParams::default())?,
+                chain,
+                elders_info0,
+            )
+            .context("failed to create section")?;
 
             let (elders_info1, _) = gen_elders_info(prefix1, ELDER_SIZE);
             let elders_info1 = proven(&our_sk, elders_info1)?;
@@ -364,8 +371,13 @@ mod tests {
         }
     }
 
-    fn create_chain(len: usize) -> Result<(SectionChain, bls::SecretKey)> {
-        let mut sk = bls::SecretKey::random();
+    // Create a chain of `len` keys starting at the key of `genesis_sk`. Returns it together with
+    // the secret key of its last key.
+    fn create_chain(
+        genesis_sk: bls::SecretKey,
+        len: usize,
+    ) -> Result<(SectionChain, bls::SecretKey)> {
+        let mut sk = genesis_sk;
         let mut chain = SectionChain::new(sk.public_key());
 
         for _ in 1..len {
@@ -375,7 +387,7 @@ mod tests {
             let new_pk = new_sk.public_key();
             let new_signature = sk.sign(&bincode::serialize(&new_pk)?);
 
-            chain.insert(&old_pk, new_pk, new_signature)?;
+            let _ = chain.insert(&old_pk, new_pk, new_signature)?;
             sk = new_sk
         }
 
@@ -0,0 +1,247 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::{self, Command};
+use crate::agreement::DkgKey;
+use lru_time_cache::LruCache;
+use std::{
+    collections::{HashMap, HashSet},
+    iter,
+    time::Duration,
+};
+use tokio::time::Instant;
+use xor_name::XorName;
+
+/// Default duration a section member can stay silent before it's proposed offline.
+pub const DEFAULT_UNRESPONSIVE_THRESHOLD: Duration = Duration::from_secs(5 * 60);
+
+// How many times per threshold period the liveness of the members is checked.
+const CHECKS_PER_THRESHOLD: u32 = 4;
+
+// Number of failed DKG sessions a member can fail to take part in before it's considered
+// unresponsive.
+const MAX_DKG_FAILURES: usize = 3;
+
+// Number of DKG sessions whose participation is tracked at once.
+const MAX_DKG_SESSIONS: usize = 16;
+
+// Tracks the activity of our section members to detect the ones that are connected but no longer
+// responsive.
+pub(crate) struct Liveness {
+    threshold: Duration,
+    peers: HashMap<XorName, PeerLiveness>,
+    // Participants we received DKG messages from, per DKG session.
+    dkg_senders: LruCache<DkgKey, HashSet<XorName>>,
+    timer_token: Option<u64>,
+}
+
+impl Liveness {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            peers: HashMap::new(),
+            dkg_senders: LruCache::with_capacity(MAX_DKG_SESSIONS),
+            timer_token: None,
+        }
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn set_threshold(&mut self, threshold: Duration) {
+        self.threshold = threshold;
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Schedule the next periodic liveness check.
+    pub fn schedule_check(&mut self) -> Command {
+        let token = command::next_timer_token();
+        self.timer_token = Some(token);
+
+        Command::ScheduleTimeout {
+            duration: self.check_interval(),
+            token,
+        }
+    }
+
+    // Start tracking the given members (if not already) and stop tracking everyone else.
+    pub fn retain<'a>(&mut self, members: impl IntoIterator<Item = &'a XorName>) {
+        let members: HashSet<_> = members.into_iter().collect();
+        self.peers.retain(|name, _| members.contains(name));
+
+        let now = Instant::now();
+        for name in members {
+            let _ = self
+                .peers
+                .entry(*name)
+                .or_insert_with(|| PeerLiveness::new(now));
+        }
+    }
+
+    // Record that we heard from the given peer.
+    pub fn record_activity(&mut self, name: &XorName) {
+        if let Some(peer) = self.peers.get_mut(name) {
+            peer.last_active = Instant::now();
+        }
+    }
+
+    // Record that we received a message of the DKG session `dkg_key` from the given peer.
+    pub fn record_dkg_message(&mut self, dkg_key: DkgKey, name: XorName) {
+        if let Some(senders) = self.dkg_senders.get_mut(&dkg_key) {
+            let _ = senders.insert(name);
+        } else {
+            let _ = self.dkg_senders.insert(dkg_key, iter::once(name).collect());
+        }
+    }
+
+    // Record that the DKG session `dkg_key` failed. Only the given participants we received no
+    // message of the session from are blamed for it.
+    pub fn record_dkg_failure<'a>(
+        &mut self,
+        dkg_key: DkgKey,
+        participants: impl IntoIterator<Item = &'a XorName>,
+    ) {
+        let senders = self.dkg_senders.remove(&dkg_key).unwrap_or_default();
+        for name in participants {
+            if senders.contains(name) {
+                continue;
+            }
+
+            if let Some(peer) = self.peers.get_mut(name) {
+                let _ = peer.dkg_failures.insert(dkg_key);
+            }
+        }
+    }
+
+    // Record that the given peers successfully completed a DKG session.
+    pub fn record_dkg_success<'a>(&mut self, names: impl IntoIterator<Item = &'a XorName>) {
+        for name in names {
+            if let Some(peer) = self.peers.get_mut(name) {
+                peer.dkg_failures.clear();
+            }
+        }
+    }
+
+    // Returns the peers that haven't been heard from for a whole check interval and should be
+    // probed.
+    pub fn stale(&self) -> impl Iterator<Item = &XorName> {
+        let deadline = self.check_interval();
+        self.peers
+            .iter()
+            .filter(move |(_, peer)| peer.last_active.elapsed() >= deadline)
+            .map(|(name, _)| name)
+    }
+
+    // Returns the peers that stayed unresponsive past the threshold.
+    pub fn unresponsive(&self) -> impl Iterator<Item = &XorName> {
+        let threshold = self.threshold;
+        self.peers
+            .iter()
+            .filter(move |(_, peer)| {
+                peer.last_active.elapsed() >= threshold
+                    || peer.dkg_failures.len() >= MAX_DKG_FAILURES
+            })
+            .map(|(name, _)| name)
+    }
+
+    fn check_interval(&self) -> Duration {
+        check_interval(self.threshold)
+    }
+}
+
+// Whether the threshold is long enough to be checked periodically.
+pub(crate) fn is_valid_threshold(threshold: Duration) -> bool {
+    check_interval(threshold) > Duration::ZERO
+}
+
+fn check_interval(threshold: Duration) -> Duration {
+    threshold / CHECKS_PER_THRESHOLD
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNRESPONSIVE_THRESHOLD)
+    }
+}
+
+struct PeerLiveness {
+    last_active: Instant,
+    dkg_failures: HashSet<DkgKey>,
+}
+
+impl PeerLiveness {
+    fn new(now: Instant) -> Self {
+        Self {
+            last_active: now,
+            dkg_failures: HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::section::test_utils::gen_elders_info;
+    use tokio::time;
+    use xor_name::Prefix;
+
+    const THRESHOLD: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn silent_peer_becomes_unresponsive() {
+        time::pause();
+
+        let active: XorName = rand::random();
+        let silent: XorName = rand::random();
+
+        let mut liveness = Liveness::new(THRESHOLD);
+        liveness.retain(&[active, silent]);
+
+        time::advance(THRESHOLD / 2).await;
+        liveness.record_activity(&active);
+        assert_eq!(liveness.stale().collect::<Vec<_>>(), [&silent]);
+        assert_eq!(liveness.unresponsive().count(), 0);
+
+        time::advance(THRESHOLD / 2).await;
+        assert_eq!(liveness.unresponsive().collect::<Vec<_>>(), [&silent]);
+
+        // Peers that are no longer members are not tracked.
+        liveness.retain(&[active]);
+        assert_eq!(liveness.unresponsive().count(), 0);
+    }
+
+    #[test]
+    fn repeated_dkg_failures_make_peer_unresponsive() {
+        let (elders_info, _) = gen_elders_info(Prefix::default(), 3);
+        let name: XorName = rand::random();
+        let other: XorName = rand::random();
+
+        let mut liveness = Liveness::new(THRESHOLD);
+        liveness.retain(&[name, other]);
+
+        for generation in 0..MAX_DKG_FAILURES as u64 {
+            assert_eq!(liveness.unresponsive().count(), 0);
+
+            // Recording the same failure twice counts only once. The participant which sent us a
+            // message of the session is not blamed.
+            let dkg_key = DkgKey::new(&elders_info, generation);
+            liveness.record_dkg_message(dkg_key, other);
+            liveness.record_dkg_failure(dkg_key, &[name, other]);
+            liveness.record_dkg_failure(dkg_key, &[name]);
+        }
+
+        assert_eq!(liveness.unresponsive().collect::<Vec<_>>(), [&name]);
+
+        liveness.record_dkg_success(&[name]);
+        assert_eq!(liveness.unresponsive().count(), 0);
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::{self, Command};
+use crate::section::Section;
+use std::time::Duration;
+
+// How long to wait for the merged section to be in place before giving up on the merge.
+const MERGE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+// Helper structure to keep track of a merge of our section with its sibling. Holds the latest known
+// state of the sibling section from the moment the merge is requested until the merged section is
+// in place or the merge times out.
+pub(crate) struct MergeBarrier {
+    sibling: Option<Section>,
+    // State of our section as we last sent it to the sibling. The merge is computed from it rather
+    // than from our current state, which the sibling might not know yet.
+    ours: Option<Section>,
+    timer_token: Option<u64>,
+}
+
+impl MergeBarrier {
+    pub fn new() -> Self {
+        Self {
+            sibling: None,
+            ours: None,
+            timer_token: None,
+        }
+    }
+
+    // Record the state of our section we are sending to the sibling. Our state only ever grows, so
+    // the latest one sent is the one the sibling ends up with.
+    pub fn record_ours(&mut self, section: &Section) {
+        self.ours = Some(section.clone());
+    }
+
+    pub fn ours(&self) -> Option<&Section> {
+        self.ours.as_ref()
+    }
+
+    // Record the state of the sibling section we are merging with. The sibling is expected to be
+    // already verified. Returns whether we learned anything new about it and, if this starts the
+    // merge, the command to schedule its timeout.
+    pub fn update(&mut self, sibling: Section) -> (bool, Option<Command>) {
+        let current = if let Some(current) = &mut self.sibling {
+            current
+        } else {
+            self.sibling = Some(sibling);
+
+            let token = command::next_timer_token();
+            self.timer_token = Some(token);
+
+            return (
+                true,
+                Some(Command::ScheduleTimeout {
+                    duration: MERGE_TIMEOUT,
+                    token,
+                }),
+            );
+        };
+
+        let old = current.clone();
+        if let Err(error) = current.merge(sibling) {
+            trace!("Ignore sibling section update: {}", error);
+            return (false, None);
+        }
+
+        (*current != old, None)
+    }
+
+    pub fn sibling(&self) -> Option<&Section> {
+        self.sibling.as_ref()
+    }
+
+    pub fn is_merging(&self) -> bool {
+        self.sibling.is_some()
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Abort the merge that didn't complete in time, returning the last known state of the sibling.
+    pub fn handle_timeout(&mut self, token: u64) -> Option<Section> {
+        if !self.is_timer(token) {
+            return None;
+        }
+
+        self.complete()
+    }
+
+    // Finish the merge, returning the last known state of the sibling.
+    pub fn complete(&mut self) -> Option<Section> {
+        self.timer_token = None;
+        self.ours = None;
+        self.sibling.take()
+    }
+}
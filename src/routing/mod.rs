@@ -11,41 +11,76 @@ pub(crate) mod command;
 mod bootstrap;
 mod comm;
 mod core;
+mod delivery;
 mod dispatcher;
 mod enduser_registry;
 mod event_stream;
+mod gossip;
+mod join_difficulty;
+mod join_queue;
 mod lazy_messaging;
+mod liveness;
+mod merge_barrier;
+mod pending;
+mod rate_limit;
+mod request;
+mod scheduler;
+mod section_signing;
+#[cfg(feature = "simulated-network")]
+mod sim_network;
 mod split_barrier;
+mod state_store;
 #[cfg(test)]
 mod tests;
 
-pub use self::event_stream::EventStream;
+#[cfg(feature = "simulated-network")]
+pub use self::sim_network::SimNetwork;
+pub use self::{
+    bootstrap::BootstrapPhase,
+    delivery::Delivery,
+    enduser_registry::ClientInfo,
+    event_stream::{EventFilter, EventStream, LagPolicy, Subscription},
+    join_queue::QueuedJoin,
+    request::Response,
+    section_signing::{SectionPayload, SectionSigningApproval},
+    state_store::{FileStateStore, StateStore},
+};
 use self::{
     comm::{Comm, ConnectionEvent},
     command::Command,
     core::Core,
     dispatcher::Dispatcher,
+    event_stream::EventSender,
+    liveness::DEFAULT_UNRESPONSIVE_THRESHOLD,
+    rate_limit::RateLimiter,
+    scheduler::Priority,
+    state_store::StoredState,
 };
 use crate::{
+    agreement::{DkgKey, DkgSessionInfo, Proven},
     crypto,
-    error::Result,
+    error::{Error, Result},
     event::{Event, NodeElderChange},
-    messages::Message,
+    messages::{Message, MessageHash},
+    metrics::Metrics,
+    network_params::NetworkParams,
     node::Node,
     peer::Peer,
+    protocol::ProtocolInfo,
     section::{EldersInfo, SectionChain},
-    TransportConfig, MIN_AGE,
+    TransportConfig,
 };
 use bytes::Bytes;
 use ed25519_dalek::{Keypair, PublicKey, Signature, Signer};
 use itertools::Itertools;
+use serde::Serialize;
 use sn_messaging::{
     client::Message as ClientMessage,
     node::NodeMessage,
     section_info::{Error as TargetSectionError, ErrorResponse, Message as SectionInfoMsg},
-    DstLocation, EndUser, Itinerary, MessageType, WireMsg,
+    DstLocation, EndUser, Itinerary, MessageType, SrcLocation, WireMsg,
 };
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::{sync::mpsc, task};
 use xor_name::{Prefix, XorName};
 
@@ -56,8 +91,45 @@ pub struct Config {
     pub first: bool,
     /// The `Keypair` of the node or `None` for randomly generated one.
     pub keypair: Option<Keypair>,
+    /// Parameters of the network. When starting a new network, they are signed with its genesis key
+    /// and fixed for its whole lifetime. When joining an existing network, they must match the
+    /// ones it was started with.
+    pub network_params: NetworkParams,
     /// Configuration for the underlying network transport.
     pub transport_config: TransportConfig,
+    /// Store to persist the node state into. If it contains a previously persisted state, the node
+    /// is restored from it (rejoining its section with its previous name and age) and `first`,
+    /// `keypair` and `network_params` are ignored. A state that can't be decoded, for example
+    /// because it was persisted by a version with an incompatible format, is discarded.
+    pub state_store: Option<Arc<dyn StateStore>>,
+    /// If set, the node communicates over this in-process simulated network instead of the real
+    /// transport. Only `local_ip`, `local_port` and `hard_coded_contacts` of `transport_config`
+    /// are used in that case. Requires the `simulated-network` feature.
+    #[cfg(feature = "simulated-network")]
+    pub simulated_network: Option<SimNetwork>,
+    /// How long a section member can stay unresponsive (not sending any messages, not answering
+    /// liveness checks) before the elders propose it offline. Defaults to 5 minutes.
+    pub unresponsive_threshold: Duration,
+    /// Capacities of the internal queues and what happens when they fill up.
+    pub queues: QueueConfig,
+    /// How the elders gossip with other sections to keep their view of the network up to date.
+    pub gossip: GossipConfig,
+    /// Limits on the clients connected to the node.
+    pub clients: ClientConfig,
+    /// Limits on the incoming client traffic.
+    pub rate_limits: RateLimitConfig,
+    /// How the elders admit new nodes into their section.
+    pub join_queue: JoinQueueConfig,
+    /// Timeouts and retries of the bootstrapping into the network.
+    pub bootstrap: BootstrapConfig,
+    /// Protocol versions and features the node advertises to the other nodes. Defaults to the ones
+    /// implemented by this build. Lowering `max_version` keeps the node from voting for a protocol
+    /// upgrade of its section, for example during a rolling upgrade of the network.
+    pub protocol: ProtocolInfo,
+    /// Decides which payloads proposed by the other elders for signing by the section (see
+    /// `sign_as_section`) this node co-signs. Without it (the default), the node co-signs no
+    /// payload proposed by other elders.
+    pub section_signing_approval: Option<SectionSigningApproval>,
 }
 
 impl Default for Config {
@@ -65,11 +137,218 @@ impl Default for Config {
         Self {
             first: false,
             keypair: None,
+            network_params: NetworkParams::default(),
             transport_config: TransportConfig::default(),
+            state_store: None,
+            #[cfg(feature = "simulated-network")]
+            simulated_network: None,
+            unresponsive_threshold: DEFAULT_UNRESPONSIVE_THRESHOLD,
+            queues: QueueConfig::default(),
+            gossip: GossipConfig::default(),
+            clients: ClientConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            join_queue: JoinQueueConfig::default(),
+            bootstrap: BootstrapConfig::default(),
+            protocol: ProtocolInfo::default(),
+            section_signing_approval: None,
+        }
+    }
+}
+
+/// Capacities of the internal queues of a node and what happens when they fill up.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QueueConfig {
+    /// Maximum number of events waiting to be taken from the `EventStream`. Defaults to 1024.
+    pub event_capacity: usize,
+    /// Maximum number of commands - produced by incoming messages or by handling other commands -
+    /// being handled at the same time. Scheduled timers don't count until they fire. Defaults to
+    /// 256.
+    pub incoming_capacity: usize,
+    /// What happens when a queue is full. Defaults to `OverflowPolicy::ShedLowPriority`.
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            event_capacity: 1024,
+            incoming_capacity: 256,
+            overflow_policy: OverflowPolicy::ShedLowPriority,
+        }
+    }
+}
+
+/// Parameters of the periodic gossip through which the elders exchange their section's elders and
+/// key with other sections, so that their view of the network converges even without any traffic
+/// between them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GossipConfig {
+    /// How often each elder gossips. Defaults to 1 minute.
+    pub interval: Duration,
+    /// Maximum number of sections each elder gossips with per round. Sections whose prefix
+    /// neighbours ours are preferred. Defaults to 3.
+    pub fanout: usize,
+    /// Maximum number of section keys included in a single gossip message. Defaults to 16.
+    pub max_keys: usize,
+    /// Maximum number of sections remembered as having a different view of the network than us,
+    /// to be gossiped with first. Defaults to 64.
+    pub max_suspects: usize,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            fanout: 3,
+            max_keys: 16,
+            max_suspects: 64,
         }
     }
 }
 
+/// Limits on the clients connected to a node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ClientConfig {
+    /// Maximum number of connected clients. Further registrations are rejected. Defaults to 10000.
+    pub max_clients: usize,
+    /// Maximum number of connections of a single client (identified by its public key). Defaults
+    /// to 8.
+    pub max_connections_per_client: usize,
+    /// How long a client can stay silent before it's disconnected. Defaults to 10 minutes.
+    pub idle_timeout: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            max_clients: 10_000,
+            max_connections_per_client: 8,
+            idle_timeout: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// Limits on the incoming client traffic. Messages exceeding them are dropped.
+///
+/// The quotas are enforced with token buckets holding one second worth of the allowance, so they
+/// also cap the bursts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimitConfig {
+    /// Maximum number of messages per second from a single address. Defaults to 100.
+    pub messages_per_addr: u32,
+    /// Maximum number of bytes per second from a single address. Defaults to 4 MiB.
+    pub bytes_per_addr: u64,
+    /// Maximum number of messages per second from a single client, over all its connections.
+    /// Defaults to 200.
+    pub messages_per_end_user: u32,
+    /// Maximum number of bytes per second from a single client, over all its connections.
+    /// Defaults to 8 MiB.
+    pub bytes_per_end_user: u64,
+    /// Number of malformed messages after which the client sending them gets banned. Malformed
+    /// messages more than `ban_duration` apart don't add up. Defaults to 3.
+    pub malformed_limit: u32,
+    /// How long a banned peer is ignored for. Defaults to 10 minutes.
+    pub ban_duration: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            messages_per_addr: 100,
+            bytes_per_addr: 4 * 1024 * 1024,
+            messages_per_end_user: 200,
+            bytes_per_end_user: 8 * 1024 * 1024,
+            malformed_limit: 3,
+            ban_duration: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// How the elders admit new nodes into their section.
+///
+/// New nodes which passed the resource proof wait in a queue and are admitted first come first
+/// served, only a few at a time. Nodes that don't fit into the queue are rejected and told when to
+/// retry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct JoinQueueConfig {
+    /// Maximum number of nodes waiting to be admitted. Defaults to 64.
+    pub capacity: usize,
+    /// Maximum number of admitted nodes whose joining is not yet agreed on. Defaults to 4.
+    pub max_in_flight: usize,
+    /// How long a node can wait in the queue, or for the agreement after being admitted, before
+    /// it's dropped. Also used as the retry hint for the rejected nodes. Defaults to 1 minute.
+    pub timeout: Duration,
+}
+
+impl Default for JoinQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            max_in_flight: 4,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Timeouts and retries of the bootstrapping into the network, used when joining it for the first
+/// time, when rejoining it after a restart and when relocating.
+///
+/// An attempt fails with `Error::BootstrapTimeout` as soon as one of its phases times out. Failed
+/// attempts are retried with exponential backoff, contacting all of the `hard_coded_contacts`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BootstrapConfig {
+    /// How long to wait for the response to the query for the section to join. Defaults to 30
+    /// seconds.
+    pub get_section_timeout: Duration,
+    /// How long to wait for a response to the request to join the section. Rejections by fewer
+    /// than a supermajority of the elders don't end the wait. Defaults to 2 minutes.
+    pub join_timeout: Duration,
+    /// How long solving a single resource proof challenge can take. Defaults to 2 minutes.
+    pub resource_proof_timeout: Duration,
+    /// Maximum number of attempts, including the first one. The retries of the join requests the
+    /// elders rejected for now count as attempts too. Defaults to 5.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry. Doubles with every further retry. Defaults to 1
+    /// second.
+    pub initial_backoff: Duration,
+    /// Maximum time to wait before a retry. Defaults to 30 seconds.
+    pub max_backoff: Duration,
+    /// Maximum time to wait before retrying a join request the elders rejected for now. Longer
+    /// waits asked for by the elders are cut to this. Defaults to 2 minutes.
+    pub max_retry_after: Duration,
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            get_section_timeout: Duration::from_secs(30),
+            join_timeout: Duration::from_secs(2 * 60),
+            resource_proof_timeout: Duration::from_secs(2 * 60),
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(2 * 60),
+        }
+    }
+}
+
+/// What happens to an event or an incoming message which doesn't fit into its queue. Commands
+/// produced while handling other commands always wait until there is room for them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Drop it. The dropped items are counted in `Metrics::events_dropped` and
+    /// `Metrics::incoming_dropped`.
+    DropNewest,
+    /// Wait until there is room for it. When the application doesn't take the events fast enough,
+    /// the node eventually stops reading incoming messages until it catches up.
+    Block,
+    /// Drop it if it's low priority - user messages (`Event::MessageReceived`,
+    /// `Event::RequestReceived`, `Event::ClientMessageReceived` and the corresponding incoming
+    /// messages), delivery receipts and section info queries - otherwise wait until there is room
+    /// for it.
+    ShedLowPriority,
+}
+
 /// Interface for sending and receiving messages to and from other nodes, in the role of a full
 /// routing node.
 ///
@@ -86,25 +365,75 @@ impl Routing {
     // Public API
     ////////////////////////////////////////////////////////////////////////////
 
-    /// Creates new node using the given config and bootstraps it to the network.
+    /// Creates new node using the given config and bootstraps it to the network. If the config
+    /// contains a state store with a previously persisted state, the node is restored from it
+    /// instead.
     ///
-    /// NOTE: It's not guaranteed this function ever returns. This can happen due to messages being
-    /// lost in transit during bootstrapping, or other reasons. It's the responsibility of the
-    /// caller to handle this case, for example by using a timeout.
+    /// Fails with `Error::JoinRejected` if a supermajority of the section elders refuse the node
+    /// for a reason that retrying won't fix, for example because its age doesn't match what the
+    /// section expects, and with
+    /// `Error::BootstrapTimeout` if none of the attempts configured in `Config::bootstrap`
+    /// succeeds.
     pub async fn new(config: Config) -> Result<(Self, EventStream)> {
+        config.network_params.validate()?;
+        if !liveness::is_valid_threshold(config.unresponsive_threshold) {
+            return Err(Error::InvalidConfig("unresponsive_threshold is too short"));
+        }
+        if !enduser_registry::is_valid_idle_timeout(config.clients.idle_timeout) {
+            return Err(Error::InvalidConfig("clients.idle_timeout is too short"));
+        }
+        if config.gossip.interval == Duration::ZERO {
+            return Err(Error::InvalidConfig("gossip.interval is zero"));
+        }
+
+        let comm_config = CommConfig {
+            transport: config.transport_config,
+            #[cfg(feature = "simulated-network")]
+            simulated_network: config.simulated_network,
+        };
+
         let keypair = config.keypair.unwrap_or_else(|| {
-            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1)
+            crypto::gen_keypair(
+                &Prefix::default().range_inclusive(),
+                config.network_params.min_age + 1,
+            )
         });
         let node_name = crypto::name(&keypair.public);
 
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let stored_state = if let Some(state_store) = &config.state_store {
+            state_store
+                .load()?
+                .and_then(|bytes| match StoredState::from_bytes(&bytes) {
+                    Ok(state) => Some(state),
+                    Err(error) => {
+                        warn!("Discarding stored state that can't be decoded: {}", error);
+                        None
+                    }
+                })
+        } else {
+            None
+        };
+
+        let (event_tx, event_stream) = event_stream::event_channel(&config.queues);
         let (connection_event_tx, mut connection_event_rx) = mpsc::channel(1);
 
-        let (state, comm, backlog) = if config.first {
+        let (mut state, comm, backlog) = if let Some(stored_state) = stored_state {
+            restore(
+                stored_state,
+                comm_config,
+                config.bootstrap,
+                config.protocol,
+                connection_event_tx,
+                &mut connection_event_rx,
+                event_tx,
+            )
+            .await?
+        } else if config.first {
             info!("{} Starting a new network as the genesis node.", node_name);
-            let comm = Comm::new(config.transport_config, connection_event_tx).await?;
-            let node = Node::new(keypair, comm.our_connection_info());
-            let state = Core::first_node(node, event_tx)?;
+            let comm = create_comm(comm_config, connection_event_tx).await?;
+            let mut node = Node::new(keypair, comm.our_connection_info());
+            node.protocol = config.protocol;
+            let state = Core::first_node(node, config.network_params, event_tx)?;
 
             let section = state.section();
 
@@ -119,18 +448,49 @@ impl Routing {
             (state, comm, vec![])
         } else {
             info!("{} Bootstrapping a new node.", node_name);
-            let (comm, bootstrap_addr) =
-                Comm::bootstrap(config.transport_config, connection_event_tx).await?;
-            let node = Node::new(keypair, comm.our_connection_info());
-            let (node, section, backlog) =
-                bootstrap::initial(node, &comm, &mut connection_event_rx, bootstrap_addr).await?;
+            let contacts = comm_config
+                .transport
+                .hard_coded_contacts
+                .iter()
+                .copied()
+                .collect();
+            let (comm, bootstrap_addr) = bootstrap_comm(comm_config, connection_event_tx).await?;
+            let mut node = Node::new(keypair, comm.our_connection_info());
+            node.protocol = config.protocol;
+            let (node, section, backlog) = bootstrap::initial(
+                node,
+                &comm,
+                &mut connection_event_rx,
+                bootstrap_addr,
+                contacts,
+                config.network_params,
+                config.bootstrap,
+            )
+            .await?;
             let state = Core::new(node, section, None, event_tx);
 
             (state, comm, backlog)
         };
 
-        let dispatcher = Arc::new(Dispatcher::new(state, comm));
-        let event_stream = EventStream::new(event_rx);
+        if let Some(state_store) = config.state_store {
+            state.set_state_store(state_store);
+        }
+
+        state.set_bootstrap_config(config.bootstrap);
+        state.set_unresponsive_threshold(config.unresponsive_threshold);
+        let liveness_command = state.start_liveness_checks();
+        state.set_gossip_config(config.gossip);
+        let gossip_command = state.start_gossip();
+        state.set_client_config(config.clients);
+        let client_expiry_command = state.start_client_expiry();
+        let join_difficulty_command = state.start_join_difficulty_adjustments();
+        state.set_join_queue_config(config.join_queue);
+        let join_queue_command = state.start_join_queue_expiry();
+        state.set_section_signing_approval(config.section_signing_approval);
+
+        let mut dispatcher = Dispatcher::new(state, comm);
+        dispatcher.set_queue_config(&config.queues);
+        let dispatcher = Arc::new(dispatcher);
 
         // Process message backlog
         for (message, sender) in backlog {
@@ -143,10 +503,27 @@ impl Routing {
                 .await?;
         }
 
+        // Start the periodic liveness checks.
+        let _ = task::spawn(dispatcher.clone().handle_commands(liveness_command));
+
+        // Start the periodic gossip with other sections.
+        let _ = task::spawn(dispatcher.clone().handle_commands(gossip_command));
+
+        // Start the periodic expiry of idle clients.
+        let _ = task::spawn(dispatcher.clone().handle_commands(client_expiry_command));
+
+        // Start the periodic adjustments of the resource proof for the joining nodes.
+        let _ = task::spawn(dispatcher.clone().handle_commands(join_difficulty_command));
+
+        // Start the periodic expiry of stale entries in the join queue.
+        let _ = task::spawn(dispatcher.clone().handle_commands(join_queue_command));
+
         // Start listening to incoming connections.
         let _ = task::spawn(handle_connection_events(
             dispatcher.clone(),
             connection_event_rx,
+            RateLimiter::new(config.rate_limits),
+            config.protocol,
         ));
 
         let routing = Self { dispatcher };
@@ -160,19 +537,69 @@ impl Routing {
         self.dispatcher.clone().handle_commands(command).await
     }
 
+    /// Returns the parameters of the network this node is part of.
+    pub async fn network_params(&self) -> NetworkParams {
+        *self
+            .dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .section()
+            .network_params()
+    }
+
+    /// Returns the metrics of this node: message and failure counters, latency histograms and the
+    /// current section and network sizes.
+    pub async fn metrics(&self) -> Metrics {
+        Metrics {
+            commands_in_flight: self.dispatcher.commands_in_flight(),
+            ..self.dispatcher.lock_core(Priority::Normal).await.metrics()
+        }
+    }
+
+    /// Subscribes to the events passing `filter`.
+    ///
+    /// The subscription receives its events independently of the `EventStream` returned from
+    /// `new` and of the other subscriptions. It buffers up to `capacity` events, what happens to
+    /// the events that don't fit is decided by `lag_policy`. Only the events raised after the
+    /// subscription was created are received.
+    pub fn subscribe(
+        &self,
+        filter: EventFilter,
+        capacity: usize,
+        lag_policy: LagPolicy,
+    ) -> Subscription {
+        self.dispatcher
+            .event_subscribers
+            .subscribe(filter, capacity, lag_policy)
+    }
+
     /// Returns the current age of this node.
     pub async fn age(&self) -> u8 {
-        self.dispatcher.core.lock().await.node().age()
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .node()
+            .age()
     }
 
     /// Returns the ed25519 public key of this node.
     pub async fn public_key(&self) -> PublicKey {
-        self.dispatcher.core.lock().await.node().keypair.public
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .node()
+            .keypair
+            .public
     }
 
     /// Signs `data` with the ed25519 key of this node.
     pub async fn sign_as_node(&self, data: &[u8]) -> Signature {
-        self.dispatcher.core.lock().await.node().keypair.sign(data)
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .node()
+            .keypair
+            .sign(data)
     }
 
     /// Signs `data` with the BLS secret key share of this node, if it has any. Returns
@@ -183,8 +610,7 @@ impl Routing {
         public_key: &bls::PublicKey,
     ) -> Result<bls::SignatureShare> {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .sign_with_section_key_share(data, public_key)
     }
@@ -192,8 +618,7 @@ impl Routing {
     /// Verifies `signature` on `data` with the ed25519 public key of this node.
     pub async fn verify(&self, data: &[u8], signature: &Signature) -> bool {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .node()
             .keypair
@@ -203,7 +628,11 @@ impl Routing {
 
     /// The name of this node.
     pub async fn name(&self) -> XorName {
-        self.dispatcher.core.lock().await.node().name()
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .node()
+            .name()
     }
 
     /// Returns connection info of this node.
@@ -213,12 +642,21 @@ impl Routing {
 
     /// Returns the Section Proof Chain
     pub async fn section_chain(&self) -> SectionChain {
-        self.dispatcher.core.lock().await.section_chain().clone()
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .section_chain()
+            .clone()
     }
 
     /// Prefix of our section
     pub async fn our_prefix(&self) -> Prefix {
-        *self.dispatcher.core.lock().await.section().prefix()
+        *self
+            .dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .section()
+            .prefix()
     }
 
     /// Finds out if the given XorName matches our prefix.
@@ -226,16 +664,32 @@ impl Routing {
         self.our_prefix().await.matches(name)
     }
 
+    /// Returns the version of the routing protocol our section runs.
+    pub async fn protocol_version(&self) -> u16 {
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .section()
+            .protocol_version()
+    }
+
+    /// Returns the protocol versions and features this node advertises.
+    pub async fn protocol_info(&self) -> ProtocolInfo {
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .protocol_info()
+    }
+
     /// Returns whether the node is Elder.
     pub async fn is_elder(&self) -> bool {
-        self.dispatcher.core.lock().await.is_elder()
+        self.dispatcher.lock_core(Priority::Normal).await.is_elder()
     }
 
     /// Returns the information of all the current section elders.
     pub async fn our_elders(&self) -> Vec<Peer> {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .section()
             .elders_info()
@@ -256,8 +710,7 @@ impl Routing {
     /// Returns the information of all the current section adults.
     pub async fn our_adults(&self) -> Vec<Peer> {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .section()
             .adults()
@@ -278,8 +731,7 @@ impl Routing {
     /// Returns the info about our section or `None` if we are not joined yet.
     pub async fn our_section(&self) -> EldersInfo {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .section()
             .elders_info()
@@ -289,8 +741,7 @@ impl Routing {
     /// Returns the info about other sections in the network known to us.
     pub async fn other_sections(&self) -> Vec<EldersInfo> {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .network()
             .all()
@@ -301,8 +752,7 @@ impl Routing {
     /// Returns the last known public key of the section with `prefix`.
     pub async fn section_key(&self, prefix: &Prefix) -> Option<bls::PublicKey> {
         self.dispatcher
-            .core
-            .lock()
+            .lock_core(Priority::Normal)
             .await
             .section_key(prefix)
             .copied()
@@ -313,7 +763,7 @@ impl Routing {
         &self,
         name: &XorName,
     ) -> (Option<bls::PublicKey>, Option<EldersInfo>) {
-        let state = self.dispatcher.core.lock().await;
+        let state = self.dispatcher.lock_core(Priority::Normal).await;
         let (key, elders_info) = state.matching_section(name);
         (key.copied(), elders_info.cloned())
     }
@@ -338,8 +788,7 @@ impl Routing {
         {
             let socket_addr = self
                 .dispatcher
-                .core
-                .lock()
+                .lock_core(Priority::Low)
                 .await
                 .get_socket_addr(socket_id)
                 .copied();
@@ -364,6 +813,213 @@ impl Routing {
         self.dispatcher.clone().handle_commands(command).await
     }
 
+    /// Send a message and ask its destination to confirm the delivery.
+    ///
+    /// Works like `send_message`, except the destination node or section returns a signed receipt
+    /// once it receives the message. The returned `Delivery` resolves when the receipt arrives.
+    /// Until then the message is periodically resent to alternate delivery targets and if the
+    /// delivery is still not confirmed after a few attempts, the `Delivery` resolves to
+    /// `Error::DeliveryFailed`. Only `DstLocation::Node` and `DstLocation::Section` destinations
+    /// are supported. Sending the same content from our section to the same destination again
+    /// before the previous delivery is confirmed fails with `Error::DeliveryPending`, because all
+    /// the elders must identify the message the same way.
+    pub async fn send_message_with_ack(
+        &self,
+        itinerary: Itinerary,
+        content: Bytes,
+        additional_proof_chain_key: Option<bls::PublicKey>,
+    ) -> Result<Delivery> {
+        let (delivery, commands) = self
+            .dispatcher
+            .lock_core(Priority::Low)
+            .await
+            .send_user_message_with_ack(itinerary, content, additional_proof_chain_key.as_ref())?;
+
+        self.spawn_commands(commands);
+
+        Ok(delivery)
+    }
+
+    /// Send a request and wait for the response to it.
+    ///
+    /// The request travels along `itinerary` like a message sent with `send_message` and is
+    /// raised at the destination as `Event::RequestReceived`, which the destination answers with
+    /// `send_response`. The response is routed back to the source of the request - if that is a
+    /// section, every elder which sent the request receives it. The returned `Response` resolves
+    /// to the content of the response, or to `Error::RequestTimeout` if it doesn't arrive within
+    /// `timeout`. Only `DstLocation::Node` and `DstLocation::Section` destinations are supported.
+    pub async fn send_request(
+        &self,
+        itinerary: Itinerary,
+        content: Bytes,
+        timeout: Duration,
+        additional_proof_chain_key: Option<bls::PublicKey>,
+    ) -> Result<Response> {
+        let (response, commands) = self
+            .dispatcher
+            .lock_core(Priority::Low)
+            .await
+            .send_user_request(
+                itinerary,
+                content,
+                timeout,
+                additional_proof_chain_key.as_ref(),
+            )?;
+
+        self.spawn_commands(commands);
+
+        Ok(response)
+    }
+
+    /// Respond to a request received in `Event::RequestReceived`. `id`, `src` and `dst` are the
+    /// ones from the event. If the request was sent to our section, the response is signed by the
+    /// section, so every elder is expected to respond with the same content.
+    pub async fn send_response(
+        &self,
+        id: MessageHash,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+    ) -> Result<()> {
+        let commands = self
+            .dispatcher
+            .lock_core(Priority::Low)
+            .await
+            .send_user_response(id, src, dst, content)?;
+        for command in commands {
+            self.dispatcher.clone().handle_commands(command).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stop waiting for the response to the request `id`, resolving its `Response` to
+    /// `Error::RequestCancelled`. Returns whether such request was pending.
+    pub async fn cancel_request(&self, id: &MessageHash) -> bool {
+        self.dispatcher
+            .lock_core(Priority::Low)
+            .await
+            .cancel_user_request(id)
+    }
+
+    /// Have our section sign `value`. Only an elder can do this: the value is proposed to the
+    /// other elders who add their signature shares to it and once enough of them are aggregated,
+    /// the value is returned together with the proof of the section signature and our section
+    /// chain, which proves the signing key to anyone trusting an older key of our section.
+    /// Returns `Error::SectionSigningTimeout` if the signature isn't aggregated within `timeout`
+    /// and `Error::Relocated` if this node gets relocated before that.
+    ///
+    /// The value is signed wrapped in `SectionPayload`, so the signature can only ever be
+    /// verified as one on an application payload.
+    ///
+    /// The other elders only co-sign values their `Config::section_signing_approval` approves.
+    /// Returns `Error::FeatureNotSupported` unless all our elders advertise
+    /// `Features::SECTION_SIGNING`.
+    pub async fn sign_as_section<T: Serialize>(
+        &self,
+        value: T,
+        timeout: Duration,
+    ) -> Result<(Proven<SectionPayload<T>>, SectionChain)> {
+        let value = SectionPayload(value);
+        let payload = Bytes::from(bincode::serialize(&value)?);
+        let (rx, commands) = self
+            .dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .sign_as_section(payload, timeout)?;
+
+        self.spawn_commands(commands);
+
+        let (proof, section_chain) = rx.await.unwrap_or(Err(Error::InvalidState))?;
+        Ok((Proven::new(value, proof), section_chain))
+    }
+
+    /// Returns the clients currently connected to this node.
+    pub async fn clients(&self) -> Vec<ClientInfo> {
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .clients()
+            .collect()
+    }
+
+    /// Returns the client connected from `addr`, if any.
+    pub async fn client_info(&self, addr: &SocketAddr) -> Option<ClientInfo> {
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .client_info(addr)
+    }
+
+    /// Disconnect the client connected from `addr` and remove it from the registry, raising
+    /// `Event::ClientLost`. Returns whether such client was connected.
+    pub async fn disconnect_client(&self, addr: SocketAddr) -> Result<bool> {
+        if !self
+            .dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .remove_client(&addr)
+        {
+            return Ok(false);
+        }
+
+        let command = Command::DisconnectClient(addr);
+        self.dispatcher.clone().handle_commands(command).await?;
+        Ok(true)
+    }
+
+    /// Returns the nodes waiting to join our section: first the admitted ones whose joining is
+    /// being agreed on, then the queued ones in the order they will be admitted. Empty unless we
+    /// are an elder.
+    pub async fn join_queue(&self) -> Vec<QueuedJoin> {
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .join_queue()
+            .collect()
+    }
+
+    /// Returns the DKG sessions this node takes part in as one of the proposed new elders, the
+    /// most recent generations first.
+    pub async fn dkg_sessions(&self) -> Vec<DkgSessionInfo> {
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .dkg_sessions()
+    }
+
+    /// Aborts the DKG session with `dkg_key`. Returns whether such session existed.
+    ///
+    /// The session is reported to the other participants as failed with
+    /// `DkgFailureReason::Aborted`. Once enough of them observe its failure, `Event::DkgFailed` is
+    /// raised and the elders restart the session with a fresh generation if it's still needed.
+    pub async fn abort_dkg(&self, dkg_key: &DkgKey) -> Result<bool> {
+        let commands = self
+            .dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .abort_dkg(dkg_key)?;
+        let commands = if let Some(commands) = commands {
+            commands
+        } else {
+            return Ok(false);
+        };
+
+        for command in commands {
+            self.dispatcher.clone().handle_commands(command).await?;
+        }
+
+        Ok(true)
+    }
+
+    // Note: spawning the commands because one of them is a timer (resend or timeout) which we
+    // don't want to wait for.
+    fn spawn_commands(&self, commands: Vec<Command>) {
+        for command in commands {
+            let _ = task::spawn(self.dispatcher.clone().handle_commands(command));
+        }
+    }
+
     /// Send a message to a client peer.
     /// Messages sent to a client are not signed or validated as part of the
     /// routing library.
@@ -376,6 +1032,7 @@ impl Routing {
             recipients: vec![recipient],
             delivery_group_size: 1,
             message: MessageType::ClientMessage(message),
+            variant: None,
         };
         self.dispatcher.clone().handle_commands(command).await
     }
@@ -383,18 +1040,29 @@ impl Routing {
     /// Returns the current BLS public key set if this node has one, or
     /// `Error::InvalidState` otherwise.
     pub async fn public_key_set(&self) -> Result<bls::PublicKeySet> {
-        self.dispatcher.core.lock().await.public_key_set()
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .public_key_set()
     }
 
     /// Returns our section proof chain.
     pub async fn our_history(&self) -> SectionChain {
-        self.dispatcher.core.lock().await.section().chain().clone()
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .section()
+            .chain()
+            .clone()
     }
 
     /// Returns our index in the current BLS group if this node is a member of one, or
     /// `Error::MissingSecretKeyShare` otherwise.
     pub async fn our_index(&self) -> Result<usize> {
-        self.dispatcher.core.lock().await.our_index()
+        self.dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .our_index()
     }
 }
 
@@ -404,16 +1072,139 @@ impl Drop for Routing {
     }
 }
 
+// Restore the node from its persisted state. If we were the only elder of our section, resume
+// right away. Otherwise rejoin the section with our previous identity.
+#[allow(clippy::too_many_arguments)]
+async fn restore(
+    stored_state: StoredState,
+    mut comm_config: CommConfig,
+    bootstrap_config: BootstrapConfig,
+    protocol: ProtocolInfo,
+    connection_event_tx: mpsc::Sender<ConnectionEvent>,
+    connection_event_rx: &mut mpsc::Receiver<ConnectionEvent>,
+    event_tx: EventSender,
+) -> Result<(Core, Comm, Vec<(Message, SocketAddr)>)> {
+    let (keypair, addr, stored_section, network, section_key_share) = stored_state.into_parts()?;
+    let node_name = crypto::name(&keypair.public);
+
+    info!("{} Restoring the node from the persisted state.", node_name);
+
+    // Try to reuse our previous address so the other members of our section can still reach us.
+    if comm_config.transport.local_ip.is_none() {
+        comm_config.transport.local_ip = Some(addr.ip());
+    }
+    if comm_config.transport.local_port.is_none() {
+        comm_config.transport.local_port = Some(addr.port());
+    }
+
+    let comm = create_comm(comm_config, connection_event_tx).await?;
+    let mut node = Node::new(keypair, comm.our_connection_info());
+    node.protocol = protocol;
+
+    let contacts: Vec<_> = stored_section
+        .elders_info()
+        .peers()
+        .filter(|peer| *peer.name() != node_name)
+        .map(Peer::addr)
+        .copied()
+        .collect();
+
+    if contacts.is_empty() {
+        let state = Core::restore(node, stored_section, network, section_key_share, event_tx);
+        let section = state.section();
+
+        state.send_event(Event::EldersChanged {
+            prefix: *section.prefix(),
+            key: *section.chain().last_key(),
+            sibling_key: None,
+            elders: section.elders_info().elders.keys().copied().collect(),
+            self_status_change: NodeElderChange::Promoted,
+        });
+
+        return Ok((state, comm, vec![]));
+    }
+
+    let genesis_key = *stored_section.genesis_key();
+    let network_params = *stored_section.network_params();
+    let (node, mut section, backlog) = bootstrap::rejoin(
+        node,
+        &comm,
+        connection_event_rx,
+        contacts,
+        genesis_key,
+        network_params,
+        bootstrap_config,
+    )
+    .await?;
+
+    // Recover the members and the parts of the chain the approval didn't bring us.
+    if let Err(error) = section.merge(stored_section) {
+        debug!("Failed to merge the persisted section: {}", error);
+    }
+
+    // A former member too young to be relocated rejoins with a fresh identity (see
+    // `bootstrap::rejoin`), to which the key share of the stored one doesn't belong.
+    let section_key_share = if node.name() == node_name {
+        section_key_share
+    } else {
+        None
+    };
+
+    let state = Core::restore(node, section, network, section_key_share, event_tx);
+
+    Ok((state, comm, backlog))
+}
+
+// What to create `Comm` over: the simulated network if any, otherwise the real transport.
+struct CommConfig {
+    transport: TransportConfig,
+    #[cfg(feature = "simulated-network")]
+    simulated_network: Option<SimNetwork>,
+}
+
+// Create `Comm` over either the simulated network (if any) or the real transport.
+async fn create_comm(
+    config: CommConfig,
+    connection_event_tx: mpsc::Sender<ConnectionEvent>,
+) -> Result<Comm> {
+    #[cfg(feature = "simulated-network")]
+    if let Some(network) = &config.simulated_network {
+        return Ok(Comm::new_simulated(
+            network,
+            &config.transport,
+            connection_event_tx,
+        ));
+    }
+
+    Comm::new(config.transport, connection_event_tx).await
+}
+
+// Create `Comm` over either the simulated network (if any) or the real transport and connect to
+// one of the hard-coded contacts.
+async fn bootstrap_comm(
+    config: CommConfig,
+    connection_event_tx: mpsc::Sender<ConnectionEvent>,
+) -> Result<(Comm, SocketAddr)> {
+    #[cfg(feature = "simulated-network")]
+    if let Some(network) = &config.simulated_network {
+        return Comm::bootstrap_simulated(network, &config.transport, connection_event_tx);
+    }
+
+    Comm::bootstrap(config.transport, connection_event_tx).await
+}
+
 // Listen for incoming connection events and handle them.
 async fn handle_connection_events(
     dispatcher: Arc<Dispatcher>,
     mut incoming_conns: mpsc::Receiver<ConnectionEvent>,
+    rate_limiter: RateLimiter,
+    protocol: ProtocolInfo,
 ) {
     while let Some(event) = incoming_conns.recv().await {
         match event {
             ConnectionEvent::Received((src, bytes)) => {
                 trace!("New message ({} bytes) received from: {}", bytes.len(), src);
-                handle_message(dispatcher.clone(), bytes, src).await;
+                handle_message(dispatcher.clone(), bytes, src, &rate_limiter, &protocol).await;
             }
             ConnectionEvent::Disconnected(addr) => {
                 trace!("Lost connection to {:?}", addr);
@@ -426,17 +1217,59 @@ async fn handle_connection_events(
     }
 }
 
-async fn handle_message(dispatcher: Arc<Dispatcher>, bytes: Bytes, sender: SocketAddr) {
+// Record that the client `sender` sent us a malformed message and ban it if it keeps doing so.
+// Malformed messages from anyone else (e.g. nodes running an incompatible version) are only logged.
+async fn handle_malformed_message(
+    dispatcher: Arc<Dispatcher>,
+    sender: SocketAddr,
+    rate_limiter: &RateLimiter,
+) {
+    {
+        let mut core = dispatcher.lock_core(Priority::Low).await;
+        if !core.is_client(&sender) || !rate_limiter.record_malformed(sender) {
+            return;
+        }
+
+        let _ = core.remove_client(&sender);
+    }
+
+    warn!("Banning {} for sending malformed messages", sender);
+    dispatcher.metrics.peer_banned();
+
+    let _ = dispatcher
+        .handle_commands(Command::DisconnectClient(sender))
+        .await;
+}
+
+async fn handle_message(
+    dispatcher: Arc<Dispatcher>,
+    bytes: Bytes,
+    sender: SocketAddr,
+    rate_limiter: &RateLimiter,
+    protocol: &ProtocolInfo,
+) {
+    if rate_limiter.is_banned(&sender) {
+        trace!("Ignoring message from banned peer {}", sender);
+        return;
+    }
+
     let span = {
-        let state = dispatcher.core.lock().await;
+        let state = dispatcher.lock_core(Priority::Normal).await;
         trace_span!("handle_message", name = %state.node().name(), %sender)
     };
     let _span_guard = span.enter();
 
+    let len = bytes.len();
     let message_type = match WireMsg::deserialize(bytes) {
         Ok(message_type) => message_type,
+        Err(error @ sn_messaging::Error::UnsupportedVersion(_)) => {
+            // Not malformed, just from a peer running an incompatible version.
+            error!("Failed to deserialize message: {}", error);
+            return;
+        }
         Err(error) => {
             error!("Failed to deserialize message: {}", error);
+            handle_malformed_message(dispatcher, sender, rate_limiter).await;
             return;
         }
     };
@@ -446,17 +1279,33 @@ async fn handle_message(dispatcher: Arc<Dispatcher>, bytes: Bytes, sender: Socke
             // Pings are not handled
         }
         MessageType::SectionInfo(message) => {
+            if !rate_limiter.check_addr(sender, len) {
+                trace!("Rate limit exceeded - dropping {:?}", message);
+                dispatcher.metrics.rate_limited();
+                return;
+            }
+
             let command = Command::HandleSectionInfoMsg { sender, message };
-            let _ = task::spawn(dispatcher.handle_commands(command));
+            if let Some(permit) = dispatcher.acquire_incoming_permit(command.priority()).await {
+                dispatcher.spawn_handle_commands(command, Some(permit));
+            }
         }
         MessageType::NodeMessage(NodeMessage(msg_bytes)) => {
-            match Message::from_bytes(Bytes::from(msg_bytes)) {
+            match Message::from_bytes(Bytes::from(msg_bytes), protocol) {
                 Ok(message) => {
+                    dispatcher
+                        .metrics
+                        .message_received(message.variant().name());
+
                     let command = Command::HandleMessage {
                         message,
                         sender: Some(sender),
                     };
-                    let _ = task::spawn(dispatcher.handle_commands(command));
+                    if let Some(permit) =
+                        dispatcher.acquire_incoming_permit(command.priority()).await
+                    {
+                        dispatcher.spawn_handle_commands(command, Some(permit));
+                    }
                 }
                 Err(error) => {
                     error!("Failed to deserialize node message: {}", error);
@@ -464,12 +1313,23 @@ async fn handle_message(dispatcher: Arc<Dispatcher>, bytes: Bytes, sender: Socke
             }
         }
         MessageType::ClientMessage(message) => {
+            if !rate_limiter.check_addr(sender, len) {
+                trace!("Rate limit exceeded - dropping {:?}", message);
+                dispatcher.metrics.rate_limited();
+                return;
+            }
+
+            let permit =
+                if let Some(permit) = dispatcher.acquire_incoming_permit(Priority::Low).await {
+                    permit
+                } else {
+                    return;
+                };
+
             let end_user = dispatcher
-                .core
-                .lock()
+                .lock_core(Priority::Low)
                 .await
-                .get_enduser_by_addr(&sender)
-                .copied();
+                .record_client_activity(&sender);
             let end_user = match end_user {
                 Some(end_user) => end_user,
                 None => {
@@ -483,12 +1343,23 @@ async fn handle_message(dispatcher: Arc<Dispatcher>, bytes: Bytes, sender: Socke
                                 sender, message
                             )),
                         )),
+                        variant: None,
                     };
-                    let _ = task::spawn(dispatcher.handle_commands(command));
+                    dispatcher.spawn_handle_commands(command, Some(permit));
                     return;
                 }
             };
 
+            if !rate_limiter.check_end_user(*end_user.id(), len) {
+                trace!(
+                    "Rate limit of {:?} exceeded - dropping {:?}",
+                    end_user,
+                    message
+                );
+                dispatcher.metrics.rate_limited();
+                return;
+            }
+
             if let Some(client_pk) = message.target_section_pk() {
                 if let Some(bls_pk) = client_pk.bls() {
                     if let Err(error) = dispatcher.check_key_status(&bls_pk).await {
@@ -502,8 +1373,9 @@ async fn handle_message(dispatcher: Arc<Dispatcher>, bytes: Bytes, sender: Socke
                                     error,
                                 },
                             )),
+                            variant: None,
                         };
-                        let _ = task::spawn(dispatcher.handle_commands(command));
+                        dispatcher.spawn_handle_commands(command, Some(permit));
                         return;
                     }
                 }
@@ -514,7 +1386,7 @@ async fn handle_message(dispatcher: Arc<Dispatcher>, bytes: Bytes, sender: Socke
                 user: end_user,
             };
 
-            dispatcher.send_event(event).await;
+            dispatcher.send_event(event, Priority::Low).await;
         }
     }
 }
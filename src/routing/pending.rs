@@ -0,0 +1,213 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::{self, Command};
+use crate::error::{Error, Result};
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, time::Duration};
+use tokio::sync::oneshot;
+
+// Results being waited for, keyed by what they are the result of. Everyone waiting for the same
+// key gets the same result, but each of them gives up after their own timeout.
+pub(crate) struct Pending<K, T> {
+    // Timeout tokens and senders of everyone waiting for the result.
+    waiters: HashMap<K, Vec<(u64, oneshot::Sender<Result<T>>)>>,
+}
+
+impl<K, T> Pending<K, T>
+where
+    K: Clone + Eq + Hash,
+    T: Clone,
+{
+    pub fn new() -> Self {
+        Self {
+            waiters: HashMap::new(),
+        }
+    }
+
+    // Start waiting for the result of `key`. Returns the receiver to await the result on and the
+    // command scheduling its timeout.
+    pub fn insert(&mut self, key: K, timeout: Duration) -> (oneshot::Receiver<Result<T>>, Command) {
+        let (tx, rx) = oneshot::channel();
+        let token = command::next_timer_token();
+        self.waiters.entry(key).or_default().push((token, tx));
+
+        (
+            rx,
+            Command::ScheduleTimeout {
+                duration: timeout,
+                token,
+            },
+        )
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.waiters.contains_key(key)
+    }
+
+    // Stop waiting for the result of `key`, which drops the senders. Returns whether anyone was
+    // waiting for it.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.waiters.remove(key).is_some()
+    }
+
+    // Pass `result` to everyone waiting for the result of `key`. Returns whether anyone was.
+    pub fn resolve<Q>(&mut self, key: &Q, result: T) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(waiters) = self.waiters.remove(key) {
+            for (_, tx) in waiters {
+                let _ = tx.send(Ok(result.clone()));
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    // Forget everyone who stopped waiting. Returns the keys no one waits for anymore.
+    pub fn remove_closed(&mut self) -> Vec<K> {
+        let mut removed = vec![];
+        self.waiters.retain(|key, waiters| {
+            waiters.retain(|(_, tx)| !tx.is_closed());
+            if waiters.is_empty() {
+                removed.push(key.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    // Pass the error returned by `error` to everyone waiting for any result.
+    pub fn fail_all(&mut self, error: impl Fn() -> Error) {
+        for (_, tx) in self.waiters.drain().flat_map(|(_, waiters)| waiters) {
+            let _ = tx.send(Err(error()));
+        }
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.waiters
+            .values()
+            .flatten()
+            .any(|(timer_token, _)| *timer_token == token)
+    }
+
+    // Pass `error` to the waiter whose timeout fired. Returns the key it was waiting for.
+    pub fn handle_timeout(&mut self, token: u64, error: Error) -> Option<K> {
+        let key = self
+            .waiters
+            .iter()
+            .find(|(_, waiters)| waiters.iter().any(|(t, _)| *t == token))
+            .map(|(key, _)| key.clone())?;
+
+        let is_empty = if let Some(waiters) = self.waiters.get_mut(&key) {
+            if let Some(index) = waiters.iter().position(|(t, _)| *t == token) {
+                let (_, tx) = waiters.swap_remove(index);
+                let _ = tx.send(Err(error));
+            }
+
+            // Also forget the waiters that already stopped waiting.
+            waiters.retain(|(_, tx)| !tx.is_closed());
+            waiters.is_empty()
+        } else {
+            false
+        };
+
+        if is_empty {
+            let _ = self.waiters.remove(&key);
+        }
+
+        Some(key)
+    }
+}
+
+impl<K, T> Default for Pending<K, T>
+where
+    K: Clone + Eq + Hash,
+    T: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use futures::FutureExt;
+
+    #[test]
+    fn resolve_and_timeout() {
+        let mut pending = Pending::new();
+        let (first, command) = pending.insert("key", Duration::from_secs(10));
+        let (second, _) = pending.insert("key", Duration::from_secs(10));
+
+        let token = assert_matches!(command, Command::ScheduleTimeout { token, .. } => token);
+        assert!(pending.is_timer(token));
+        assert_eq!(
+            pending.handle_timeout(token, Error::RequestTimeout),
+            Some("key")
+        );
+        assert!(!pending.is_timer(token));
+        assert_eq!(pending.handle_timeout(token, Error::RequestTimeout), None);
+        assert_matches!(first.now_or_never(), Some(Ok(Err(Error::RequestTimeout))));
+
+        assert!(pending.contains("key"));
+        assert!(pending.resolve("key", 42));
+        assert!(!pending.contains("key"));
+        assert!(!pending.resolve("key", 42));
+        assert_matches!(second.now_or_never(), Some(Ok(Ok(42))));
+    }
+
+    #[test]
+    fn remove() {
+        let mut pending: Pending<_, u32> = Pending::new();
+        let (rx, _) = pending.insert("key", Duration::from_secs(10));
+
+        assert!(pending.remove("key"));
+        assert!(!pending.remove("key"));
+        assert_matches!(rx.now_or_never(), Some(Err(_)));
+    }
+
+    #[test]
+    fn remove_closed() {
+        let mut pending: Pending<_, u32> = Pending::new();
+        let (dropped, _) = pending.insert("dropped", Duration::from_secs(10));
+        let (_kept, _) = pending.insert("kept", Duration::from_secs(10));
+        drop(dropped);
+
+        assert_eq!(pending.remove_closed(), vec!["dropped"]);
+        assert!(!pending.contains("dropped"));
+        assert!(pending.contains("kept"));
+    }
+
+    #[test]
+    fn fail_all() {
+        let mut pending: Pending<_, u32> = Pending::new();
+        let (first, _) = pending.insert("first", Duration::from_secs(10));
+        let (second, _) = pending.insert("second", Duration::from_secs(10));
+
+        pending.fail_all(|| Error::Relocated);
+        assert!(!pending.contains("first"));
+        assert!(!pending.contains("second"));
+        assert_matches!(first.now_or_never(), Some(Ok(Err(Error::Relocated))));
+        assert_matches!(second.now_or_never(), Some(Ok(Err(Error::Relocated))));
+    }
+}
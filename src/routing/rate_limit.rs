@@ -0,0 +1,320 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::RateLimitConfig;
+use lru_time_cache::LruCache;
+use sn_data_types::PublicKey as EndUserPK;
+use std::{
+    net::SocketAddr,
+    sync::{Mutex, MutexGuard},
+};
+use tokio::time::Instant;
+
+// Maximum number of peers tracked in each of the maps. Beyond it, the least recently seen peer is
+// forgotten.
+const MAX_TRACKED: usize = 10_000;
+
+// Throttles the incoming client traffic: enforces the message and byte quotas per sender address
+// and per end user and temporarily bans the peers that keep sending malformed messages.
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<State>,
+}
+
+struct State {
+    addrs: LruCache<SocketAddr, Quota>,
+    end_users: LruCache<EndUserPK, Quota>,
+    // Number of malformed messages per sender and when the last one arrived. The count is reset
+    // once `ban_duration` passed since then.
+    malformed: LruCache<SocketAddr, (u32, Instant)>,
+    bans: LruCache<SocketAddr, Instant>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            addrs: LruCache::with_capacity(MAX_TRACKED),
+            end_users: LruCache::with_capacity(MAX_TRACKED),
+            malformed: LruCache::with_capacity(MAX_TRACKED),
+            bans: LruCache::with_capacity(MAX_TRACKED),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    // Whether messages from `addr` are to be ignored because it's currently banned.
+    pub fn is_banned(&self, addr: &SocketAddr) -> bool {
+        let mut state = self.lock();
+        match state.bans.get(addr).copied() {
+            Some(until) if until > Instant::now() => true,
+            Some(_) => {
+                let _ = state.bans.remove(addr);
+                false
+            }
+            None => false,
+        }
+    }
+
+    // Record that `addr` sent us a message we couldn't deserialize. Returns whether it got banned
+    // for it.
+    pub fn record_malformed(&self, addr: SocketAddr) -> bool {
+        let ban_duration = self.config.ban_duration;
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        let (count, last) = state.malformed.entry(addr).or_insert((0, now));
+        if now.saturating_duration_since(*last) >= ban_duration {
+            *count = 0;
+        }
+        *count += 1;
+        *last = now;
+
+        if *count < self.config.malformed_limit {
+            return false;
+        }
+
+        let _ = state.malformed.remove(&addr);
+        let _ = state.bans.insert(addr, now + ban_duration);
+        true
+    }
+
+    // Take one message of `len` bytes from the quota of the sender `addr`. Returns whether the
+    // message is within the quota.
+    pub fn check_addr(&self, addr: SocketAddr, len: usize) -> bool {
+        let config = &self.config;
+        let mut state = self.lock();
+        take(&mut state.addrs, addr, len, || {
+            Quota::new(config.messages_per_addr, config.bytes_per_addr)
+        })
+    }
+
+    // Take one message of `len` bytes from the quota of the end user `public_key`, shared by all
+    // its connections. Returns whether the message is within the quota.
+    pub fn check_end_user(&self, public_key: EndUserPK, len: usize) -> bool {
+        let config = &self.config;
+        let mut state = self.lock();
+        take(&mut state.end_users, public_key, len, || {
+            Quota::new(config.messages_per_end_user, config.bytes_per_end_user)
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn take<K: Ord + Clone>(
+    quotas: &mut LruCache<K, Quota>,
+    key: K,
+    len: usize,
+    new: impl FnOnce() -> Quota,
+) -> bool {
+    quotas
+        .entry(key)
+        .or_insert_with(new)
+        .take(len, Instant::now())
+}
+
+// Pair of token buckets limiting the number of messages and the number of bytes per second.
+struct Quota {
+    messages: TokenBucket,
+    bytes: TokenBucket,
+}
+
+impl Quota {
+    fn new(messages_per_sec: u32, bytes_per_sec: u64) -> Self {
+        Self {
+            messages: TokenBucket::new(messages_per_sec as f64),
+            bytes: TokenBucket::new(bytes_per_sec as f64),
+        }
+    }
+
+    fn take(&mut self, len: usize, now: Instant) -> bool {
+        self.messages.refill(now);
+        self.bytes.refill(now);
+
+        // A message bigger than the whole byte quota is let through when the bucket is full. The
+        // bucket then goes into debt which has to be paid off before the next message.
+        let len = len as f64;
+        if self.messages.tokens < 1.0 || self.bytes.tokens < len.min(self.bytes.rate) {
+            return false;
+        }
+
+        self.messages.tokens -= 1.0;
+        self.bytes.tokens -= len;
+        true
+    }
+}
+
+// Bucket holding up to one second worth of tokens, replenished continuously at `rate` per second.
+// The tokens can go negative after taking more than the bucket holds.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_data_types::Keypair;
+    use std::net::Ipv4Addr;
+    use tokio::time::{self, Duration};
+
+    fn gen_addr(port: u16) -> SocketAddr {
+        (Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    #[tokio::test]
+    async fn message_quota_per_addr() {
+        time::pause();
+
+        let limiter = RateLimiter::new(RateLimitConfig {
+            messages_per_addr: 2,
+            ..Default::default()
+        });
+
+        assert!(limiter.check_addr(gen_addr(1), 10));
+        assert!(limiter.check_addr(gen_addr(1), 10));
+        assert!(!limiter.check_addr(gen_addr(1), 10));
+
+        // Other addresses have their own quota.
+        assert!(limiter.check_addr(gen_addr(2), 10));
+
+        time::advance(Duration::from_millis(500)).await;
+        assert!(limiter.check_addr(gen_addr(1), 10));
+        assert!(!limiter.check_addr(gen_addr(1), 10));
+    }
+
+    #[tokio::test]
+    async fn byte_quota_per_end_user() {
+        time::pause();
+
+        let limiter = RateLimiter::new(RateLimitConfig {
+            bytes_per_end_user: 100,
+            ..Default::default()
+        });
+        let public_key = Keypair::new_ed25519(&mut rand::thread_rng()).public_key();
+
+        assert!(limiter.check_end_user(public_key, 60));
+        assert!(!limiter.check_end_user(public_key, 60));
+        // A rejected message doesn't use up the quota.
+        assert!(limiter.check_end_user(public_key, 40));
+
+        time::advance(Duration::from_secs(1)).await;
+        assert!(limiter.check_end_user(public_key, 100));
+    }
+
+    #[tokio::test]
+    async fn ban_for_malformed_messages() {
+        time::pause();
+
+        let ban_duration = Duration::from_secs(60);
+        let limiter = RateLimiter::new(RateLimitConfig {
+            malformed_limit: 2,
+            ban_duration,
+            ..Default::default()
+        });
+        let addr = gen_addr(1);
+
+        assert!(!limiter.record_malformed(addr));
+        assert!(!limiter.is_banned(&addr));
+        assert!(limiter.record_malformed(addr));
+        assert!(limiter.is_banned(&addr));
+        assert!(!limiter.is_banned(&gen_addr(2)));
+
+        time::advance(ban_duration).await;
+        assert!(!limiter.is_banned(&addr));
+    }
+
+    #[tokio::test]
+    async fn malformed_count_expires() {
+        time::pause();
+
+        let ban_duration = Duration::from_secs(60);
+        let limiter = RateLimiter::new(RateLimitConfig {
+            malformed_limit: 2,
+            ban_duration,
+            ..Default::default()
+        });
+        let addr = gen_addr(1);
+
+        assert!(!limiter.record_malformed(addr));
+        time::advance(ban_duration).await;
+        assert!(!limiter.record_malformed(addr));
+        assert!(!limiter.is_banned(&addr));
+    }
+
+    #[tokio::test]
+    async fn oversize_message_needs_full_quota() {
+        time::pause();
+
+        let limiter = RateLimiter::new(RateLimitConfig {
+            bytes_per_addr: 100,
+            ..Default::default()
+        });
+        let addr = gen_addr(1);
+
+        assert!(limiter.check_addr(addr, 10));
+        assert!(!limiter.check_addr(addr, 150));
+
+        time::advance(Duration::from_millis(100)).await;
+        assert!(limiter.check_addr(addr, 150));
+
+        // The excess is paid off before anything else gets through.
+        time::advance(Duration::from_millis(400)).await;
+        assert!(!limiter.check_addr(addr, 1));
+        time::advance(Duration::from_millis(200)).await;
+        assert!(limiter.check_addr(addr, 1));
+    }
+
+    #[tokio::test]
+    async fn tracked_peers_are_capped() {
+        time::pause();
+
+        let limiter = RateLimiter::new(RateLimitConfig {
+            messages_per_addr: 1,
+            ..Default::default()
+        });
+
+        assert!(limiter.check_addr(gen_addr(0), 10));
+        assert!(!limiter.check_addr(gen_addr(0), 10));
+
+        for port in 1..=MAX_TRACKED as u16 {
+            assert!(limiter.check_addr(gen_addr(port), 10));
+        }
+        assert_eq!(limiter.lock().addrs.len(), MAX_TRACKED);
+
+        // The least recently seen address was forgotten, together with its used up quota.
+        assert!(limiter.check_addr(gen_addr(0), 10));
+        assert_eq!(limiter.lock().addrs.len(), MAX_TRACKED);
+    }
+}
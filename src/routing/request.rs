@@ -0,0 +1,270 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{command::Command, pending::Pending};
+use crate::{
+    error::{Error, Result},
+    messages::{MessageHash, SrcAuthority},
+    rng::MainRng,
+};
+use bytes::Bytes;
+use rand::Rng;
+use sn_messaging::{DstLocation, SrcLocation};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// Future returned by `Routing::send_request` which resolves to the content of the matching
+/// response. Resolves to `Error::RequestTimeout` if no response arrives in time and to
+/// `Error::RequestCancelled` if the request is cancelled with `Routing::cancel_request` and to
+/// `Error::Relocated` if the node gets relocated before the response arrives. Dropping it cancels
+/// the request too.
+pub struct Response {
+    id: MessageHash,
+    rx: oneshot::Receiver<Result<Bytes>>,
+}
+
+impl Response {
+    /// Id correlating the request with its response.
+    pub fn id(&self) -> &MessageHash {
+        &self.id
+    }
+}
+
+impl Future for Response {
+    type Output = Result<Bytes>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(Error::RequestCancelled)))
+    }
+}
+
+// Id of a request or an acknowledged message with `content` from `src` to `dst`. Those from a
+// section are sent by each of its elders, so their id must be the same on all of them and is
+// derived from the message itself. Those from a single node get a random id so identical ones
+// don't get mixed up.
+pub(crate) fn user_message_id(
+    src: &SrcLocation,
+    dst: &DstLocation,
+    content: &[u8],
+) -> Result<MessageHash> {
+    let nonce: u64 = if matches!(src, SrcLocation::Section(_)) {
+        0
+    } else {
+        MainRng.gen()
+    };
+    let bytes = bincode::serialize(&(src, dst, content, nonce))?;
+    Ok(MessageHash::from_bytes(&bytes))
+}
+
+// Requests sent with `Routing::send_request` which are still waiting for their response.
+pub(crate) struct Requests {
+    responses: Pending<MessageHash, Bytes>,
+    // Destinations the pending requests were sent to.
+    dsts: HashMap<MessageHash, DstLocation>,
+}
+
+impl Requests {
+    pub fn new() -> Self {
+        Self {
+            responses: Pending::new(),
+            dsts: HashMap::new(),
+        }
+    }
+
+    // Start waiting for the response to the request `id` sent to `dst`. Returns the future to
+    // await the response on and the command scheduling its timeout. Also forgets the requests
+    // whose `Response` was dropped.
+    pub fn insert(
+        &mut self,
+        id: MessageHash,
+        dst: DstLocation,
+        timeout: Duration,
+    ) -> (Response, Command) {
+        for id in self.responses.remove_closed() {
+            let _ = self.dsts.remove(&id);
+        }
+
+        let _ = self.dsts.entry(id).or_insert(dst);
+        let (rx, command) = self.responses.insert(id, timeout);
+        (Response { id, rx }, command)
+    }
+
+    // Stop waiting for the response to the request `id`. Returns whether there was such request.
+    pub fn remove(&mut self, id: &MessageHash) -> bool {
+        let _ = self.dsts.remove(id);
+        self.responses.remove(id)
+    }
+
+    // Handle a response to the request `id`. The response is only accepted if it comes from the
+    // destination the request was sent to. Returns whether the response was accepted.
+    pub fn resolve(&mut self, id: &MessageHash, src: &SrcAuthority, content: Bytes) -> bool {
+        let is_from_dst = match self.dsts.get(id) {
+            Some(DstLocation::Node(name)) => !src.is_section() && src.name() == *name,
+            Some(DstLocation::Section(name)) => src.is_section() && src.name() == *name,
+            Some(DstLocation::EndUser(_)) | Some(DstLocation::Direct) | None => false,
+        };
+
+        if !is_from_dst {
+            return false;
+        }
+
+        let _ = self.dsts.remove(id);
+        self.responses.resolve(id, content)
+    }
+
+    // Stop waiting for all the responses, resolving them to `error`.
+    pub fn fail_all(&mut self, error: impl Fn() -> Error) {
+        self.dsts.clear();
+        self.responses.fail_all(error)
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.responses.is_timer(token)
+    }
+
+    // Give up waiting for the response whose timeout fired.
+    pub fn handle_timeout(&mut self, token: u64) {
+        if let Some(id) = self.responses.handle_timeout(token, Error::RequestTimeout) {
+            trace!("Request {:?} timed out", id);
+            if !self.responses.contains(&id) {
+                let _ = self.dsts.remove(&id);
+            }
+        }
+    }
+}
+
+impl Default for Requests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto,
+        messages::{Message, Variant},
+        node::Node,
+        section::test_utils::gen_addr,
+        MIN_AGE,
+    };
+    use anyhow::Result;
+    use assert_matches::assert_matches;
+    use futures::FutureExt;
+    use xor_name::Prefix;
+
+    #[test]
+    fn resolve_only_from_destination() -> Result<()> {
+        let dst_node = gen_node();
+        let other_node = gen_node();
+
+        let src = SrcLocation::Node(rand::random());
+        let dst = DstLocation::Node(dst_node.name());
+        let id = user_message_id(&src, &dst, b"ping")?;
+
+        let mut requests = Requests::new();
+        let (mut response, _) = requests.insert(id, dst, Duration::from_secs(10));
+        assert_eq!(response.id(), &id);
+
+        // Response from someone else than the destination is ignored.
+        let msg = gen_response(&other_node, &id)?;
+        assert!(!requests.resolve(&id, msg.src(), Bytes::from_static(b"pong")));
+        assert!((&mut response).now_or_never().is_none());
+
+        let msg = gen_response(&dst_node, &id)?;
+        assert!(requests.resolve(&id, msg.src(), Bytes::from_static(b"pong")));
+        assert_matches!(response.now_or_never(), Some(Ok(content)) => {
+            assert_eq!(content, Bytes::from_static(b"pong"))
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn cancel() -> Result<()> {
+        let src = SrcLocation::Node(rand::random());
+        let dst = DstLocation::Node(rand::random());
+        let id = user_message_id(&src, &dst, b"ping")?;
+
+        let mut requests = Requests::new();
+        let (response, _) = requests.insert(id, dst, Duration::from_secs(10));
+
+        assert!(requests.remove(&id));
+        assert!(!requests.remove(&id));
+        assert_matches!(response.now_or_never(), Some(Err(Error::RequestCancelled)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn drop_response() -> Result<()> {
+        let src = SrcLocation::Node(rand::random());
+        let dst = DstLocation::Node(rand::random());
+        let id = user_message_id(&src, &dst, b"ping")?;
+
+        let mut requests = Requests::new();
+        let (response, _) = requests.insert(id, dst, Duration::from_secs(10));
+        drop(response);
+
+        // The dropped request is forgotten once another one is sent.
+        let other_id = user_message_id(&src, &dst, b"ping")?;
+        let _other = requests.insert(other_id, dst, Duration::from_secs(10));
+        assert!(!requests.remove(&id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn section_user_message_id() -> Result<()> {
+        let src = SrcLocation::Section(rand::random());
+        let dst = DstLocation::Section(rand::random());
+
+        // Section sourced requests get the same id on every elder.
+        assert_eq!(
+            user_message_id(&src, &dst, b"ping")?,
+            user_message_id(&src, &dst, b"ping")?
+        );
+
+        let src = SrcLocation::Node(rand::random());
+        assert_ne!(
+            user_message_id(&src, &dst, b"ping")?,
+            user_message_id(&src, &dst, b"ping")?
+        );
+
+        Ok(())
+    }
+
+    fn gen_node() -> Node {
+        Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        )
+    }
+
+    fn gen_response(node: &Node, id: &MessageHash) -> Result<Message> {
+        Ok(Message::single_src(
+            node,
+            DstLocation::Direct,
+            Variant::UserResponse {
+                id: *id,
+                content: Bytes::from_static(b"pong"),
+            },
+            None,
+            None,
+        )?)
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    ops::{Deref, DerefMut},
+    sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard},
+};
+use tokio::sync::{oneshot, Mutex, MutexGuard};
+
+/// Priority of a command. Commands with higher priority are given access to the node state first.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum Priority {
+    /// User and client traffic.
+    Low,
+    /// Everything not in the other two categories.
+    Normal,
+    /// Consensus critical work: DKG, proposals, agreements, section updates and relocations.
+    High,
+}
+
+// Grants the access to a mutex to the waiting tasks in the order of their priority and, within the
+// same priority, in the order they asked for it.
+//
+// Note: under a constant stream of higher priority work, the lower priority work waits until it
+// subsides. This is intentional - consensus critical work must not be delayed by user traffic.
+pub(crate) struct Scheduler {
+    state: StdMutex<State>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            state: StdMutex::new(State {
+                busy: false,
+                next_seq: 0,
+                waiting: BinaryHeap::new(),
+            }),
+        }
+    }
+
+    // Lock `mutex` once it's our turn according to `priority`.
+    pub async fn lock<'a, T>(
+        &'a self,
+        mutex: &'a Mutex<T>,
+        priority: Priority,
+    ) -> ScheduledGuard<'a, T> {
+        let turn = self.turn(priority).await;
+        let guard = mutex.lock().await;
+
+        ScheduledGuard { guard, _turn: turn }
+    }
+
+    async fn turn(&self, priority: Priority) -> Turn<'_> {
+        let rx = {
+            let mut state = self.lock_state();
+            if !state.busy {
+                state.busy = true;
+                return Turn { scheduler: self };
+            }
+
+            let (tx, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiter { priority, seq, tx });
+            rx
+        };
+
+        let mut waiting = Waiting {
+            scheduler: self,
+            rx,
+        };
+        // The sender is only dropped together with the scheduler.
+        let _ = (&mut waiting.rx).await;
+
+        Turn { scheduler: self }
+    }
+
+    // Pass the turn to the next waiter, if any.
+    fn release(&self) {
+        let mut state = self.lock_state();
+        while let Some(waiter) = state.waiting.pop() {
+            if waiter.tx.send(()).is_ok() {
+                return;
+            }
+        }
+
+        state.busy = false;
+    }
+
+    fn lock_state(&self) -> StdMutexGuard<State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Guard of a mutex locked through the `Scheduler`. Unlocking it passes the turn to the next waiter.
+pub(crate) struct ScheduledGuard<'a, T> {
+    // Note: declared first so the mutex is unlocked before the turn is passed on.
+    guard: MutexGuard<'a, T>,
+    _turn: Turn<'a>,
+}
+
+impl<'a, T> Deref for ScheduledGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for ScheduledGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+struct State {
+    busy: bool,
+    next_seq: u64,
+    waiting: BinaryHeap<Waiter>,
+}
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    tx: oneshot::Sender<()>,
+}
+
+impl Waiter {
+    fn key(&self) -> (Priority, Reverse<u64>) {
+        (self.priority, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+struct Turn<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.scheduler.release()
+    }
+}
+
+// Waiting for the turn. If the waiting task is cancelled after the turn was already passed to it,
+// passes it on so it isn't lost.
+struct Waiting<'a> {
+    scheduler: &'a Scheduler,
+    rx: oneshot::Receiver<()>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.rx.close();
+        if self.rx.try_recv().is_ok() {
+            self.scheduler.release()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use std::sync::Arc;
+    use tokio::task;
+
+    #[tokio::test]
+    async fn higher_priority_first() {
+        let scheduler = Arc::new(Scheduler::new());
+        let mutex = Arc::new(Mutex::new(Vec::new()));
+
+        let guard = scheduler.lock(&mutex, Priority::Normal).await;
+
+        let mut handles = vec![];
+        for (index, priority) in [
+            Priority::Low,
+            Priority::High,
+            Priority::Normal,
+            Priority::High,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let scheduler = scheduler.clone();
+            let mutex = mutex.clone();
+            let priority = *priority;
+            handles.push(task::spawn(async move {
+                scheduler.lock(&mutex, priority).await.push(index)
+            }));
+
+            // Let the task start waiting before spawning the next one.
+            task::yield_now().await;
+        }
+
+        drop(guard);
+        for handle in handles {
+            handle.await.expect("task panicked");
+        }
+
+        assert_eq!(*mutex.lock().await, vec![1, 3, 2, 0]);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter_passes_turn() {
+        let scheduler = Scheduler::new();
+        let mutex = Mutex::new(());
+
+        let guard = scheduler.lock(&mutex, Priority::Normal).await;
+
+        let mut cancelled = Box::pin(scheduler.lock(&mutex, Priority::High));
+        assert!((&mut cancelled).now_or_never().is_none());
+
+        // The turn is passed to the waiter which then gives up without taking it.
+        drop(guard);
+        drop(cancelled);
+
+        assert!(scheduler
+            .lock(&mutex, Priority::Low)
+            .now_or_never()
+            .is_some());
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{command::Command, pending::Pending};
+use crate::{
+    agreement::Proof,
+    error::{Error, Result},
+    messages::MessageHash,
+    section::SectionChain,
+};
+use bytes::Bytes;
+use lru_time_cache::LruCache;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+// Tag serialized in front of every application payload signed by the section.
+const TAG: &str = "sn_routing::SectionPayload";
+
+// How long and how many of the payloads we signed are remembered so we don't sign them again.
+const SIGNED_EXPIRY_DURATION: Duration = Duration::from_secs(10 * 60);
+const MAX_SIGNED: usize = 1_000;
+
+/// Application value signed by the section elders with `Routing::sign_as_section`. It is
+/// serialized (and so signed) together with a tag which sets it apart from everything the section
+/// signs for its own purposes, so a section signature on an application payload can never be
+/// passed off as a routing agreement.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct SectionPayload<T>(pub T);
+
+impl<T: Serialize> Serialize for SectionPayload<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (TAG, &self.0).serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for SectionPayload<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (tag, value) = <(String, T)>::deserialize(deserializer)?;
+        if tag == TAG {
+            Ok(Self(value))
+        } else {
+            Err(D::Error::custom(format!(
+                "invalid section payload tag: {}",
+                tag
+            )))
+        }
+    }
+}
+
+// Returns whether `bytes` are a serialized `SectionPayload`. Only those are ever co-signed.
+pub(crate) fn is_section_payload(bytes: &[u8]) -> bool {
+    section_payload_value(bytes).is_some()
+}
+
+// Returns the serialized value wrapped in the serialized `SectionPayload` `bytes`, or `None` if
+// `bytes` aren't a serialized `SectionPayload`.
+pub(crate) fn section_payload_value(bytes: &[u8]) -> Option<&[u8]> {
+    let tag = bincode::serialize(TAG).ok()?;
+    if bytes.starts_with(&tag) {
+        Some(&bytes[tag.len()..])
+    } else {
+        None
+    }
+}
+
+/// Decides which payloads proposed by the other elders for signing by the section (see
+/// `Routing::sign_as_section`) this node co-signs. It's given the value wrapped in the
+/// `SectionPayload`, serialized with bincode, and returns whether to co-sign it.
+#[derive(Clone)]
+pub struct SectionSigningApproval(Arc<dyn Fn(&[u8]) -> bool + Send + Sync>);
+
+impl SectionSigningApproval {
+    /// Creates the approval from a function deciding whether to co-sign the given serialized
+    /// value.
+    pub fn new<F>(approve: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(approve))
+    }
+
+    fn approves(&self, value: &[u8]) -> bool {
+        (self.0)(value)
+    }
+}
+
+impl Debug for SectionSigningApproval {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "SectionSigningApproval")
+    }
+}
+
+// Payloads being signed by our section.
+pub(crate) struct SectionSigning {
+    // Everyone waiting for the signature of the payload.
+    pending: Pending<Bytes, (Proof, SectionChain)>,
+    // Payloads we already added our signature share to.
+    signed: LruCache<MessageHash, ()>,
+    // Decides which payloads proposed by the other elders we co-sign. None without it.
+    approval: Option<SectionSigningApproval>,
+}
+
+impl SectionSigning {
+    pub fn new() -> Self {
+        Self {
+            pending: Pending::new(),
+            signed: LruCache::with_expiry_duration_and_capacity(SIGNED_EXPIRY_DURATION, MAX_SIGNED),
+            approval: None,
+        }
+    }
+
+    pub fn approval(&self) -> Option<&SectionSigningApproval> {
+        self.approval.as_ref()
+    }
+
+    pub fn set_approval(&mut self, approval: Option<SectionSigningApproval>) {
+        self.approval = approval;
+    }
+
+    // Returns whether the application approves co-signing of the serialized `SectionPayload`
+    // `payload` proposed by another elder.
+    pub fn approves(&self, payload: &[u8]) -> bool {
+        match (&self.approval, section_payload_value(payload)) {
+            (Some(approval), Some(value)) => approval.approves(value),
+            _ => false,
+        }
+    }
+
+    // Start waiting for the section signature of `payload`. Returns the receiver to await the
+    // signature on and the command scheduling its timeout. Also forgets those who stopped waiting.
+    pub fn insert(
+        &mut self,
+        payload: Bytes,
+        timeout: Duration,
+    ) -> (oneshot::Receiver<Result<(Proof, SectionChain)>>, Command) {
+        let _ = self.pending.remove_closed();
+        self.pending.insert(payload, timeout)
+    }
+
+    // Record that we are signing `payload`. Returns whether we haven't signed it yet.
+    pub fn mark_signed(&mut self, payload: &[u8]) -> bool {
+        self.signed
+            .insert(MessageHash::from_bytes(payload), ())
+            .is_none()
+    }
+
+    // Handle the agreement on `payload`, passing its proof to everyone waiting for it.
+    pub fn resolve(&mut self, payload: &[u8], proof: &Proof, section_chain: &SectionChain) {
+        let _ = self
+            .pending
+            .resolve(payload, (proof.clone(), section_chain.clone()));
+    }
+
+    // Stop waiting for all the signatures, resolving them to `error`.
+    pub fn fail_all(&mut self, error: impl Fn() -> Error) {
+        self.pending.fail_all(error)
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.pending.is_timer(token)
+    }
+
+    // Give up waiting for the signature whose timeout fired.
+    pub fn handle_timeout(&mut self, token: u64) {
+        let _ = self
+            .pending
+            .handle_timeout(token, Error::SectionSigningTimeout);
+    }
+}
+
+impl Default for SectionSigning {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agreement::Proven;
+    use anyhow::Result;
+    use assert_matches::assert_matches;
+    use futures::FutureExt;
+
+    #[test]
+    fn payload_is_tagged() -> Result<()> {
+        let bytes = bincode::serialize(&SectionPayload("hello"))?;
+        assert!(is_section_payload(&bytes));
+        assert!(!is_section_payload(&bincode::serialize("hello")?));
+
+        let payload: SectionPayload<String> = bincode::deserialize(&bytes)?;
+        assert_eq!(payload.0, "hello");
+        assert!(
+            bincode::deserialize::<SectionPayload<String>>(&bincode::serialize(&(
+                "other", "hello"
+            ))?)
+            .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn approval() -> Result<()> {
+        let mut signing = SectionSigning::new();
+        let approved = bincode::serialize(&SectionPayload(42u32))?;
+        let rejected = bincode::serialize(&SectionPayload(7u32))?;
+
+        // Nothing is approved without the approval.
+        assert!(!signing.approves(&approved));
+
+        signing.set_approval(Some(SectionSigningApproval::new(|value| {
+            bincode::deserialize::<u32>(value).map_or(false, |value| value == 42)
+        })));
+        assert!(signing.approves(&approved));
+        assert!(!signing.approves(&rejected));
+        assert!(!signing.approves(&bincode::serialize(&42u32)?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve() -> Result<()> {
+        let sk = bls::SecretKey::random();
+        let chain = SectionChain::new(sk.public_key());
+        let payload = Bytes::from(bincode::serialize(&SectionPayload(42u32))?);
+
+        let mut signing = SectionSigning::new();
+        assert!(signing.mark_signed(&payload));
+        assert!(!signing.mark_signed(&payload));
+
+        let (rx, _) = signing.insert(payload.clone(), Duration::from_secs(10));
+
+        let proof = Proof {
+            public_key: sk.public_key(),
+            signature: sk.sign(&payload),
+        };
+        signing.resolve(&payload, &proof, &chain);
+        let (proof, _) = assert_matches!(rx.now_or_never(), Some(Ok(Ok(result))) => result);
+
+        let proven = Proven::new(SectionPayload(42u32), proof);
+        assert!(proven.verify(&chain));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::comm::{ConnectionEvent, TransportError};
+use bytes::Bytes;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Formatter},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::{sync::mpsc, task, time};
+
+const FIRST_PORT: u16 = 10_000;
+
+/// In-process simulated network to run many nodes in a single process without opening any real
+/// sockets.
+///
+/// Pass a clone of it in `Config::simulated_network` of every node that should be part of the
+/// same network. Messages between the nodes can be delayed, lost or blocked by partitions. All the
+/// randomness is derived from the seed the network was created with and the delays are measured by
+/// the tokio clock, so pausing it (`tokio::time::pause`) in a single-threaded runtime turns it
+/// into a virtual clock and makes the whole run reproducible from the seed.
+#[derive(Clone)]
+pub struct SimNetwork {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl SimNetwork {
+    /// Creates new simulated network with no latency, no loss and no partitions.
+    ///
+    /// Also seeds the generator of the keys and nonces of the nodes created on the current thread,
+    /// so the nodes get the same names in every run with the same seed.
+    pub fn new(seed: u64) -> Self {
+        crate::rng::seed(seed);
+
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                rng: ChaCha8Rng::seed_from_u64(seed),
+                endpoints: HashMap::new(),
+                next_port: FIRST_PORT,
+                min_latency: Duration::default(),
+                max_latency: Duration::default(),
+                loss_rate: 0.0,
+                blocked: HashSet::new(),
+            })),
+        }
+    }
+
+    /// Sets the range the latency of every delivered message is picked from.
+    pub fn set_latency(&self, min: Duration, max: Duration) {
+        let mut inner = self.lock();
+        inner.min_latency = min;
+        inner.max_latency = max.max(min);
+    }
+
+    /// Sets the probability (between 0.0 and 1.0) that a message gets lost in transit.
+    pub fn set_loss_rate(&self, loss_rate: f64) {
+        self.lock().loss_rate = loss_rate.max(0.0).min(1.0);
+    }
+
+    /// Splits the network so no messages pass between any node in `side_a` and any node in
+    /// `side_b`. Sending to a node on the other side fails as if the node was unreachable.
+    pub fn partition(&self, side_a: &[SocketAddr], side_b: &[SocketAddr]) {
+        let mut inner = self.lock();
+
+        for a in side_a {
+            for b in side_b {
+                let _ = inner.blocked.insert(link(a, b));
+            }
+        }
+    }
+
+    /// Removes all the partitions.
+    pub fn heal(&self) {
+        self.lock().blocked.clear()
+    }
+
+    /// Returns the addresses of all the currently open endpoints.
+    pub fn endpoints(&self) -> Vec<SocketAddr> {
+        self.lock().endpoints.keys().copied().collect()
+    }
+
+    pub(crate) fn new_endpoint(
+        &self,
+        local_ip: Option<IpAddr>,
+        local_port: Option<u16>,
+        event_tx: mpsc::Sender<ConnectionEvent>,
+    ) -> SimEndpoint {
+        let mut inner = self.lock();
+        let ip = local_ip.unwrap_or_else(|| Ipv4Addr::LOCALHOST.into());
+
+        // Use the requested port if it's free, otherwise pick the next unused one.
+        let addr = match local_port.map(|port| SocketAddr::new(ip, port)) {
+            Some(addr) if !inner.endpoints.contains_key(&addr) => addr,
+            _ => loop {
+                let addr = SocketAddr::new(ip, inner.next_port);
+                inner.next_port = inner.next_port.wrapping_add(1).max(FIRST_PORT);
+
+                if !inner.endpoints.contains_key(&addr) {
+                    break addr;
+                }
+            },
+        };
+
+        let _ = inner.endpoints.insert(addr, event_tx);
+
+        SimEndpoint {
+            network: self.clone(),
+            addr,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<Inner> {
+        self.inner.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl Debug for SimNetwork {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SimNetwork {{ endpoints: {} }}",
+            self.lock().endpoints.len()
+        )
+    }
+}
+
+struct Inner {
+    rng: ChaCha8Rng,
+    endpoints: HashMap<SocketAddr, mpsc::Sender<ConnectionEvent>>,
+    next_port: u16,
+    min_latency: Duration,
+    max_latency: Duration,
+    loss_rate: f64,
+    blocked: HashSet<(SocketAddr, SocketAddr)>,
+}
+
+impl Inner {
+    fn is_reachable(&self, src: &SocketAddr, dst: &SocketAddr) -> bool {
+        self.endpoints.contains_key(src)
+            && self.endpoints.contains_key(dst)
+            && !self.blocked.contains(&link(src, dst))
+    }
+
+    fn latency(&mut self) -> Duration {
+        if self.max_latency > self.min_latency {
+            let min = self.min_latency.as_nanos() as u64;
+            let max = self.max_latency.as_nanos() as u64;
+            Duration::from_nanos(self.rng.gen_range(min, max))
+        } else {
+            self.min_latency
+        }
+    }
+}
+
+// Endpoint of a single node in the simulated network.
+pub(crate) struct SimEndpoint {
+    network: SimNetwork,
+    addr: SocketAddr,
+}
+
+impl SimEndpoint {
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn connect_to(&self, recipient: &SocketAddr) -> Result<(), TransportError> {
+        let inner = self.network.lock();
+
+        if !inner.endpoints.contains_key(&self.addr) {
+            Err(TransportError::LocallyClosed)
+        } else if inner.is_reachable(&self.addr, recipient) {
+            Ok(())
+        } else {
+            Err(TransportError::Unreachable(*recipient))
+        }
+    }
+
+    pub fn send_message(&self, msg: Bytes, recipient: &SocketAddr) -> Result<(), TransportError> {
+        self.connect_to(recipient)?;
+
+        let mut inner = self.network.lock();
+
+        if inner.loss_rate > 0.0 && inner.rng.gen_bool(inner.loss_rate) {
+            trace!(
+                "Simulated loss of message from {} to {}",
+                self.addr,
+                recipient
+            );
+            return Ok(());
+        }
+
+        let latency = inner.latency();
+        let event_tx = if let Some(event_tx) = inner.endpoints.get(recipient) {
+            event_tx.clone()
+        } else {
+            return Err(TransportError::Unreachable(*recipient));
+        };
+        let src = self.addr;
+
+        let _ = task::spawn(async move {
+            time::sleep(latency).await;
+            let _ = event_tx.send(ConnectionEvent::Received((src, msg))).await;
+        });
+
+        Ok(())
+    }
+
+    pub fn close(&self) {
+        let _ = self.network.lock().endpoints.remove(&self.addr);
+    }
+}
+
+// Links are undirected so normalize the pair of addresses.
+fn link(a: &SocketAddr, b: &SocketAddr) -> (SocketAddr, SocketAddr) {
+    if a < b {
+        (*a, *b)
+    } else {
+        (*b, *a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use assert_matches::assert_matches;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn delivery_with_latency() -> Result<()> {
+        time::pause();
+
+        let network = SimNetwork::new(0);
+        network.set_latency(Duration::from_millis(100), Duration::from_millis(200));
+
+        let (tx0, _rx0) = mpsc::channel(1);
+        let endpoint0 = network.new_endpoint(None, None, tx0);
+        let (tx1, mut rx1) = mpsc::channel(1);
+        let endpoint1 = network.new_endpoint(None, None, tx1);
+
+        let start = Instant::now();
+        let msg = Bytes::from_static(b"hello");
+        endpoint0.send_message(msg.clone(), &endpoint1.socket_addr())?;
+
+        assert_matches!(rx1.recv().await, Some(ConnectionEvent::Received((src, received))) => {
+            assert_eq!(src, endpoint0.socket_addr());
+            assert_eq!(received, msg);
+        });
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_millis(200));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn partition_and_heal() -> Result<()> {
+        let network = SimNetwork::new(0);
+
+        let (tx0, _rx0) = mpsc::channel(1);
+        let endpoint0 = network.new_endpoint(None, None, tx0);
+        let (tx1, mut rx1) = mpsc::channel(1);
+        let endpoint1 = network.new_endpoint(None, None, tx1);
+        let addr1 = endpoint1.socket_addr();
+
+        network.partition(&[endpoint0.socket_addr()], &[addr1]);
+        assert_matches!(
+            endpoint0.send_message(Bytes::from_static(b"lost"), &addr1),
+            Err(TransportError::Unreachable(addr)) => assert_eq!(addr, addr1)
+        );
+
+        network.heal();
+        let msg = Bytes::from_static(b"delivered");
+        endpoint0.send_message(msg.clone(), &addr1)?;
+        assert_matches!(
+            rx1.recv().await,
+            Some(ConnectionEvent::Received((_, received))) => assert_eq!(received, msg)
+        );
+
+        endpoint0.close();
+        assert_matches!(
+            endpoint0.send_message(msg, &addr1),
+            Err(TransportError::LocallyClosed)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn loss_is_deterministic_by_seed() -> Result<()> {
+        let run = |seed| {
+            let network = SimNetwork::new(seed);
+            network.set_loss_rate(0.5);
+
+            let (tx0, _rx0) = mpsc::channel(1);
+            let endpoint0 = network.new_endpoint(None, None, tx0);
+            let (tx1, rx1) = mpsc::channel(100);
+            let endpoint1 = network.new_endpoint(None, None, tx1);
+
+            for index in 0..100u8 {
+                let msg = Bytes::copy_from_slice(&[index]);
+                let _ = endpoint0.send_message(msg, &endpoint1.socket_addr());
+            }
+
+            rx1
+        };
+
+        let collect = |mut rx: mpsc::Receiver<ConnectionEvent>| async move {
+            let mut received = vec![];
+            while let Ok(Some(ConnectionEvent::Received((_, msg)))) =
+                time::timeout(Duration::from_millis(100), rx.recv()).await
+            {
+                received.push(msg);
+            }
+            received.sort();
+            received
+        };
+
+        let first = collect(run(7)).await;
+        let second = collect(run(7)).await;
+
+        assert!(!first.is_empty());
+        assert!(first.len() < 100);
+        assert_eq!(first, second);
+
+        Ok(())
+    }
+}
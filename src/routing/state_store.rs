@@ -0,0 +1,182 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::{Error, Result},
+    network::Network,
+    node::Node,
+    section::{Section, SectionKeyShare},
+};
+use bls::serde_impl::SerdeSecret;
+use ed25519_dalek::Keypair;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+/// Storage for the state of a node which allows it to be restored after a restart.
+///
+/// The state is passed to the store as an opaque blob of bytes. It contains the node keypair and
+/// (if the node is an elder) its BLS secret key share, so implementations should store it
+/// securely.
+pub trait StateStore: Debug + Send + Sync {
+    /// Loads the previously stored state, or returns `None` if nothing has been stored yet.
+    fn load(&self) -> io::Result<Option<Vec<u8>>>;
+
+    /// Stores the state, replacing the previously stored one (if any).
+    fn store(&self, state: &[u8]) -> io::Result<()>;
+}
+
+/// `StateStore` that keeps the state in a single file on the local disk.
+#[derive(Debug)]
+pub struct FileStateStore {
+    path: PathBuf,
+}
+
+impl FileStateStore {
+    /// Creates a store that keeps the state in the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns the path of the file the state is kept in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn load(&self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(state) => Ok(Some(state)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn store(&self, state: &[u8]) -> io::Result<()> {
+        // Write into a temporary file first and then rename it, so a crash in the middle of the
+        // write doesn't leave us with a corrupted state.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, state)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+// State of a node as persisted in the `StateStore`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredState {
+    keypair: Vec<u8>,
+    addr: SocketAddr,
+    section: Section,
+    network: Network,
+    key_share: Option<StoredKeyShare>,
+}
+
+impl StoredState {
+    pub fn new(
+        node: &Node,
+        section: &Section,
+        network: &Network,
+        key_share: Option<&SectionKeyShare>,
+    ) -> Self {
+        Self {
+            keypair: node.keypair.to_bytes().to_vec(),
+            addr: node.addr,
+            section: section.clone(),
+            network: network.clone(),
+            key_share: key_share.map(|share| StoredKeyShare {
+                public_key_set: share.public_key_set.clone(),
+                index: share.index,
+                secret_key_share: SerdeSecret(share.secret_key_share.clone()),
+            }),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn into_parts(
+        self,
+    ) -> Result<(
+        Keypair,
+        SocketAddr,
+        Section,
+        Network,
+        Option<SectionKeyShare>,
+    )> {
+        let keypair = Keypair::from_bytes(&self.keypair).map_err(|_| Error::InvalidStoredState)?;
+        let key_share = self.key_share.map(|share| SectionKeyShare {
+            public_key_set: share.public_key_set,
+            index: share.index,
+            secret_key_share: share.secret_key_share.into_inner(),
+        });
+
+        Ok((keypair, self.addr, self.section, self.network, key_share))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredKeyShare {
+    public_key_set: bls::PublicKeySet,
+    index: usize,
+    secret_key_share: SerdeSecret<bls::SecretKeyShare>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto, network_params::NetworkParams, section::test_utils::gen_addr, MIN_AGE};
+    use anyhow::Result;
+    use xor_name::Prefix;
+
+    #[test]
+    fn store_and_restore_state() -> Result<()> {
+        let node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
+            gen_addr(),
+        );
+        let (section, key_share) = Section::first_node(node.peer(), NetworkParams::default())?;
+        let network = Network::new();
+
+        let path = std::env::temp_dir().join(format!("sn_routing-state-{}", node.name()));
+        let store = FileStateStore::new(&path);
+        assert!(store.load()?.is_none());
+
+        let state = StoredState::new(&node, &section, &network, Some(&key_share));
+        store.store(&state.to_bytes()?)?;
+
+        let bytes = store.load()?.expect("state not stored");
+        let (keypair, addr, restored_section, restored_network, restored_key_share) =
+            StoredState::from_bytes(&bytes)?.into_parts()?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(keypair.to_bytes()[..], node.keypair.to_bytes()[..]);
+        assert_eq!(addr, node.addr);
+        assert_eq!(restored_section, section);
+        assert_eq!(restored_network, network);
+
+        let restored_key_share = restored_key_share.expect("key share not restored");
+        assert_eq!(restored_key_share.public_key_set, key_share.public_key_set);
+        assert_eq!(restored_key_share.index, key_share.index);
+        assert_eq!(
+            restored_key_share.secret_key_share,
+            key_share.secret_key_share
+        );
+
+        Ok(())
+    }
+}
@@ -7,17 +7,24 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    core::{RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY},
-    Comm, Command, Core, Dispatcher,
+    core::CHAIN_KEYS_KEPT, event_stream::event_channel, scheduler::Priority, ClientConfig, Comm,
+    Command, Config, Core, Dispatcher, GossipConfig, JoinQueueConfig, OverflowPolicy, QueueConfig,
+    Routing, SectionPayload, SectionSigningApproval,
 };
 use crate::{
-    agreement::{test_utils::*, Proposal, Proven},
+    agreement::{test_utils::*, DkgFailureProof, DkgFailureReason, DkgKey, Proposal, Proven},
     crypto,
+    error::Error,
     event::Event,
-    messages::{JoinRequest, Message, PlainMessage, ResourceProofResponse, Variant, VerifyStatus},
+    messages::{
+        JoinRejectionReason, JoinRequest, Message, PlainMessage, ResourceProofResponse, Variant,
+        VerifyStatus,
+    },
     network::Network,
+    network_params::NetworkParams,
     node::Node,
     peer::Peer,
+    protocol::{Features, ProtocolInfo, ProtocolUpgrade, INITIAL_PROTOCOL_VERSION},
     relocation::{self, RelocateDetails, RelocatePayload, SignedRelocateDetails},
     section::{
         test_utils::*, EldersInfo, MemberInfo, PeerState, Section, SectionChain, SectionKeyShare,
@@ -39,17 +46,22 @@ use sn_messaging::{
 use std::{
     collections::{BTreeSet, HashSet},
     iter,
-    net::Ipv4Addr,
+    net::{Ipv4Addr, SocketAddr},
     ops::Deref,
+    sync::Arc,
 };
 use tokio::sync::mpsc;
-use tokio::time::{timeout, Duration};
+use tokio::time::{self, timeout, Duration};
 use xor_name::{Prefix, XorName};
 
 #[tokio::test]
 async fn receive_matching_get_section_request_as_elder() -> Result<()> {
     let node = create_node(MIN_AGE + 1);
-    let state = Core::first_node(node, mpsc::unbounded_channel().0)?;
+    let state = Core::first_node(
+        node,
+        NetworkParams::default(),
+        event_channel(&QueueConfig::default()).0,
+    )?;
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     let new_node = Node::new(
@@ -96,7 +108,12 @@ async fn receive_mismatching_get_section_request_as_adult() -> Result<()> {
     let (section, _) = create_section(&sk_set, &elders_info)?;
 
     let node = create_node(MIN_AGE + 1);
-    let state = Core::new(node, section, None, mpsc::unbounded_channel().0);
+    let state = Core::new(
+        node,
+        section,
+        None,
+        event_channel(&QueueConfig::default()).0,
+    );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     let new_node_name = bad_prefix.substituted_in(rand::random());
@@ -138,14 +155,23 @@ async fn receive_mismatching_get_section_request_as_adult() -> Result<()> {
 #[tokio::test]
 async fn receive_join_request_without_resource_proof_response() -> Result<()> {
     let node = create_node(MIN_AGE + 1);
-    let state = Core::first_node(node, mpsc::unbounded_channel().0)?;
+    let state = Core::first_node(
+        node,
+        NetworkParams::default(),
+        event_channel(&QueueConfig::default()).0,
+    )?;
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     let new_node = Node::new(
         crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
         gen_addr(),
     );
-    let section_key = *dispatcher.core.lock().await.section().chain().last_key();
+    let section_key = *dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .section()
+        .chain()
+        .last_key();
 
     let message = Message::single_src(
         &new_node,
@@ -170,7 +196,8 @@ async fn receive_join_request_without_resource_proof_response() -> Result<()> {
         commands.next(),
         Some(Command::SendMessage { message: MessageType::NodeMessage(NodeMessage(message)), .. }) => message
     );
-    let response_message = Message::from_bytes(Bytes::from(response_message))?;
+    let response_message =
+        Message::from_bytes(Bytes::from(response_message), &ProtocolInfo::default())?;
 
     assert_matches!(
         response_message.variant(),
@@ -180,23 +207,131 @@ async fn receive_join_request_without_resource_proof_response() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn receive_join_request_with_custom_network_params() -> Result<()> {
+    let network_params = NetworkParams {
+        min_age: MIN_AGE + 1,
+        resource_proof_data_size: 32,
+        resource_proof_difficulty: 3,
+        ..Default::default()
+    };
+
+    let node = create_node(network_params.min_age + 1);
+    let state = Core::first_node(
+        node,
+        network_params,
+        event_channel(&QueueConfig::default()).0,
+    )?;
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let section_key = *dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .section()
+        .chain()
+        .last_key();
+    let join_request = |age| -> Result<_> {
+        let new_node = Node::new(
+            crypto::gen_keypair(&Prefix::default().range_inclusive(), age),
+            gen_addr(),
+        );
+        let message = Message::single_src(
+            &new_node,
+            DstLocation::Direct,
+            Variant::JoinRequest(Box::new(JoinRequest {
+                section_key,
+                relocate_payload: None,
+                resource_proof_response: None,
+            })),
+            None,
+            None,
+        )?;
+
+        Ok(Command::HandleMessage {
+            sender: Some(new_node.addr),
+            message,
+        })
+    };
+
+    // The age of new nodes is derived from the network params, not from the default.
+    let mut commands = dispatcher
+        .handle_command(join_request(MIN_AGE + 1)?)
+        .await?
+        .into_iter();
+
+    let response_message = assert_matches!(
+        commands.next(),
+        Some(Command::SendMessage { message: MessageType::NodeMessage(NodeMessage(message)), .. }) => message
+    );
+    let response_message =
+        Message::from_bytes(Bytes::from(response_message), &ProtocolInfo::default())?;
+
+    assert_matches!(
+        response_message.variant(),
+        Variant::JoinRejected { reason, retry_after: None } => {
+            assert_eq!(
+                *reason,
+                JoinRejectionReason::AgeMismatch { expected: network_params.min_age + 1 }
+            );
+        }
+    );
+
+    let mut commands = dispatcher
+        .handle_command(join_request(network_params.min_age + 1)?)
+        .await?
+        .into_iter();
+
+    let response_message = assert_matches!(
+        commands.next(),
+        Some(Command::SendMessage { message: MessageType::NodeMessage(NodeMessage(message)), .. }) => message
+    );
+    let response_message =
+        Message::from_bytes(Bytes::from(response_message), &ProtocolInfo::default())?;
+
+    assert_matches!(
+        response_message.variant(),
+        Variant::ResourceChallenge { data_size, difficulty, .. } => {
+            assert_eq!(*data_size, network_params.resource_proof_data_size);
+            assert_eq!(*difficulty, network_params.resource_proof_difficulty);
+        }
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn receive_join_request_with_resource_proof_response() -> Result<()> {
     let node = create_node(MIN_AGE + 1);
-    let state = Core::first_node(node, mpsc::unbounded_channel().0)?;
+    let state = Core::first_node(
+        node,
+        NetworkParams::default(),
+        event_channel(&QueueConfig::default()).0,
+    )?;
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     let new_node = Node::new(
         crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1),
         gen_addr(),
     );
-    let section_key = *dispatcher.core.lock().await.section().chain().last_key();
+    let section_key = *dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .section()
+        .chain()
+        .last_key();
 
     let nonce: [u8; 32] = rand::random();
     let serialized = bincode::serialize(&(new_node.name(), nonce))?;
-    let nonce_signature = crypto::sign(&serialized, &dispatcher.core.lock().await.node().keypair);
+    let nonce_signature = crypto::sign(
+        &serialized,
+        &dispatcher.lock_core(Priority::Normal).await.node().keypair,
+    );
 
-    let rp = ResourceProof::new(RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY);
+    let network_params = NetworkParams::default();
+    let rp = ResourceProof::new(
+        network_params.resource_proof_data_size,
+        network_params.resource_proof_difficulty,
+    );
     let data = rp.create_proof_data(&nonce);
     let mut prover = rp.create_prover(data.clone());
     let solution = prover.solve();
@@ -251,62 +386,95 @@ async fn receive_join_request_with_resource_proof_response() -> Result<()> {
 }
 
 #[tokio::test]
-async fn receive_join_request_from_relocated_node() -> Result<()> {
-    let (elders_info, mut nodes) = create_elders_info();
+async fn receive_join_request_with_full_join_queue() -> Result<()> {
+    let node = create_node(MIN_AGE + 1);
+    let mut state = Core::first_node(
+        node,
+        NetworkParams::default(),
+        event_channel(&QueueConfig::default()).0,
+    )?;
+    let join_queue_config = JoinQueueConfig {
+        capacity: 1,
+        max_in_flight: 0,
+        ..Default::default()
+    };
+    state.set_join_queue_config(join_queue_config);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // The first node takes the only place in the queue but is not admitted yet.
+    let node0 = create_node(MIN_AGE + 1);
+    let commands = dispatcher
+        .handle_command(join_request_with_resource_proof(&dispatcher, &node0).await?)
+        .await?;
+    assert!(commands.is_empty());
+
+    let queue: Vec<_> = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .join_queue()
+        .collect();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].name, node0.name());
+    assert!(!queue[0].admitted);
+
+    // The second one doesn't fit and is told when to retry.
+    let node1 = create_node(MIN_AGE + 1);
+    let mut commands = dispatcher
+        .handle_command(join_request_with_resource_proof(&dispatcher, &node1).await?)
+        .await?
+        .into_iter();
+
+    let (recipients, message) = assert_matches!(
+        commands.next(),
+        Some(Command::SendMessage {
+            recipients,
+            message: MessageType::NodeMessage(NodeMessage(message)),
+            ..
+        }) => (recipients, message)
+    );
+    assert_eq!(recipients, [node1.addr]);
+
+    let message = Message::from_bytes(Bytes::from(message), &ProtocolInfo::default())?;
+    assert_matches!(
+        message.variant(),
+        Variant::JoinRejected { reason, retry_after } => {
+            assert_eq!(*reason, JoinRejectionReason::QueueFull);
+            assert_eq!(*retry_after, Some(join_queue_config.timeout));
+        }
+    );
 
+    Ok(())
+}
+
+#[tokio::test]
+async fn receive_join_request_from_former_member() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
     let sk_set = SecretKeySet::random();
-    let pk_set = sk_set.public_keys();
-    let section_key = pk_set.public_key();
+    let section_key = sk_set.secret_key().public_key();
+
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let former_node = create_node(16);
+    let member_info = MemberInfo::joined(former_node.peer()).leave()?;
+    let member_info = proven(sk_set.secret_key(), member_info)?;
+    let _ = section.update_member(member_info);
 
-    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
     let node = nodes.remove(0);
     let state = Core::new(
         node,
         section,
         Some(section_key_share),
-        mpsc::unbounded_channel().0,
+        event_channel(&QueueConfig::default()).0,
     );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
-    let relocated_node_old_keypair =
-        crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1);
-    let relocated_node_old_name = crypto::name(&relocated_node_old_keypair.public);
-    let relocated_node = Node::new(
-        crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 2),
-        gen_addr(),
-    );
-
-    let relocate_details = RelocateDetails {
-        pub_id: relocated_node_old_name,
-        destination: rand::random(),
-        destination_key: section_key,
-        age: relocated_node.age(),
-    };
-
-    let relocate_message = PlainMessage {
-        src: Prefix::default().name(),
-        dst: DstLocation::Node(relocated_node_old_name),
-        dst_key: section_key,
-        variant: Variant::Relocate(relocate_details),
-    };
-    let signature = sk_set
-        .secret_key()
-        .sign(&bincode::serialize(&relocate_message.as_signable())?);
-    let proof_chain = SectionChain::new(section_key);
-    let relocate_message = Message::section_src(relocate_message, signature, proof_chain)?;
-    let relocate_details = SignedRelocateDetails::new(relocate_message)?;
-    let relocate_payload = RelocatePayload::new(
-        relocate_details,
-        &relocated_node.name(),
-        &relocated_node_old_keypair,
-    );
-
-    let join_request = Message::single_src(
-        &relocated_node,
+    // A former member needs to pass the resource proof too.
+    let message = Message::single_src(
+        &former_node,
         DstLocation::Direct,
         Variant::JoinRequest(Box::new(JoinRequest {
             section_key,
-            relocate_payload: Some(relocate_payload),
+            relocate_payload: None,
             resource_proof_response: None,
         })),
         None,
@@ -315,114 +483,805 @@ async fn receive_join_request_from_relocated_node() -> Result<()> {
 
     let commands = dispatcher
         .handle_command(Command::HandleMessage {
-            sender: Some(relocated_node.addr),
-            message: join_request,
+            sender: Some(former_node.addr),
+            message,
         })
         .await?;
+    let challenged = commands.into_iter().any(|command| match command {
+        Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        } => Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())
+            .map_or(false, |message| {
+                matches!(message.variant(), Variant::ResourceChallenge { .. })
+            }),
+        _ => false,
+    });
+    assert!(challenged);
 
-    let mut online_proposed = false;
+    let command = join_request_with_resource_proof(&dispatcher, &former_node).await?;
+    let message = dispatcher
+        .handle_command(command)
+        .await?
+        .into_iter()
+        .find_map(|command| match command {
+            Command::HandleMessage { message, .. } => Some(message),
+            _ => None,
+        });
+
+    let message = assert_matches!(message, Some(message) => message);
+    let proposal = assert_matches!(
+        message.variant(),
+        Variant::Propose { content, .. } => content.clone()
+    );
+
+    assert_matches!(
+        &proposal,
+        Proposal::Online { member_info, previous_name, .. } => {
+            assert_eq!(*member_info.peer.name(), former_node.name());
+            assert_eq!(member_info.peer.age(), 16);
+            assert_eq!(member_info.state, PeerState::Joined);
+            assert_eq!(*previous_name, None);
+        }
+    );
+
+    // Once agreed on, the former member is approved and relocated with half its age.
+    let proof = prove(sk_set.secret_key(), &proposal.as_signable())?;
+    let commands = dispatcher
+        .handle_command(Command::HandleAgreement { proposal, proof })
+        .await?;
+
+    let mut node_approval_sent = false;
+    let mut relocate_details = None;
 
     for command in commands {
         let message = match command {
-            Command::HandleMessage { message, .. } => message,
-            _ => continue,
-        };
-        let proposal = match message.variant() {
-            Variant::Propose { content, .. } => content,
+            Command::SendMessage {
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
             _ => continue,
         };
 
-        if let Proposal::Online {
-            member_info,
-            previous_name,
-            their_knowledge,
-        } = proposal
-        {
-            assert_eq!(member_info.peer, relocated_node.peer());
-            assert_eq!(member_info.state, PeerState::Joined);
-            assert_eq!(*previous_name, Some(relocated_node_old_name));
-            assert_eq!(*their_knowledge, Some(section_key));
-
-            online_proposed = true;
+        match message.variant() {
+            Variant::NodeApproval { .. } => node_approval_sent = true,
+            Variant::Relocate(details) if details.pub_id == former_node.name() => {
+                relocate_details = Some(details.clone())
+            }
+            _ => continue,
         }
     }
 
-    assert!(online_proposed);
+    assert!(node_approval_sent);
+    assert_matches!(relocate_details, Some(details) => assert_eq!(details.age, 8));
 
     Ok(())
 }
 
 #[tokio::test]
-async fn aggregate_proposals() -> Result<()> {
-    let (elders_info, nodes) = create_elders_info();
+async fn receive_join_request_from_young_former_member() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
     let sk_set = SecretKeySet::random();
-    let pk_set = sk_set.public_keys();
-    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let section_key = sk_set.secret_key().public_key();
+
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    // Too young to be relocated on rejoin.
+    let former_node = create_node(MIN_AGE + 2);
+    let member_info = MemberInfo::joined(former_node.peer()).leave()?;
+    let member_info = proven(sk_set.secret_key(), member_info)?;
+    let _ = section.update_member(member_info);
+
+    let node = nodes.remove(0);
     let state = Core::new(
-        nodes[0].clone(),
+        node,
         section,
         Some(section_key_share),
-        mpsc::unbounded_channel().0,
+        event_channel(&QueueConfig::default()).0,
     );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
-    let new_peer = create_peer(MIN_AGE);
-    let member_info = MemberInfo::joined(new_peer);
-    let proposal = Proposal::Online {
-        member_info,
-        previous_name: None,
-        their_knowledge: None,
-    };
-
-    for index in 0..THRESHOLD {
-        let proof_share = proposal.prove(pk_set.clone(), index, &sk_set.secret_key_share(index))?;
-        let message = Message::single_src(
-            &nodes[index],
-            DstLocation::Direct,
-            Variant::Propose {
-                content: proposal.clone(),
-                proof_share,
-            },
-            None,
-            None,
-        )?;
-
-        let commands = dispatcher
-            .handle_command(Command::HandleMessage {
-                message,
-                sender: Some(nodes[0].addr),
-            })
-            .await?;
-        assert!(commands.is_empty());
-    }
-
-    let proof_share = proposal.prove(
-        pk_set.clone(),
-        THRESHOLD,
-        &sk_set.secret_key_share(THRESHOLD),
-    )?;
     let message = Message::single_src(
-        &nodes[THRESHOLD],
+        &former_node,
         DstLocation::Direct,
-        Variant::Propose {
-            content: proposal.clone(),
-            proof_share,
-        },
+        Variant::JoinRequest(Box::new(JoinRequest {
+            section_key,
+            relocate_payload: None,
+            resource_proof_response: None,
+        })),
+        None,
+        None,
+    )?;
+
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            sender: Some(former_node.addr),
+            message,
+        })
+        .await?;
+
+    let reason = commands.into_iter().find_map(|command| match command {
+        Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        } => match Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())
+            .ok()?
+            .variant()
+        {
+            Variant::JoinRejected { reason, .. } => Some(*reason),
+            _ => None,
+        },
+        _ => None,
+    });
+    assert_eq!(reason, Some(JoinRejectionReason::FormerMember));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn receive_join_request_with_wrong_prefix() -> Result<()> {
+    let our_prefix: Prefix = "0".parse().unwrap();
+    let their_prefix: Prefix = "1".parse().unwrap();
+
+    let (our_elders_info, mut nodes) = gen_elders_info(our_prefix, ELDER_SIZE);
+    let (their_elders_info, _) = gen_elders_info(their_prefix, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &our_elders_info)?;
+    let section_key = *section.chain().last_key();
+
+    let new_node = Node::new(
+        crypto::gen_keypair(&their_prefix.range_inclusive(), MIN_AGE + 1),
+        gen_addr(),
+    );
+    let join_request = || -> Result<_> {
+        let message = Message::single_src(
+            &new_node,
+            DstLocation::Direct,
+            Variant::JoinRequest(Box::new(JoinRequest {
+                section_key,
+                relocate_payload: None,
+                resource_proof_response: None,
+            })),
+            None,
+            None,
+        )?;
+
+        Ok(Command::HandleMessage {
+            sender: Some(new_node.addr),
+            message,
+        })
+    };
+    let response = |commands: Vec<Command>| -> Result<Variant> {
+        let message = assert_matches!(
+            commands.into_iter().next(),
+            Some(Command::SendMessage {
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            }) => msg_bytes
+        );
+        Ok(
+            Message::from_bytes(Bytes::from(message), &ProtocolInfo::default())?
+                .variant()
+                .clone(),
+        )
+    };
+
+    // Without knowing the section of the node, we let it try again later.
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let state = Core::new(
+        nodes[0].clone(),
+        section.clone(),
+        Some(create_section_key_share(&sk_set, 0)),
+        event_tx,
+    );
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let commands = dispatcher.handle_command(join_request()?).await?;
+    assert_matches!(
+        response(commands)?,
+        Variant::JoinRejected {
+            reason: JoinRejectionReason::WrongPrefix,
+            retry_after: Some(_),
+        }
+    );
+
+    // Otherwise we point it to its section.
+    let their_key = bls::SecretKey::random().public_key();
+    let mut network = Network::new();
+    assert!(network.update_section(
+        proven(sk_set.secret_key(), their_elders_info.clone())?,
+        None,
+        section.chain(),
+    ));
+    let _ = network.update_their_key(proven(sk_set.secret_key(), (their_prefix, their_key))?);
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = nodes.remove(0);
+    let state = Core::restore(node, section, network, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let commands = dispatcher.handle_command(join_request()?).await?;
+    assert_matches!(
+        response(commands)?,
+        Variant::JoinRetry { elders_info, section_key } => {
+            assert_eq!(elders_info, their_elders_info);
+            assert_eq!(section_key, their_key);
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn receive_join_request_from_relocated_node() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+
+    let sk_set = SecretKeySet::random();
+    let pk_set = sk_set.public_keys();
+    let section_key = pk_set.public_key();
+
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let state = Core::new(
+        node,
+        section,
+        Some(section_key_share),
+        event_channel(&QueueConfig::default()).0,
+    );
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let relocated_node_old_keypair =
+        crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 1);
+    let relocated_node_old_name = crypto::name(&relocated_node_old_keypair.public);
+    let relocated_node = Node::new(
+        crypto::gen_keypair(&Prefix::default().range_inclusive(), MIN_AGE + 2),
+        gen_addr(),
+    );
+
+    let relocate_details = RelocateDetails {
+        pub_id: relocated_node_old_name,
+        destination: rand::random(),
+        destination_key: section_key,
+        age: relocated_node.age(),
+    };
+
+    let relocate_message = PlainMessage {
+        src: Prefix::default().name(),
+        dst: DstLocation::Node(relocated_node_old_name),
+        dst_key: section_key,
+        protocol: ProtocolInfo::section(INITIAL_PROTOCOL_VERSION),
+        variant: Variant::Relocate(relocate_details),
+    };
+    let signature = sk_set
+        .secret_key()
+        .sign(&bincode::serialize(&relocate_message.as_signable())?);
+    let proof_chain = SectionChain::new(section_key);
+    let relocate_message = Message::section_src(relocate_message, signature, proof_chain)?;
+    let relocate_details = SignedRelocateDetails::new(relocate_message)?;
+    let relocate_payload = RelocatePayload::new(
+        relocate_details,
+        &relocated_node.name(),
+        &relocated_node_old_keypair,
+    );
+
+    let join_request = Message::single_src(
+        &relocated_node,
+        DstLocation::Direct,
+        Variant::JoinRequest(Box::new(JoinRequest {
+            section_key,
+            relocate_payload: Some(relocate_payload),
+            resource_proof_response: None,
+        })),
+        None,
+        None,
+    )?;
+
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            sender: Some(relocated_node.addr),
+            message: join_request,
+        })
+        .await?;
+
+    let mut online_proposed = false;
+
+    for command in commands {
+        let message = match command {
+            Command::HandleMessage { message, .. } => message,
+            _ => continue,
+        };
+        let proposal = match message.variant() {
+            Variant::Propose { content, .. } => content,
+            _ => continue,
+        };
+
+        if let Proposal::Online {
+            member_info,
+            previous_name,
+            their_knowledge,
+        } = proposal
+        {
+            assert_eq!(member_info.peer, relocated_node.peer());
+            assert_eq!(member_info.state, PeerState::Joined);
+            assert_eq!(*previous_name, Some(relocated_node_old_name));
+            assert_eq!(*their_knowledge, Some(section_key));
+
+            online_proposed = true;
+        }
+    }
+
+    assert!(online_proposed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn aggregate_proposals() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let pk_set = sk_set.public_keys();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Core::new(
+        nodes[0].clone(),
+        section,
+        Some(section_key_share),
+        event_channel(&QueueConfig::default()).0,
+    );
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let new_peer = create_peer(MIN_AGE);
+    let member_info = MemberInfo::joined(new_peer);
+    let proposal = Proposal::Online {
+        member_info,
+        previous_name: None,
+        their_knowledge: None,
+    };
+
+    for index in 0..THRESHOLD {
+        let proof_share = proposal.prove(pk_set.clone(), index, &sk_set.secret_key_share(index))?;
+        let message = Message::single_src(
+            &nodes[index],
+            DstLocation::Direct,
+            Variant::Propose {
+                content: proposal.clone(),
+                proof_share,
+            },
+            None,
+            None,
+        )?;
+
+        let commands = dispatcher
+            .handle_command(Command::HandleMessage {
+                message,
+                sender: Some(nodes[0].addr),
+            })
+            .await?;
+        assert!(commands.is_empty());
+    }
+
+    let proof_share = proposal.prove(
+        pk_set.clone(),
+        THRESHOLD,
+        &sk_set.secret_key_share(THRESHOLD),
+    )?;
+    let message = Message::single_src(
+        &nodes[THRESHOLD],
+        DstLocation::Direct,
+        Variant::Propose {
+            content: proposal.clone(),
+            proof_share,
+        },
+        None,
+        None,
+    )?;
+    let mut commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(nodes[THRESHOLD].addr),
+        })
+        .await?
+        .into_iter();
+
+    assert_matches!(
+        commands.next(),
+        Some(Command::HandleAgreement { proposal: agreement, .. }) => {
+            assert_eq!(agreement, proposal);
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sign_as_section() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let pk_set = sk_set.public_keys();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let chain = section.chain().clone();
+    let state = Core::new(
+        nodes[0].clone(),
+        section,
+        Some(section_key_share),
+        event_channel(&QueueConfig::default()).0,
+    );
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // All the elders advertise support for section signing.
+    for node in &nodes[1..] {
+        let _ = send_sync(&dispatcher, node).await?;
+    }
+
+    let value = SectionPayload("hello".to_string());
+    let payload = Bytes::from(bincode::serialize(&value)?);
+    let (rx, commands) = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .sign_as_section(payload.clone(), Duration::from_secs(10))?;
+    let proposal = Proposal::SectionSigned(payload);
+
+    // We propose the payload to all the elders, including us.
+    let proposed = commands.iter().any(|command| match command {
+        Command::HandleMessage { message, .. } => matches!(
+            message.variant(),
+            Variant::Propose { content, .. } if *content == proposal
+        ),
+        _ => false,
+    });
+    assert!(proposed);
+
+    // Receiving the shares of the other elders doesn't make us sign the payload again.
+    let mut agreement = None;
+    for index in 1..=THRESHOLD + 1 {
+        let proof_share = proposal.prove(pk_set.clone(), index, &sk_set.secret_key_share(index))?;
+        let message = Message::single_src(
+            &nodes[index],
+            DstLocation::Direct,
+            Variant::Propose {
+                content: proposal.clone(),
+                proof_share,
+            },
+            None,
+            None,
+        )?;
+
+        let commands = dispatcher
+            .handle_command(Command::HandleMessage {
+                message,
+                sender: Some(nodes[index].addr),
+            })
+            .await?;
+
+        for command in commands {
+            match command {
+                Command::HandleAgreement { .. } => agreement = Some(command),
+                command => panic!("unexpected command {:?}", command),
+            }
+        }
+    }
+
+    let agreement = agreement.expect("the shares should aggregate");
+    let _ = dispatcher.handle_command(agreement).await?;
+
+    let (proof, section_chain) = rx.await??;
+    let proven = Proven::new(value, proof);
+    assert!(proven.verify(&section_chain));
+    assert_eq!(section_chain, chain);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sign_as_section_unsupported_by_elders() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Core::new(
+        nodes[0].clone(),
+        section,
+        Some(section_key_share),
+        event_channel(&QueueConfig::default()).0,
+    );
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // One of the elders doesn't advertise section signing, another one advertises nothing yet.
+    let mut old_node = nodes[2].clone();
+    old_node.protocol.features = Features::CHAIN_CHECKPOINTS;
+    let _ = send_sync(&dispatcher, &old_node).await?;
+    for node in &nodes[3..] {
+        let _ = send_sync(&dispatcher, node).await?;
+    }
+
+    let payload = Bytes::from(bincode::serialize(&SectionPayload(42u64))?);
+    let result = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .sign_as_section(payload.clone(), Duration::from_secs(10));
+    assert_matches!(result, Err(Error::FeatureNotSupported));
+
+    // Still not supported once the silent elder advertises its features.
+    let _ = send_sync(&dispatcher, &nodes[1]).await?;
+    let result = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .sign_as_section(payload, Duration::from_secs(10));
+    assert_matches!(result, Err(Error::FeatureNotSupported));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cosign_section_payload() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let pk_set = sk_set.public_keys();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let mut state = Core::new(
+        nodes[0].clone(),
+        section,
+        Some(section_key_share),
+        event_channel(&QueueConfig::default()).0,
+    );
+    state.set_section_signing_approval(Some(SectionSigningApproval::new(|value| {
+        bincode::deserialize::<u64>(value).map_or(false, |value| value != 7)
+    })));
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let propose = |sender: &Node, index: usize, payload: Bytes| -> Result<Message> {
+        let proposal = Proposal::SectionSigned(payload);
+        let proof_share = proposal.prove(pk_set.clone(), index, &sk_set.secret_key_share(index))?;
+        Ok(Message::single_src(
+            sender,
+            DstLocation::Direct,
+            Variant::Propose {
+                content: proposal,
+                proof_share,
+            },
+            None,
+            None,
+        )?)
+    };
+    let count_our_shares = |commands: &[Command]| {
+        commands
+            .iter()
+            .filter(|command| match command {
+                Command::HandleMessage { message, .. } => {
+                    matches!(message.variant(), Variant::Propose { .. })
+                }
+                _ => false,
+            })
+            .count()
+    };
+
+    // An application payload proposed by another elder is co-signed, but only once.
+    let payload = Bytes::from(bincode::serialize(&SectionPayload(42u64))?);
+    for (index, expected) in &[(1, 1), (2, 0)] {
+        let commands = dispatcher
+            .handle_command(Command::HandleMessage {
+                message: propose(&nodes[*index], *index, payload.clone())?,
+                sender: Some(nodes[*index].addr),
+            })
+            .await?;
+        assert_eq!(count_our_shares(&commands), *expected);
+    }
+
+    // A payload the application doesn't approve is never co-signed.
+    let payload = Bytes::from(bincode::serialize(&SectionPayload(7u64))?);
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: propose(&nodes[1], 1, payload)?,
+            sender: Some(nodes[1].addr),
+        })
+        .await?;
+    assert_eq!(count_our_shares(&commands), 0);
+
+    // Anything else is never co-signed.
+    let payload = Bytes::from(bincode::serialize(&42u64)?);
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: propose(&nodes[1], 1, payload)?,
+            sender: Some(nodes[1].addr),
+        })
+        .await?;
+    assert_eq!(count_our_shares(&commands), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cosign_section_payload_from_non_elder() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let pk_set = sk_set.public_keys();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let mut state = Core::new(
+        nodes[0].clone(),
+        section,
+        Some(section_key_share),
+        event_channel(&QueueConfig::default()).0,
+    );
+    state.set_section_signing_approval(Some(SectionSigningApproval::new(|_| true)));
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let count_our_shares = |commands: &[Command]| {
+        commands
+            .iter()
+            .filter(|command| match command {
+                Command::HandleMessage { message, .. } => {
+                    matches!(message.variant(), Variant::Propose { .. })
+                }
+                _ => false,
+            })
+            .count()
+    };
+
+    // A payload proposed by a non-elder is not co-signed, even with a valid share.
+    let non_elder = create_node(MIN_AGE + 1);
+    let proposal =
+        Proposal::SectionSigned(Bytes::from(bincode::serialize(&SectionPayload(42u64))?));
+    let proof_share = proposal.prove(pk_set.clone(), 1, &sk_set.secret_key_share(1))?;
+    let message = Message::single_src(
+        &non_elder,
+        DstLocation::Direct,
+        Variant::Propose {
+            content: proposal,
+            proof_share,
+        },
+        None,
+        None,
+    )?;
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(non_elder.addr),
+        })
+        .await?;
+    assert_eq!(count_our_shares(&commands), 0);
+
+    // Neither is a payload proposed by an elder with a share of another key.
+    let other_sk_set = SecretKeySet::random();
+    let proposal =
+        Proposal::SectionSigned(Bytes::from(bincode::serialize(&SectionPayload(43u64))?));
+    let proof_share = proposal.prove(
+        other_sk_set.public_keys(),
+        1,
+        &other_sk_set.secret_key_share(1),
+    )?;
+    let message = Message::single_src(
+        &nodes[1],
+        DstLocation::Direct,
+        Variant::Propose {
+            content: proposal,
+            proof_share,
+        },
+        None,
+        None,
+    )?;
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(nodes[1].addr),
+        })
+        .await?;
+    assert_eq!(count_our_shares(&commands), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrade_protocol() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
+    let mut state = Core::new(nodes[0].clone(), section, Some(section_key_share), event_tx);
+
+    let protocol = ProtocolInfo {
+        min_version: 1,
+        max_version: 2,
+        features: Features::all(),
+    };
+    state.set_protocol_info(protocol);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let upgrade = Proposal::ProtocolUpgrade(ProtocolUpgrade { version: 2 });
+    let proposes_upgrade = |commands: &[Command]| {
+        commands.iter().any(|command| match command {
+            Command::HandleMessage { message, .. } => matches!(
+                message.variant(),
+                Variant::Propose { content, .. } if *content == upgrade
+            ),
+            _ => false,
+        })
+    };
+
+    // We propose the upgrade once a supermajority of the elders (including us) support it.
+    for index in 1..supermajority(ELDER_SIZE) {
+        let mut node = nodes[index].clone();
+        node.protocol = protocol;
+        let commands = send_sync(&dispatcher, &node).await?;
+
+        assert_eq!(
+            proposes_upgrade(&commands),
+            index == supermajority(ELDER_SIZE) - 1
+        );
+    }
+
+    // This elder keeps speaking the old version only.
+    let old_node = &nodes[ELDER_SIZE - 1];
+    let _ = send_sync(&dispatcher, old_node).await?;
+
+    let proof = proven(sk_set.secret_key(), ProtocolUpgrade { version: 2 })?.proof;
+    let commands = dispatcher
+        .handle_command(Command::HandleAgreement {
+            proposal: upgrade,
+            proof,
+        })
+        .await?;
+
+    assert_eq!(
+        dispatcher
+            .lock_core(Priority::Normal)
+            .await
+            .section()
+            .protocol_version(),
+        2
+    );
+
+    // The elder that doesn't speak the new version is proposed offline.
+    let proposes_offline = commands.iter().any(|command| match command {
+        Command::HandleMessage { message, .. } => matches!(
+            message.variant(),
+            Variant::Propose { content: Proposal::Offline(info), .. }
+                if info.peer.name() == &old_node.name()
+        ),
+        _ => false,
+    });
+    assert!(proposes_offline);
+    assert_matches!(
+        event_rx.next().await,
+        Some(Event::ProtocolUpgraded { version: 2 })
+    );
+
+    // Nodes that don't speak the new version can't join anymore.
+    let new_node = create_node(MIN_AGE + 1);
+    let section_key = *dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .section()
+        .chain()
+        .last_key();
+    let message = Message::single_src(
+        &new_node,
+        DstLocation::Direct,
+        Variant::JoinRequest(Box::new(JoinRequest {
+            section_key,
+            relocate_payload: None,
+            resource_proof_response: None,
+        })),
         None,
         None,
     )?;
     let mut commands = dispatcher
         .handle_command(Command::HandleMessage {
             message,
-            sender: Some(nodes[THRESHOLD].addr),
+            sender: Some(new_node.addr),
         })
         .await?
         .into_iter();
 
-    assert_matches!(
+    let response_message = assert_matches!(
         commands.next(),
-        Some(Command::HandleAgreement { proposal: agreement, .. }) => {
-            assert_eq!(agreement, proposal);
+        Some(Command::SendMessage { message: MessageType::NodeMessage(NodeMessage(message)), .. }) => message
+    );
+    let response_message =
+        Message::from_bytes(Bytes::from(response_message), &ProtocolInfo::default())?;
+
+    assert_matches!(
+        response_message.variant(),
+        Variant::JoinRejected {
+            reason: JoinRejectionReason::IncompatibleProtocol { section_version: 2 },
+            retry_after: None,
         }
     );
 
@@ -431,7 +1290,7 @@ async fn aggregate_proposals() -> Result<()> {
 
 #[tokio::test]
 async fn handle_agreement_on_online() -> Result<()> {
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
 
     let prefix = Prefix::default();
 
@@ -447,7 +1306,7 @@ async fn handle_agreement_on_online() -> Result<()> {
     let status = handle_online_command(&new_peer, &sk_set, &dispatcher, &elders_info).await?;
     assert!(status.node_approval_sent);
 
-    assert_matches!(event_rx.recv().await, Some(Event::MemberJoined { name, age, .. }) => {
+    assert_matches!(event_rx.next().await, Some(Event::MemberJoined { name, age, .. }) => {
         assert_eq!(name, *new_peer.name());
         assert_eq!(age, MIN_AGE);
     });
@@ -466,16 +1325,18 @@ async fn handle_agreement_on_online_of_elder_candidate() -> Result<()> {
     let elders_info = EldersInfo::new(nodes.iter().map(Node::peer), Prefix::default());
     let proven_elders_info = proven(sk_set.secret_key(), elders_info.clone())?;
 
-    let mut section = Section::new(*chain.root_key(), chain, proven_elders_info)?;
+    let mut section = Section::new(
+        *chain.root_key(),
+        proven(sk_set.secret_key(), NetworkParams::default())?,
+        chain,
+        proven_elders_info,
+    )?;
     let mut expected_new_elders = BTreeSet::new();
 
     for peer in elders_info.elders.values() {
         let member_info = MemberInfo::joined(*peer);
         let proof = prove(sk_set.secret_key(), &member_info)?;
-        let _ = section.update_member(Proven {
-            value: member_info,
-            proof,
-        });
+        let _ = section.update_member(Proven::new(member_info, proof));
         if peer.age() == MIN_AGE + 2 {
             let _ = expected_new_elders.insert(*peer);
         }
@@ -488,7 +1349,7 @@ async fn handle_agreement_on_online_of_elder_candidate() -> Result<()> {
         node,
         section,
         Some(section_key_share),
-        mpsc::unbounded_channel().0,
+        event_channel(&QueueConfig::default()).0,
     );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
@@ -504,12 +1365,226 @@ async fn handle_agreement_on_online_of_elder_candidate() -> Result<()> {
     let proof = prove(sk_set.secret_key(), &proposal.as_signable())?;
 
     let commands = dispatcher
-        .handle_command(Command::HandleAgreement { proposal, proof })
+        .handle_command(Command::HandleAgreement { proposal, proof })
+        .await?;
+
+    // Verify we sent a `DkgStart` message with the expected participants.
+    let mut dkg_start_sent = false;
+    let _ = expected_new_elders.insert(new_peer);
+
+    for command in commands {
+        let (recipients, message) = match command {
+            Command::SendMessage {
+                recipients,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
+            _ => continue,
+        };
+
+        let actual_elders_info = match message.variant() {
+            Variant::DkgStart { elders_info, .. } => elders_info,
+            _ => continue,
+        };
+        itertools::assert_equal(
+            actual_elders_info.elders.values().cloned(),
+            expected_new_elders.clone(),
+        );
+
+        let expected_dkg_start_recipients: Vec<_> = expected_new_elders
+            .iter()
+            .filter(|peer| *peer.name() != node_name)
+            .map(Peer::addr)
+            .copied()
+            .collect();
+        assert_eq!(recipients, expected_dkg_start_recipients);
+
+        dkg_start_sent = true;
+    }
+
+    assert!(dkg_start_sent);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn abort_dkg_session() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let pk = sk_set.secret_key().public_key();
+    let chain = SectionChain::new(pk);
+
+    // The first node is a member but not an elder, so the section needs a DKG to promote it.
+    let mut nodes = gen_sorted_nodes(&Prefix::default(), ELDER_SIZE, false);
+    let elders_info = EldersInfo::new(nodes.iter().skip(1).map(Node::peer), Prefix::default());
+    let mut section = Section::new(
+        pk,
+        proven(sk_set.secret_key(), NetworkParams::default())?,
+        chain.clone(),
+        proven(sk_set.secret_key(), elders_info)?,
+    )?;
+
+    for node in &nodes {
+        let member_info = MemberInfo::joined(node.peer());
+        let proof = prove(sk_set.secret_key(), &member_info)?;
+        let _ = section.update_member(Proven::new(member_info, proof));
+    }
+
+    let new_elders_info = EldersInfo::new(nodes.iter().map(Node::peer), Prefix::default());
+    let node = nodes.remove(1);
+    assert_eq!(
+        section.promote_and_demote_elders(&node.name()),
+        vec![new_elders_info.clone()]
+    );
+
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
+    let section_key_share = create_section_key_share(&sk_set, 0);
+    let state = Core::new(node, section, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // Start the DKG session.
+    let dkg_key = DkgKey::new(&new_elders_info, 1);
+    let message = PlainMessage {
+        src: Prefix::default().name(),
+        dst: DstLocation::Direct,
+        dst_key: pk,
+        protocol: ProtocolInfo::section(INITIAL_PROTOCOL_VERSION),
+        variant: Variant::DkgStart {
+            dkg_key,
+            elders_info: new_elders_info.clone(),
+        },
+    };
+    let signature = sk_set
+        .secret_key()
+        .sign(&bincode::serialize(&message.as_signable())?);
+    let message = Message::section_src(message, signature, chain)?;
+
+    let _ = dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: None,
+        })
+        .await?;
+
+    assert_matches!(
+        event_rx.next().await,
+        Some(Event::DkgStarted { dkg_key: key, elders_info }) => {
+            assert_eq!(key, dkg_key);
+            assert_eq!(elders_info, new_elders_info);
+        }
+    );
+
+    let sessions = dispatcher.lock_core(Priority::Normal).await.dkg_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].dkg_key, dkg_key);
+    assert!(!sessions[0].complete);
+
+    // Abort the session. Our failure is reported to the other participants.
+    let commands = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .abort_dkg(&dkg_key)?
+        .expect("session not found");
+    let observation_sent = commands.into_iter().any(|command| match command {
+        Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        } => Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default()).map_or(
+            false,
+            |message| {
+                matches!(
+                    message.variant(),
+                    Variant::DkgFailureObservation { dkg_key: key, .. } if *key == dkg_key
+                )
+            },
+        ),
+        _ => false,
+    });
+    assert!(observation_sent);
+
+    // The session is kept, but we no longer take part in it.
+    let sessions = dispatcher.lock_core(Priority::Normal).await.dkg_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].failure, Some(DkgFailureReason::Aborted));
+    assert!(sessions[0].complete);
+
+    // Once enough participants observe the failure, the agreement is sent to the elders.
+    let mut failure_commands = vec![];
+    for other in &nodes[..2] {
+        let message = Message::single_src(
+            other,
+            DstLocation::Direct,
+            Variant::DkgFailureObservation {
+                dkg_key,
+                proof: DkgFailureProof::new(&other.keypair, &dkg_key),
+            },
+            None,
+            None,
+        )?;
+        failure_commands.extend(
+            dispatcher
+                .handle_command(Command::HandleMessage {
+                    message,
+                    sender: Some(other.addr),
+                })
+                .await?,
+        );
+    }
+
+    let failure_command = failure_commands
+        .into_iter()
+        .find(|command| matches!(command, Command::HandleDkgFailure { .. }))
+        .expect("DKG failure not agreed on");
+    let commands = dispatcher.handle_command(failure_command).await?;
+
+    assert_matches!(
+        event_rx.next().await,
+        Some(Event::DkgFailed { dkg_key: key, reason, .. }) => {
+            assert_eq!(key, dkg_key);
+            assert_eq!(reason, Some(DkgFailureReason::Aborted));
+        }
+    );
+
+    let proofs = commands
+        .into_iter()
+        .find_map(|command| match command {
+            Command::SendMessage {
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => match Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())
+                .ok()?
+                .variant()
+            {
+                Variant::DkgFailureAgreement {
+                    dkg_key: key,
+                    proofs,
+                } if *key == dkg_key => Some(proofs.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .expect("DKG failure agreement not sent");
+
+    // The elders restart the session with a fresh generation on the failure agreement from a
+    // participant.
+    let sender = &nodes[0];
+    let message = Message::single_src(
+        sender,
+        DstLocation::Direct,
+        Variant::DkgFailureAgreement { dkg_key, proofs },
+        None,
+        None,
+    )?;
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(sender.addr),
+        })
         .await?;
 
-    // Verify we sent a `DkgStart` message with the expected participants.
     let mut dkg_start_sent = false;
-    let _ = expected_new_elders.insert(new_peer);
 
     for command in commands {
         let (recipients, message) = match command {
@@ -517,32 +1592,35 @@ async fn handle_agreement_on_online_of_elder_candidate() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
-            _ => continue,
-        };
-
-        let actual_elders_info = match message.variant() {
-            Variant::DkgStart { elders_info, .. } => elders_info,
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
-        itertools::assert_equal(
-            actual_elders_info.elders.values().cloned(),
-            expected_new_elders.clone(),
-        );
-
-        let expected_dkg_start_recipients: Vec<_> = expected_new_elders
-            .iter()
-            .filter(|peer| *peer.name() != node_name)
-            .map(Peer::addr)
-            .copied()
-            .collect();
-        assert_eq!(recipients, expected_dkg_start_recipients);
 
-        dkg_start_sent = true;
+        if let Variant::DkgStart {
+            dkg_key: restarted_dkg_key,
+            elders_info,
+        } = message.variant()
+        {
+            assert_eq!(*restarted_dkg_key, DkgKey::new(&new_elders_info, 2));
+            assert_eq!(*elders_info, new_elders_info);
+            assert_eq!(recipients, [sender.addr]);
+            dkg_start_sent = true;
+        }
     }
 
     assert!(dkg_start_sent);
 
+    // Aborting an unknown session does nothing.
+    let unknown_dkg_key = DkgKey::new(&new_elders_info, 3);
+    assert!(dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .abort_dkg(&unknown_dkg_key)?
+        .is_none());
+
     Ok(())
 }
 
@@ -576,7 +1654,10 @@ async fn handle_online_command(
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (Message::from_bytes(Bytes::from(msg_bytes))?, recipients),
+            } => (
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+                recipients,
+            ),
             _ => continue,
         };
 
@@ -634,7 +1715,7 @@ async fn handle_agreement_on_online_of_rejoined_node(phase: NetworkPhase, age: u
     let _ = section.update_member(member_info);
 
     // Make a Node
-    let (event_tx, _event_rx) = mpsc::unbounded_channel();
+    let (event_tx, _event_rx) = event_channel(&QueueConfig::default());
     let node = nodes.remove(0);
     let state = Core::new(node, section, Some(section_key_share), event_tx);
     let dispatcher = Dispatcher::new(state, create_comm().await?);
@@ -690,7 +1771,7 @@ async fn handle_agreement_on_offline_of_non_elder() -> Result<()> {
     let member_info = proven(sk_set.secret_key(), member_info)?;
     let _ = section.update_member(member_info);
 
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
     let node = nodes.remove(0);
     let state = Core::new(node, section, Some(section_key_share), event_tx);
     let dispatcher = Dispatcher::new(state, create_comm().await?);
@@ -706,7 +1787,7 @@ async fn handle_agreement_on_offline_of_non_elder() -> Result<()> {
         .handle_command(Command::HandleAgreement { proposal, proof })
         .await?;
 
-    assert_matches!(event_rx.recv().await, Some(Event::MemberLeft { name, age, }) => {
+    assert_matches!(event_rx.next().await, Some(Event::MemberLeft { name, age, }) => {
         assert_eq!(name, *existing_peer.name());
         assert_eq!(age, MIN_AGE);
     });
@@ -740,7 +1821,7 @@ async fn handle_agreement_on_offline_of_elder() -> Result<()> {
         .leave()?;
 
     // Create our node
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
     let node = nodes.remove(0);
     let node_name = node.name();
     let state = Core::new(node, section, Some(section_key_share), event_tx);
@@ -763,7 +1844,10 @@ async fn handle_agreement_on_offline_of_elder() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -794,14 +1878,13 @@ async fn handle_agreement_on_offline_of_elder() -> Result<()> {
 
     assert!(dkg_start_sent);
 
-    assert_matches!(event_rx.recv().await, Some(Event::MemberLeft { name, .. }) => {
+    assert_matches!(event_rx.next().await, Some(Event::MemberLeft { name, .. }) => {
         assert_eq!(name, *remove_peer.name());
     });
 
     // The removed peer is still our elder because we haven't yet processed the section update.
     assert!(dispatcher
-        .core
-        .lock()
+        .lock_core(Priority::Normal)
         .await
         .section()
         .elders_info()
@@ -811,6 +1894,154 @@ async fn handle_agreement_on_offline_of_elder() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn propose_offline_of_unresponsive_member() -> Result<()> {
+    time::pause();
+
+    let threshold = Duration::from_secs(60);
+
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let existing_peer = create_peer(MIN_AGE);
+    let member_info = MemberInfo::joined(existing_peer);
+    let member_info = proven(sk_set.secret_key(), member_info)?;
+    let _ = section.update_member(member_info);
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = nodes.remove(0);
+    let mut state = Core::new(node, section, Some(section_key_share), event_tx);
+    state.set_unresponsive_threshold(threshold);
+    let command = state.start_liveness_checks();
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // The first check only starts tracking the members.
+    let token = assert_matches!(command, Command::ScheduleTimeout { token, .. } => token);
+    let commands = dispatcher
+        .handle_command(Command::HandleTimeout(token))
+        .await?;
+    assert!(find_offline_proposal(&commands, &existing_peer)?.is_none());
+
+    time::advance(threshold).await;
+
+    let token = commands
+        .iter()
+        .find_map(|command| match command {
+            Command::ScheduleTimeout { token, .. } => Some(*token),
+            _ => None,
+        })
+        .expect("next liveness check not scheduled");
+    let commands = dispatcher
+        .handle_command(Command::HandleTimeout(token))
+        .await?;
+
+    let recipients = find_offline_proposal(&commands, &existing_peer)?
+        .expect("unresponsive member not proposed offline");
+    assert!(!recipients.contains(existing_peer.addr()));
+
+    Ok(())
+}
+
+// Returns the recipients of the `Offline` proposal of `peer` among the `commands`, if any.
+fn find_offline_proposal(commands: &[Command], peer: &Peer) -> Result<Option<Vec<SocketAddr>>> {
+    for command in commands {
+        let (recipients, message) = match command {
+            Command::SendMessage {
+                recipients,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes.clone()), &ProtocolInfo::default())?,
+            ),
+            _ => continue,
+        };
+
+        if let Variant::Propose {
+            content: Proposal::Offline(member_info),
+            ..
+        } = message.variant()
+        {
+            if member_info.peer == *peer {
+                return Ok(Some(recipients.clone()));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[tokio::test]
+async fn gossip_with_other_sections() -> Result<()> {
+    let our_prefix: Prefix = "0".parse().unwrap();
+    let their_prefix: Prefix = "1".parse().unwrap();
+
+    let (our_elders_info, mut nodes) = gen_elders_info(our_prefix, ELDER_SIZE);
+    let (their_elders_info, _) = gen_elders_info(their_prefix, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &our_elders_info)?;
+
+    let their_key = bls::SecretKey::random().public_key();
+    let mut network = Network::new();
+    assert!(network.update_section(
+        proven(sk_set.secret_key(), their_elders_info.clone())?,
+        None,
+        section.chain(),
+    ));
+    let _ = network.update_their_key(proven(sk_set.secret_key(), (their_prefix, their_key))?);
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = nodes.remove(0);
+    let mut state = Core::restore(node, section, network, Some(section_key_share), event_tx);
+    let command = state.start_gossip();
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let token = assert_matches!(command, Command::ScheduleTimeout { token, .. } => token);
+    let commands = dispatcher
+        .handle_command(Command::HandleTimeout(token))
+        .await?;
+
+    assert!(commands
+        .iter()
+        .any(|command| matches!(command, Command::ScheduleTimeout { .. })));
+
+    let mut gossip_sent = false;
+    for command in &commands {
+        let (recipients, message) = match command {
+            Command::SendMessage {
+                recipients,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes.clone()), &ProtocolInfo::default())?,
+            ),
+            _ => continue,
+        };
+
+        let elders_info = if let Variant::Gossip { elders_info, .. } = message.variant() {
+            elders_info
+        } else {
+            continue;
+        };
+
+        assert_eq!(elders_info.value, our_elders_info);
+        assert_eq!(message.dst_key(), Some(&their_key));
+
+        let expected_recipients: Vec<_> =
+            their_elders_info.peers().map(Peer::addr).copied().collect();
+        assert_eq!(*recipients, expected_recipients);
+
+        gossip_sent = true;
+    }
+
+    assert!(gossip_sent);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn handle_unknown_message_from_our_elder() -> Result<()> {
     handle_unknown_message(UnknownMessageSource::OurElder).await
@@ -859,10 +2090,20 @@ async fn handle_unknown_message(source: UnknownMessageSource) -> Result<()> {
     let chain = SectionChain::new(sk.public_key());
 
     let proven_elders_info = proven(&sk, elders_info)?;
-    let section = Section::new(*chain.root_key(), chain, proven_elders_info)?;
+    let section = Section::new(
+        *chain.root_key(),
+        proven(&sk, NetworkParams::default())?,
+        chain,
+        proven_elders_info,
+    )?;
 
     let node = create_node(MIN_AGE + 1);
-    let state = Core::new(node, section, None, mpsc::unbounded_channel().0);
+    let state = Core::new(
+        node,
+        section,
+        None,
+        event_channel(&QueueConfig::default()).0,
+    );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     // non-elders can't handle messages addressed to sections.
@@ -893,7 +2134,10 @@ async fn handle_unknown_message(source: UnknownMessageSource) -> Result<()> {
             ..
         } = command
         {
-            (recipients, Message::from_bytes(Bytes::from(msg_bytes))?)
+            (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            )
         } else {
             continue;
         };
@@ -967,11 +2211,21 @@ async fn handle_untrusted_message(source: UntrustedMessageSource) -> Result<()>
     };
 
     let proven_elders_info = proven(&sk0, elders_info)?;
-    let section = Section::new(pk0, chain.clone(), proven_elders_info)?;
+    let section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain.clone(),
+        proven_elders_info,
+    )?;
 
     let node = create_node(MIN_AGE + 1);
     let node_name = node.name();
-    let state = Core::new(node, section, None, mpsc::unbounded_channel().0);
+    let state = Core::new(
+        node,
+        section,
+        None,
+        event_channel(&QueueConfig::default()).0,
+    );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     let sk1 = bls::SecretKey::random();
@@ -982,6 +2236,7 @@ async fn handle_untrusted_message(source: UntrustedMessageSource) -> Result<()>
         src: Prefix::default().name(),
         dst: DstLocation::Node(node_name),
         dst_key: pk1,
+        protocol: ProtocolInfo::section(INITIAL_PROTOCOL_VERSION),
         variant: Variant::UserMessage(Bytes::from_static(b"hello")),
     };
     let signature = sk1.sign(&bincode::serialize(&message.as_signable())?);
@@ -1003,7 +2258,10 @@ async fn handle_untrusted_message(source: UntrustedMessageSource) -> Result<()>
             ..
         } = command
         {
-            (recipients, Message::from_bytes(Bytes::from(msg_bytes))?)
+            (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            )
         } else {
             continue;
         };
@@ -1038,7 +2296,12 @@ async fn handle_bounced_unknown_message() -> Result<()> {
     let _ = section_chain.insert(&pk0, pk1, pk1_signature);
 
     let proven_elders_info = proven(sk1_set.secret_key(), elders_info)?;
-    let section = Section::new(pk0, section_chain, proven_elders_info)?;
+    let section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        section_chain,
+        proven_elders_info,
+    )?;
     let section_key_share = create_section_key_share(&sk1_set, 0);
 
     let node = nodes.remove(0);
@@ -1062,7 +2325,7 @@ async fn handle_bounced_unknown_message() -> Result<()> {
         node,
         section,
         Some(section_key_share),
-        mpsc::unbounded_channel().0,
+        event_channel(&QueueConfig::default()).0,
     );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
@@ -1093,7 +2356,10 @@ async fn handle_bounced_unknown_message() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1134,7 +2400,12 @@ async fn handle_bounced_untrusted_message() -> Result<()> {
     let _ = chain.insert(&pk0, pk1, pk1_signature);
 
     let proven_elders_info = proven(sk1_set.secret_key(), elders_info)?;
-    let section = Section::new(pk0, chain.clone(), proven_elders_info)?;
+    let section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain.clone(),
+        proven_elders_info,
+    )?;
     let section_key_share = create_section_key_share(&sk1_set, 0);
 
     let node = nodes.remove(0);
@@ -1151,6 +2422,7 @@ async fn handle_bounced_untrusted_message() -> Result<()> {
         src: Prefix::default().name(),
         dst: DstLocation::Node(other_node.name()),
         dst_key: pk1,
+        protocol: ProtocolInfo::section(INITIAL_PROTOCOL_VERSION),
         variant: Variant::UserMessage(original_message_content.clone()),
     };
     let signature = sk1_set
@@ -1164,7 +2436,7 @@ async fn handle_bounced_untrusted_message() -> Result<()> {
         node,
         section,
         Some(section_key_share),
-        mpsc::unbounded_channel().0,
+        event_channel(&QueueConfig::default()).0,
     );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
@@ -1192,7 +2464,10 @@ async fn handle_bounced_untrusted_message() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1223,14 +2498,19 @@ async fn handle_sync() -> Result<()> {
     let pk1_signature = sk0.sign(bincode::serialize(&pk1)?);
 
     let mut chain = SectionChain::new(pk0);
-    assert_eq!(chain.insert(&pk0, pk1, pk1_signature), Ok(()));
+    assert_eq!(chain.insert(&pk0, pk1, pk1_signature), Ok(None));
 
     let (old_elders_info, mut nodes) = create_elders_info();
     let proven_old_elders_info = proven(sk1_set.secret_key(), old_elders_info.clone())?;
-    let old_section = Section::new(pk0, chain.clone(), proven_old_elders_info)?;
+    let old_section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain.clone(),
+        proven_old_elders_info,
+    )?;
 
     // Create our node
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
     let section_key_share = create_section_key_share(&sk1_set, 0);
     let node = nodes.remove(0);
     let state = Core::new(node, old_section, Some(section_key_share), event_tx);
@@ -1240,7 +2520,7 @@ async fn handle_sync() -> Result<()> {
     let sk2 = bls::SecretKey::random();
     let pk2 = sk2.public_key();
     let pk2_signature = sk1_set.secret_key().sign(bincode::serialize(&pk2)?);
-    chain.insert(&pk1, pk2, pk2_signature)?;
+    let _ = chain.insert(&pk1, pk2, pk2_signature)?;
 
     let old_node = nodes.remove(0);
 
@@ -1257,7 +2537,12 @@ async fn handle_sync() -> Result<()> {
     );
     let new_elders: BTreeSet<_> = new_elders_info.elders.keys().copied().collect();
     let proven_new_elders_info = proven(&sk2, new_elders_info)?;
-    let new_section = Section::new(pk0, chain, proven_new_elders_info)?;
+    let new_section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain,
+        proven_new_elders_info,
+    )?;
 
     // Create the `Sync` message containing the new `Section`.
     let message = Message::single_src(
@@ -1281,7 +2566,7 @@ async fn handle_sync() -> Result<()> {
 
     // Verify our `Section` got updated.
     assert_matches!(
-        event_rx.recv().await,
+        event_rx.next().await,
         Some(Event::EldersChanged { key, elders, .. }) => {
             assert_eq!(key, pk2);
             assert_eq!(elders, new_elders);
@@ -1291,6 +2576,84 @@ async fn handle_sync() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn handle_sync_with_fork() -> Result<()> {
+    // Create our `Section` with the chain 0->1->2
+    let sk0 = bls::SecretKey::random();
+    let pk0 = sk0.public_key();
+    let sk1 = bls::SecretKey::random();
+    let pk1 = sk1.public_key();
+    let sk2_set = SecretKeySet::random();
+    let pk2 = sk2_set.secret_key().public_key();
+
+    let mut chain = SectionChain::new(pk0);
+    let _ = chain.insert(&pk0, pk1, sk0.sign(bincode::serialize(&pk1)?))?;
+    let mut other_chain = chain.clone();
+    let _ = chain.insert(&pk1, pk2, sk1.sign(bincode::serialize(&pk2)?))?;
+
+    let (elders_info, mut nodes) = create_elders_info();
+    let section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain,
+        proven(sk2_set.secret_key(), elders_info.clone())?,
+    )?;
+
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
+    let section_key_share = create_section_key_share(&sk2_set, 0);
+    let node = nodes.remove(0);
+    let state = Core::new(node, section, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // Another elder sends us the section with the chain 0->1->3, that is, the key 1 signed two
+    // different keys.
+    let sk3 = bls::SecretKey::random();
+    let pk3 = sk3.public_key();
+    let _ = other_chain.insert(&pk1, pk3, sk1.sign(bincode::serialize(&pk3)?))?;
+    let other_section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        other_chain,
+        proven(&sk3, elders_info)?,
+    )?;
+
+    let other_node = nodes.remove(0);
+    let message = Message::single_src(
+        &other_node,
+        DstLocation::Direct,
+        Variant::Sync {
+            section: other_section,
+            network: Network::new(),
+        },
+        None,
+        None,
+    )?;
+
+    let _ = dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(other_node.addr),
+        })
+        .await?;
+
+    let mut expected_keys = vec![pk2, pk3];
+    expected_keys.sort();
+
+    loop {
+        match timeout(Duration::from_secs(5), event_rx.next()).await? {
+            Some(Event::SectionChainForked { parent, keys }) => {
+                assert_eq!(parent, pk1);
+                assert_eq!(keys, expected_keys);
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("event stream closed before the fork was reported"),
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn handle_untrusted_sync() -> Result<()> {
     let sk0 = bls::SecretKey::random();
@@ -1305,18 +2668,28 @@ async fn handle_untrusted_sync() -> Result<()> {
     let sig2 = sk1.sign(&bincode::serialize(&pk2)?);
 
     let mut chain = SectionChain::new(pk0);
-    chain.insert(&pk0, pk1, sig1)?;
-    chain.insert(&pk1, pk2, sig2)?;
+    let _ = chain.insert(&pk0, pk1, sig1)?;
+    let _ = chain.insert(&pk1, pk2, sig2)?;
 
     let (old_elders_info, _) = create_elders_info();
     let proven_old_elders_info = proven(&sk0, old_elders_info.clone())?;
-    let old_section = Section::new(pk0, SectionChain::new(pk0), proven_old_elders_info)?;
+    let old_section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        SectionChain::new(pk0),
+        proven_old_elders_info,
+    )?;
 
     let (new_elders_info, _) = create_elders_info();
     let proven_new_elders_info = proven(&sk2, new_elders_info.clone())?;
-    let new_section = Section::new(pk0, chain.truncate(2), proven_new_elders_info)?;
+    let new_section = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain.truncate(2),
+        proven_new_elders_info,
+    )?;
 
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
     let node = create_node(MIN_AGE + 1);
     let state = Core::new(node, old_section, None, event_tx);
     let dispatcher = Dispatcher::new(state, create_comm().await?);
@@ -1348,7 +2721,10 @@ async fn handle_untrusted_sync() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1363,7 +2739,7 @@ async fn handle_untrusted_sync() -> Result<()> {
     }
 
     assert!(bounce_sent);
-    assert!(timeout(Duration::from_secs(5), event_rx.recv())
+    assert!(timeout(Duration::from_secs(5), event_rx.next())
         .await
         .is_err());
 
@@ -1385,15 +2761,20 @@ async fn handle_bounced_untrusted_sync() -> Result<()> {
     let sig2 = sk1.sign(&bincode::serialize(&pk2)?);
 
     let mut chain = SectionChain::new(pk0);
-    chain.insert(&pk0, pk1, sig1)?;
-    chain.insert(&pk1, pk2, sig2)?;
+    let _ = chain.insert(&pk0, pk1, sig1)?;
+    let _ = chain.insert(&pk1, pk2, sig2)?;
 
     let (elders_info, mut nodes) = create_elders_info();
     let proven_elders_info = proven(sk2, elders_info.clone())?;
-    let section_full = Section::new(pk0, chain, proven_elders_info)?;
+    let section_full = Section::new(
+        pk0,
+        proven(&sk0, NetworkParams::default())?,
+        chain,
+        proven_elders_info,
+    )?;
     let section_trimmed = section_full.trimmed(2);
 
-    let (event_tx, _) = mpsc::unbounded_channel();
+    let (event_tx, _) = event_channel(&QueueConfig::default());
     let node = nodes.remove(0);
     let section_key_share = create_section_key_share(&sk2_set, 0);
     let state = Core::new(
@@ -1439,7 +2820,10 @@ async fn handle_bounced_untrusted_sync() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1488,7 +2872,7 @@ async fn relocation(relocated_peer_role: RelocatedPeerRole) -> Result<()> {
         node,
         section,
         Some(section_key_share),
-        mpsc::unbounded_channel().0,
+        event_channel(&QueueConfig::default()).0,
     );
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
@@ -1510,7 +2894,10 @@ async fn relocation(relocated_peer_role: RelocatedPeerRole) -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1563,7 +2950,11 @@ enum MessageDst {
 async fn message_to_self(dst: MessageDst) -> Result<()> {
     let node = create_node(MIN_AGE + 1);
     let peer = node.peer();
-    let state = Core::first_node(node, mpsc::unbounded_channel().0)?;
+    let state = Core::first_node(
+        node,
+        NetworkParams::default(),
+        event_channel(&QueueConfig::default()).0,
+    )?;
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
     let src = SrcLocation::Node(*peer.name());
@@ -1585,15 +2976,149 @@ async fn message_to_self(dst: MessageDst) -> Result<()> {
         })
         .await?;
 
-    assert_matches!(&commands[..], [Command::HandleMessage { sender, message }] => {
-        assert_eq!(sender.as_ref(), Some(peer.addr()));
-        assert_eq!(message.src().src_location(), src);
-        assert_eq!(message.dst(), &dst);
-        assert_matches!(
-            message.variant(),
-            Variant::UserMessage(actual_content) if actual_content == &content
-        );
-    });
+    assert_matches!(&commands[..], [Command::HandleMessage { sender, message }] => {
+        assert_eq!(sender.as_ref(), Some(peer.addr()));
+        assert_eq!(message.src().src_location(), src);
+        assert_eq!(message.dst(), &dst);
+        assert_matches!(
+            message.variant(),
+            Variant::UserMessage(actual_content) if actual_content == &content
+        );
+    });
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn prune_chain_of_long_lived_section() -> Result<()> {
+    let node = create_node(MIN_AGE + 2);
+    let adult_peer = create_peer(MIN_AGE + 1);
+    let elders_info = EldersInfo::new(iter::once(node.peer()), Prefix::default());
+
+    // A chain grown long enough to be pruned, with the members proven with its genesis key.
+    let genesis_sk = bls::SecretKey::random();
+    let mut chain = SectionChain::new(genesis_sk.public_key());
+    let mut last_sk = genesis_sk.clone();
+    for _ in 1..2 * CHAIN_KEYS_KEPT {
+        let sk = bls::SecretKey::random();
+        let pk = sk.public_key();
+        let _ = chain.insert(
+            &last_sk.public_key(),
+            pk,
+            last_sk.sign(bincode::serialize(&pk)?),
+        )?;
+        last_sk = sk;
+    }
+
+    let sk_set0 = SecretKeySet::random();
+    let pk0 = sk_set0.secret_key().public_key();
+    let _ = chain.insert(
+        &last_sk.public_key(),
+        pk0,
+        last_sk.sign(bincode::serialize(&pk0)?),
+    )?;
+
+    let mut section = Section::new(
+        genesis_sk.public_key(),
+        proven(&genesis_sk, NetworkParams::default())?,
+        chain,
+        proven(sk_set0.secret_key(), elders_info.clone())?,
+    )?;
+    for peer in &[node.peer(), adult_peer] {
+        assert!(section.update_member(proven(&genesis_sk, MemberInfo::joined(*peer))?));
+    }
+
+    let state = Core::new(
+        node,
+        section,
+        Some(create_section_key_share(&sk_set0, 0)),
+        event_channel(&QueueConfig::default()).0,
+    );
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let proposals = |commands: &[Command]| -> Vec<Proposal> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::HandleMessage { message, .. } => match message.variant() {
+                    Variant::Propose { content, .. } => Some(content.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    };
+
+    // The section key changes.
+    let sk_set1 = SecretKeySet::random();
+    let _ = dispatcher
+        .handle_command(Command::HandleDkgOutcome {
+            dkg_key: DkgKey::new(&elders_info, 0),
+            elders_info: elders_info.clone(),
+            outcome: create_section_key_share(&sk_set1, 0),
+        })
+        .await?;
+
+    let proposal = Proposal::OurElders(proven(sk_set1.secret_key(), elders_info)?);
+    let proof = Proof {
+        signature: sk_set0
+            .secret_key()
+            .sign(&bincode::serialize(&proposal.as_signable())?),
+        public_key: pk0,
+    };
+    let commands = dispatcher
+        .handle_command(Command::HandleAgreement { proposal, proof })
+        .await?;
+
+    // The members proven with the keys to prune are proven again with the new key first.
+    let reproved: Vec<_> = proposals(&commands)
+        .into_iter()
+        .filter_map(|proposal| match proposal {
+            Proposal::Reprove(info) => Some(info),
+            Proposal::Checkpoint(_) => panic!("checkpoint proposed before reproving members"),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(reproved.len(), 2);
+
+    let mut checkpoint = None;
+    for (index, info) in reproved.into_iter().enumerate() {
+        let proof = proven(sk_set1.secret_key(), info.clone())?.proof;
+        let commands = dispatcher
+            .handle_command(Command::HandleAgreement {
+                proposal: Proposal::Reprove(info),
+                proof,
+            })
+            .await?;
+
+        checkpoint = proposals(&commands)
+            .into_iter()
+            .find_map(|proposal| match proposal {
+                Proposal::Checkpoint(checkpoint) => Some(checkpoint),
+                _ => None,
+            });
+        assert_eq!(checkpoint.is_some(), index == 1);
+    }
+
+    // Then the chain is pruned and still proves all the members.
+    let checkpoint = checkpoint.expect("checkpoint should be proposed");
+    let proof = proven(sk_set1.secret_key(), checkpoint)?.proof;
+    let _ = dispatcher
+        .handle_command(Command::HandleAgreement {
+            proposal: Proposal::Checkpoint(checkpoint),
+            proof,
+        })
+        .await?;
+
+    let state = dispatcher.lock_core(Priority::Normal).await;
+    let section = state.section();
+    assert_eq!(section.chain().root_key(), &checkpoint.key);
+    assert_eq!(section.chain().len(), CHAIN_KEYS_KEPT);
+    assert_eq!(section.members().all_proven().count(), 2);
+    assert!(section
+        .members()
+        .all_proven()
+        .all(|info| info.verify(section.chain())));
 
     Ok(())
 }
@@ -1650,7 +3175,7 @@ async fn handle_elders_update() -> Result<()> {
         public_key: pk0,
     };
 
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let (event_tx, mut event_rx) = event_channel(&QueueConfig::default());
     let state = Core::new(node, section0.clone(), Some(section_key_share), event_tx);
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
@@ -1666,7 +3191,10 @@ async fn handle_elders_update() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1681,7 +3209,7 @@ async fn handle_elders_update() -> Result<()> {
         assert_matches!(message.verify(iter::once(&pk0)), Ok(VerifyStatus::Full));
 
         // Merging the section contained in the message with the original section succeeds.
-        assert_matches!(section0.clone().merge(section.clone()), Ok(()));
+        assert_matches!(section0.clone().merge(section.clone()), Ok(_));
 
         sync_actual_recipients.extend(recipients);
     }
@@ -1697,7 +3225,7 @@ async fn handle_elders_update() -> Result<()> {
     assert_eq!(sync_actual_recipients, sync_expected_recipients);
 
     assert_matches!(
-        event_rx.recv().await,
+        event_rx.next().await,
         Some(Event::EldersChanged { key, elders, .. }) => {
             assert_eq!(key, pk1);
             assert_eq!(elders, elder_names1);
@@ -1743,7 +3271,7 @@ async fn handle_demote_during_split() -> Result<()> {
         assert!(section.update_member(member_info));
     }
 
-    let (event_tx, _) = mpsc::unbounded_channel();
+    let (event_tx, _) = event_channel(&QueueConfig::default());
     let state = Core::new(node, section, Some(section_key_share), event_tx);
     let dispatcher = Dispatcher::new(state, create_comm().await?);
 
@@ -1784,7 +3312,10 @@ async fn handle_demote_during_split() -> Result<()> {
                 recipients,
                 message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
                 ..
-            } => (recipients, Message::from_bytes(Bytes::from(msg_bytes))?),
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
             _ => continue,
         };
 
@@ -1810,6 +3341,390 @@ async fn handle_demote_during_split() -> Result<()> {
     Ok(())
 }
 
+// Test that an elder of a shrunk section replies to `MergeRequest` from its sibling and starts the
+// DKG for the merged section.
+#[tokio::test]
+async fn handle_merge_request() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+    let sk0 = bls::SecretKey::random();
+
+    // Create both sibling sections, each with fewer members than `ELDER_SIZE`.
+    let (our_section, our_sk_set, mut our_nodes) = create_sibling(&sk0, prefix0, 3)?;
+    let (their_section, _, their_nodes) = create_sibling(&sk0, prefix1, 3)?;
+    assert!(our_section.should_merge());
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = our_nodes.remove(0);
+    let section_key_share = create_section_key_share(&our_sk_set, 0);
+    let state = Core::new(node, our_section, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: merge_request(&their_nodes[0], &their_section)?,
+            sender: Some(their_nodes[0].addr),
+        })
+        .await?;
+
+    let mut merge_request_recipients = HashSet::new();
+    let mut dkg_start_recipients = HashSet::new();
+
+    for command in commands {
+        let (recipients, message) = match command {
+            Command::SendMessage {
+                recipients,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => (
+                recipients,
+                Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+            ),
+            _ => continue,
+        };
+
+        match message.variant() {
+            Variant::MergeRequest { section } => {
+                assert_eq!(section.prefix(), &prefix0);
+                merge_request_recipients.extend(recipients);
+            }
+            Variant::DkgStart { elders_info, .. } => {
+                // All the members of both sections are the elders of the merged section.
+                assert_eq!(elders_info.prefix, Prefix::default());
+                assert_eq!(elders_info.elders.len(), 6);
+                dkg_start_recipients.extend(recipients);
+            }
+            _ => continue,
+        }
+    }
+
+    let their_addrs: HashSet<_> = their_nodes.iter().map(|node| node.addr).collect();
+    assert_eq!(merge_request_recipients, their_addrs);
+    // The DKG is started by each section with its own members only (we handle ours directly).
+    let our_addrs: HashSet<_> = our_nodes.iter().map(|node| node.addr).collect();
+    assert_eq!(dkg_start_recipients, our_addrs);
+
+    Ok(())
+}
+
+// Test that `MergeRequest` is ignored unless it comes from an elder of the sibling and one of the
+// two sections needs to merge.
+#[tokio::test]
+async fn handle_invalid_merge_request() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+    let sk0 = bls::SecretKey::random();
+
+    let create_dispatcher = |section, sk_set, mut nodes: Vec<Node>| async move {
+        let node = nodes.remove(0);
+        let section_key_share = create_section_key_share(&sk_set, 0);
+        let (event_tx, _) = event_channel(&QueueConfig::default());
+        let state = Core::new(node, section, Some(section_key_share), event_tx);
+        Ok::<_, anyhow::Error>(Dispatcher::new(state, create_comm().await?))
+    };
+
+    // Not from an elder of the sibling.
+    let (our_section, our_sk_set, our_nodes) = create_sibling(&sk0, prefix0, 3)?;
+    let (their_section, _, _) = create_sibling(&sk0, prefix1, 3)?;
+    let dispatcher = create_dispatcher(our_section, our_sk_set, our_nodes).await?;
+
+    let non_elder = create_node(MIN_AGE + 1);
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: merge_request(&non_elder, &their_section)?,
+            sender: Some(non_elder.addr),
+        })
+        .await?;
+    assert!(commands.is_empty());
+
+    // Neither section shrunk.
+    let (our_section, our_sk_set, our_nodes) = create_sibling(&sk0, prefix0, ELDER_SIZE)?;
+    let (their_section, _, their_nodes) = create_sibling(&sk0, prefix1, ELDER_SIZE)?;
+    assert!(!our_section.should_merge());
+    assert!(!their_section.should_merge());
+    let dispatcher = create_dispatcher(our_section, our_sk_set, our_nodes).await?;
+
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: merge_request(&their_nodes[0], &their_section)?,
+            sender: Some(their_nodes[0].addr),
+        })
+        .await?;
+    assert!(commands.is_empty());
+
+    Ok(())
+}
+
+// Test that a merge which doesn't complete in time is aborted.
+#[tokio::test]
+async fn merge_timeout() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+    let sk0 = bls::SecretKey::random();
+
+    let (our_section, our_sk_set, mut our_nodes) = create_sibling(&sk0, prefix0, 3)?;
+    let (their_section, _, their_nodes) = create_sibling(&sk0, prefix1, 3)?;
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = our_nodes.remove(0);
+    let section_key_share = create_section_key_share(&our_sk_set, 0);
+    let state = Core::new(node, our_section, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let handle_merge_request = || {
+        dispatcher.handle_command(Command::HandleMessage {
+            message: merge_request(&their_nodes[0], &their_section)
+                .expect("failed to create MergeRequest"),
+            sender: Some(their_nodes[0].addr),
+        })
+    };
+
+    let commands = handle_merge_request().await?;
+    let token = commands
+        .iter()
+        .find_map(|command| match command {
+            Command::ScheduleTimeout { token, .. } => Some(*token),
+            _ => None,
+        })
+        .expect("merge timeout not scheduled");
+
+    // Nothing new while merging.
+    assert!(handle_merge_request().await?.is_empty());
+
+    // After the timeout, the merge is aborted and can be requested again.
+    let commands = dispatcher
+        .handle_command(Command::HandleTimeout(token))
+        .await?;
+    assert!(commands.is_empty());
+    assert!(!handle_merge_request().await?.is_empty());
+
+    Ok(())
+}
+
+// Test that the `SectionInfo` of the merged section is proposed to the elders of both sections.
+#[tokio::test]
+async fn handle_merged_dkg_outcome() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+    let sk0 = bls::SecretKey::random();
+
+    let (our_section, our_sk_set, mut our_nodes) = create_sibling(&sk0, prefix0, 3)?;
+    let (their_section, _, their_nodes) = create_sibling(&sk0, prefix1, 3)?;
+    let merged_elders_info = our_section.merged_elders_info(&their_section);
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = our_nodes.remove(0);
+    let section_key_share = create_section_key_share(&our_sk_set, 0);
+    let state = Core::new(node, our_section, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    let _ = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: merge_request(&their_nodes[0], &their_section)?,
+            sender: Some(their_nodes[0].addr),
+        })
+        .await?;
+
+    let merged_sk_set = SecretKeySet::random();
+    let commands = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .handle_dkg_outcome(
+            DkgKey::new(&merged_elders_info, 0),
+            merged_elders_info.clone(),
+            create_section_key_share(&merged_sk_set, 0),
+        )?;
+
+    let mut recipients = HashSet::new();
+    for command in commands {
+        match command {
+            Command::SendMessage {
+                recipients: addrs,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => {
+                let message =
+                    Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?;
+                if let Variant::Propose {
+                    content: Proposal::SectionInfo(elders_info),
+                    ..
+                } = message.variant()
+                {
+                    assert_eq!(*elders_info, merged_elders_info);
+                    recipients.extend(addrs);
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    let expected_recipients: HashSet<_> = our_nodes
+        .iter()
+        .chain(&their_nodes)
+        .map(|node| node.addr)
+        .collect();
+    assert_eq!(recipients, expected_recipients);
+
+    Ok(())
+}
+
+// Test that the elders of the merged section we start the DKG for are the ones our sibling
+// computes from the state of our section we sent to it, even once our members change.
+#[tokio::test]
+async fn merged_elders_info_with_divergent_member_views() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+    let sk0 = bls::SecretKey::random();
+
+    let (our_section, our_sk_set, mut our_nodes) = create_sibling(&sk0, prefix0, 3)?;
+    let (their_section, _, their_nodes) = create_sibling(&sk0, prefix1, 3)?;
+
+    let (event_tx, _) = event_channel(&QueueConfig::default());
+    let node = our_nodes.remove(0);
+    let section_key_share = create_section_key_share(&our_sk_set, 0);
+    let state = Core::new(node, our_section, Some(section_key_share), event_tx);
+    let dispatcher = Dispatcher::new(state, create_comm().await?);
+
+    // Returns the state of our section sent in `MergeRequest` and the elders of the DKG started.
+    let sent_merge = |commands: Vec<Command>| -> Result<(Option<Section>, Option<EldersInfo>)> {
+        let mut section = None;
+        let mut elders_info = None;
+
+        for command in commands {
+            let message = match command {
+                Command::SendMessage {
+                    message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                    ..
+                } => Message::from_bytes(Bytes::from(msg_bytes), &ProtocolInfo::default())?,
+                Command::HandleMessage { message, .. } => message,
+                _ => continue,
+            };
+
+            match message.variant() {
+                Variant::MergeRequest { section: sent } => section = Some(sent.clone()),
+                Variant::DkgStart {
+                    elders_info: started,
+                    ..
+                } => elders_info = Some(started.clone()),
+                _ => (),
+            }
+        }
+
+        Ok((section, elders_info))
+    };
+
+    let commands = dispatcher
+        .handle_command(Command::HandleMessage {
+            message: merge_request(&their_nodes[0], &their_section)?,
+            sender: Some(their_nodes[0].addr),
+        })
+        .await?;
+    let (sent, started) = sent_merge(commands)?;
+    let sent = sent.expect("MergeRequest not sent");
+    let started = started.expect("DkgStart not sent");
+    assert_eq!(started, their_section.merged_elders_info(&sent));
+
+    // A new member joins our section during the merge.
+    let new_peer = create_peer_in_prefix(&prefix0, MIN_AGE + 4);
+    let proposal = Proposal::Online {
+        member_info: MemberInfo::joined(new_peer),
+        previous_name: None,
+        their_knowledge: None,
+    };
+    let proof = prove(our_sk_set.secret_key(), &proposal.as_signable())?;
+    let commands = dispatcher
+        .handle_command(Command::HandleAgreement { proposal, proof })
+        .await?;
+    let (sent, started) = sent_merge(commands)?;
+    let sent = sent.expect("MergeRequest not resent");
+    let started = started.expect("DkgStart not restarted");
+    assert!(sent.members().is_joined(new_peer.name()));
+    assert!(started.elders.contains_key(new_peer.name()));
+    assert_eq!(started, their_section.merged_elders_info(&sent));
+    assert_eq!(started, sent.merged_elders_info(&their_section));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_invalid_config() {
+    let config = Config {
+        unresponsive_threshold: Duration::from_nanos(1),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Routing::new(config).await,
+        Err(Error::InvalidConfig(_))
+    ));
+
+    let config = Config {
+        gossip: GossipConfig {
+            interval: Duration::ZERO,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(matches!(
+        Routing::new(config).await,
+        Err(Error::InvalidConfig(_))
+    ));
+
+    let config = Config {
+        clients: ClientConfig {
+            idle_timeout: Duration::from_nanos(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(matches!(
+        Routing::new(config).await,
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn bounded_command_queue() -> Result<()> {
+    let node = create_node(MIN_AGE + 1);
+    let state = Core::first_node(
+        node,
+        NetworkParams::default(),
+        event_channel(&QueueConfig::default()).0,
+    )?;
+    let mut dispatcher = Dispatcher::new(state, create_comm().await?);
+    dispatcher.set_queue_config(&QueueConfig {
+        incoming_capacity: 1,
+        overflow_policy: OverflowPolicy::ShedLowPriority,
+        ..Default::default()
+    });
+    let dispatcher = Arc::new(dispatcher);
+
+    let permit = dispatcher.acquire_incoming_permit(Priority::Normal).await;
+    assert!(permit.is_some());
+
+    // Section info queries are shed as low priority traffic once the queue is full.
+    let command = Command::HandleSectionInfoMsg {
+        sender: gen_addr(),
+        message: SectionInfoMsg::GetSectionQuery(rand::random()),
+    };
+    assert!(dispatcher
+        .acquire_incoming_permit(command.priority())
+        .await
+        .is_none());
+    assert_eq!(dispatcher.metrics.snapshot().incoming_dropped, 1);
+
+    // The follow-up commands wait until there is room for them.
+    let handle = dispatcher.clone().handle_commands(command);
+    tokio::pin!(handle);
+    assert!(timeout(Duration::from_millis(100), &mut handle)
+        .await
+        .is_err());
+
+    drop(permit);
+    timeout(Duration::from_secs(1), handle).await??;
+
+    Ok(())
+}
+
 // TODO: add more tests here
 
 #[allow(unused)]
@@ -1821,6 +3736,51 @@ fn init_log() {
         .init()
 }
 
+// Create a `JoinRequest` from `new_node` carrying a valid response to the resource proof challenge
+// of the node behind `dispatcher`.
+async fn join_request_with_resource_proof(
+    dispatcher: &Dispatcher,
+    new_node: &Node,
+) -> Result<Command> {
+    let core = dispatcher.lock_core(Priority::Normal).await;
+    let section_key = *core.section().chain().last_key();
+
+    let nonce: [u8; 32] = rand::random();
+    let serialized = bincode::serialize(&(new_node.name(), nonce))?;
+    let nonce_signature = crypto::sign(&serialized, &core.node().keypair);
+
+    let network_params = NetworkParams::default();
+    let rp = ResourceProof::new(
+        network_params.resource_proof_data_size,
+        network_params.resource_proof_difficulty,
+    );
+    let data = rp.create_proof_data(&nonce);
+    let mut prover = rp.create_prover(data.clone());
+    let solution = prover.solve();
+
+    let message = Message::single_src(
+        new_node,
+        DstLocation::Direct,
+        Variant::JoinRequest(Box::new(JoinRequest {
+            section_key,
+            relocate_payload: None,
+            resource_proof_response: Some(ResourceProofResponse {
+                solution,
+                data,
+                nonce,
+                nonce_signature,
+            }),
+        })),
+        None,
+        None,
+    )?;
+
+    Ok(Command::HandleMessage {
+        sender: Some(new_node.addr),
+        message,
+    })
+}
+
 fn create_peer(age: u8) -> Peer {
     let name = crypto::gen_name_with_age(age);
     Peer::new(name, gen_addr())
@@ -1855,6 +3815,49 @@ fn create_elders_info() -> (EldersInfo, Vec<Node>) {
     gen_elders_info(Default::default(), ELDER_SIZE)
 }
 
+// Create a section with `size` members, all of them elders, whose chain starts at the key of
+// `genesis_sk`.
+fn create_sibling(
+    genesis_sk: &bls::SecretKey,
+    prefix: Prefix,
+    size: usize,
+) -> Result<(Section, SecretKeySet, Vec<Node>)> {
+    let genesis_pk = genesis_sk.public_key();
+    let sk_set = SecretKeySet::random();
+    let pk = sk_set.secret_key().public_key();
+
+    let mut chain = SectionChain::new(genesis_pk);
+    let _ = chain.insert(&genesis_pk, pk, genesis_sk.sign(bincode::serialize(&pk)?))?;
+
+    let (elders_info, nodes) = gen_elders_info(prefix, size);
+    let proven_elders_info = proven(sk_set.secret_key(), elders_info.clone())?;
+    let mut section = Section::new(
+        genesis_pk,
+        proven(genesis_sk, NetworkParams::default())?,
+        chain,
+        proven_elders_info,
+    )?;
+
+    for peer in elders_info.elders.values() {
+        let member_info = proven(sk_set.secret_key(), MemberInfo::joined(*peer))?;
+        assert!(section.update_member(member_info));
+    }
+
+    Ok((section, sk_set, nodes))
+}
+
+fn merge_request(sender: &Node, section: &Section) -> Result<Message> {
+    Ok(Message::single_src(
+        sender,
+        DstLocation::Direct,
+        Variant::MergeRequest {
+            section: section.clone(),
+        },
+        None,
+        None,
+    )?)
+}
+
 fn create_section_key_share(sk_set: &bls::SecretKeySet, index: usize) -> SectionKeyShare {
     SectionKeyShare {
         public_key_set: sk_set.public_keys(),
@@ -1870,7 +3873,12 @@ fn create_section(
     let section_chain = SectionChain::new(sk_set.secret_key().public_key());
     let proven_elders_info = proven(sk_set.secret_key(), elders_info.clone())?;
 
-    let mut section = Section::new(*section_chain.root_key(), section_chain, proven_elders_info)?;
+    let mut section = Section::new(
+        *section_chain.root_key(),
+        proven(sk_set.secret_key(), NetworkParams::default())?,
+        section_chain,
+        proven_elders_info,
+    )?;
 
     for peer in elders_info.elders.values().copied() {
         let member_info = MemberInfo::joined(peer);
@@ -1883,6 +3891,32 @@ fn create_section(
     Ok((section, section_key_share))
 }
 
+// Send `Sync` with our current section from `sender`, advertising its protocol info.
+async fn send_sync(dispatcher: &Dispatcher, sender: &Node) -> Result<Vec<Command>> {
+    let section = dispatcher
+        .lock_core(Priority::Normal)
+        .await
+        .section()
+        .clone();
+    let message = Message::single_src(
+        sender,
+        DstLocation::Direct,
+        Variant::Sync {
+            section,
+            network: Network::new(),
+        },
+        None,
+        None,
+    )?;
+
+    Ok(dispatcher
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(sender.addr),
+        })
+        .await?)
+}
+
 // Create a `Proposal::Online` whose agreement handling triggers relocation of a node with the
 // given age.
 // NOTE: recommended to call this with low `age` (4 or 5), otherwise it might take very long time
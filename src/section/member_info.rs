@@ -10,8 +10,9 @@ use crate::{error::Error, peer::Peer};
 use serde::{Deserialize, Serialize};
 use xor_name::XorName;
 
-/// The minimum age a node can have. The Infants will start at age 4. This is to prevent frequent
-/// relocations during the beginning of a node's lifetime.
+/// The default minimum age a node can have. The Infants will start at age 4. This is to prevent
+/// frequent relocations during the beginning of a node's lifetime. Can be changed via
+/// `NetworkParams::min_age`.
 pub const MIN_AGE: u8 = 4;
 
 /// Information about a member of our section.
@@ -30,9 +31,9 @@ impl MemberInfo {
         }
     }
 
-    // Is the age > `MIN_AGE`?
-    pub fn is_mature(&self) -> bool {
-        self.peer.age() > MIN_AGE
+    // Is the age > `min_age`?
+    pub fn is_mature(&self, min_age: u8) -> bool {
+        self.peer.age() > min_age
     }
 
     pub fn leave(self) -> Result<Self, Error> {
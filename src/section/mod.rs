@@ -18,39 +18,53 @@ pub(crate) use self::section_peers::SectionPeers;
 pub use self::{
     elders_info::EldersInfo,
     member_info::{MemberInfo, PeerState, MIN_AGE},
-    section_chain::{Error as SectionChainError, SectionChain},
+    section_chain::{
+        Checkpoint, Error as SectionChainError, Fork as SectionChainFork, SectionChain,
+    },
     section_keys::{SectionKeyShare, SectionKeysProvider},
 };
 
 use crate::{
     agreement::Proven,
     error::{Error, Result},
+    network_params::NetworkParams,
     peer::Peer,
-    ELDER_SIZE, RECOMMENDED_SECTION_SIZE,
+    protocol::{ProtocolUpgrade, INITIAL_PROTOCOL_VERSION},
+    rng::MainRng,
 };
 use bls_signature_aggregator::Proof;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeSet, convert::TryInto, iter, net::SocketAddr};
+use std::{cmp::Ordering, collections::BTreeSet, convert::TryInto, iter, net::SocketAddr};
 use xor_name::{Prefix, XorName};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) struct Section {
     genesis_key: bls::PublicKey,
+    // Signed with the genesis key, so every node in the network agrees on them.
+    network_params: Proven<NetworkParams>,
     chain: SectionChain,
     elders_info: Proven<EldersInfo>,
     members: SectionPeers,
+    // Latest protocol upgrade agreed on by the section, if any.
+    protocol_upgrade: Option<Proven<ProtocolUpgrade>>,
 }
 
 impl Section {
     /// Creates a minimal `Section` initially containing only info about our elders
     /// (`elders_info`).
     ///
-    /// Returns error if `elders_info` is not signed with the last key of `chain`.
+    /// Returns error if `network_params` are not signed with `genesis_key` or `elders_info` is not
+    /// signed with the last key of `chain`.
     pub fn new(
         genesis_key: bls::PublicKey,
+        network_params: Proven<NetworkParams>,
         chain: SectionChain,
         elders_info: Proven<EldersInfo>,
     ) -> Result<Self, Error> {
+        if network_params.proof.public_key != genesis_key || !network_params.self_verify() {
+            error!("can't create section: network_params not signed with the genesis key");
+            return Err(Error::InvalidMessage);
+        }
         if elders_info.proof.public_key != *chain.last_key() {
             error!("can't create section: elders_info signed with incorrect key");
             // TODO: consider more specific error here.
@@ -59,22 +73,30 @@ impl Section {
 
         Ok(Self {
             genesis_key,
+            network_params,
             chain,
             elders_info,
             members: SectionPeers::default(),
+            protocol_upgrade: None,
         })
     }
 
     /// Creates `Section` for the first node in the network
-    pub fn first_node(peer: Peer) -> Result<(Self, SectionKeyShare)> {
-        let secret_key_set = bls::SecretKeySet::random(0, &mut rand::thread_rng());
+    pub fn first_node(
+        peer: Peer,
+        network_params: NetworkParams,
+    ) -> Result<(Self, SectionKeyShare)> {
+        let secret_key_set = bls::SecretKeySet::random(0, &mut MainRng);
         let public_key_set = secret_key_set.public_keys();
         let secret_key_share = secret_key_set.secret_key_share(0);
 
         let elders_info = create_first_elders_info(&public_key_set, &secret_key_share, peer)?;
+        let proof = create_first_proof(&public_key_set, &secret_key_share, &network_params)?;
+        let network_params = Proven::new(network_params, proof);
 
         let mut section = Self::new(
             elders_info.proof.public_key,
+            network_params,
             SectionChain::new(elders_info.proof.public_key),
             elders_info,
         )?;
@@ -82,10 +104,7 @@ impl Section {
         for peer in section.elders_info.value.peers() {
             let member_info = MemberInfo::joined(*peer);
             let proof = create_first_proof(&public_key_set, &secret_key_share, &member_info)?;
-            let _ = section.members.update(Proven {
-                value: member_info,
-                proof,
-            });
+            let _ = section.members.update(Proven::new(member_info, proof));
         }
 
         let section_key_share = SectionKeyShare {
@@ -101,9 +120,22 @@ impl Section {
         &self.genesis_key
     }
 
+    pub fn network_params(&self) -> &NetworkParams {
+        &self.network_params.value
+    }
+
+    pub fn proven_network_params(&self) -> &Proven<NetworkParams> {
+        &self.network_params
+    }
+
     /// Try to merge this `Section` with `other`. Returns `InvalidMessage` if `other` is invalid or
-    /// its chain is not compatible with the chain of `self`.
-    pub fn merge(&mut self, other: Self) -> Result<()> {
+    /// its chain is not compatible with the chain of `self`. Otherwise returns the section chain
+    /// forks the merge brought in.
+    pub fn merge(&mut self, other: Self) -> Result<Vec<SectionChainFork>> {
+        if other.network_params != self.network_params {
+            error!("can't merge sections: other network params differ");
+            return Err(Error::InvalidMessage);
+        }
         if !other.elders_info.self_verify() {
             error!("can't merge sections: other elders_info failed self-verification");
             return Err(Error::InvalidMessage);
@@ -114,7 +146,40 @@ impl Section {
             return Err(Error::InvalidMessage);
         }
 
-        self.chain.merge(other.chain.clone())?;
+        if self.prefix().is_extension_of(other.prefix())
+            && !other.chain.has_key(self.chain.last_key())
+            && !self.chain.has_key(other.chain.last_key())
+        {
+            // Our section merged with its sibling and the merged section continues the chain of
+            // the sibling, so our chain is a dead branch. Switch to the other chain instead,
+            // provided it can be trusted.
+            if !other
+                .chain
+                .check_trust(self.chain.keys().chain(iter::once(&self.genesis_key)))
+            {
+                error!("can't merge sections: other chain not trusted");
+                return Err(Error::InvalidMessage);
+            }
+
+            // None of the other chain was ours, so all its forks are new to us.
+            let forks = other.chain.forks();
+
+            self.chain = other.chain;
+            self.elders_info = other.elders_info;
+            self.members = SectionPeers::default();
+
+            for info in other.members {
+                let _ = self.update_member(info);
+            }
+
+            if let Some(upgrade) = other.protocol_upgrade {
+                let _ = self.upgrade_protocol(upgrade);
+            }
+
+            return Ok(forks);
+        }
+
+        let forks = self.chain.merge(other.chain.clone())?;
 
         if &other.elders_info.proof.public_key == self.chain.last_key() {
             self.elders_info = other.elders_info;
@@ -127,36 +192,46 @@ impl Section {
         self.members
             .prune_not_matching(&self.elders_info.value.prefix);
 
-        Ok(())
+        if let Some(upgrade) = other.protocol_upgrade {
+            let _ = self.upgrade_protocol(upgrade);
+        }
+
+        Ok(forks)
     }
 
-    /// Update the `EldersInfo` of our section.
+    /// Update the `EldersInfo` of our section. The new prefix must be either equal to our
+    /// prefix, an extension of it (split) or its parent (merge). Returns the section chain fork
+    /// the new key created or extended, if any.
     pub fn update_elders(
         &mut self,
         new_elders_info: Proven<EldersInfo>,
         new_key_proof: Proof,
-    ) -> bool {
+    ) -> Result<Option<SectionChainFork>> {
         if new_elders_info.value.prefix != *self.prefix()
             && !new_elders_info.value.prefix.is_extension_of(self.prefix())
+            && (self.prefix().is_empty() || new_elders_info.value.prefix != self.prefix().popped())
         {
-            return false;
+            return Err(Error::InvalidMessage);
         }
 
         if !new_elders_info.self_verify() {
-            return false;
+            return Err(Error::FailedSignature);
         }
 
-        if let Err(error) = self.chain.insert(
+        let fork = match self.chain.insert(
             &new_key_proof.public_key,
             new_elders_info.proof.public_key,
             new_key_proof.signature,
         ) {
-            error!(
-                "failed to insert key {:?} (signed with {:?}) into the section chain: {}",
-                new_elders_info.proof.public_key, new_key_proof.public_key, error,
-            );
-            return false;
-        }
+            Ok(fork) => fork,
+            Err(error) => {
+                error!(
+                    "failed to insert key {:?} (signed with {:?}) into the section chain: {}",
+                    new_elders_info.proof.public_key, new_key_proof.public_key, error,
+                );
+                return Err(error.into());
+            }
+        };
 
         if &new_elders_info.proof.public_key == self.chain.last_key() {
             self.elders_info = new_elders_info;
@@ -165,15 +240,28 @@ impl Section {
         self.members
             .prune_not_matching(&self.elders_info.value.prefix);
 
-        true
+        Ok(fork)
     }
 
     /// Update the member. Returns whether it actually changed anything.
+    ///
+    /// The same info proven with a later key of our chain replaces the current proof, so the chain
+    /// can eventually be pruned past the key the member was proven with (see `chain_checkpoint`).
     pub fn update_member(&mut self, member_info: Proven<MemberInfo>) -> bool {
         if !member_info.verify(&self.chain) {
             return false;
         }
 
+        if let Some(current) = self.members.get_proven(member_info.value.peer.name()) {
+            if current.value == member_info.value {
+                return self
+                    .chain
+                    .cmp_by_position(&member_info.proof.public_key, &current.proof.public_key)
+                    == Ordering::Greater
+                    && self.members.reprove(member_info);
+            }
+        }
+
         self.members.update(member_info)
     }
 
@@ -183,9 +271,11 @@ impl Section {
     pub fn trimmed(&self, chain_len: usize) -> Self {
         Self {
             genesis_key: self.genesis_key,
+            network_params: self.network_params.clone(),
             elders_info: self.elders_info.clone(),
             chain: self.chain.truncate(chain_len),
             members: SectionPeers::default(),
+            protocol_upgrade: self.protocol_upgrade.clone(),
         }
     }
 
@@ -193,6 +283,77 @@ impl Section {
         &self.chain
     }
 
+    // Returns the checkpoint to prune the section chain at which keeps its last `count` keys and
+    // all the `required_keys`, if the chain can be pruned. The members and the protocol upgrade
+    // proven with keys the checkpoint prunes (see `stale_members` and `stale_protocol_upgrade`)
+    // must be proven again with a later key before pruning the chain at it.
+    pub fn chain_checkpoint<'a, I>(&self, count: usize, required_keys: I) -> Option<Checkpoint>
+    where
+        I: IntoIterator<Item = &'a bls::PublicKey>,
+    {
+        self.chain.checkpoint_keeping(count, required_keys)
+    }
+
+    // Returns the members proven with keys pruning the chain at `checkpoint` would discard.
+    pub fn stale_members<'a>(
+        &'a self,
+        checkpoint: &'a Checkpoint,
+    ) -> impl Iterator<Item = &'a MemberInfo> + 'a {
+        self.members
+            .all_proven()
+            .filter(move |info| !self.chain.keeps(checkpoint, &info.proof.public_key))
+            .map(|info| &info.value)
+    }
+
+    // Returns the protocol upgrade if it's proven with a key pruning the chain at `checkpoint`
+    // would discard.
+    pub fn stale_protocol_upgrade(&self, checkpoint: &Checkpoint) -> Option<ProtocolUpgrade> {
+        self.protocol_upgrade
+            .as_ref()
+            .filter(|upgrade| !self.chain.keeps(checkpoint, &upgrade.proof.public_key))
+            .map(|upgrade| upgrade.value)
+    }
+
+    // Prune the section chain at the checkpoint agreed on by the section.
+    pub fn prune_chain(&mut self, checkpoint: Proven<Checkpoint>) -> Result<()> {
+        Ok(self.chain.prune(checkpoint)?)
+    }
+
+    // Version of the routing protocol the section runs.
+    pub fn protocol_version(&self) -> u16 {
+        self.protocol_upgrade
+            .as_ref()
+            .map_or(INITIAL_PROTOCOL_VERSION, |upgrade| upgrade.value.version)
+    }
+
+    // Switch the section to the newer protocol version agreed on by the section. Returns whether
+    // the version actually changed. The current version proven with a later key only replaces the
+    // current proof.
+    pub fn upgrade_protocol(&mut self, upgrade: Proven<ProtocolUpgrade>) -> bool {
+        if upgrade.value.version < self.protocol_version() || !upgrade.verify(&self.chain) {
+            return false;
+        }
+
+        if let Some(current) = &self.protocol_upgrade {
+            if current.value == upgrade.value {
+                if self
+                    .chain
+                    .cmp_by_position(&upgrade.proof.public_key, &current.proof.public_key)
+                    == Ordering::Greater
+                {
+                    self.protocol_upgrade = Some(upgrade);
+                }
+
+                return false;
+            }
+        } else if upgrade.value.version == INITIAL_PROTOCOL_VERSION {
+            return false;
+        }
+
+        self.protocol_upgrade = Some(upgrade);
+        true
+    }
+
     // Extend the section chain so it starts at `trusted_key` while keeping the last key intact.
     pub(crate) fn extend_chain(
         &self,
@@ -211,9 +372,11 @@ impl Section {
 
         Ok(Self {
             genesis_key: self.genesis_key,
+            network_params: self.network_params.clone(),
             elders_info: self.elders_info.clone(),
             chain,
             members: self.members.clone(),
+            protocol_upgrade: self.protocol_upgrade.clone(),
         })
     }
 
@@ -236,7 +399,7 @@ impl Section {
             return vec![our_info, other_info];
         }
 
-        let expected_peers = self.elder_candidates(ELDER_SIZE);
+        let expected_peers = self.elder_candidates(self.network_params().elder_size);
         let expected_names: BTreeSet<_> = expected_peers.iter().map(Peer::name).collect();
         let current_names: BTreeSet<_> = self.elders_info().elders.keys().collect();
 
@@ -251,6 +414,61 @@ impl Section {
         }
     }
 
+    /// Returns whether our section shrunk so much it should merge with its sibling. That is when it
+    /// doesn't have enough members to fill all the elder slots anymore.
+    pub fn should_merge(&self) -> bool {
+        !self.prefix().is_empty()
+            && self.members.joined().count() < self.network_params().elder_size
+    }
+
+    /// Returns whether `sibling` is a valid section that is the sibling of ours and that can be
+    /// trusted by us.
+    pub fn verify_sibling(&self, sibling: &Self) -> bool {
+        sibling.genesis_key == self.genesis_key
+            && sibling.network_params == self.network_params
+            && sibling.prefix() == &self.prefix().sibling()
+            && sibling.prefix() != self.prefix()
+            && &sibling.elders_info.proof.public_key == sibling.chain.last_key()
+            && sibling.elders_info.self_verify()
+            && sibling
+                .chain
+                .check_trust(self.chain.keys().chain(iter::once(&self.genesis_key)))
+    }
+
+    /// Generate the `EldersInfo` of the section resulting from merging our section with `sibling`.
+    /// The result depends only on the two sections, not on which of them is ours: it's computed
+    /// from the union of their members, each verified against the chain of its section. So both
+    /// sections compute the same result as long as they use the same states of each other.
+    pub fn merged_elders_info(&self, sibling: &Self) -> EldersInfo {
+        let prefix = self.prefix().popped();
+
+        // Resolve ties in favour of the elders of the leading section so both sections pick the
+        // same elders.
+        let (leader, other) = if is_merge_leader(self.prefix()) {
+            (self, sibling)
+        } else {
+            (sibling, self)
+        };
+
+        // `SectionPeers::update` is commutative, so the order of the updates doesn't matter.
+        let mut members = SectionPeers::default();
+        for section in &[leader, other] {
+            for info in section.members.all_proven() {
+                if info.verify(&section.chain) {
+                    let _ = members.update(info.clone());
+                }
+            }
+        }
+
+        let elders = members.elder_candidates_matching_prefix(
+            &prefix,
+            self.network_params().elder_size,
+            leader.elders_info(),
+        );
+
+        EldersInfo::new(elders, prefix)
+    }
+
     // Prefix of our section.
     pub fn prefix(&self) -> &Prefix {
         &self.elders_info().prefix
@@ -273,7 +491,7 @@ impl Section {
     /// Returns adults from our section.
     pub fn adults(&self) -> impl Iterator<Item = &Peer> {
         self.members
-            .mature()
+            .mature(self.network_params().min_age)
             .filter(move |peer| !self.is_elder(peer.name()))
     }
 
@@ -299,7 +517,7 @@ impl Section {
 
         let (our_new_size, sibling_new_size) = self
             .members
-            .mature()
+            .mature(self.network_params().min_age)
             .map(|peer| peer.name().bit(next_bit_index) == next_bit)
             .fold((0, 0), |(ours, siblings), is_our_prefix| {
                 if is_our_prefix {
@@ -310,7 +528,8 @@ impl Section {
             });
 
         // If none of the two new sections would contain enough entries, return `None`.
-        if our_new_size < RECOMMENDED_SECTION_SIZE || sibling_new_size < RECOMMENDED_SECTION_SIZE {
+        let min_size = self.network_params().recommended_section_size;
+        if our_new_size < min_size || sibling_new_size < min_size {
             return None;
         }
 
@@ -319,12 +538,12 @@ impl Section {
 
         let our_elders = self.members.elder_candidates_matching_prefix(
             &our_prefix,
-            ELDER_SIZE,
+            self.network_params().elder_size,
             self.elders_info(),
         );
         let other_elders = self.members.elder_candidates_matching_prefix(
             &other_prefix,
-            ELDER_SIZE,
+            self.network_params().elder_size,
             self.elders_info(),
         );
 
@@ -342,6 +561,12 @@ impl Section {
     }
 }
 
+/// Returns whether the section with `prefix` leads a merge with its sibling. The leading section
+/// signs the key of the merged section, so the merged section continues its chain.
+pub(crate) fn is_merge_leader(prefix: &Prefix) -> bool {
+    !prefix.is_empty() && prefix.popped().pushed(false) == *prefix
+}
+
 // Create `EldersInfo` for the first node.
 fn create_first_elders_info(
     pk_set: &bls::PublicKeySet,
@@ -6,11 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::{agreement::Proven, wire};
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     cmp::Ordering,
-    collections::HashSet,
+    collections::{BTreeMap, BTreeSet, HashSet},
     convert::TryFrom,
     fmt::{self, Debug, Formatter},
     iter, mem,
@@ -30,18 +31,30 @@ use thiserror::Error;
 ///
 /// It's possible to insert multiple keys that all have the same parent key. This is called a
 /// "fork". The chain implements automatic fork resolution which means that even in the presence of
-/// forks the chain presents the blocks in a well-defined unique and deterministic order.
+/// forks the chain presents the blocks in a well-defined unique and deterministic order. The
+/// forks can still be queried using [`forks`](Self::forks) and [`fork_at`](Self::fork_at).
 ///
 /// # Block order
 ///
 /// Block are ordered primarily according to their parent-child relation (parents always precede
 /// children) and forks are resolved by additionally ordering the sibling blocks according to the
 /// `Ord` relation of their public key. That is, "lower" keys precede "higher" keys.
-#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
-#[serde(try_from = "Deserialized")]
+///
+/// # Checkpoints
+///
+/// A long-lived chain can be pruned at a [`Checkpoint`](Checkpoint) agreed on by the section (see
+/// [`prune`](Self::prune)). The pruned chain starts at the checkpoint key and still proves trust
+/// in all the later keys to anyone trusting the checkpoint key or any key after it.
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct SectionChain {
     root: bls::PublicKey,
     tree: Vec<Block>,
+    // Checkpoint the chain was pruned at, if any. Its key is the root key.
+    checkpoint: Option<Proven<Checkpoint>>,
+    // Position of every key in the chain, so keys can be looked up without scanning the chain.
+    index: BTreeMap<bls::PublicKey, usize>,
+    // Number of ancestors of every block in `tree`.
+    depths: Vec<usize>,
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -51,17 +64,21 @@ impl SectionChain {
         Self {
             root,
             tree: Vec::new(),
+            checkpoint: None,
+            index: iter::once((root, 0)).collect(),
+            depths: Vec::new(),
         }
     }
 
     /// Insert new key into the chain. `parent_key` must exists in the chain and must validate
-    /// `signature`, otherwise error is returned.
+    /// `signature`, otherwise error is returned. Returns the fork at `parent_key` if the new key
+    /// created or extended one.
     pub fn insert(
         &mut self,
         parent_key: &bls::PublicKey,
         key: bls::PublicKey,
         signature: bls::Signature,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<Fork>, Error> {
         let parent_index = self.index_of(parent_key).ok_or(Error::KeyNotFound)?;
         let block = Block {
             key,
@@ -70,8 +87,12 @@ impl SectionChain {
         };
 
         if block.verify(parent_key) {
-            let _ = self.insert_block(block);
-            Ok(())
+            let (_, inserted) = self.insert_block(block);
+            Ok(if inserted {
+                self.fork_at(parent_key)
+            } else {
+                None
+            })
         } else {
             Err(Error::FailedSignature)
         }
@@ -80,19 +101,24 @@ impl SectionChain {
     /// Merges two chains into one.
     ///
     /// This succeeds only if the root key of one of the chain is present in the other one.
-    /// Otherwise it returns `Error::InvalidOperation`
-    pub fn merge(&mut self, mut other: Self) -> Result<(), Error> {
-        let root_index = if let Some(index) = self.index_of(other.root_key()) {
-            index
+    /// Otherwise it returns `Error::InvalidOperation`. Returns the forks created or extended by
+    /// the keys this chain didn't have yet.
+    pub fn merge(&mut self, mut other: Self) -> Result<Vec<Fork>, Error> {
+        // When the chains get swapped, the keys of this chain are the ones merged in, so the new
+        // keys are those not among them.
+        let (root_index, ours) = if let Some(index) = self.index_of(other.root_key()) {
+            (index, None)
         } else if let Some(index) = other.index_of(self.root_key()) {
+            let ours: HashSet<_> = self.keys().copied().collect();
             mem::swap(self, &mut other);
-            index
+            (index, Some(ours))
         } else {
             return Err(Error::InvalidOperation);
         };
 
         let mut reindex_map = vec![0; other.len()];
         reindex_map[0] = root_index;
+        let mut fork_parents = BTreeSet::new();
 
         for (other_index, mut other_block) in other
             .tree
@@ -101,12 +127,155 @@ impl SectionChain {
             .map(|(index, block)| (index + 1, block))
         {
             other_block.parent_index = reindex_map[other_block.parent_index];
-            reindex_map[other_index] = self.insert_block(other_block);
+            let parent_key = *self.key_at(other_block.parent_index);
+            let (index, inserted) = self.insert_block(other_block);
+            reindex_map[other_index] = index;
+
+            if inserted && ours.is_none() {
+                let _ = fork_parents.insert(parent_key);
+            }
+        }
+
+        if let Some(ours) = ours {
+            fork_parents.extend(
+                self.tree
+                    .iter()
+                    .filter(|block| !ours.contains(&block.key))
+                    .map(|block| *self.key_at(block.parent_index)),
+            );
+        }
+
+        // Keep the chain pruned at the latest of the checkpoints. Failing to prune only means the
+        // chain is longer than necessary, so the error is ignored.
+        if let Some(checkpoint) = other.checkpoint {
+            let _ = self.prune(checkpoint);
+        }
+
+        Ok(fork_parents
+            .iter()
+            .filter_map(|parent_key| self.fork_at(parent_key))
+            .collect())
+    }
+
+    /// Prunes the chain at `checkpoint`: the checkpoint key becomes the root key and all the
+    /// blocks not descending from it are discarded. The checkpoint is kept in the chain, so
+    /// [`main_branch_len`](Self::main_branch_len) still counts the discarded keys.
+    ///
+    /// Does nothing if the chain is already pruned at the same or a later checkpoint.
+    ///
+    /// Returns `Error::KeyNotFound` if the checkpoint key or the key that signed the checkpoint is
+    /// not present in `self`, `Error::FailedSignature` if the checkpoint is not validly signed by
+    /// the checkpoint key or a key descending from it and `Error::InvalidOperation` if the height
+    /// of the checkpoint doesn't match the position of its key in `self`.
+    pub fn prune(&mut self, checkpoint: Proven<Checkpoint>) -> Result<(), Error> {
+        if self.checkpoint.as_ref().map_or(false, |current| {
+            current.value.height >= checkpoint.value.height
+        }) {
+            return Ok(());
         }
 
+        let key_index = self
+            .index_of(&checkpoint.value.key)
+            .ok_or(Error::KeyNotFound)?;
+        let signer_index = self
+            .index_of(&checkpoint.proof.public_key)
+            .ok_or(Error::KeyNotFound)?;
+
+        if !self.is_ancestor(key_index, signer_index) || !checkpoint.self_verify() {
+            return Err(Error::FailedSignature);
+        }
+
+        if self.height() + self.depth_at(key_index) != checkpoint.value.height {
+            return Err(Error::InvalidOperation);
+        }
+
+        let mut reindex_map = vec![None; self.len()];
+        reindex_map[key_index] = Some(0);
+        let mut tree = Vec::new();
+
+        for (index, block) in self
+            .tree
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (index + 1, block))
+            .skip(key_index)
+        {
+            if let Some(parent_index) = reindex_map[block.parent_index] {
+                tree.push(Block {
+                    key: block.key,
+                    signature: block.signature.clone(),
+                    parent_index,
+                });
+                reindex_map[index] = Some(tree.len());
+            }
+        }
+
+        self.root = checkpoint.value.key;
+        self.tree = tree;
+        self.checkpoint = Some(checkpoint);
+        self.reindex();
+
         Ok(())
     }
 
+    /// Returns the checkpoint the chain was pruned at, if any.
+    pub fn checkpoint(&self) -> Option<&Proven<Checkpoint>> {
+        self.checkpoint.as_ref()
+    }
+
+    /// Returns the checkpoint at the latest key of the main branch such that pruning the chain at
+    /// it keeps at least the last `count` keys of the main branch and all the `required_keys`
+    /// present in the chain. Returns `None` if that key is the root key, as there is nothing to
+    /// prune then. The checkpoint still needs to be signed by the section before the chain can be
+    /// pruned at it.
+    pub fn checkpoint_keeping<'a, I>(&self, count: usize, required_keys: I) -> Option<Checkpoint>
+    where
+        I: IntoIterator<Item = &'a bls::PublicKey>,
+    {
+        // Indices of the keys on the main branch, ordered by their depth.
+        let mut main_branch: Vec<_> = iter::once(self.len() - 1)
+            .chain(self.branch(self.tree.len()).map(|block| block.parent_index))
+            .collect();
+        main_branch.reverse();
+
+        let mut depth = main_branch.len().saturating_sub(count.max(1));
+
+        for key in required_keys {
+            let mut index = if let Some(index) = self.index_of(key) {
+                index
+            } else {
+                continue;
+            };
+
+            // Find the closest ancestor of the key on the main branch.
+            while main_branch.get(self.depth_at(index)) != Some(&index) {
+                index = self.parent_index_at(index).unwrap_or(0);
+            }
+
+            depth = depth.min(self.depth_at(index));
+        }
+
+        if depth == 0 {
+            return None;
+        }
+
+        Some(Checkpoint {
+            key: *self.keys().nth(main_branch[depth])?,
+            height: self.height() + depth,
+        })
+    }
+
+    /// Returns whether pruning the chain at `checkpoint` keeps `key`, that is, whether `key` is the
+    /// checkpoint key or descends from it.
+    pub fn keeps(&self, checkpoint: &Checkpoint, key: &bls::PublicKey) -> bool {
+        match (self.index_of(&checkpoint.key), self.index_of(key)) {
+            (Some(checkpoint_index), Some(key_index)) => {
+                self.is_ancestor(checkpoint_index, key_index)
+            }
+            _ => false,
+        }
+    }
+
     /// Creates a minimal sub-chain of `self` that contains all `required_keys`.
     /// Returns `Error::KeyNotFound` if some of `required_keys` is not present in `self`.
     ///
@@ -166,6 +335,11 @@ impl SectionChain {
             })
         }
 
+        chain.reindex();
+        if min_index == 0 {
+            chain.retain_checkpoint(self.checkpoint.as_ref());
+        }
+
         Ok(chain)
     }
 
@@ -190,7 +364,14 @@ impl SectionChain {
             block.parent_index = index;
         }
 
-        Self { root, tree }
+        let mut chain = Self::new(root);
+        chain.tree = tree;
+        chain.reindex();
+        if root == self.root {
+            chain.retain_checkpoint(self.checkpoint.as_ref());
+        }
+
+        chain
     }
 
     /// Returns the smallest super-chain of `self` that would be trusted by a peer that trust
@@ -249,7 +430,7 @@ impl SectionChain {
 
     /// Returns whether `key` is present in this chain.
     pub fn has_key(&self, key: &bls::PublicKey) -> bool {
-        self.keys().any(|existing_key| existing_key == key)
+        self.index.contains_key(key)
     }
 
     /// Given a collection of keys that are already trusted, returns whether this chain is also
@@ -300,6 +481,27 @@ impl SectionChain {
             .any(|key| trusted_keys.contains(key))
     }
 
+    /// Given a collection of keys that are already trusted, returns whether `key` is also trusted.
+    /// That is only if `key` is one of the `trusted_keys` or descends from one of them on its own
+    /// branch. Unlike the chain itself (see `check_trust`), a key above the trusted ones is not
+    /// trusted, because anyone can sign the bytes of a public key with a key of their own.
+    pub fn check_key_trust<'a, I>(&self, key: &bls::PublicKey, trusted_keys: I) -> bool
+    where
+        I: IntoIterator<Item = &'a bls::PublicKey>,
+    {
+        let index = if let Some(index) = self.index_of(key) {
+            index
+        } else {
+            return false;
+        };
+
+        let trusted_keys: HashSet<_> = trusted_keys.into_iter().collect();
+        self.branch(index)
+            .map(|block| &block.key)
+            .chain(iter::once(&self.root))
+            .any(|key| trusted_keys.contains(key))
+    }
+
     /// Compare the two keys by their position in the chain. The key that is higher (closer to the
     /// last key) is considered `Greater`. If exactly one of the keys is not in the chain, the other
     /// one is implicitly considered `Greater`. If none are in the chain, they are considered
@@ -319,14 +521,56 @@ impl SectionChain {
     }
 
     /// Returns the number of block on the main branch of the chain - that is - the ones reachable
-    /// from the last block.
-    ///
-    /// NOTE: this is a `O(n)` operation.
+    /// from the last block. If the chain was pruned, this includes the discarded blocks that
+    /// preceded the checkpoint.
     pub fn main_branch_len(&self) -> usize {
-        self.branch(self.tree.len()).count() + 1
+        self.height() + self.depth_at(self.len() - 1) + 1
+    }
+
+    /// Returns all the forks in the chain, ordered by the position of their parent keys.
+    pub fn forks(&self) -> Vec<Fork> {
+        let mut children: BTreeMap<usize, Vec<bls::PublicKey>> = BTreeMap::new();
+        for block in &self.tree {
+            children
+                .entry(block.parent_index)
+                .or_default()
+                .push(block.key);
+        }
+
+        children
+            .into_iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .map(|(parent_index, keys)| Fork {
+                parent: *self.key_at(parent_index),
+                keys,
+            })
+            .collect()
+    }
+
+    /// Returns the fork at `parent_key`, if `parent_key` is in the chain and signed more than one
+    /// key.
+    pub fn fork_at(&self, parent_key: &bls::PublicKey) -> Option<Fork> {
+        let parent_index = self.index_of(parent_key)?;
+        let keys: Vec<_> = self
+            .tree
+            .iter()
+            .filter(|block| block.parent_index == parent_index)
+            .map(|block| block.key)
+            .collect();
+
+        if keys.len() > 1 {
+            Some(Fork {
+                parent: *parent_key,
+                keys,
+            })
+        } else {
+            None
+        }
     }
 
-    fn insert_block(&mut self, new_block: Block) -> usize {
+    // Insert the block unless its key is already in the chain. Returns the index of the key and
+    // whether it was inserted.
+    fn insert_block(&mut self, new_block: Block) -> (usize, bool) {
         // Find the index into `self.tree` to insert the new block at so that the block order as
         // described in the `SectionChain` doc comment is maintained.
         let insert_at = self
@@ -342,25 +586,87 @@ impl SectionChain {
 
         // If the key already exists in the chain, do nothing but still return success to make the
         // `insert` operation idempotent.
-        if self.tree.get(insert_at).map(|block| &block.key) != Some(&new_block.key) {
+        let inserted = self.tree.get(insert_at).map(|block| &block.key) != Some(&new_block.key);
+        if inserted {
+            let key = new_block.key;
+            let depth = self.depth_at(new_block.parent_index) + 1;
             self.tree.insert(insert_at, new_block);
 
-            // Adjust the parent indices of the keys whose parents are after the inserted key.
-            for block in &mut self.tree[insert_at + 1..] {
-                if block.parent_index > insert_at {
-                    block.parent_index += 1;
+            if insert_at + 1 == self.tree.len() {
+                // Appended, no other block moved.
+                let _ = self.index.insert(key, insert_at + 1);
+                self.depths.push(depth);
+            } else {
+                // Adjust the parent indices of the keys whose parents are after the inserted key.
+                for block in &mut self.tree[insert_at + 1..] {
+                    if block.parent_index > insert_at {
+                        block.parent_index += 1;
+                    }
                 }
+
+                self.reindex();
             }
         }
 
-        insert_at + 1
+        (insert_at + 1, inserted)
+    }
+
+    // Rebuild the key index and the block depths after the blocks were rearranged.
+    fn reindex(&mut self) {
+        self.index = self
+            .keys()
+            .enumerate()
+            .map(|(index, key)| (*key, index))
+            .collect();
+
+        let mut depths: Vec<usize> = Vec::with_capacity(self.tree.len());
+        for block in &self.tree {
+            let parent_depth = if block.parent_index == 0 {
+                0
+            } else {
+                depths[block.parent_index - 1]
+            };
+            depths.push(parent_depth + 1);
+        }
+        self.depths = depths;
+    }
+
+    // Keep `checkpoint` if it still applies to this chain, that is, if it's at the root key and
+    // its signing key is present.
+    fn retain_checkpoint(&mut self, checkpoint: Option<&Proven<Checkpoint>>) {
+        self.checkpoint = checkpoint
+            .filter(|checkpoint| {
+                checkpoint.value.key == self.root && self.has_key(&checkpoint.proof.public_key)
+            })
+            .cloned();
     }
 
     fn index_of(&self, key: &bls::PublicKey) -> Option<usize> {
-        self.keys()
-            .rev()
-            .position(|existing_key| existing_key == key)
-            .map(|rev_position| self.len() - rev_position - 1)
+        self.index.get(key).copied()
+    }
+
+    fn key_at(&self, index: usize) -> &bls::PublicKey {
+        if index == 0 {
+            &self.root
+        } else {
+            &self.tree[index - 1].key
+        }
+    }
+
+    // Number of the ancestors of the key at `index`.
+    fn depth_at(&self, index: usize) -> usize {
+        if index == 0 {
+            0
+        } else {
+            self.depths.get(index - 1).copied().unwrap_or(0)
+        }
+    }
+
+    // Number of the keys that preceded the root key before the chain was pruned.
+    fn height(&self) -> usize {
+        self.checkpoint
+            .as_ref()
+            .map_or(0, |checkpoint| checkpoint.value.height as usize)
     }
 
     fn parent_index_at(&self, index: usize) -> Option<usize> {
@@ -440,6 +746,29 @@ pub enum Error {
     InvalidOperation,
 }
 
+/// Key of a [`SectionChain`] at which the chain can be pruned. Checkpoints are agreed on by the
+/// section, so every node prunes its chain at the same key and the checkpoint is signed by a key
+/// descending from the checkpoint key, making it trusted whenever the pruned chain is.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The key the chain is pruned to. It becomes the root key of the pruned chain.
+    pub key: bls::PublicKey,
+    /// Number of keys preceding `key` on the main branch of the unpruned chain.
+    pub height: usize,
+}
+
+/// Key of a [`SectionChain`] that signed more than one key. This means the section agreed on
+/// multiple successors of the key, for example because two DKG sessions completed and both their
+/// outcomes got signed. The chain resolves the fork on its own (see the `SectionChain` docs), but
+/// it still indicates a problem in the section.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Fork {
+    /// The key that signed all of `keys`.
+    pub parent: bls::PublicKey,
+    /// The keys signed by `parent`, in the chain order.
+    pub keys: Vec<bls::PublicKey>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 struct Block {
     key: bls::PublicKey,
@@ -489,15 +818,34 @@ impl<'a> Iterator for Branch<'a> {
     }
 }
 
+// `SectionChain` uses the versioned wire format (see the `wire` module). Only the root, the blocks
+// and the checkpoint are serialized, the index is rebuilt on deserialization.
+impl Serialize for SectionChain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        wire::serialize(&(&self.root, &self.tree, &self.checkpoint), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SectionChain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (root, tree, checkpoint) = wire::deserialize(deserializer)?;
+        Self::try_from(Deserialized {
+            root,
+            tree,
+            checkpoint,
+        })
+        .map_err(D::Error::custom)
+    }
+}
+
 // `SectionChain` is deserialized by first deserializing it into this intermediate structure and
 // then converting it into `SectionChain` using `try_from` which fails when the chain is invalid.
 // This makes it impossible to obtain invalid `SectionChain` from malformed serialized data, thus
 // making `SectionChain` "correct by deserialization".
-#[derive(Deserialize)]
-#[serde(rename = "SectionChain")]
 struct Deserialized {
     root: bls::PublicKey,
     tree: Vec<Block>,
+    checkpoint: Option<Proven<Checkpoint>>,
 }
 
 impl TryFrom<Deserialized> for SectionChain {
@@ -537,10 +885,19 @@ impl TryFrom<Deserialized> for SectionChain {
             prev_block = Some(block);
         }
 
-        Ok(Self {
-            root: src.root,
-            tree: src.tree,
-        })
+        let mut chain = Self::new(src.root);
+        chain.tree = src.tree;
+        chain.reindex();
+
+        if let Some(checkpoint) = src.checkpoint {
+            if checkpoint.value.key != chain.root || !checkpoint.verify(&chain) {
+                return Err(IntegrityError::InvalidCheckpoint);
+            }
+
+            chain.checkpoint = Some(checkpoint);
+        }
+
+        Ok(chain)
     }
 }
 
@@ -552,11 +909,15 @@ enum IntegrityError {
     ParentNotFound,
     #[error("chain blocks are in a wrong order")]
     WrongBlockOrder,
+    #[error("checkpoint is not valid for the chain")]
+    InvalidCheckpoint,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agreement::test_utils;
+    use anyhow::Result;
 
     #[test]
     fn insert_last() {
@@ -570,7 +931,7 @@ mod tests {
             let last_pk = &expected_keys[expected_keys.len() - 1];
             let (sk, pk, sig) = gen_signed_keypair(&last_sk);
 
-            assert_eq!(chain.insert(last_pk, pk, sig), Ok(()));
+            assert_eq!(chain.insert(last_pk, pk, sig), Ok(None));
 
             expected_keys.push(pk);
             last_sk = sk;
@@ -587,9 +948,18 @@ mod tests {
         let (_, pk1_b, sig1_b) = gen_signed_keypair(&sk0);
 
         let mut chain = SectionChain::new(pk0);
-        assert_eq!(chain.insert(&pk0, pk1_a, sig1_a), Ok(()));
-        assert_eq!(chain.insert(&pk1_a, pk2_a, sig2_a), Ok(()));
-        assert_eq!(chain.insert(&pk0, pk1_b, sig1_b), Ok(()));
+        assert_eq!(chain.insert(&pk0, pk1_a, sig1_a), Ok(None));
+        assert_eq!(chain.insert(&pk1_a, pk2_a, sig2_a), Ok(None));
+
+        let mut fork_keys = vec![pk1_a, pk1_b];
+        fork_keys.sort();
+        assert_eq!(
+            chain.insert(&pk0, pk1_b, sig1_b),
+            Ok(Some(Fork {
+                parent: pk0,
+                keys: fork_keys
+            }))
+        );
 
         let expected_keys = if pk1_a > pk1_b {
             vec![&pk0, &pk1_b, &pk1_a, &pk2_a]
@@ -608,8 +978,8 @@ mod tests {
         let (_, pk1, sig1) = gen_signed_keypair(&sk0);
 
         let mut chain = SectionChain::new(pk0);
-        assert_eq!(chain.insert(&pk0, pk1, sig1.clone()), Ok(()));
-        assert_eq!(chain.insert(&pk0, pk1, sig1), Ok(()));
+        assert_eq!(chain.insert(&pk0, pk1, sig1.clone()), Ok(None));
+        assert_eq!(chain.insert(&pk0, pk1, sig1), Ok(None));
         assert_eq!(chain.keys().collect::<Vec<_>>(), vec![&pk0, &pk1]);
     }
 
@@ -628,6 +998,7 @@ mod tests {
                 signature: bad_sig,
                 parent_index: 0,
             }],
+            checkpoint: None,
         };
 
         assert_eq!(
@@ -660,6 +1031,7 @@ mod tests {
                     parent_index: 0,
                 },
             ],
+            checkpoint: None,
         };
 
         assert_eq!(
@@ -703,6 +1075,7 @@ mod tests {
         let src = Deserialized {
             root: pk0,
             tree: vec![large, small],
+            checkpoint: None,
         };
 
         assert_eq!(
@@ -744,6 +1117,7 @@ mod tests {
                     parent_index: 0,
                 },
             ],
+            checkpoint: None,
         };
 
         assert_eq!(
@@ -811,6 +1185,63 @@ mod tests {
         assert_eq!(merge_chains(lhs, rhs), Ok(expected))
     }
 
+    #[test]
+    fn forks() {
+        let (sk0, pk0) = gen_keypair();
+        let (sk1, pk1, sig1) = gen_signed_keypair(&sk0);
+        let (_, pk2, sig2) = gen_signed_keypair(&sk1);
+
+        // 0->1->2
+        let mut chain = make_chain(pk0, vec![(&pk0, pk1, sig1), (&pk1, pk2, sig2)]);
+        assert_eq!(chain.forks(), vec![]);
+        assert_eq!(chain.fork_at(&pk1), None);
+
+        // 0->1->2
+        //    |
+        //    +->3
+        //    |
+        //    +->4
+        let (_, pk3, sig3) = gen_signed_keypair(&sk1);
+        let (_, pk4, sig4) = gen_signed_keypair(&sk1);
+        let other = make_chain(pk1, vec![(&pk1, pk3, sig3)]);
+        let mut keys = vec![pk2, pk3];
+        keys.sort();
+        assert_eq!(chain.merge(other), Ok(vec![Fork { parent: pk1, keys }]));
+
+        let mut keys = vec![pk2, pk3, pk4];
+        keys.sort();
+        let expected = Fork { parent: pk1, keys };
+
+        assert_eq!(chain.insert(&pk1, pk4, sig4), Ok(Some(expected.clone())));
+        assert_eq!(chain.forks(), vec![expected.clone()]);
+        assert_eq!(chain.fork_at(&pk1), Some(expected));
+        assert_eq!(chain.fork_at(&pk0), None);
+        assert_eq!(chain.fork_at(&pk2), None);
+    }
+
+    #[test]
+    fn merge_returns_only_new_forks() {
+        let (sk0, pk0) = gen_keypair();
+        let (sk1, pk1, sig1) = gen_signed_keypair(&sk0);
+        let (_, pk2, sig2) = gen_signed_keypair(&sk1);
+        let (_, pk3, sig3) = gen_signed_keypair(&sk1);
+
+        let mut keys = vec![pk2, pk3];
+        keys.sort();
+        let expected = Fork { parent: pk1, keys };
+
+        // 1->3 merged with 0->1->2. The chains get swapped as the root of the other chain is not
+        // in ours.
+        let mut chain = make_chain(pk1, vec![(&pk1, pk3, sig3.clone())]);
+        let other = make_chain(pk0, vec![(&pk0, pk1, sig1.clone()), (&pk1, pk2, sig2)]);
+        assert_eq!(chain.merge(other), Ok(vec![expected.clone()]));
+        assert_eq!(chain.fork_at(&pk1), Some(expected));
+
+        // Merging keys we already have reports no fork, even though the chain has one.
+        let other = make_chain(pk0, vec![(&pk0, pk1, sig1), (&pk1, pk3, sig3)]);
+        assert_eq!(chain.merge(other), Ok(vec![]));
+    }
+
     #[test]
     fn minimize() {
         let (sk0, pk0) = gen_keypair();
@@ -1107,6 +1538,135 @@ mod tests {
         assert_eq!(chain.main_branch_len(), 2);
     }
 
+    #[test]
+    fn prune() -> Result<()> {
+        // 0->1->2->3
+        //    |
+        //    +->4
+        let (sk0, pk0) = gen_keypair();
+        let (sk1, pk1, sig1) = gen_signed_keypair(&sk0);
+        let (sk2, pk2, sig2) = gen_signed_keypair(&sk1);
+        let (sk3, pk3, sig3) = gen_signed_keypair(&sk2);
+        let (_, pk4, sig4) = gen_signed_keypair_filter(&sk1, |pk| pk > &pk2);
+
+        let mut chain = make_chain(
+            pk0,
+            vec![
+                (&pk0, pk1, sig1),
+                (&pk1, pk2, sig2),
+                (&pk2, pk3, sig3),
+                (&pk1, pk4, sig4),
+            ],
+        );
+        assert_eq!(chain.main_branch_len(), 4);
+        assert_eq!(chain.checkpoint_keeping(4, iter::empty()), None);
+
+        // A required key on a fork holds the checkpoint back at its ancestor on the main branch.
+        assert_eq!(
+            chain.checkpoint_keeping(2, iter::once(&pk4)),
+            Some(Checkpoint {
+                key: pk1,
+                height: 1
+            })
+        );
+
+        let checkpoint = chain
+            .checkpoint_keeping(2, iter::once(&pk3))
+            .expect("no checkpoint");
+        assert_eq!(
+            checkpoint,
+            Checkpoint {
+                key: pk2,
+                height: 2
+            }
+        );
+
+        // The checkpoint must be signed by a key descending from the checkpoint key.
+        assert_eq!(
+            chain.prune(test_utils::proven(&sk1, checkpoint)?),
+            Err(Error::FailedSignature)
+        );
+
+        // The height must match the position of the key.
+        let wrong_height = Checkpoint {
+            height: 3,
+            ..checkpoint
+        };
+        assert_eq!(
+            chain.prune(test_utils::proven(&sk3, wrong_height)?),
+            Err(Error::InvalidOperation)
+        );
+
+        let full_chain = chain.clone();
+        let checkpoint = test_utils::proven(&sk3, checkpoint)?;
+        assert_eq!(chain.prune(checkpoint.clone()), Ok(()));
+
+        // The fork not descending from the checkpoint key was discarded.
+        assert_eq!(chain.keys().collect::<Vec<_>>(), vec![&pk2, &pk3]);
+        assert_eq!(chain.checkpoint(), Some(&checkpoint));
+        assert_eq!(chain.main_branch_len(), 4);
+        assert!(!chain.has_key(&pk1));
+        assert!(chain.check_trust(iter::once(&pk2)));
+        assert!(!chain.check_trust(iter::once(&pk0)));
+
+        // The checkpoint survives serialization.
+        let bytes = bincode::serialize(&chain)?;
+        assert_eq!(bincode::deserialize::<SectionChain>(&bytes)?, chain);
+
+        // Merging with the full chain keeps the chain pruned, regardless of the order.
+        let mut merged = full_chain.clone();
+        let _ = merged.merge(chain.clone())?;
+        assert_eq!(merged, chain);
+
+        let mut merged = chain.clone();
+        let _ = merged.merge(full_chain)?;
+        assert_eq!(merged, chain);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_deserialized_chain_invalid_checkpoint() -> Result<()> {
+        let (sk0, pk0) = gen_keypair();
+        let (sk1, pk1, sig1) = gen_signed_keypair(&sk0);
+
+        // Checkpoint signed by a key not in the chain.
+        let checkpoint = Checkpoint {
+            key: pk0,
+            height: 1,
+        };
+        let src = Deserialized {
+            root: pk0,
+            tree: vec![Block {
+                key: pk1,
+                signature: sig1,
+                parent_index: 0,
+            }],
+            checkpoint: Some(test_utils::proven(&bls::SecretKey::random(), checkpoint)?),
+        };
+        assert_eq!(
+            SectionChain::try_from(src),
+            Err(IntegrityError::InvalidCheckpoint)
+        );
+
+        // Checkpoint not at the root key.
+        let checkpoint = Checkpoint {
+            key: pk1,
+            height: 1,
+        };
+        let src = Deserialized {
+            root: pk0,
+            tree: vec![],
+            checkpoint: Some(test_utils::proven(&sk1, checkpoint)?),
+        };
+        assert_eq!(
+            SectionChain::try_from(src),
+            Err(IntegrityError::InvalidCheckpoint)
+        );
+
+        Ok(())
+    }
+
     fn gen_keypair() -> (bls::SecretKey, bls::PublicKey) {
         let sk = bls::SecretKey::random();
         let pk = sk.public_key();
@@ -1146,7 +1706,7 @@ mod tests {
     ) -> SectionChain {
         let mut chain = SectionChain::new(root);
         for (parent_key, key, signature) in rest {
-            assert_eq!(chain.insert(parent_key, key, signature), Ok(()));
+            assert!(chain.insert(parent_key, key, signature).is_ok());
         }
         chain
     }
@@ -1156,7 +1716,7 @@ mod tests {
         mut lhs: SectionChain,
         rhs: SectionChain,
     ) -> Result<Vec<bls::PublicKey>, Error> {
-        lhs.merge(rhs)?;
+        let _ = lhs.merge(rhs)?;
         Ok(lhs.keys().copied().collect())
     }
 
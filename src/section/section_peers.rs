@@ -45,10 +45,15 @@ impl SectionPeers {
             .filter(|member| member.state == PeerState::Joined)
     }
 
-    /// Returns joined nodes from our section with age greater than `MIN_AGE`
-    pub fn mature(&self) -> impl Iterator<Item = &Peer> {
+    /// Returns an iterator over the proven infos of all current and past members.
+    pub fn all_proven(&self) -> impl Iterator<Item = &Proven<MemberInfo>> {
+        self.members.values()
+    }
+
+    /// Returns joined nodes from our section with age greater than `min_age`
+    pub fn mature(&self, min_age: u8) -> impl Iterator<Item = &Peer> {
         self.joined()
-            .filter(|info| info.is_mature())
+            .filter(move |info| info.is_mature(min_age))
             .map(|info| &info.peer)
     }
 
@@ -126,6 +131,18 @@ impl SectionPeers {
         }
     }
 
+    /// Replace the proof of a member whose info doesn't change.
+    /// Returns whether such member exists.
+    pub fn reprove(&mut self, new_info: Proven<MemberInfo>) -> bool {
+        match self.members.get_mut(new_info.value.peer.name()) {
+            Some(info) if info.value == new_info.value => {
+                *info = new_info;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Remove all members whose name does not match `prefix`.
     pub fn prune_not_matching(&mut self, prefix: &Prefix) {
         self.members = mem::take(&mut self.members)
@@ -208,7 +225,7 @@ fn cmp_elder_candidates(
 // Compare candidates for the next elders according to their peer state. The one comparing `Less`
 // wins. `Joined` is preferred over `Relocated` which is preferred over `Left`.
 // NOTE: we only consider `Relocated` peers as elder candidates if we don't have enough `Joined`
-// members to reach `ELDER_SIZE`.
+// members to reach the elder size.
 fn cmp_elder_candidates_by_peer_state(lhs: &PeerState, rhs: &PeerState) -> Ordering {
     use PeerState::*;
 
@@ -0,0 +1,267 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Verification of items signed by a section.
+//!
+//! An item signed by a section comes with the section signature and a proof chain - a
+//! [`SectionChain`] in which every key is signed by its parent. The item is trusted if its
+//! signing key is one of the keys the verifier already trusts (for example the genesis key, or
+//! the section keys it learned before) or descends from one of them on its own branch (see
+//! [`SectionChain::check_key_trust`]). Keys above a trusted key prove nothing - anyone can sign
+//! the bytes of a public key - so a signature by a key which isn't trusted is only accepted, as
+//! untrusted, if it's made by the last key of the proof chain.
+//!
+//! The functions here don't need a running [`Routing`](crate::Routing) node, so clients and
+//! services receiving section signed items can use them directly.
+
+use crate::{agreement::Proven, section::SectionChain};
+pub use bls_signature_aggregator::Proof;
+use serde::Serialize;
+
+/// Result of verifying an item signed by a section.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verification {
+    /// The signature is valid and made by a trusted key or a key descending from one.
+    Trusted,
+    /// The signature is valid and made by the last key of the proof chain, but the key doesn't
+    /// descend from any of the trusted keys. The item might still be valid, but the verifier
+    /// doesn't know the keys needed to tell, for example because its knowledge of the signing
+    /// section is outdated or the proof chain was trimmed too much.
+    Untrusted,
+    /// The signing key is not in the proof chain, or it's neither trusted nor the last key of the
+    /// proof chain.
+    UnknownKey,
+    /// The signature is not valid for the payload.
+    InvalidSignature,
+}
+
+impl Verification {
+    /// Returns whether the item is valid and trusted.
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, Self::Trusted)
+    }
+}
+
+/// Verifies `signature` of `payload` made by any key of `proof_chain`, trusting it if the signing
+/// key is one of `trusted_keys` or descends from one. The keys are tried from the last one, as
+/// items are usually signed by the current section key. When the signing key is known, prefer
+/// [`verify_proof`] which checks only that key.
+pub fn verify<'a, I>(
+    payload: &[u8],
+    signature: &bls::Signature,
+    proof_chain: &SectionChain,
+    trusted_keys: I,
+) -> Verification
+where
+    I: IntoIterator<Item = &'a bls::PublicKey>,
+{
+    if let Some(key) = proof_chain
+        .keys()
+        .rev()
+        .find(|key| key.verify(signature, payload))
+    {
+        check_trust(proof_chain, key, trusted_keys)
+    } else {
+        Verification::InvalidSignature
+    }
+}
+
+/// Verifies `proof` of `payload`, which must be signed by a key of `proof_chain`, trusting it if
+/// the signing key is one of `trusted_keys` or descends from one.
+pub fn verify_proof<'a, I>(
+    payload: &[u8],
+    proof: &Proof,
+    proof_chain: &SectionChain,
+    trusted_keys: I,
+) -> Verification
+where
+    I: IntoIterator<Item = &'a bls::PublicKey>,
+{
+    if !proof_chain.has_key(&proof.public_key) {
+        Verification::UnknownKey
+    } else if !proof.verify(payload) {
+        Verification::InvalidSignature
+    } else {
+        check_trust(proof_chain, &proof.public_key, trusted_keys)
+    }
+}
+
+/// Verifies the proof of `proven` - for example one returned by
+/// [`Routing::sign_as_section`](crate::Routing::sign_as_section) - against its
+/// [`signed_bytes`](Proven::signed_bytes), as [`verify_proof`] does.
+pub fn verify_proven<'a, T, I>(
+    proven: &Proven<T>,
+    proof_chain: &SectionChain,
+    trusted_keys: I,
+) -> Verification
+where
+    T: Serialize,
+    I: IntoIterator<Item = &'a bls::PublicKey>,
+{
+    match proven.signed_bytes() {
+        Ok(payload) => verify_proof(&payload, &proven.proof, proof_chain, trusted_keys),
+        Err(error) => {
+            error!("Failed to serialize proven value: {}", error);
+            Verification::InvalidSignature
+        }
+    }
+}
+
+// Classify the valid signature made by `key` of `proof_chain`.
+fn check_trust<'a, I>(
+    proof_chain: &SectionChain,
+    key: &bls::PublicKey,
+    trusted_keys: I,
+) -> Verification
+where
+    I: IntoIterator<Item = &'a bls::PublicKey>,
+{
+    if proof_chain.check_key_trust(key, trusted_keys) {
+        Verification::Trusted
+    } else if key == proof_chain.last_key() {
+        Verification::Untrusted
+    } else {
+        Verification::UnknownKey
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{agreement::test_utils, routing::SectionPayload};
+    use anyhow::Result;
+    use std::iter;
+
+    #[test]
+    fn verify_signature() -> Result<()> {
+        let (chain, sks) = gen_chain(3)?;
+        let payload = b"hello";
+        let signature = sks[1].sign(payload);
+
+        let trusted = [*chain.root_key()];
+        assert_eq!(
+            verify(payload, &signature, &chain, &trusted),
+            Verification::Trusted
+        );
+
+        // Without a trusted key, only a signature by the last key is accepted.
+        let other_key = bls::SecretKey::random().public_key();
+        assert_eq!(
+            verify(payload, &signature, &chain, iter::once(&other_key)),
+            Verification::UnknownKey
+        );
+        assert_eq!(
+            verify(
+                payload,
+                &sks[2].sign(payload),
+                &chain,
+                iter::once(&other_key)
+            ),
+            Verification::Untrusted
+        );
+
+        // A key above the trusted one is not trusted.
+        let trusted = [*chain.last_key()];
+        assert_eq!(
+            verify(payload, &signature, &chain, &trusted),
+            Verification::UnknownKey
+        );
+
+        assert_eq!(
+            verify(b"other", &signature, &chain, &trusted),
+            Verification::InvalidSignature
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_proven_value() -> Result<()> {
+        let (chain, sks) = gen_chain(2)?;
+        let trusted = [*chain.last_key()];
+
+        let proven = test_utils::proven(&sks[1], SectionPayload(42u32))?;
+        assert!(verify_proven(&proven, &chain, &trusted).is_trusted());
+
+        let proven = Proven::new(SectionPayload(43u32), proven.proof);
+        assert_eq!(
+            verify_proven(&proven, &chain, &trusted),
+            Verification::InvalidSignature
+        );
+
+        let proven = test_utils::proven(&bls::SecretKey::random(), SectionPayload(42u32))?;
+        assert_eq!(
+            verify_proven(&proven, &chain, &trusted),
+            Verification::UnknownKey
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_forged_chain() -> Result<()> {
+        let payload = b"hello";
+        let trusted_key = bls::SecretKey::random().public_key();
+
+        // The attacker signs the trusted key with their own key and the payload with it too.
+        let attacker_sk = bls::SecretKey::random();
+        let mut chain = SectionChain::new(attacker_sk.public_key());
+        let _ = chain.insert(
+            &attacker_sk.public_key(),
+            trusted_key,
+            attacker_sk.sign(&bincode::serialize(&trusted_key)?),
+        )?;
+
+        let signature = attacker_sk.sign(payload);
+        let verification = verify(payload, &signature, &chain, iter::once(&trusted_key));
+        assert!(!verification.is_trusted());
+
+        let proof = Proof {
+            public_key: attacker_sk.public_key(),
+            signature,
+        };
+        let verification = verify_proof(payload, &proof, &chain, iter::once(&trusted_key));
+        assert!(!verification.is_trusted());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_side_fork_signer() -> Result<()> {
+        let payload = b"hello";
+        let (mut chain, sks) = gen_chain(2)?;
+        let trusted = [*chain.last_key()];
+
+        // A sibling of the trusted key, signed by their common parent.
+        let fork_sk = bls::SecretKey::random();
+        let fork_pk = fork_sk.public_key();
+        let _ = chain.insert(
+            &sks[0].public_key(),
+            fork_pk,
+            sks[0].sign(&bincode::serialize(&fork_pk)?),
+        )?;
+
+        let signature = fork_sk.sign(payload);
+        assert!(!verify(payload, &signature, &chain, &trusted).is_trusted());
+
+        Ok(())
+    }
+
+    // Generate a chain of `len` keys, returning it together with their secret keys.
+    fn gen_chain(len: usize) -> Result<(SectionChain, Vec<bls::SecretKey>)> {
+        let sks: Vec<_> = (0..len).map(|_| bls::SecretKey::random()).collect();
+        let mut chain = SectionChain::new(sks[0].public_key());
+
+        for (parent, sk) in sks.iter().zip(&sks[1..]) {
+            let signature = parent.sign(&bincode::serialize(&sk.public_key())?);
+            let _ = chain.insert(&parent.public_key(), sk.public_key(), signature)?;
+        }
+
+        Ok((chain, sks))
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Versioned encoding of the values nodes exchange and persist long term: `SectionChain` and
+//! `Proven<T>` (such as `Proven<EldersInfo>` and `Proven<MemberInfo>`).
+//!
+//! Such a value is serialized as an envelope: the format version (`u16`) followed by the body, a
+//! length prefixed byte sequence. The body of a `SectionChain` holds its fields, serialized with
+//! bincode as a tuple. The body of a `Proven<T>` holds its value, serialized with bincode, and the
+//! proof follows the envelope. The values nested in a body are plain bincode, so there is a single
+//! envelope per `Proven` or `SectionChain` rather than one per nested value. The rules for
+//! changing the format are:
+//!
+//! - A new version may only append fields at the end of the top-level value of a body (for
+//!   example `MemberInfo` in the body of `Proven<MemberInfo>`). Existing fields and the nested
+//!   types never change their type, meaning or position.
+//! - A decoder accepts any version since `MIN_VERSION`, including newer versions than it knows.
+//!   It decodes the fields it knows and keeps the rest of the body, which the length prefix
+//!   delimits, as unknown fields.
+//! - The encoder writes `VERSION` (or the version the value was decoded from, if newer), followed
+//!   by the known fields and then the unknown ones, so a value is relayed unchanged. Bump
+//!   `VERSION` only together with appending fields, and add a golden file for the new version next
+//!   to the existing ones, which must keep decoding.
+//!
+//! The section signs the body of a `Proven` value, that is the serialized value together with its
+//! unknown fields, so a node verifies the signature on a value even when it doesn't know all of
+//! its fields. `SectionChain` has no signature over its body (its blocks sign just the keys), so
+//! it drops its unknown fields.
+//!
+//! The body of a value that existed before this format is its plain bincode encoding, which is
+//! what the section signed before, so the signatures made then stay valid. The envelopes do change
+//! the encoding of the messages and of the persisted node state (`StoredState`): nodes of the
+//! previous versions don't speak a common protocol version with this one, and a node restarting
+//! with the state persisted by a previous version discards it and joins anew.
+
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    ser::Error as _,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::cmp;
+
+// Version of the format written by this node.
+pub(crate) const VERSION: u16 = 1;
+// Oldest version this node can still decode.
+pub(crate) const MIN_VERSION: u16 = 1;
+
+// Fields appended to a value by a newer version than ours, as they were serialized.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Unknown {
+    version: u16,
+    fields: Vec<u8>,
+}
+
+impl Default for Unknown {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            fields: Vec::new(),
+        }
+    }
+}
+
+// Returns the version and the body of the envelope of a value with the given known and unknown
+// fields.
+pub(crate) fn encode<T: Serialize>(
+    fields: &T,
+    unknown: &Unknown,
+) -> bincode::Result<(u16, Vec<u8>)> {
+    let mut body = bincode::serialize(fields)?;
+    body.extend_from_slice(&unknown.fields);
+    Ok((cmp::max(unknown.version, VERSION), body))
+}
+
+// Decodes the known fields of a value from the version and the body of its envelope. Returns them
+// together with the unknown ones.
+pub(crate) fn decode<T: DeserializeOwned>(
+    version: u16,
+    body: &[u8],
+) -> bincode::Result<(T, Unknown)> {
+    if version < MIN_VERSION {
+        return Err(Box::new(bincode::ErrorKind::Custom(format!(
+            "unsupported wire format version {} (oldest supported: {})",
+            version, MIN_VERSION
+        ))));
+    }
+
+    let mut rest = body;
+    let fields = bincode::deserialize_from(&mut rest)?;
+    let unknown = Unknown {
+        version,
+        fields: rest.to_vec(),
+    };
+
+    Ok((fields, unknown))
+}
+
+// Serialize `fields` wrapped in the current version envelope.
+pub(crate) fn serialize<T, S>(fields: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    encode(fields, &Unknown::default())
+        .map_err(S::Error::custom)?
+        .serialize(serializer)
+}
+
+// Deserialize the fields of a value from its version envelope. The fields appended by versions
+// newer than ours are dropped.
+pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>,
+{
+    let (version, body) = <(u16, Vec<u8>)>::deserialize(deserializer)?;
+    decode(version, &body)
+        .map(|(fields, _)| fields)
+        .map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agreement::{Proof, Proven},
+        peer::Peer,
+        section::{Checkpoint, EldersInfo, MemberInfo, PeerState, SectionChain, SectionChainFork},
+        Prefix, XorName,
+    };
+    use anyhow::{anyhow, Result};
+    use std::net::SocketAddr;
+
+    const MEMBER_INFO: &[u8] = include_bytes!("../tests/golden/member_info.bin");
+    const ELDERS_INFO: &[u8] = include_bytes!("../tests/golden/elders_info.bin");
+    const SECTION_CHAIN_V1: &[u8] = include_bytes!("../tests/golden/section_chain_v1.bin");
+    const SECTION_CHAIN_FORK_V1: &[u8] =
+        include_bytes!("../tests/golden/section_chain_fork_v1.bin");
+    const PROVEN_MEMBER_INFO_V1: &[u8] =
+        include_bytes!("../tests/golden/proven_member_info_v1.bin");
+
+    // Compressed generators of the BLS12-381 G1 and G2 groups, used as a fixed public key and a
+    // fixed (not actually valid) signature.
+    const G1_GENERATOR: [u8; 48] = [
+        0x97, 0xf1, 0xd3, 0xa7, 0x31, 0x97, 0xd7, 0x94, 0x26, 0x95, 0x63, 0x8c, 0x4f, 0xa9, 0xac,
+        0x0f, 0xc3, 0x68, 0x8c, 0x4f, 0x97, 0x74, 0xb9, 0x05, 0xa1, 0x4e, 0x3a, 0x3f, 0x17, 0x1b,
+        0xac, 0x58, 0x6c, 0x55, 0xe8, 0x3f, 0xf9, 0x7a, 0x1a, 0xef, 0xfb, 0x3a, 0xf0, 0x0a, 0xdb,
+        0x22, 0xc6, 0xbb,
+    ];
+    const G2_GENERATOR: [u8; 96] = [
+        0x93, 0xe0, 0x2b, 0x60, 0x52, 0x71, 0x9f, 0x60, 0x7d, 0xac, 0xd3, 0xa0, 0x88, 0x27, 0x4f,
+        0x65, 0x59, 0x6b, 0xd0, 0xd0, 0x99, 0x20, 0xb6, 0x1a, 0xb5, 0xda, 0x61, 0xbb, 0xdc, 0x7f,
+        0x50, 0x49, 0x33, 0x4c, 0xf1, 0x12, 0x13, 0x94, 0x5d, 0x57, 0xe5, 0xac, 0x7d, 0x05, 0x5d,
+        0x04, 0x2b, 0x7e, 0x02, 0x4a, 0xa2, 0xb2, 0xf0, 0x8f, 0x0a, 0x91, 0x26, 0x08, 0x05, 0x27,
+        0x2d, 0xc5, 0x10, 0x51, 0xc6, 0xe4, 0x7a, 0xd4, 0xfa, 0x40, 0x3b, 0x02, 0xb4, 0x51, 0x0b,
+        0x64, 0x7a, 0xe3, 0xd1, 0x77, 0x0b, 0xac, 0x03, 0x26, 0xa8, 0x05, 0xbb, 0xef, 0xd4, 0x80,
+        0x56, 0xc8, 0xc1, 0x21, 0xbd, 0xb8,
+    ];
+
+    // The section signs the plain encoding of the values nested in envelopes, so it never changes.
+    #[test]
+    fn member_info_golden() -> Result<()> {
+        let member_info = golden_member_info();
+
+        assert_eq!(bincode::serialize(&member_info)?, MEMBER_INFO);
+        assert_eq!(
+            bincode::deserialize::<MemberInfo>(MEMBER_INFO)?,
+            member_info
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn elders_info_golden() -> Result<()> {
+        let elders_info = golden_elders_info()?;
+
+        assert_eq!(bincode::serialize(&elders_info)?, ELDERS_INFO);
+        assert_eq!(
+            bincode::deserialize::<EldersInfo>(ELDERS_INFO)?,
+            elders_info
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn section_chain_golden() -> Result<()> {
+        let chain = SectionChain::new(golden_public_key()?);
+
+        assert_eq!(bincode::serialize(&chain)?, SECTION_CHAIN_V1);
+        assert_eq!(
+            bincode::deserialize::<SectionChain>(SECTION_CHAIN_V1)?,
+            chain
+        );
+
+        Ok(())
+    }
+
+    // Chain pruned at a checkpoint, with two keys signed by the checkpoint key. The checkpoint key
+    // is the identity point of G1: all its signatures are the identity point of G2 whatever they
+    // sign, which lets the golden file pass the integrity checks without a secret key to sign it.
+    #[test]
+    fn section_chain_with_fork_and_checkpoint_golden() -> Result<()> {
+        let chain: SectionChain = bincode::deserialize(SECTION_CHAIN_FORK_V1)?;
+
+        let root = identity_public_key()?;
+        let first = golden_public_key()?;
+        let second = negated_public_key()?;
+
+        assert_eq!(chain.root_key(), &root);
+        itertools::assert_equal(chain.keys(), vec![&root, &first, &second]);
+        assert_eq!(
+            chain.forks(),
+            vec![SectionChainFork {
+                parent: root,
+                keys: vec![first, second],
+            }]
+        );
+
+        let checkpoint = chain
+            .checkpoint()
+            .ok_or_else(|| anyhow!("missing checkpoint"))?;
+        assert_eq!(
+            checkpoint.value,
+            Checkpoint {
+                key: root,
+                height: 1,
+            }
+        );
+        assert!(checkpoint.verify(&chain));
+
+        assert_eq!(bincode::serialize(&chain)?, SECTION_CHAIN_FORK_V1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proven_golden() -> Result<()> {
+        let proof = Proof {
+            public_key: golden_public_key()?,
+            signature: golden_signature()?,
+        };
+        let proven = Proven::new(golden_member_info(), proof);
+
+        assert_eq!(bincode::serialize(&proven)?, PROVEN_MEMBER_INFO_V1);
+        assert_eq!(
+            bincode::deserialize::<Proven<MemberInfo>>(PROVEN_MEMBER_INFO_V1)?,
+            proven
+        );
+        assert_eq!(proven.signed_bytes()?, MEMBER_INFO);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_newer_version() -> Result<()> {
+        // A future version appending a field to `MemberInfo`, signed along with the rest.
+        let mut value = MEMBER_INFO.to_vec();
+        value.extend_from_slice(b"new member info field");
+
+        let sk = bls::SecretKey::random();
+        let proof = Proof {
+            public_key: sk.public_key(),
+            signature: sk.sign(&value),
+        };
+        let mut bytes = envelope(2, &value);
+        bytes.extend(bincode::serialize(&proof)?);
+
+        let decoded: Proven<MemberInfo> = bincode::deserialize(&bytes)?;
+        assert_eq!(decoded.value, golden_member_info());
+        assert_eq!(decoded.proof, proof);
+
+        // The unknown field still verifies and is relayed unchanged.
+        assert!(decoded.self_verify());
+        assert_eq!(bincode::serialize(&decoded)?, bytes);
+
+        // Without it, the signature doesn't verify.
+        assert!(!Proven::new(decoded.value, decoded.proof).self_verify());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unsupported_version() {
+        let mut bytes = envelope(0, MEMBER_INFO);
+        bytes.extend_from_slice(&PROVEN_MEMBER_INFO_V1[MEMBER_INFO.len() + 10..]);
+
+        assert!(bincode::deserialize::<Proven<MemberInfo>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn golden_files_are_envelopes() {
+        for golden in &[SECTION_CHAIN_V1, SECTION_CHAIN_FORK_V1] {
+            assert_eq!(golden[..2], 1u16.to_le_bytes());
+            assert_eq!(golden[2..10], (golden.len() as u64 - 10).to_le_bytes());
+        }
+
+        assert_eq!(PROVEN_MEMBER_INFO_V1[..10], envelope(1, MEMBER_INFO)[..10]);
+    }
+
+    fn envelope(version: u16, body: &[u8]) -> Vec<u8> {
+        let mut bytes = version.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(body.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    fn golden_public_key() -> Result<bls::PublicKey> {
+        bls::PublicKey::from_bytes(G1_GENERATOR).map_err(|_| anyhow!("invalid public key"))
+    }
+
+    // Negation of the golden public key, by flipping its sign bit.
+    fn negated_public_key() -> Result<bls::PublicKey> {
+        let mut bytes = G1_GENERATOR;
+        bytes[0] ^= 0x20;
+        bls::PublicKey::from_bytes(bytes).map_err(|_| anyhow!("invalid public key"))
+    }
+
+    fn identity_public_key() -> Result<bls::PublicKey> {
+        let mut bytes = [0; 48];
+        bytes[0] = 0xc0;
+        bls::PublicKey::from_bytes(bytes).map_err(|_| anyhow!("invalid public key"))
+    }
+
+    fn golden_signature() -> Result<bls::Signature> {
+        bls::Signature::from_bytes(G2_GENERATOR).map_err(|_| anyhow!("invalid signature"))
+    }
+
+    fn golden_peer(byte: u8, port: u16) -> Peer {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        Peer::new(XorName([byte; 32]), addr)
+    }
+
+    fn golden_member_info() -> MemberInfo {
+        MemberInfo {
+            peer: golden_peer(1, 12000),
+            state: PeerState::Relocated(XorName([3; 32])),
+        }
+    }
+
+    fn golden_elders_info() -> Result<EldersInfo> {
+        let prefix: Prefix = "1".parse().map_err(|_| anyhow!("invalid prefix"))?;
+        Ok(EldersInfo::new(
+            vec![golden_peer(0x81, 12001), golden_peer(0x82, 12002)],
+            prefix,
+        ))
+    }
+}
@@ -0,0 +1,486 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+mod utils;
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::FutureExt;
+use sn_messaging::{
+    location::{Aggregation, Itinerary},
+    DstLocation, SrcLocation,
+};
+use sn_routing::{
+    Config, Error, Event, EventFilter, LagPolicy, NetworkParams, NodeElderChange, OverflowPolicy,
+    QueueConfig, SimNetwork, TransportConfig, ELDER_SIZE,
+};
+use std::{net::SocketAddr, time::Duration};
+use tokio::time;
+use utils::*;
+
+const SEED: u64 = 1234;
+
+fn simulated_config(network: &SimNetwork, first: bool) -> Config {
+    let hard_coded_contacts = if first {
+        Default::default()
+    } else {
+        network.endpoints().into_iter().collect()
+    };
+
+    Config {
+        first,
+        transport_config: TransportConfig {
+            hard_coded_contacts,
+            ..Default::default()
+        },
+        simulated_network: Some(network.clone()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_startup_section_bootstrapping_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+    network.set_latency(Duration::from_millis(10), Duration::from_millis(100));
+
+    let (_genesis_node, mut genesis_events) = create_node(simulated_config(&network, true)).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    let mut nodes = vec![];
+    for _ in 1..ELDER_SIZE {
+        let (node, mut events) = create_node(simulated_config(&network, false)).await?;
+        assert_event!(
+            events,
+            Event::EldersChanged {
+                self_status_change: NodeElderChange::Promoted,
+                ..
+            }
+        );
+        nodes.push(node);
+    }
+
+    for node in &nodes {
+        verify_invariants_for_node(node, nodes.len() + 1).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metrics_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+
+    let (genesis_node, mut genesis_events) = create_node(simulated_config(&network, true)).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    let (node, mut events) = create_node(simulated_config(&network, false)).await?;
+    assert_event!(
+        events,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
+
+    let genesis_metrics = genesis_node.metrics().await;
+    assert!(genesis_metrics.messages_received["JoinRequest"] >= 1);
+    assert!(genesis_metrics.messages_sent["NodeApproval"] >= 1);
+    assert_eq!(genesis_metrics.section_members, 2);
+
+    let metrics = node.metrics().await;
+    assert!(metrics.dkg_session_duration.count() >= 1);
+    assert_eq!(metrics.section_elders, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_acknowledged_delivery_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+    network.set_latency(Duration::from_millis(10), Duration::from_millis(100));
+
+    let (genesis_node, mut genesis_events) = create_node(simulated_config(&network, true)).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    let (node, mut events) = create_node(simulated_config(&network, false)).await?;
+    assert_event!(
+        events,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
+
+    let content = Bytes::from_static(b"hello");
+    let itinerary = Itinerary {
+        src: SrcLocation::Node(node.name().await),
+        dst: DstLocation::Node(genesis_node.name().await),
+        aggregation: Aggregation::None,
+    };
+
+    let delivery = node
+        .send_message_with_ack(itinerary, content.clone(), None)
+        .await?;
+    time::timeout(TIMEOUT, delivery).await??;
+
+    assert_event!(
+        genesis_events,
+        Event::MessageReceived { content: received, .. } if received == content
+    );
+
+    // Acknowledged delivery to end users is not supported.
+    let itinerary = Itinerary {
+        src: SrcLocation::Node(node.name().await),
+        dst: DstLocation::Direct,
+        aggregation: Aggregation::None,
+    };
+    assert!(matches!(
+        node.send_message_with_ack(itinerary, content, None).await,
+        Err(Error::InvalidDstLocation)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_response_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+    network.set_latency(Duration::from_millis(10), Duration::from_millis(100));
+
+    let (genesis_node, mut genesis_events) = create_node(simulated_config(&network, true)).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    let (node, mut events) = create_node(simulated_config(&network, false)).await?;
+    assert_event!(
+        events,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
+
+    let src = SrcLocation::Node(node.name().await);
+    let dst = DstLocation::Node(genesis_node.name().await);
+    let itinerary = || Itinerary {
+        src,
+        dst,
+        aggregation: Aggregation::None,
+    };
+    let response = node
+        .send_request(
+            itinerary(),
+            Bytes::from_static(b"ping"),
+            Duration::from_secs(30),
+            None,
+        )
+        .await?;
+
+    let (id, src, dst) = loop {
+        match time::timeout(TIMEOUT, genesis_events.next()).await? {
+            Some(Event::RequestReceived {
+                id,
+                content,
+                src,
+                dst,
+                ..
+            }) if content == "ping" => break (id, src, dst),
+            Some(_) => {}
+            None => panic!("event stream closed"),
+        }
+    };
+    assert_eq!(&id, response.id());
+    genesis_node
+        .send_response(id, src, dst, Bytes::from_static(b"pong"))
+        .await?;
+
+    assert_eq!(time::timeout(TIMEOUT, response).await??, "pong");
+
+    // The request times out if no one responds.
+    let response = node
+        .send_request(
+            itinerary(),
+            Bytes::from_static(b"ping"),
+            Duration::from_secs(30),
+            None,
+        )
+        .await?;
+    assert!(matches!(
+        time::timeout(TIMEOUT, response).await?,
+        Err(Error::RequestTimeout)
+    ));
+
+    // Cancelled request resolves immediately.
+    let response = node
+        .send_request(
+            itinerary(),
+            Bytes::from_static(b"ping"),
+            Duration::from_secs(30),
+            None,
+        )
+        .await?;
+    assert!(node.cancel_request(response.id()).await);
+    assert!(matches!(response.await, Err(Error::RequestCancelled)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_event_subscriptions_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+
+    let (genesis_node, mut genesis_events) = create_node(simulated_config(&network, true)).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    let mut elders_changes =
+        genesis_node.subscribe(EventFilter::EldersChanged, 10, LagPolicy::DropNewest);
+    let mut client_events =
+        genesis_node.subscribe(EventFilter::ClientMessages, 10, LagPolicy::DropNewest);
+
+    let (_node, mut events) = create_node(simulated_config(&network, false)).await?;
+    assert_event!(
+        events,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
+
+    // The subscription receives the event independently of the main event stream.
+    assert_event!(genesis_events, Event::EldersChanged { .. });
+    assert_event!(elders_changes, Event::EldersChanged { .. });
+    assert!(client_events.next().now_or_never().is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bounded_event_queue_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+
+    let mut config = simulated_config(&network, true);
+    config.queues = QueueConfig {
+        event_capacity: 1,
+        overflow_policy: OverflowPolicy::DropNewest,
+        ..Default::default()
+    };
+    // Not taking any events from the genesis node so its event queue stays full.
+    let (genesis_node, _genesis_events) = create_node(config).await?;
+
+    let (_node, mut events) = create_node(simulated_config(&network, false)).await?;
+    assert_event!(
+        events,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
+
+    let metrics = genesis_node.metrics().await;
+    assert_eq!(metrics.event_queue_depth, 1);
+    assert!(metrics.events_dropped >= 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_join_fails_across_partition() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+
+    let (genesis_node, mut genesis_events) = create_node(simulated_config(&network, true)).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    // Reserve the address the joining node is going to use and cut it off from the genesis node.
+    let joining_addr: SocketAddr = "127.0.0.1:20000".parse()?;
+    network.partition(&[genesis_node.our_connection_info()], &[joining_addr]);
+
+    let mut config = simulated_config(&network, false);
+    config.transport_config.local_port = Some(joining_addr.port());
+
+    assert!(create_node(config).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_small_elder_size_over_simulated_network() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+    let network_params = NetworkParams {
+        elder_size: 3,
+        recommended_section_size: 6,
+        ..Default::default()
+    };
+
+    let mut config = simulated_config(&network, true);
+    config.network_params = network_params;
+    let (genesis_node, mut genesis_events) = create_node(config).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    for _ in 1..network_params.elder_size {
+        let mut config = simulated_config(&network, false);
+        config.network_params = network_params;
+        let (_node, mut events) = create_node(config).await?;
+        assert_event!(
+            events,
+            Event::EldersChanged {
+                self_status_change: NodeElderChange::Promoted,
+                ..
+            }
+        );
+    }
+
+    // The section is already full so the next node joins as adult.
+    let mut config = simulated_config(&network, false);
+    config.network_params = network_params;
+    let (node, _events) = create_node(config).await?;
+
+    assert!(!node.is_elder().await);
+    assert_eq!(node.network_params().await, network_params);
+    assert_eq!(
+        genesis_node.our_section().await.elders.len(),
+        network_params.elder_size
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_join_fails_with_different_network_params() -> Result<()> {
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+
+    let mut config = simulated_config(&network, true);
+    config.network_params = NetworkParams {
+        elder_size: 3,
+        recommended_section_size: 6,
+        ..Default::default()
+    };
+    let (_genesis_node, mut genesis_events) = create_node(config).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    // Joining with the default params.
+    let result = create_node(simulated_config(&network, false)).await;
+    assert!(matches!(
+        result.map_err(|error| error.downcast::<Error>()),
+        Err(Ok(Error::InvalidNetworkParams))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_same_seed_gives_same_names() -> Result<()> {
+    time::pause();
+
+    let mut names = vec![];
+    for _ in 0..2 {
+        let network = SimNetwork::new(SEED);
+
+        let (genesis_node, mut genesis_events) =
+            create_node(simulated_config(&network, true)).await?;
+        assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+        let (node, _events) = create_node(simulated_config(&network, false)).await?;
+
+        names.push((genesis_node.name().await, node.name().await));
+    }
+
+    assert_eq!(names[0], names[1]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_split_and_relocation_over_simulated_network() -> Result<()> {
+    // Upper bound on the number of nodes joining before the split and before the first relocation.
+    const MAX_JOINS: usize = 64;
+
+    time::pause();
+
+    let network = SimNetwork::new(SEED);
+    network.set_latency(Duration::from_millis(10), Duration::from_millis(100));
+
+    // Small sections split early and young nodes get relocated on most churns.
+    let network_params = NetworkParams {
+        elder_size: 3,
+        recommended_section_size: 3,
+        min_age: 1,
+        ..Default::default()
+    };
+
+    let mut config = simulated_config(&network, true);
+    config.network_params = network_params;
+    let (genesis_node, mut genesis_events) = create_node(config).await?;
+    assert_next_event!(genesis_events, Event::EldersChanged { .. });
+
+    let mut nodes = vec![(genesis_node, genesis_events)];
+
+    // Keep adding nodes until the section splits. The new prefix is only adopted once the DKG of
+    // both new sections completed.
+    while nodes[0].0.our_prefix().await.is_empty() {
+        assert!(
+            nodes.len() < MAX_JOINS,
+            "no split after {} nodes",
+            nodes.len()
+        );
+
+        let mut config = simulated_config(&network, false);
+        config.network_params = network_params;
+        nodes.push(create_node(config).await?);
+
+        time::sleep(Duration::from_secs(1)).await;
+    }
+
+    let our_prefix = nodes[0].0.our_prefix().await;
+    assert_eq!(our_prefix.bit_count(), 1);
+
+    for (node, _) in &nodes {
+        if node.is_elder().await {
+            verify_invariants_for_node(node, network_params.elder_size).await?;
+        }
+    }
+
+    // Keep adding nodes until their churn relocates one of the members.
+    let mut joins = 0;
+    'relocation: loop {
+        for (_, events) in &mut nodes {
+            while let Some(Some(event)) = events.next().now_or_never() {
+                if let Event::Relocated { .. } = event {
+                    break 'relocation;
+                }
+            }
+        }
+
+        assert!(joins < MAX_JOINS, "no relocation after {} joins", joins);
+        joins += 1;
+
+        let mut config = simulated_config(&network, false);
+        config.network_params = network_params;
+        nodes.push(create_node(config).await?);
+
+        time::sleep(Duration::from_secs(1)).await;
+    }
+
+    Ok(())
+}